@@ -0,0 +1,512 @@
+#![deny(clippy::unwrap_used)]
+
+//! 角色（1.11 起的领导人、顾问与将领）
+//!
+//! 解析 `common/characters/*.txt`，提取立绘（civilian/army/navy × large/small）、
+//! `country_leader` 意识形态块、`advisor` 槽位与特质以及
+//! `corps_commander` / `field_marshal` / `navy_leader` 的能力值。
+//! 立绘为 GFX 名称时从本地化检查共用的 `.gfx` sprite 目录中查找纹理。
+//! 同时报告在 `history/countries` 中招募却从未定义的角色。
+
+use crate::clausewitz::{self, Block, Field, LineIndex};
+use crate::file_cache::{self, FileSetCache, FileStamps};
+use crate::load_order::{self, LoadOrderRoot, RootSource};
+use crate::localisation_lint;
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const CHARACTERS_DIR: &str = "common/characters";
+const HISTORY_DIR: &str = "history/countries";
+
+const PORTRAIT_CATEGORIES: [(&str, PortraitCategory); 3] = [
+    ("civilian", PortraitCategory::Civilian),
+    ("army", PortraitCategory::Army),
+    ("navy", PortraitCategory::Navy),
+];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PortraitCategory {
+    Civilian,
+    Army,
+    Navy,
+}
+
+/// ：一张立绘，`value` 为脚本原文（GFX 名称或纹理路径），`texture` 为解析出的纹理绝对路径。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacterPortrait {
+    pub category: PortraitCategory,
+    /// `large` 或 `small`
+    pub size: String,
+    pub value: String,
+    pub texture: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountryLeaderRole {
+    pub ideology: Option<String>,
+    pub traits: Vec<String>,
+    pub expire: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdvisorRole {
+    pub slot: Option<String>,
+    pub idea_token: Option<String>,
+    pub ledger: Option<String>,
+    pub cost: Option<f64>,
+    pub traits: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CommanderKind {
+    CorpsCommander,
+    FieldMarshal,
+    NavyLeader,
+}
+
+/// ：将领能力值，陆军与海军将领各自只有其中一部分。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommanderRole {
+    pub kind: CommanderKind,
+    pub skill: Option<f64>,
+    pub attack_skill: Option<f64>,
+    pub defense_skill: Option<f64>,
+    pub planning_skill: Option<f64>,
+    pub logistics_skill: Option<f64>,
+    pub maneuvering_skill: Option<f64>,
+    pub coordination_skill: Option<f64>,
+    pub traits: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacterEntry {
+    pub id: String,
+    /// `name` 字段（通常为本地化键）
+    pub name: Option<String>,
+    pub portraits: Vec<CharacterPortrait>,
+    pub country_leader: Vec<CountryLeaderRole>,
+    pub advisors: Vec<AdvisorRole>,
+    pub commanders: Vec<CommanderRole>,
+    pub source: RootSource,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CharacterWarningKind {
+    UndefinedCharacter,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacterWarning {
+    pub kind: CharacterWarningKind,
+    pub id: String,
+    pub message: String,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacterLoadResponse {
+    pub success: bool,
+    pub message: String,
+    pub characters: Option<Vec<CharacterEntry>>,
+    pub warnings: Vec<CharacterWarning>,
+}
+
+/// ：对外暴露的清理接口，可在调试时手动失效缓存。
+#[tauri::command]
+pub fn reset_character_cache() -> bool {
+    CHARACTER_CACHE.clear();
+    println!("[character_registry] cache cleared by frontend command");
+    true
+}
+
+/// 加载角色列表并解析立绘纹理
+#[tauri::command]
+pub fn load_characters(
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> CharacterLoadResponse {
+    let roots = load_order::load_order(project_root.as_deref(), dependency_roots, game_root.as_deref());
+    if roots.is_empty() {
+        return CharacterLoadResponse {
+            success: false,
+            message: "未提供项目或游戏目录".to_string(),
+            characters: None,
+            warnings: Vec::new(),
+        };
+    }
+
+    let loaded = load_all(&roots);
+    // 没有游戏目录时缺少原版角色，不检查招募的角色是否存在
+    let has_game = game_root.as_deref().is_some_and(|g| !g.trim().is_empty());
+    let warnings = if has_game { collect_warnings(&loaded) } else { Vec::new() };
+
+    let mut characters = loaded.characters.clone();
+    let root_paths: Vec<PathBuf> = roots.iter().map(|r| PathBuf::from(&r.path)).collect();
+    let gfx = localisation_lint::catalogue_for_roots(&roots);
+    resolve_portraits(&mut characters, &root_paths, &gfx.textures);
+
+    CharacterLoadResponse {
+        success: true,
+        message: format!("共 {} 个角色，{} 条警告", characters.len(), warnings.len()),
+        characters: Some(characters),
+        warnings,
+    }
+}
+
+// ==================== 收集 ====================
+
+/// ：历史文件中的 `recruit_character = id`。
+#[derive(Debug, Clone)]
+struct Recruitment {
+    id: String,
+    source: RootSource,
+    file: String,
+    start: usize,
+    end: usize,
+    line: usize,
+}
+
+#[derive(Debug, Default)]
+struct LoadedCharacters {
+    characters: Vec<CharacterEntry>,
+    recruitments: Vec<Recruitment>,
+}
+
+/// ：全局缓存容器，key 为按加载顺序拼接的根目录。
+static CHARACTER_CACHE: Lazy<FileSetCache<LoadedCharacters>> = Lazy::new(FileSetCache::new);
+
+/// ：按加载顺序收集生效文件（同一相对路径只取优先级最高的根目录），文件未变化时复用缓存。
+fn load_all(roots: &[LoadOrderRoot]) -> Arc<LoadedCharacters> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut files: FileStamps = Vec::new();
+    // (是否为历史文件, 来源)
+    let mut meta: Vec<(bool, RootSource)> = Vec::new();
+    for (index, root) in roots.iter().enumerate() {
+        for (dir, is_history) in [(CHARACTERS_DIR, false), (HISTORY_DIR, true)] {
+            if load_order::blanked_by(roots, index, dir).is_some() {
+                continue;
+            }
+            let base = Path::new(&root.path).join(dir);
+            let Ok(entries) = fs::read_dir(&base) else { continue };
+            let mut paths: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+            paths.sort();
+            for path in paths {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+                if !path.is_file() || !name.ends_with(".txt") || !seen.insert(format!("{}/{}", dir, name)) {
+                    continue;
+                }
+                files.push((path.to_string_lossy().replace('\\', "/"), file_cache::modified(&path)));
+                meta.push((is_history, root.source));
+            }
+        }
+    }
+
+    CHARACTER_CACHE.get_or_build(file_cache::roots_key(roots), files.clone(), |_| {
+        let parsed: Vec<LoadedCharacters> = files
+            .par_iter()
+            .zip(meta.par_iter())
+            .map(|((path, _), (is_history, source))| {
+                let content = fs::read(path)
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                    .unwrap_or_default();
+                if *is_history {
+                    LoadedCharacters {
+                        recruitments: parse_recruitments(path, &content, *source),
+                        ..Default::default()
+                    }
+                } else {
+                    LoadedCharacters {
+                        characters: parse_characters(path, &content, *source),
+                        ..Default::default()
+                    }
+                }
+            })
+            .collect();
+
+        // 同名角色由高优先级的定义生效
+        let mut loaded = LoadedCharacters::default();
+        let mut ids: HashSet<String> = HashSet::new();
+        for file in parsed {
+            for character in file.characters {
+                if ids.insert(character.id.clone()) {
+                    loaded.characters.push(character);
+                }
+            }
+            loaded.recruitments.extend(file.recruitments);
+        }
+        loaded
+    })
+}
+
+// ==================== 解析 ====================
+
+fn trait_list(block: &Block) -> Vec<String> {
+    block
+        .get_block("traits")
+        .map(|b| b.scalars().map(|s| s.as_str().to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// ：`characters = { id = { ... } }`。
+fn parse_characters(file: &str, content: &str, source: RootSource) -> Vec<CharacterEntry> {
+    let doc = clausewitz::parse(content);
+    let index = LineIndex::new(content);
+    doc.root
+        .fields_named("characters")
+        .filter_map(|f| f.value.as_block())
+        .flat_map(|b| b.fields())
+        .filter_map(|field| {
+            let block = field.value.as_block()?;
+            Some(parse_character(file, &index, source, field, block))
+        })
+        .collect()
+}
+
+fn parse_character(file: &str, index: &LineIndex, source: RootSource, field: &Field, block: &Block) -> CharacterEntry {
+    let mut portraits = Vec::new();
+    if let Some(portrait_block) = block.get_block("portraits") {
+        for (key, category) in PORTRAIT_CATEGORIES {
+            for sizes in portrait_block.fields_named(key).filter_map(|f| f.value.as_block()) {
+                for size in sizes.fields() {
+                    let Some(value) = size.value.as_scalar() else { continue };
+                    portraits.push(CharacterPortrait {
+                        category,
+                        size: size.key.as_str().to_string(),
+                        value: value.as_str().to_string(),
+                        texture: None,
+                    });
+                }
+            }
+        }
+    }
+
+    let country_leader = block
+        .fields_named("country_leader")
+        .filter_map(|f| f.value.as_block())
+        .map(|b| CountryLeaderRole {
+            ideology: b.get_str("ideology").map(|s| s.to_string()),
+            traits: trait_list(b),
+            expire: b.get_str("expire").map(|s| s.to_string()),
+        })
+        .collect();
+
+    let advisors = block
+        .fields_named("advisor")
+        .filter_map(|f| f.value.as_block())
+        .map(|b| AdvisorRole {
+            slot: b.get_str("slot").map(|s| s.to_string()),
+            idea_token: b.get_str("idea_token").map(|s| s.to_string()),
+            ledger: b.get_str("ledger").map(|s| s.to_string()),
+            cost: b.get_f64("cost"),
+            traits: trait_list(b),
+        })
+        .collect();
+
+    let mut commanders = Vec::new();
+    for (key, kind) in [
+        ("corps_commander", CommanderKind::CorpsCommander),
+        ("field_marshal", CommanderKind::FieldMarshal),
+        ("navy_leader", CommanderKind::NavyLeader),
+    ] {
+        for b in block.fields_named(key).filter_map(|f| f.value.as_block()) {
+            commanders.push(CommanderRole {
+                kind,
+                skill: b.get_f64("skill"),
+                attack_skill: b.get_f64("attack_skill"),
+                defense_skill: b.get_f64("defense_skill"),
+                planning_skill: b.get_f64("planning_skill"),
+                logistics_skill: b.get_f64("logistics_skill"),
+                maneuvering_skill: b.get_f64("maneuvering_skill"),
+                coordination_skill: b.get_f64("coordination_skill"),
+                traits: trait_list(b),
+            });
+        }
+    }
+
+    CharacterEntry {
+        id: field.key.as_str().to_string(),
+        name: block.get_str("name").map(|s| s.to_string()),
+        portraits,
+        country_leader,
+        advisors,
+        commanders,
+        source,
+        file: file.to_string(),
+        start: field.span.start,
+        end: field.span.end,
+        line: index.line(field.span.start),
+    }
+}
+
+/// ：任意层级（含日期块、`if` 块）中的 `recruit_character = id`。
+fn parse_recruitments(file: &str, content: &str, source: RootSource) -> Vec<Recruitment> {
+    let doc = clausewitz::parse(content);
+    let index = LineIndex::new(content);
+    let mut recruitments = Vec::new();
+    doc.root.visit_fields(&mut |field, _| {
+        if !field.key_is("recruit_character") {
+            return;
+        }
+        let Some(value) = field.value.as_scalar() else { return };
+        recruitments.push(Recruitment {
+            id: value.as_str().to_string(),
+            source,
+            file: file.to_string(),
+            start: value.span.start,
+            end: value.span.end,
+            line: index.line(value.span.start),
+        });
+    });
+    recruitments
+}
+
+// ==================== 立绘 ====================
+
+/// ：纹理路径（`gfx/leaders/...dds`）而不是 GFX 名称。
+fn is_texture_path(value: &str) -> bool {
+    let lower = value.to_lowercase();
+    value.contains('/') || value.contains('\\') || [".dds", ".tga", ".png"].iter().any(|ext| lower.ends_with(ext))
+}
+
+/// ：纹理路径按根目录优先级取第一个存在的文件；GFX 名称取 sprite 目录中的纹理。
+fn resolve_portraits(characters: &mut [CharacterEntry], roots: &[PathBuf], sprites: &HashMap<String, String>) {
+    for portrait in characters.iter_mut().flat_map(|c| c.portraits.iter_mut()) {
+        portrait.texture = if is_texture_path(&portrait.value) {
+            let relative = portrait.value.replace('\\', "/");
+            roots
+                .iter()
+                .map(|root| root.join(&relative))
+                .find(|path| path.is_file())
+                .map(|path| path.to_string_lossy().replace('\\', "/"))
+        } else {
+            sprites.get(&portrait.value).cloned()
+        };
+    }
+}
+
+// ==================== 警告 ====================
+
+fn collect_warnings(loaded: &LoadedCharacters) -> Vec<CharacterWarning> {
+    let defined: HashSet<&str> = loaded.characters.iter().map(|c| c.id.as_str()).collect();
+    loaded
+        .recruitments
+        .iter()
+        .filter(|r| r.source != RootSource::Game && !defined.contains(r.id.as_str()))
+        .map(|r| CharacterWarning {
+            kind: CharacterWarningKind::UndefinedCharacter,
+            id: r.id.clone(),
+            message: format!("招募的角色 {} 未定义", r.id),
+            file: r.file.clone(),
+            start: r.start,
+            end: r.end,
+            line: r.line,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHARACTERS: &str = r#"
+characters = {
+    GER_leader = {
+        name = GER_leader
+        portraits = {
+            civilian = { large = "gfx/leaders/GER/leader.dds" small = GFX_idea_leader }
+            army = { large = GFX_portrait_leader_army }
+        }
+        country_leader = { ideology = nazism traits = { dictator } expire = "1965.1.1.1" }
+        advisor = { slot = political_advisor idea_token = GER_leader_advisor cost = 150 traits = { fascist_demagogue } }
+        field_marshal = { skill = 4 attack_skill = 3 defense_skill = 4 planning_skill = 5 logistics_skill = 2 traits = { } }
+        navy_leader = { skill = 2 maneuvering_skill = 3 coordination_skill = 1 }
+    }
+}
+"#;
+
+    #[test]
+    fn test_parse_character() {
+        let characters = parse_characters("/mod/common/characters/GER.txt", CHARACTERS, RootSource::Project);
+        assert_eq!(characters.len(), 1);
+        let c = &characters[0];
+        assert_eq!(c.id, "GER_leader");
+        assert_eq!(c.portraits.len(), 3);
+        assert_eq!(c.portraits[1].category, PortraitCategory::Civilian);
+        assert_eq!(c.portraits[1].size, "small");
+        assert_eq!(c.portraits[2].category, PortraitCategory::Army);
+        assert!(is_texture_path(&c.portraits[0].value));
+        assert!(!is_texture_path(&c.portraits[1].value));
+        assert_eq!(c.country_leader[0].ideology.as_deref(), Some("nazism"));
+        assert_eq!(c.country_leader[0].traits, vec!["dictator"]);
+        assert_eq!(c.advisors[0].slot.as_deref(), Some("political_advisor"));
+        assert_eq!(c.advisors[0].cost, Some(150.0));
+        let kinds: Vec<CommanderKind> = c.commanders.iter().map(|r| r.kind).collect();
+        assert_eq!(kinds, vec![CommanderKind::FieldMarshal, CommanderKind::NavyLeader]);
+        assert_eq!(c.commanders[0].planning_skill, Some(5.0));
+        assert_eq!(c.commanders[1].coordination_skill, Some(1.0));
+    }
+
+    #[test]
+    fn test_undefined_recruitment() {
+        let history = "capital = 64\nrecruit_character = GER_leader\n1939.1.1 = { recruit_character = GER_missing }\n";
+        let loaded = LoadedCharacters {
+            characters: parse_characters("/mod/common/characters/GER.txt", CHARACTERS, RootSource::Project),
+            recruitments: parse_recruitments("/mod/history/countries/GER.txt", history, RootSource::Project),
+        };
+        assert_eq!(loaded.recruitments.len(), 2);
+        let warnings = collect_warnings(&loaded);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].id, "GER_missing");
+        assert_eq!(warnings[0].line, 3);
+    }
+
+    #[test]
+    fn test_portrait_sprites_follow_gfx_changes() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        let characters = root.join("common/characters");
+        fs::create_dir_all(&characters).expect("create dir");
+        fs::write(characters.join("GER.txt"), CHARACTERS).expect("write characters");
+        let project = root.to_string_lossy().to_string();
+        let small_texture = |response: CharacterLoadResponse| {
+            let characters = response.characters.expect("characters");
+            characters[0].portraits[1].texture.clone()
+        };
+
+        assert_eq!(small_texture(load_characters(Some(project.clone()), None, None)), None);
+
+        // 角色文件不变，只新增 .gfx 时也要重新解析立绘
+        let gfx = root.join("interface");
+        fs::create_dir_all(&gfx).expect("create dir");
+        fs::write(
+            gfx.join("leaders.gfx"),
+            "spriteTypes = {\n\tspriteType = {\n\t\tname = \"GFX_idea_leader\"\n\t\ttexturefile = \"gfx/leaders/small.dds\"\n\t}\n}\n",
+        )
+        .expect("write gfx");
+        let texture = small_texture(load_characters(Some(project), None, None)).expect("texture");
+        assert!(texture.ends_with("gfx/leaders/small.dds"), "{}", texture);
+    }
+}
@@ -0,0 +1,858 @@
+#![deny(clippy::unwrap_used)]
+
+//! Clausewitz 脚本解析器
+//!
+//! 为 HOI4 的 `.txt` / `.gui` / `.gfx` / `.mod` 脚本生成无损语法树：
+//! 空白与注释作为独立节点保留，所有节点都带有字节区间，
+//! 顶层节点首尾相接即可还原原文。各后端模块统一基于此解析，
+//! 避免字符串或注释中的花括号破坏提取结果。
+
+use serde::Serialize;
+
+/// 源文本中的字节区间 `[start, end)`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[allow(dead_code)]
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 判断偏移量是否落在区间内（含结束位置，便于光标命中）。
+    pub fn contains(&self, offset: usize) -> bool {
+        offset >= self.start && offset <= self.end
+    }
+
+    /// 取出区间对应的原文，越界时返回空串。
+    pub fn slice<'a>(&self, source: &'a str) -> &'a str {
+        source.get(self.start..self.end).unwrap_or("")
+    }
+}
+
+/// 赋值 / 比较运算符。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Operator {
+    /// `=`
+    Eq,
+    /// `==`
+    EqEq,
+    /// `!=`
+    NotEq,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `?=`
+    Exists,
+}
+
+#[allow(dead_code)]
+impl Operator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Eq => "=",
+            Operator::EqEq => "==",
+            Operator::NotEq => "!=",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+            Operator::Exists => "?=",
+        }
+    }
+}
+
+/// 标量值：带引号或不带引号的文本、数字、`@变量` 等。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scalar {
+    /// 去掉引号后的文本（转义序列保持原样）
+    pub text: String,
+    pub quoted: bool,
+    pub span: Span,
+}
+
+#[allow(dead_code)]
+impl Scalar {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// 是否为 `@variable` 或 `@[ ... ]` 形式的脚本变量。
+    pub fn is_variable(&self) -> bool {
+        !self.quoted && self.text.starts_with('@')
+    }
+
+    pub fn eq_ignore_case(&self, other: &str) -> bool {
+        self.text.eq_ignore_ascii_case(other)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.text.trim().parse::<f64>().ok()
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        let trimmed = self.text.trim();
+        trimmed
+            .parse::<i64>()
+            .ok()
+            .or_else(|| trimmed.parse::<f64>().ok().map(|f| f.round() as i64))
+    }
+
+    /// `yes` / `no` 布尔值。
+    pub fn as_bool(&self) -> Option<bool> {
+        if self.eq_ignore_case("yes") {
+            Some(true)
+        } else if self.eq_ignore_case("no") {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// 颜色字面量 `rgb { r g b }` / `hsv { h s v }`。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorLiteral {
+    pub model: Scalar,
+    pub block: Block,
+    pub span: Span,
+}
+
+#[allow(dead_code)]
+impl ColorLiteral {
+    /// 依次返回块内的数值分量。
+    pub fn components(&self) -> Vec<f64> {
+        self.block.scalars().filter_map(|s| s.as_f64()).collect()
+    }
+}
+
+/// 花括号块，根节点同样用 Block 表示（此时没有括号）。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Block {
+    pub nodes: Vec<Node>,
+    pub span: Span,
+    /// `{` 的位置，根节点为 None
+    pub open: Option<Span>,
+    /// `}` 的位置，未闭合或根节点为 None
+    pub close: Option<Span>,
+}
+
+/// 值：标量、块或颜色字面量。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Value {
+    Scalar(Scalar),
+    Block(Block),
+    Color(ColorLiteral),
+}
+
+/// `key op value` 形式的条目。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Field {
+    pub key: Scalar,
+    pub operator: Operator,
+    pub operator_span: Span,
+    pub value: Value,
+    pub span: Span,
+    /// 夹在键、运算符和值之间的注释
+    pub comments: Vec<Span>,
+}
+
+/// 语法树节点。同一层级的节点按顺序首尾相接。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Node {
+    Whitespace(Span),
+    Comment(Span),
+    Field(Field),
+    /// 数组中的裸值，例如 `provinces = { 1 2 3 }` 中的数字
+    Value(Value),
+    /// 无法识别的片段（多余的 `}`、缺值的赋值等），仍保留原文
+    Error(Span),
+}
+
+/// 解析错误。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseError {
+    pub span: Span,
+    pub message: String,
+}
+
+/// 完整文档。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Document {
+    pub root: Block,
+    pub errors: Vec<ParseError>,
+}
+
+#[allow(dead_code)]
+impl Value {
+    pub fn span(&self) -> Span {
+        match self {
+            Value::Scalar(s) => s.span,
+            Value::Block(b) => b.span,
+            Value::Color(c) => c.span,
+        }
+    }
+
+    pub fn as_scalar(&self) -> Option<&Scalar> {
+        match self {
+            Value::Scalar(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_scalar().map(|s| s.as_str())
+    }
+
+    pub fn as_block(&self) -> Option<&Block> {
+        match self {
+            Value::Block(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_color(&self) -> Option<&ColorLiteral> {
+        match self {
+            Value::Color(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Node {
+    pub fn span(&self) -> Span {
+        match self {
+            Node::Whitespace(s) | Node::Comment(s) | Node::Error(s) => *s,
+            Node::Field(f) => f.span,
+            Node::Value(v) => v.span(),
+        }
+    }
+
+    pub fn as_field(&self) -> Option<&Field> {
+        match self {
+            Node::Field(f) => Some(f),
+            _ => None,
+        }
+    }
+}
+
+impl Field {
+    pub fn key_is(&self, key: &str) -> bool {
+        self.key.eq_ignore_case(key)
+    }
+}
+
+#[allow(dead_code)]
+impl Block {
+    /// 直接子级中的全部字段。
+    pub fn fields(&self) -> impl Iterator<Item = &Field> {
+        self.nodes.iter().filter_map(Node::as_field)
+    }
+
+    /// 直接子级中的裸值。
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.nodes.iter().filter_map(|n| match n {
+            Node::Value(v) => Some(v),
+            _ => None,
+        })
+    }
+
+    /// 直接子级中的裸标量，常用于 `{ A B C }` 列表。
+    pub fn scalars(&self) -> impl Iterator<Item = &Scalar> {
+        self.values().filter_map(Value::as_scalar)
+    }
+
+    /// 按键名（忽略大小写）查找第一个字段。
+    pub fn field(&self, key: &str) -> Option<&Field> {
+        self.fields().find(|f| f.key_is(key))
+    }
+
+    /// 按键名（忽略大小写）查找全部字段。
+    pub fn fields_named<'a, 'k>(&'a self, key: &'k str) -> impl Iterator<Item = &'a Field> + 'k
+    where
+        'a: 'k,
+    {
+        self.fields().filter(move |f| f.key_is(key))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.field(key).map(|f| &f.value)
+    }
+
+    pub fn get_str<'a>(&'a self, key: &str) -> Option<&'a str> {
+        self.fields_named(key).find_map(|f| f.value.as_str())
+    }
+
+    pub fn get_block<'a>(&'a self, key: &str) -> Option<&'a Block> {
+        self.fields_named(key).find_map(|f| f.value.as_block())
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.fields_named(key)
+            .find_map(|f| f.value.as_scalar().and_then(Scalar::as_f64))
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.fields_named(key)
+            .find_map(|f| f.value.as_scalar().and_then(Scalar::as_bool))
+    }
+
+    /// 块内容区间（不含花括号）。
+    pub fn inner_span(&self) -> Span {
+        let start = self.open.map(|s| s.end).unwrap_or(self.span.start);
+        let end = self.close.map(|s| s.start).unwrap_or(self.span.end);
+        Span::new(start, end.max(start))
+    }
+
+    /// 前序遍历所有字段（含嵌套块与颜色块内的字段），回调同时获得祖先字段链。
+    pub fn visit_fields<'a, F>(&'a self, visit: &mut F)
+    where
+        F: FnMut(&'a Field, &[&'a Field]),
+    {
+        let mut ancestors: Vec<&'a Field> = Vec::new();
+        visit_block(self, &mut ancestors, visit);
+    }
+}
+
+fn visit_block<'a, F>(block: &'a Block, ancestors: &mut Vec<&'a Field>, visit: &mut F)
+where
+    F: FnMut(&'a Field, &[&'a Field]),
+{
+    for node in &block.nodes {
+        match node {
+            Node::Field(field) => {
+                visit(field, ancestors);
+                let child = match &field.value {
+                    Value::Block(b) => Some(b),
+                    Value::Color(c) => Some(&c.block),
+                    Value::Scalar(_) => None,
+                };
+                if let Some(b) = child {
+                    ancestors.push(field);
+                    visit_block(b, ancestors, visit);
+                    ancestors.pop();
+                }
+            }
+            Node::Value(Value::Block(b)) => visit_block(b, ancestors, visit),
+            _ => {}
+        }
+    }
+}
+
+/// 行号索引，将字节偏移转换为 1 起始的行列号。
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+#[allow(dead_code)]
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let mut starts = vec![0usize];
+        for (idx, b) in source.bytes().enumerate() {
+            if b == b'\n' {
+                starts.push(idx + 1);
+            }
+        }
+        LineIndex { starts }
+    }
+
+    /// 1 起始的行号。
+    pub fn line(&self, offset: usize) -> usize {
+        match self.starts.binary_search(&offset) {
+            Ok(idx) => idx + 1,
+            Err(idx) => idx,
+        }
+    }
+
+    /// 1 起始的行号与列号（列按字符计）。
+    pub fn line_col(&self, source: &str, offset: usize) -> (usize, usize) {
+        let line = self.line(offset);
+        let line_start = self.starts.get(line - 1).copied().unwrap_or(0);
+        let col = source
+            .get(line_start..offset.min(source.len()))
+            .map(|s| s.chars().count())
+            .unwrap_or(0);
+        (line, col + 1)
+    }
+
+    /// 指定行（1 起始）的起始偏移。
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.starts.get(line.checked_sub(1)?).copied()
+    }
+}
+
+/// 解析脚本文本。
+pub fn parse(source: &str) -> Document {
+    let tokens = tokenize(source);
+    let mut parser = Parser {
+        source,
+        tokens,
+        pos: 0,
+        errors: Vec::new(),
+    };
+    let (nodes, _) = parser.parse_nodes(false);
+    Document {
+        root: Block {
+            nodes,
+            span: Span::new(0, source.len()),
+            open: None,
+            close: None,
+        },
+        errors: parser.errors,
+    }
+}
+
+// ==================== 词法分析 ====================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Whitespace,
+    Comment,
+    Open,
+    Close,
+    Op(Operator),
+    Quoted { terminated: bool },
+    Bare,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    kind: TokenKind,
+    span: Span,
+}
+
+impl Token {
+    fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenKind::Whitespace | TokenKind::Comment)
+    }
+}
+
+fn operator_at(bytes: &[u8], i: usize) -> Option<(Operator, usize)> {
+    let next = bytes.get(i + 1).copied();
+    match (bytes[i], next) {
+        (b'=', Some(b'=')) => Some((Operator::EqEq, 2)),
+        (b'=', _) => Some((Operator::Eq, 1)),
+        (b'<', Some(b'=')) => Some((Operator::Le, 2)),
+        (b'<', _) => Some((Operator::Lt, 1)),
+        (b'>', Some(b'=')) => Some((Operator::Ge, 2)),
+        (b'>', _) => Some((Operator::Gt, 1)),
+        (b'!', Some(b'=')) => Some((Operator::NotEq, 2)),
+        (b'?', Some(b'=')) => Some((Operator::Exists, 2)),
+        _ => None,
+    }
+}
+
+fn is_bare_delimiter(bytes: &[u8], i: usize) -> bool {
+    let b = bytes[i];
+    b.is_ascii_whitespace()
+        || matches!(b, b'{' | b'}' | b'#' | b'"')
+        || operator_at(bytes, i).is_some()
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let bytes = source.as_bytes();
+    let len = bytes.len();
+    let mut tokens = Vec::new();
+    let mut i = 0usize;
+
+    // UTF-8 BOM 视为空白保留
+    if source.starts_with('\u{feff}') {
+        tokens.push(Token {
+            kind: TokenKind::Whitespace,
+            span: Span::new(0, 3),
+        });
+        i = 3;
+    }
+
+    while i < len {
+        let start = i;
+        let b = bytes[i];
+        let kind = if b.is_ascii_whitespace() {
+            while i < len && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            TokenKind::Whitespace
+        } else if b == b'#' {
+            while i < len && bytes[i] != b'\n' && bytes[i] != b'\r' {
+                i += 1;
+            }
+            TokenKind::Comment
+        } else if b == b'{' {
+            i += 1;
+            TokenKind::Open
+        } else if b == b'}' {
+            i += 1;
+            TokenKind::Close
+        } else if b == b'"' {
+            i += 1;
+            let mut terminated = false;
+            while i < len {
+                match bytes[i] {
+                    b'\\' => i += 2,
+                    b'"' => {
+                        i += 1;
+                        terminated = true;
+                        break;
+                    }
+                    b'\n' => break,
+                    _ => i += 1,
+                }
+            }
+            i = i.min(len);
+            TokenKind::Quoted { terminated }
+        } else if let Some((op, width)) = operator_at(bytes, i) {
+            i += width;
+            TokenKind::Op(op)
+        } else if b == b'@' && bytes.get(i + 1) == Some(&b'[') {
+            // 内联数学表达式 @[ a + b ]
+            while i < len && bytes[i] != b']' && bytes[i] != b'\n' {
+                i += 1;
+            }
+            if i < len && bytes[i] == b']' {
+                i += 1;
+            }
+            TokenKind::Bare
+        } else {
+            i += 1;
+            while i < len && !is_bare_delimiter(bytes, i) {
+                i += 1;
+            }
+            TokenKind::Bare
+        };
+        tokens.push(Token {
+            kind,
+            span: Span::new(start, i),
+        });
+    }
+
+    tokens
+}
+
+// ==================== 语法分析 ====================
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    errors: Vec<ParseError>,
+}
+
+fn is_color_model(scalar: &Scalar) -> bool {
+    !scalar.quoted
+        && (scalar.eq_ignore_case("rgb")
+            || scalar.eq_ignore_case("hsv")
+            || scalar.eq_ignore_case("hsv360"))
+}
+
+impl<'a> Parser<'a> {
+    fn error(&mut self, span: Span, message: &str) {
+        self.errors.push(ParseError {
+            span,
+            message: message.to_string(),
+        });
+    }
+
+    fn next_significant(&self, from: usize) -> usize {
+        let mut idx = from;
+        while idx < self.tokens.len() && self.tokens[idx].is_trivia() {
+            idx += 1;
+        }
+        idx
+    }
+
+    fn comments_between(&self, from: usize, to: usize) -> Vec<Span> {
+        self.tokens[from..to]
+            .iter()
+            .filter(|t| t.kind == TokenKind::Comment)
+            .map(|t| t.span)
+            .collect()
+    }
+
+    fn scalar(&mut self, token: Token) -> Scalar {
+        let raw = token.span.slice(self.source);
+        match token.kind {
+            TokenKind::Quoted { terminated } => {
+                if !terminated {
+                    self.error(token.span, "字符串缺少结束引号");
+                }
+                let inner = raw.strip_prefix('"').unwrap_or(raw);
+                let inner = if terminated {
+                    inner.strip_suffix('"').unwrap_or(inner)
+                } else {
+                    inner
+                };
+                Scalar {
+                    text: inner.to_string(),
+                    quoted: true,
+                    span: token.span,
+                }
+            }
+            _ => Scalar {
+                text: raw.to_string(),
+                quoted: false,
+                span: token.span,
+            },
+        }
+    }
+
+    /// 解析节点序列；`nested` 为 true 时遇到 `}` 结束并返回其位置。
+    fn parse_nodes(&mut self, nested: bool) -> (Vec<Node>, Option<Span>) {
+        let mut nodes = Vec::new();
+        while let Some(token) = self.tokens.get(self.pos).copied() {
+            match token.kind {
+                TokenKind::Whitespace => {
+                    nodes.push(Node::Whitespace(token.span));
+                    self.pos += 1;
+                }
+                TokenKind::Comment => {
+                    nodes.push(Node::Comment(token.span));
+                    self.pos += 1;
+                }
+                TokenKind::Close => {
+                    self.pos += 1;
+                    if nested {
+                        return (nodes, Some(token.span));
+                    }
+                    self.error(token.span, "多余的右花括号");
+                    nodes.push(Node::Error(token.span));
+                }
+                TokenKind::Open => {
+                    let block = self.parse_block();
+                    nodes.push(Node::Value(Value::Block(block)));
+                }
+                TokenKind::Op(_) => {
+                    self.error(token.span, "运算符左侧缺少键");
+                    nodes.push(Node::Error(token.span));
+                    self.pos += 1;
+                }
+                TokenKind::Quoted { .. } | TokenKind::Bare => {
+                    let node = self.parse_entry();
+                    nodes.push(node);
+                }
+            }
+        }
+        (nodes, None)
+    }
+
+    /// 当前位置为 `{`。
+    fn parse_block(&mut self) -> Block {
+        let open = self.tokens[self.pos].span;
+        self.pos += 1;
+        let (nodes, close) = self.parse_nodes(true);
+        let end = match close {
+            Some(c) => c.end,
+            None => {
+                self.error(open, "未闭合的花括号");
+                self.source.len()
+            }
+        };
+        Block {
+            nodes,
+            span: Span::new(open.start, end),
+            open: Some(open),
+            close,
+        }
+    }
+
+    /// 当前位置为标量，解析 `key op value`、颜色字面量或裸值。
+    fn parse_entry(&mut self) -> Node {
+        let key_token = self.tokens[self.pos];
+        let key = self.scalar(key_token);
+        let after_key = self.pos + 1;
+        let op_idx = self.next_significant(after_key);
+
+        let op = match self.tokens.get(op_idx).map(|t| t.kind) {
+            Some(TokenKind::Op(op)) => op,
+            Some(TokenKind::Open) if is_color_model(&key) => {
+                self.pos = op_idx;
+                let block = self.parse_block();
+                let span = Span::new(key.span.start, block.span.end);
+                return Node::Value(Value::Color(ColorLiteral {
+                    model: key,
+                    block,
+                    span,
+                }));
+            }
+            _ => {
+                self.pos = after_key;
+                return Node::Value(Value::Scalar(key));
+            }
+        };
+        let operator_span = self.tokens[op_idx].span;
+        let mut comments = self.comments_between(after_key, op_idx);
+
+        let value_idx = self.next_significant(op_idx + 1);
+        comments.extend(self.comments_between(op_idx + 1, value_idx));
+
+        let value = match self.tokens.get(value_idx).copied() {
+            Some(t) if matches!(t.kind, TokenKind::Quoted { .. } | TokenKind::Bare) => {
+                let scalar = self.scalar(t);
+                self.pos = value_idx + 1;
+                let open_idx = self.next_significant(self.pos);
+                let is_color = is_color_model(&scalar)
+                    && self.tokens.get(open_idx).map(|t| t.kind) == Some(TokenKind::Open);
+                if is_color {
+                    comments.extend(self.comments_between(self.pos, open_idx));
+                    self.pos = open_idx;
+                    let block = self.parse_block();
+                    let span = Span::new(scalar.span.start, block.span.end);
+                    Value::Color(ColorLiteral {
+                        model: scalar,
+                        block,
+                        span,
+                    })
+                } else {
+                    Value::Scalar(scalar)
+                }
+            }
+            Some(t) if t.kind == TokenKind::Open => {
+                self.pos = value_idx;
+                Value::Block(self.parse_block())
+            }
+            _ => {
+                let span = Span::new(key.span.start, operator_span.end);
+                self.error(span, "运算符后缺少值");
+                self.pos = op_idx + 1;
+                return Node::Error(span);
+            }
+        };
+
+        let span = Span::new(key.span.start, value.span().end);
+        Node::Field(Field {
+            key,
+            operator: op,
+            operator_span,
+            value,
+            span,
+            comments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_tiles(block: &Block) {
+        let inner = block.inner_span();
+        let mut cursor = inner.start;
+        for node in &block.nodes {
+            let span = node.span();
+            assert_eq!(span.start, cursor, "节点之间不应有缺口");
+            cursor = span.end;
+            if let Node::Field(Field {
+                value: Value::Block(b),
+                ..
+            })
+            | Node::Value(Value::Block(b)) = node
+            {
+                assert_tiles(b);
+            }
+        }
+        assert_eq!(cursor, inner.end);
+    }
+
+    #[test]
+    fn test_lossless_spans() {
+        let src = "\u{feff}# header\nfocus = {\n\tid = GER_focus # trailing\n\tx = 1 y = -2\n}\n";
+        let doc = parse(src);
+        assert!(doc.errors.is_empty());
+        assert_tiles(&doc.root);
+        let rebuilt: String = doc.root.nodes.iter().map(|n| n.span().slice(src)).collect();
+        assert_eq!(rebuilt, src);
+    }
+
+    #[test]
+    fn test_operators_and_variables() {
+        let src = "a ?= b c != 3 d <= @var e >= 2 f == yes g < 1 h > 0 i = @[x + 1]";
+        let doc = parse(src);
+        let ops: Vec<Operator> = doc.root.fields().map(|f| f.operator).collect();
+        assert_eq!(
+            ops,
+            vec![
+                Operator::Exists,
+                Operator::NotEq,
+                Operator::Le,
+                Operator::Ge,
+                Operator::EqEq,
+                Operator::Lt,
+                Operator::Gt,
+                Operator::Eq,
+            ]
+        );
+        let d = doc.root.field("d").and_then(|f| f.value.as_scalar());
+        assert!(d.map(|s| s.is_variable()).unwrap_or(false));
+        assert_eq!(doc.root.get_str("i"), Some("@[x + 1]"));
+    }
+
+    #[test]
+    fn test_braces_in_strings_and_comments() {
+        let src = "a = { name = \"text { with } braces\" # } not closing\n b = 1 }\nc = 2";
+        let doc = parse(src);
+        assert!(doc.errors.is_empty());
+        let a = doc.root.get_block("a").expect("a 应为块");
+        assert_eq!(a.get_str("name"), Some("text { with } braces"));
+        assert_eq!(a.get_str("b"), Some("1"));
+        assert_eq!(doc.root.get_str("c"), Some("2"));
+    }
+
+    #[test]
+    fn test_color_literals_and_lists() {
+        let src = "GER = { color = rgb { 10 20 30 } color_ui = hsv{ 0.1 0.5 0.9 } }\nprovinces = { 1 2 3 }";
+        let doc = parse(src);
+        let ger = doc.root.get_block("GER").expect("GER 应为块");
+        let color = ger.get("color").and_then(Value::as_color).expect("颜色字面量");
+        assert_eq!(color.components(), vec![10.0, 20.0, 30.0]);
+        assert!(ger.get("color_ui").and_then(Value::as_color).is_some());
+        let provinces: Vec<&str> = doc
+            .root
+            .get_block("provinces")
+            .map(|b| b.scalars().map(Scalar::as_str).collect())
+            .unwrap_or_default();
+        assert_eq!(provinces, vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_errors_keep_text() {
+        let src = "a = { b = }\n}\nc = \"open";
+        let doc = parse(src);
+        assert_eq!(doc.errors.len(), 3);
+        assert_tiles(&doc.root);
+    }
+
+    #[test]
+    fn test_line_index() {
+        let src = "a = 1\n中文 = 2\n";
+        let index = LineIndex::new(src);
+        let offset = src.find('2').unwrap_or(0);
+        assert_eq!(index.line_col(src, offset), (2, 6));
+        assert_eq!(index.line(0), 1);
+    }
+}
@@ -1,16 +1,17 @@
 #![deny(clippy::unwrap_used)]
+use crate::clausewitz::{self, Value};
+use crate::file_cache::{FileSetCache, FileStamps};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
-use std::sync::RwLock;
 use std::time::SystemTime;
 
 /// ：表示标签来源（项目、游戏目录或依赖项），用于前端区分来源显示。
-#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum TagSource {
     Project,
@@ -18,13 +19,42 @@ pub enum TagSource {
     Dependency,
 }
 
-/// ：单个国家标签条目，包含标签代码、名称与来源。
-#[derive(Debug, Clone, Serialize)]
+/// ：单个国家标签条目，包含标签代码、名称与来源，以及悬浮提示所需的附加信息。
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TagEntry {
     pub code: String,
     pub name: Option<String>,
     pub source: TagSource,
+    /// 来自 `dynamic_tags = yes` 文件或 `create_dynamic_country` 的动态标签
+    #[serde(default)]
+    pub dynamic: bool,
+    /// `common/countries/colors.txt` 中的地图颜色，统一为 `#rrggbb`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_ui: Option<String>,
+    /// 脚本中通过 `set_cosmetic_tag` 为该国设置过的外观标签
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cosmetic_tags: Vec<String>,
+    /// `history/countries/TAG - Name.txt` 的路径
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_file: Option<String>,
+}
+
+impl TagEntry {
+    fn new(code: String, name: Option<String>, source: TagSource) -> Self {
+        Self {
+            code,
+            name,
+            source,
+            dynamic: false,
+            color: None,
+            color_ui: None,
+            cosmetic_tags: Vec::new(),
+            history_file: None,
+        }
+    }
 }
 
 /// ：命令返回值，包含成功状态、消息及标签数组。
@@ -36,16 +66,8 @@ pub struct TagLoadResponse {
     pub tags: Option<Vec<TagEntry>>,
 }
 
-/// ：记录缓存内容与文件时间戳，用于检测缓存是否仍然有效。
-#[derive(Debug, Clone)]
-struct TagCacheEntry {
-    tags: Vec<TagEntry>,
-    timestamps: HashMap<String, Option<SystemTime>>,
-}
-
 /// ：缓存容器，key 为“项目路径||游戏路径”。
-static TAG_CACHE: Lazy<RwLock<HashMap<String, TagCacheEntry>>> =
-    Lazy::new(|| RwLock::new(HashMap::new()));
+static TAG_CACHE: Lazy<FileSetCache<Vec<TagEntry>>> = Lazy::new(FileSetCache::new);
 
 /// ：解析 `A = "B"` 的正则，允许可选空白。
 static TAG_LINE_REGEX: Lazy<Regex> = Lazy::new(|| {
@@ -53,6 +75,17 @@ static TAG_LINE_REGEX: Lazy<Regex> = Lazy::new(|| {
         .expect("failed to compile tag parser regex")
 });
 
+/// ：`dynamic_tags = yes` 标记整个文件内的标签为动态标签。
+static DYNAMIC_TAGS_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?m)^\s*dynamic_tags\s*=\s*yes\b").expect("failed to compile dynamic tags regex")
+});
+
+/// ：原版为 `create_dynamic_country` 预留的 `D01`…`D50` 槽位数。
+const DYNAMIC_TAG_SLOTS: u32 = 50;
+
+/// ：扫描 `set_cosmetic_tag` / `create_dynamic_country` 的脚本目录（仅项目与依赖项）。
+const SCRIPT_DIRS: [&str; 2] = ["common", "events"];
+
 /// ：命令入口，加载项目、游戏目录与依赖项目录下的全部国家标签，并应用缓存。
 #[tauri::command]
 pub fn load_country_tags(
@@ -62,8 +95,9 @@ pub fn load_country_tags(
 ) -> TagLoadResponse {
     let normalized_project = normalize_root(project_root.as_deref());
     let normalized_game = normalize_root(game_root.as_deref());
+    let dependency_roots =
+        crate::dependency::with_project_dependencies(project_root.as_deref(), dependency_roots);
     let normalized_deps: Vec<String> = dependency_roots
-        .unwrap_or_default()
         .into_iter()
         .filter_map(|s| normalize_root(Some(&s)))
         .collect();
@@ -76,20 +110,11 @@ pub fn load_country_tags(
     );
 
     // ：收集所有候选文件以及其最新修改时间。
-    let file_infos = match collect_file_infos(
+    let file_infos = collect_file_infos(
         normalized_project.as_deref(),
         normalized_game.as_deref(),
         &normalized_deps,
-    ) {
-        Ok(infos) => infos,
-        Err(err) => {
-            return TagLoadResponse {
-                success: false,
-                message: format!("读取标签文件失败: {}", err),
-                tags: None,
-            };
-        }
-    };
+    );
 
     // ：若缓存有效则直接返回。
     if let Some(tags) = try_use_cache(&cache_key, &file_infos) {
@@ -101,30 +126,38 @@ pub fn load_country_tags(
         };
     }
 
-    // ：缓存失效时重新解析所有文件。
-    match parse_tags(&file_infos) {
-        Ok(tags) => {
-            store_cache(&cache_key, &tags, &file_infos);
-            let count = tags.len();
-            TagLoadResponse {
-                success: true,
-                message: format!("重新解析完成，共获取 {} 个标签", count),
-                tags: Some(tags),
-            }
-        }
-        Err(err) => TagLoadResponse {
-            success: false,
-            message: format!("解析标签失败: {}", err),
-            tags: None,
-        },
+    // ：缓存失效时重新解析所有文件，无法读取的文件与目录会被跳过。
+    let tags = parse_tags(&file_infos);
+    store_cache(&cache_key, &tags, &file_infos);
+    let count = tags.len();
+    TagLoadResponse {
+        success: true,
+        message: format!("重新解析完成，共获取 {} 个标签", count),
+        tags: Some(tags),
     }
 }
 
+/// ：候选文件的用途。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TagFileKind {
+    /// `common/country_tags`
+    Tags,
+    /// `common/countries/colors.txt`
+    Colors,
+    /// `history/countries`
+    History,
+    /// 其余脚本，只关心外观标签与动态国家
+    Script,
+    /// 压缩包形式的依赖项，解析时按包内路径分类
+    Archive,
+}
+
 /// ：描述单个候选文件与其来源及修改时间。
 #[derive(Debug, Clone)]
 struct TagFileInfo {
     path: PathBuf,
     source: TagSource,
+    kind: TagFileKind,
     modified: Option<SystemTime>,
 }
 
@@ -143,54 +176,87 @@ fn normalize_root(path: Option<&str>) -> Option<String> {
     }
 }
 
-/// 收集 project/game/dependency 下 `common/country_tags` 内所有 `.txt` 文件。
+/// 收集 project/game/dependency 下的标签、颜色、历史文件，以及项目与依赖项的脚本文件。
 fn collect_file_infos(
     project_root: Option<&str>,
     game_root: Option<&str>,
     dependency_roots: &[String],
-) -> io::Result<Vec<TagFileInfo>> {
+) -> Vec<TagFileInfo> {
     let mut files: Vec<TagFileInfo> = Vec::new();
-    add_files_under_root(project_root, TagSource::Project, &mut files)?;
-    add_files_under_root(game_root, TagSource::Game, &mut files)?;
+    add_files_under_root(project_root, TagSource::Project, &mut files);
+    add_files_under_root(game_root, TagSource::Game, &mut files);
     // 添加所有依赖项的标签文件
     for dep_root in dependency_roots {
-        add_files_under_root(Some(dep_root.as_str()), TagSource::Dependency, &mut files)?;
+        add_files_under_root(Some(dep_root.as_str()), TagSource::Dependency, &mut files);
     }
 
     // ：若所有目录均不存在，则返回空列表以免误报。
-    Ok(files)
+    files
 }
 
-/// 帮助函数，向列表填充某根路径下的所有标签文件。
+/// 帮助函数，向列表填充某根路径下的所有候选文件。
 fn add_files_under_root(
     root: Option<&str>,
     source: TagSource,
     out: &mut Vec<TagFileInfo>,
-) -> io::Result<()> {
-    if let Some(root_path) = root {
-        let dir = Path::new(root_path).join("common/country_tags");
-        if dir.exists() {
-            let mut stack = vec![dir];
-            while let Some(current) = stack.pop() {
-                let entries = fs::read_dir(&current)?;
-                for entry in entries {
-                    let entry = entry?;
-                    let path = entry.path();
-                    if path.is_dir() {
-                        stack.push(path);
-                    } else if is_script_file(&path) {
-                        let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
-                        out.push(TagFileInfo {
-                            path,
-                            source,
-                            modified,
-                        });
-                    }
+) {
+    let Some(root_path) = root else {
+        return;
+    };
+    let root = Path::new(root_path);
+    if crate::dependency::is_archive(root) {
+        let modified = fs::metadata(root).ok().and_then(|m| m.modified().ok());
+        out.push(TagFileInfo { path: root.to_path_buf(), source, kind: TagFileKind::Archive, modified });
+        return;
+    }
+    add_files_in_dir(&root.join("common/country_tags"), source, TagFileKind::Tags, out);
+    add_files_in_dir(&root.join("history/countries"), source, TagFileKind::History, out);
+    let colors = root.join("common/countries/colors.txt");
+    if colors.is_file() {
+        let modified = fs::metadata(&colors).ok().and_then(|m| m.modified().ok());
+        out.push(TagFileInfo { path: colors, source, kind: TagFileKind::Colors, modified });
+    }
+    // 游戏本体脚本量大且不会引入新的外观标签，只扫描项目与依赖项
+    if source != TagSource::Game {
+        for dir in SCRIPT_DIRS {
+            add_files_in_dir(&root.join(dir), source, TagFileKind::Script, out);
+        }
+    }
+}
+
+/// 递归收集目录下的 `.txt` 文件；脚本目录会跳过已按其他用途收集的子目录。
+fn add_files_in_dir(
+    dir: &Path,
+    source: TagSource,
+    kind: TagFileKind,
+    out: &mut Vec<TagFileInfo>,
+) {
+    if !dir.exists() {
+        return;
+    }
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        // 单个目录或条目无法读取时跳过，不影响其余文件
+        let Ok(entries) = fs::read_dir(&current) else { continue };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                let skipped = kind == TagFileKind::Script
+                    && (path.ends_with("country_tags") || path.ends_with("countries"));
+                if !skipped {
+                    stack.push(path);
                 }
+            } else if is_script_file(&path) {
+                let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+                out.push(TagFileInfo {
+                    path,
+                    source,
+                    kind,
+                    modified,
+                });
             }
         }
     }
-    Ok(())
 }
 
 /// 判断是否为脚本文本文件（扩展名 .txt）。
@@ -203,83 +269,193 @@ fn is_script_file(path: &Path) -> bool {
 
 /// 尝试使用缓存，若缓存仍然有效则返回克隆后的标签集合。
 fn try_use_cache(cache_key: &str, files: &[TagFileInfo]) -> Option<Vec<TagEntry>> {
-    let cache_map = TAG_CACHE.read().ok()?;
-    let cached = cache_map.get(cache_key)?;
+    TAG_CACHE.get(cache_key, &file_stamps(files)).map(|cached| cached.to_vec())
+}
 
-    // ：校验文件数量与时间戳是否完全匹配。
-    if !is_cache_valid(cached, files) {
-        return None;
-    }
-    Some(cached.tags.clone())
+/// ：缓存校验用的文件路径与时间戳。
+fn file_stamps(files: &[TagFileInfo]) -> FileStamps {
+    files
+        .iter()
+        .map(|info| (info.path.to_string_lossy().into_owned(), info.modified))
+        .collect()
 }
 
-/// 检测缓存记录与当前文件列表是否一致。
-fn is_cache_valid(entry: &TagCacheEntry, files: &[TagFileInfo]) -> bool {
-    if entry.timestamps.len() != files.len() {
-        return false;
+/// ：来源优先级，数值越大越优先 (Project > Dependency > Game)。
+fn source_priority(source: TagSource) -> u8 {
+    match source {
+        TagSource::Game => 0,
+        TagSource::Dependency => 1,
+        TagSource::Project => 2,
     }
-    for info in files {
-        let path_str = match info.path.to_str() {
-            Some(v) => v,
-            None => return false,
-        };
-        match entry.timestamps.get(path_str) {
-            Some(stored) => {
-                if &info.modified != stored {
-                    return false;
-                }
-            }
-            None => return false,
-        }
-    }
-    true
+}
+
+/// ：`set_cosmetic_tag` 的一次出现，`scopes` 为可能的所属国家（由内向外）。
+#[derive(Debug)]
+struct CosmeticTag {
+    scopes: Vec<String>,
+    name: String,
+}
+
+/// ：单个文件中提取到的信息，合并时按来源优先级处理。
+#[derive(Debug, Default)]
+struct FileFacts {
+    tags: Vec<TagEntry>,
+    colors: Vec<(String, Option<String>, Option<String>)>,
+    history_tag: Option<String>,
+    cosmetic_tags: Vec<CosmeticTag>,
+    creates_dynamic_country: bool,
+}
+
+/// 读取文本文件，兼容带 BOM 或非 UTF-8 编码的游戏文件。
+fn read_text(path: &Path) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    Ok(crate::decode_text_bytes(&bytes).map(|(text, _)| text).unwrap_or_default())
 }
 
 /// 解析所有文件内容并返回去重后的标签列表。使用并行读取以提升效率。
-fn parse_tags(files: &[TagFileInfo]) -> io::Result<Vec<TagEntry>> {
+fn parse_tags(files: &[TagFileInfo]) -> Vec<TagEntry> {
     use rayon::prelude::*;
 
-    // 并行解析所有文件的内容
-    let parsed_results: Result<Vec<Vec<TagEntry>>, io::Error> = files
+    // 并行解析所有文件的内容，得到 (来源, 文件路径, 提取结果)
+    // 无法读取的文件直接跳过
+    let mut parsed: Vec<(TagSource, String, FileFacts)> = files
         .par_iter()
-        .map(|info| {
-            let content = fs::read_to_string(&info.path)?;
-            Ok(extract_tags(&content, info.source))
+        .flat_map_iter(|info| {
+            let contents: Vec<(TagFileKind, String, String)> = match info.kind {
+                TagFileKind::Archive => archive_contents(&info.path),
+                kind => read_text(&info.path)
+                    .map(|content| vec![(kind, info.path.to_string_lossy().replace('\\', "/"), content)])
+                    .unwrap_or_default(),
+            };
+            contents.into_iter().map(|(kind, path, content)| {
+                let facts = file_facts(kind, info.source, &path, &content);
+                (info.source, path, facts)
+            })
         })
         .collect();
+    // 稳定排序后按顺序覆盖，高优先级来源的定义最后写入
+    parsed.sort_by_key(|(source, _, _)| source_priority(*source));
 
-    let all_tags_lists = parsed_results?;
-    
-    // 合并结果，按来源优先级去重 (Project > Dependency > Game)
-    let mut grouped: HashMap<String, (TagEntry, u8)> = HashMap::new();
-    
-    for list in all_tags_lists {
-        for entry in list {
-            let priority = match entry.source {
-                TagSource::Game => 0u8,
-                TagSource::Dependency => 1u8,
-                TagSource::Project => 2u8,
-            };
-            
-            grouped.entry(entry.code.clone())
-                .and_modify(|(existing_entry, existing_priority)| {
-                    if priority >= *existing_priority {
-                        *existing_entry = entry.clone();
-                        *existing_priority = priority;
-                    }
-                })
-                .or_insert((entry, priority));
+    let mut grouped: HashMap<String, TagEntry> = HashMap::new();
+    for (_, _, facts) in &parsed {
+        for entry in &facts.tags {
+            grouped.insert(entry.code.clone(), entry.clone());
         }
     }
 
-    let mut tags: Vec<TagEntry> = grouped.into_values().map(|(entry, _)| entry).collect();
+    // 脚本创建了动态国家但没有文件声明动态标签时（例如未设置游戏目录），补齐原版预留的槽位
+    let creator = parsed
+        .iter()
+        .rev()
+        .find(|(_, _, facts)| facts.creates_dynamic_country)
+        .map(|(source, _, _)| *source);
+    if let Some(source) = creator.filter(|_| !grouped.values().any(|entry| entry.dynamic)) {
+        for slot in 1..=DYNAMIC_TAG_SLOTS {
+            let code = format!("D{:02}", slot);
+            grouped.entry(code.clone()).or_insert_with(|| TagEntry {
+                dynamic: true,
+                ..TagEntry::new(code, None, source)
+            });
+        }
+    }
+
+    for (_, path, facts) in &parsed {
+        for (code, color, color_ui) in &facts.colors {
+            if let Some(entry) = grouped.get_mut(code) {
+                if color.is_some() {
+                    entry.color = color.clone();
+                }
+                if color_ui.is_some() {
+                    entry.color_ui = color_ui.clone();
+                }
+            }
+        }
+        if let Some(entry) = facts.history_tag.as_ref().and_then(|code| grouped.get_mut(code)) {
+            entry.history_file = Some(path.clone());
+        }
+        for cosmetic in &facts.cosmetic_tags {
+            // 找不到所属作用域时，按 `GER_xxx` 的命名惯例取前缀
+            let prefix = cosmetic.name.split_once('_').map(|(prefix, _)| prefix.to_uppercase());
+            let owner = cosmetic
+                .scopes
+                .iter()
+                .find(|code| grouped.contains_key(*code))
+                .cloned()
+                .or(prefix.filter(|code| grouped.contains_key(code)));
+            if let Some(entry) = owner.and_then(|code| grouped.get_mut(&code)) {
+                entry.cosmetic_tags.push(cosmetic.name.clone());
+            }
+        }
+    }
+
+    let mut tags: Vec<TagEntry> = grouped.into_values().collect();
+    for entry in &mut tags {
+        entry.cosmetic_tags.sort();
+        entry.cosmetic_tags.dedup();
+    }
     // 按代码字母顺序排序
     tags.sort_by(|a, b| a.code.cmp(&b.code));
-    Ok(tags)
+    tags
+}
+
+/// ：按用途解析单个文件内容。
+fn file_facts(kind: TagFileKind, source: TagSource, path: &str, content: &str) -> FileFacts {
+    match kind {
+        TagFileKind::Tags => FileFacts {
+            tags: extract_tags(content, source),
+            ..FileFacts::default()
+        },
+        TagFileKind::Colors => FileFacts {
+            colors: extract_colors(content),
+            ..FileFacts::default()
+        },
+        TagFileKind::History => {
+            let history_tag = history_file_tag(Path::new(path));
+            let mut facts = extract_script_facts(content, history_tag.as_deref());
+            facts.history_tag = history_tag;
+            facts
+        }
+        TagFileKind::Script => extract_script_facts(content, None),
+        TagFileKind::Archive => FileFacts::default(),
+    }
+}
+
+/// ：压缩包内参与解析的文件，按包内路径分类，返回 (用途, 显示路径, 内容)。
+fn archive_contents(archive: &Path) -> Vec<(TagFileKind, String, String)> {
+    let kind_of = |relative: &str| {
+        if !relative.ends_with(".txt") {
+            None
+        } else if relative.starts_with("common/country_tags/") {
+            Some(TagFileKind::Tags)
+        } else if relative == "common/countries/colors.txt" {
+            Some(TagFileKind::Colors)
+        } else if relative.starts_with("history/countries/") {
+            Some(TagFileKind::History)
+        } else if relative.starts_with("common/countries/") {
+            None
+        } else if SCRIPT_DIRS.iter().any(|dir| relative.starts_with(&format!("{}/", dir))) {
+            Some(TagFileKind::Script)
+        } else {
+            None
+        }
+    };
+    // 无法打开的压缩包与目录一样跳过
+    let Ok(entries) = crate::dependency::read_archive_entries(archive, |relative| kind_of(relative).is_some()) else {
+        return Vec::new();
+    };
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            let kind = kind_of(&entry.relative)?;
+            let content = crate::decode_text_bytes(&entry.bytes).map(|(text, _)| text).unwrap_or_default();
+            Some((kind, entry.path, content))
+        })
+        .collect()
 }
 
 /// 从单个文件内容中提取全部标签。
-fn extract_tags(content: &str, source: TagSource) -> Vec<TagEntry> {
+pub(crate) fn extract_tags(content: &str, source: TagSource) -> Vec<TagEntry> {
+    let dynamic = DYNAMIC_TAGS_REGEX.is_match(content);
     content
         .lines()
         .filter_map(|line| {
@@ -291,30 +467,190 @@ fn extract_tags(content: &str, source: TagSource) -> Vec<TagEntry> {
                     .unwrap_or_default();
                 let name = caps.get(2).map(|m| m.as_str().trim().to_string());
                 TagEntry {
-                    code,
-                    name,
-                    source,
+                    dynamic,
+                    ..TagEntry::new(code, name, source)
                 }
             })
         })
         .collect()
 }
 
-/// 写入缓存，确保出现异常时不会破坏原缓存。
-fn store_cache(key: &str, tags: &[TagEntry], files: &[TagFileInfo]) {
-    if let Ok(mut cache) = TAG_CACHE.write() {
-        let mut timestamps = HashMap::new();
-        for info in files {
-            if let Some(path_str) = info.path.to_str() {
-                timestamps.insert(path_str.to_string(), info.modified);
+/// 形如国家标签的键：3 位大写字母或数字，首位为字母。
+fn is_tag_code(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 3
+        && bytes[0].is_ascii_uppercase()
+        && bytes.iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
+
+/// 从 `history/countries/GER - Germany.txt` 文件名中取出标签。
+fn history_file_tag(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let code = stem.split_once('-').map(|(code, _)| code).unwrap_or(stem).trim().to_uppercase();
+    is_tag_code(&code).then_some(code)
+}
+
+/// 解析 `colors.txt`：`GER = { color = rgb { ... } color_ui = rgb { ... } }`。
+fn extract_colors(content: &str) -> Vec<(String, Option<String>, Option<String>)> {
+    let doc = clausewitz::parse(content);
+    doc.root
+        .fields()
+        .filter_map(|field| {
+            let block = field.value.as_block()?;
+            let color_of = |key: &str| block.field(key).and_then(|f| color_hex(&f.value));
+            Some((field.key.as_str().to_uppercase(), color_of("color"), color_of("color_ui")))
+        })
+        .collect()
+}
+
+/// 将 `rgb` / `hsv` / `hsv360` 颜色值转换为 `#rrggbb`，省略颜色模型时按 rgb 处理。
+fn color_hex(value: &Value) -> Option<String> {
+    let (model, components) = match value {
+        Value::Color(color) => (color.model.as_str().to_ascii_lowercase(), color.components()),
+        Value::Block(block) => ("rgb".to_string(), block.scalars().filter_map(|s| s.as_f64()).collect()),
+        _ => return None,
+    };
+    let [a, b, c] = components.get(..3)? else {
+        return None;
+    };
+    let (r, g, b) = match model.as_str() {
+        "hsv" => hsv_to_rgb(*a, *b, *c),
+        "hsv360" => hsv_to_rgb(a / 360.0, b / 100.0, c / 100.0),
+        _ => (*a, *b, *c),
+    };
+    let channel = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+    Some(format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b)))
+}
+
+/// HSV（各分量 0–1）转 RGB（0–255）。
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> (f64, f64, f64) {
+    let h = (h.fract() + 1.0).fract() * 6.0;
+    let c = v * s;
+    let x = c * (1.0 - ((h % 2.0) - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    ((r + m) * 255.0, (g + m) * 255.0, (b + m) * 255.0)
+}
+
+/// 从脚本中收集 `set_cosmetic_tag` 与 `create_dynamic_country`。
+/// `default_owner` 为历史文件对应的国家，作为最外层作用域。
+fn extract_script_facts(content: &str, default_owner: Option<&str>) -> FileFacts {
+    let mut facts = FileFacts::default();
+    if !content.contains("set_cosmetic_tag") && !content.contains("create_dynamic_country") {
+        return facts;
+    }
+    let doc = clausewitz::parse(content);
+    doc.root.visit_fields(&mut |field, ancestors| {
+        if field.key_is("set_cosmetic_tag") {
+            if let Some(name) = field.value.as_str() {
+                let scopes = ancestors
+                    .iter()
+                    .rev()
+                    .map(|parent| parent.key.as_str())
+                    .filter(|key| is_tag_code(key))
+                    .chain(default_owner)
+                    .map(str::to_string)
+                    .collect();
+                facts.cosmetic_tags.push(CosmeticTag { scopes, name: name.to_string() });
             }
+        } else if field.key_is("create_dynamic_country") && field.value.as_block().is_some() {
+            facts.creates_dynamic_country = true;
         }
-        cache.insert(
-            key.to_string(),
-            TagCacheEntry {
-                tags: tags.to_vec(),
-                timestamps,
-            },
+    });
+    facts
+}
+
+/// 写入缓存，确保出现异常时不会破坏原缓存。
+fn store_cache(key: &str, tags: &[TagEntry], files: &[TagFileInfo]) {
+    TAG_CACHE.insert(key.to_string(), file_stamps(files), tags.to_vec());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, relative: &str, content: &str) {
+        let path = root.join(relative);
+        fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+        fs::write(path, content).expect("write file");
+    }
+
+    #[test]
+    fn test_colors_and_dynamic_tags_file() {
+        let tags = extract_tags("dynamic_tags = yes\nD01 = \"countries/D01.txt\"\n", TagSource::Game);
+        assert!(tags.iter().all(|t| t.dynamic) && tags.len() == 1);
+        assert!(!extract_tags("GER = \"countries/Germany.txt\"", TagSource::Game)[0].dynamic);
+
+        let colors = extract_colors(
+            "GER = { color = rgb { 255 0 16 } color_ui = hsv { 0 0 1 } }\nENG = { color = { 1 2 3 } }",
+        );
+        assert_eq!(colors[0], ("GER".to_string(), Some("#ff0010".to_string()), Some("#ffffff".to_string())));
+        assert_eq!(colors[1], ("ENG".to_string(), Some("#010203".to_string()), None));
+    }
+
+    #[test]
+    fn test_registry_links_history_and_cosmetic_tags() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        write(root, "common/country_tags/00_countries.txt", "GER = \"countries/Germany.txt\"\nENG = \"countries/England.txt\"");
+        write(root, "common/countries/colors.txt", "GER = { color = rgb { 10 20 30 } }");
+        write(root, "history/countries/GER - Germany.txt", "capital = 64\nset_cosmetic_tag = GER_empire");
+        write(
+            root,
+            "events/test.txt",
+            "country_event = { immediate = {\n\
+                ENG = { set_cosmetic_tag = british_raj }\n\
+                set_cosmetic_tag = GER_weimar\n\
+                create_dynamic_country = { original_tag = GER }\n\
+            } }",
         );
+        // 非 UTF-8 与无法读取的文件不影响其余结果
+        fs::write(root.join("events/latin1.txt"), b"country_event = { id = caf\xe9 }").expect("write");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.join("missing"), root.join("events/broken.txt")).expect("symlink");
+
+        let root_str = root.to_string_lossy().to_string();
+        let files = collect_file_infos(Some(&root_str), None, &[]);
+        let tags = parse_tags(&files);
+        let ger = tags.iter().find(|t| t.code == "GER").expect("GER");
+        assert_eq!(ger.color.as_deref(), Some("#0a141e"));
+        assert_eq!(ger.cosmetic_tags, ["GER_empire", "GER_weimar"]);
+        assert!(ger.history_file.as_deref().is_some_and(|p| p.ends_with("GER - Germany.txt")));
+        let eng = tags.iter().find(|t| t.code == "ENG").expect("ENG");
+        assert_eq!(eng.cosmetic_tags, ["british_raj"]);
+        // 未声明动态标签文件时，由 create_dynamic_country 补齐 D01…D50
+        assert!(tags.iter().any(|t| t.code == "D50" && t.dynamic));
+    }
+
+    #[test]
+    fn test_zipped_dependency_is_read_in_place() {
+        use std::io::Write;
+        let dir = tempfile::tempdir().expect("tempdir");
+        let archive = dir.path().join("dep.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&archive).expect("create zip"));
+        for (name, content) in [
+            ("common/country_tags/01_dep.txt", "ZZZ = \"countries/Zed.txt\""),
+            ("common/countries/colors.txt", "ZZZ = { color = rgb { 1 2 3 } }"),
+            ("history/countries/ZZZ - Zed.txt", "set_cosmetic_tag = ZZZ_union"),
+        ] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default()).expect("start file");
+            zip.write_all(content.as_bytes()).expect("write entry");
+        }
+        zip.finish().expect("finish zip");
+
+        let files = collect_file_infos(None, None, &[archive.to_string_lossy().replace('\\', "/")]);
+        let tags = parse_tags(&files);
+        let zed = tags.iter().find(|t| t.code == "ZZZ").expect("ZZZ");
+        assert_eq!(zed.source, TagSource::Dependency);
+        assert_eq!(zed.color.as_deref(), Some("#010203"));
+        assert_eq!(zed.cosmetic_tags, ["ZZZ_union"]);
+        assert!(zed.history_file.as_deref().is_some_and(|p| p.ends_with("dep.zip/history/countries/ZZZ - Zed.txt")));
     }
 }
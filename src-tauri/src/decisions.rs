@@ -0,0 +1,455 @@
+#![deny(clippy::unwrap_used)]
+
+//! 决议与决议类别
+//!
+//! 解析 `common/decisions/*.txt` 与 `common/decisions/categories/*.txt`，把决议挂到所属类别下，
+//! 提取花费、`days_remove`、`days_re_enable`、`fire_only_once`、`ai_will_do`、
+//! `visible` / `available` 条件以及针对性决议的 `targets` / `target_array`。
+//! 同时报告引用未定义类别的决议与图标找不到 sprite 的类别。
+
+use crate::clausewitz::{self, Block, Field, LineIndex};
+use crate::file_cache::{self, FileSetCache, FileStamps};
+use crate::load_order::{self, LoadOrderRoot, RootSource};
+use crate::localisation_lint;
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+const DECISIONS_DIR: &str = "common/decisions";
+const CATEGORIES_DIR: &str = "common/decisions/categories";
+
+/// ：`ai_will_do` 的概要。
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DecisionAiWeight {
+    pub base: Option<f64>,
+    pub factor: Option<f64>,
+    /// `modifier = { }` 的个数
+    pub modifiers: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecisionEntry {
+    pub id: String,
+    pub category: String,
+    pub icon: Option<String>,
+    /// 原文，可能是数字或 `@变量`
+    pub cost: Option<String>,
+    pub days_remove: Option<f64>,
+    pub days_re_enable: Option<f64>,
+    pub fire_only_once: bool,
+    pub ai_will_do: Option<DecisionAiWeight>,
+    /// `visible` 块的原文
+    pub visible: Option<String>,
+    /// `available` 块的原文
+    pub available: Option<String>,
+    /// 有 `targets`、`target_array`、`target_trigger` 或 `state_target` 之一
+    pub targeted: bool,
+    pub targets: Vec<String>,
+    pub target_array: Option<String>,
+    pub source: RootSource,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecisionCategoryEntry {
+    pub id: String,
+    pub icon: Option<String>,
+    pub picture: Option<String>,
+    pub decision_count: usize,
+    pub source: RootSource,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DecisionWarningKind {
+    UndefinedCategory,
+    MissingIcon,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecisionWarning {
+    pub kind: DecisionWarningKind,
+    pub id: String,
+    pub message: String,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecisionLoadResponse {
+    pub success: bool,
+    pub message: String,
+    pub categories: Option<Vec<DecisionCategoryEntry>>,
+    pub decisions: Option<Vec<DecisionEntry>>,
+    pub warnings: Vec<DecisionWarning>,
+}
+
+/// 加载决议与决议类别
+#[tauri::command]
+pub fn load_decisions(
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> DecisionLoadResponse {
+    let roots = load_order::load_order(project_root.as_deref(), dependency_roots.clone(), game_root.as_deref());
+    if roots.is_empty() {
+        return DecisionLoadResponse {
+            success: false,
+            message: "未提供项目或游戏目录".to_string(),
+            categories: None,
+            decisions: None,
+            warnings: Vec::new(),
+        };
+    }
+
+    let loaded = load_all(&roots);
+    // 没有游戏目录时缺少原版 sprite，不检查图标是否存在
+    let has_game = game_root.as_deref().is_some_and(|g| !g.trim().is_empty());
+    let gfx = has_game
+        .then(|| localisation_lint::load_catalogue(project_root.as_deref(), game_root.as_deref(), dependency_roots));
+    let warnings = collect_warnings(&loaded.categories, &loaded.decisions, gfx.as_deref().map(|g| &g.sprites));
+
+    let mut categories = loaded.categories.clone();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for decision in &loaded.decisions {
+        *counts.entry(decision.category.as_str()).or_default() += 1;
+    }
+    for category in &mut categories {
+        category.decision_count = counts.get(category.id.as_str()).copied().unwrap_or(0);
+    }
+
+    DecisionLoadResponse {
+        success: true,
+        message: format!(
+            "共 {} 个类别、{} 个决议，{} 条警告",
+            categories.len(),
+            loaded.decisions.len(),
+            warnings.len()
+        ),
+        categories: Some(categories),
+        decisions: Some(loaded.decisions.clone()),
+        warnings,
+    }
+}
+
+// ==================== 收集 ====================
+
+#[derive(Debug, Default)]
+struct LoadedDecisions {
+    categories: Vec<DecisionCategoryEntry>,
+    decisions: Vec<DecisionEntry>,
+}
+
+static DECISION_CACHE: Lazy<FileSetCache<LoadedDecisions>> = Lazy::new(FileSetCache::new);
+
+/// ：按加载顺序收集生效文件（同一相对路径只取优先级最高的根目录），文件未变化时复用缓存。
+fn load_all(roots: &[LoadOrderRoot]) -> Arc<LoadedDecisions> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut files: FileStamps = Vec::new();
+    // (是否为类别文件, 来源)
+    let mut meta: Vec<(bool, RootSource)> = Vec::new();
+    for (index, root) in roots.iter().enumerate() {
+        for (dir, is_category) in [(DECISIONS_DIR, false), (CATEGORIES_DIR, true)] {
+            if load_order::blanked_by(roots, index, dir).is_some() {
+                continue;
+            }
+            let base = Path::new(&root.path).join(dir);
+            let Ok(entries) = fs::read_dir(&base) else { continue };
+            let mut paths: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+            paths.sort();
+            for path in paths {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+                if !path.is_file() || !name.ends_with(".txt") || !seen.insert(format!("{}/{}", dir, name)) {
+                    continue;
+                }
+                files.push((path.to_string_lossy().replace('\\', "/"), file_cache::modified(&path)));
+                meta.push((is_category, root.source));
+            }
+        }
+    }
+
+    DECISION_CACHE.get_or_build(file_cache::roots_key(roots), files, |files| {
+        let parsed: Vec<LoadedDecisions> = files
+            .par_iter()
+            .zip(meta.par_iter())
+            .map(|((path, _), (is_category, source))| {
+                let content = fs::read(path)
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                    .unwrap_or_default();
+                if *is_category {
+                    LoadedDecisions {
+                        categories: parse_categories(path, &content, *source),
+                        decisions: Vec::new(),
+                    }
+                } else {
+                    LoadedDecisions {
+                        categories: Vec::new(),
+                        decisions: parse_decisions(path, &content, *source),
+                    }
+                }
+            })
+            .collect();
+
+        // 同名类别由高优先级的定义生效；决议按文件顺序保留
+        let mut loaded = LoadedDecisions::default();
+        let mut category_ids: HashSet<String> = HashSet::new();
+        for file in parsed {
+            for category in file.categories {
+                if category_ids.insert(category.id.clone()) {
+                    loaded.categories.push(category);
+                }
+            }
+            loaded.decisions.extend(file.decisions);
+        }
+        loaded
+    })
+}
+
+// ==================== 解析 ====================
+
+fn block_text(content: &str, block: &Block, key: &str) -> Option<String> {
+    block
+        .get_block(key)
+        .map(|b| b.inner_span().slice(content).trim().to_string())
+}
+
+fn ai_weight(block: &Block) -> DecisionAiWeight {
+    DecisionAiWeight {
+        base: block.get_f64("base"),
+        factor: block.get_f64("factor"),
+        modifiers: block.fields_named("modifier").count(),
+    }
+}
+
+/// ：`category = { decision = { ... } }`。
+fn parse_decisions(file: &str, content: &str, source: RootSource) -> Vec<DecisionEntry> {
+    let doc = clausewitz::parse(content);
+    let index = LineIndex::new(content);
+    let mut decisions = Vec::new();
+    for category in doc.root.fields() {
+        let Some(category_block) = category.value.as_block() else { continue };
+        for field in category_block.fields() {
+            if let Some(block) = field.value.as_block() {
+                decisions.push(parse_decision(file, content, &index, source, category, field, block));
+            }
+        }
+    }
+    decisions
+}
+
+fn parse_decision(
+    file: &str,
+    content: &str,
+    index: &LineIndex,
+    source: RootSource,
+    category: &Field,
+    field: &Field,
+    block: &Block,
+) -> DecisionEntry {
+    let targets: Vec<String> = block
+        .get_block("targets")
+        .map(|b| b.scalars().map(|s| s.as_str().to_string()).collect())
+        .unwrap_or_default();
+    let target_array = block.get_str("target_array").map(|s| s.to_string());
+    let targeted = !targets.is_empty()
+        || target_array.is_some()
+        || block.field("target_trigger").is_some()
+        || block.field("state_target").is_some();
+    DecisionEntry {
+        id: field.key.as_str().to_string(),
+        category: category.key.as_str().to_string(),
+        icon: block.get_str("icon").map(|s| s.to_string()),
+        cost: block
+            .field("cost")
+            .and_then(|f| f.value.as_scalar())
+            .map(|s| s.as_str().to_string()),
+        days_remove: block.get_f64("days_remove"),
+        days_re_enable: block.get_f64("days_re_enable"),
+        fire_only_once: block.get_bool("fire_only_once").unwrap_or(false),
+        ai_will_do: block.get_block("ai_will_do").map(ai_weight),
+        visible: block_text(content, block, "visible"),
+        available: block_text(content, block, "available"),
+        targeted,
+        targets,
+        target_array,
+        source,
+        file: file.to_string(),
+        start: field.span.start,
+        end: field.span.end,
+        line: index.line(field.span.start),
+    }
+}
+
+fn parse_categories(file: &str, content: &str, source: RootSource) -> Vec<DecisionCategoryEntry> {
+    let doc = clausewitz::parse(content);
+    let index = LineIndex::new(content);
+    doc.root
+        .fields()
+        .filter_map(|field| {
+            let block = field.value.as_block()?;
+            Some(DecisionCategoryEntry {
+                id: field.key.as_str().to_string(),
+                icon: block.get_str("icon").map(|s| s.to_string()),
+                picture: block.get_str("picture").map(|s| s.to_string()),
+                decision_count: 0,
+                source,
+                file: file.to_string(),
+                start: field.span.start,
+                end: field.span.end,
+                line: index.line(field.span.start),
+            })
+        })
+        .collect()
+}
+
+// ==================== 警告 ====================
+
+/// ：类别图标可以写完整的 `GFX_` 名称，也可以省略 `GFX_decision_category_` 前缀。
+fn category_icon_defined(icon: &str, sprites: &HashSet<String>) -> bool {
+    sprites.contains(icon)
+        || sprites.contains(&format!("GFX_decision_category_{}", icon))
+        || sprites.contains(&format!("GFX_{}", icon))
+}
+
+/// ：游戏本体中的类别不检查图标，避免大量与项目无关的提示；`sprites` 为 None 时只检查是否设置了图标。
+fn collect_warnings(
+    categories: &[DecisionCategoryEntry],
+    decisions: &[DecisionEntry],
+    sprites: Option<&HashSet<String>>,
+) -> Vec<DecisionWarning> {
+    let mut warnings = Vec::new();
+    let ids: HashSet<&str> = categories.iter().map(|c| c.id.as_str()).collect();
+    for decision in decisions {
+        if !ids.contains(decision.category.as_str()) {
+            warnings.push(DecisionWarning {
+                kind: DecisionWarningKind::UndefinedCategory,
+                id: decision.id.clone(),
+                message: format!("决议 {} 所属的类别 {} 未定义", decision.id, decision.category),
+                file: decision.file.clone(),
+                start: decision.start,
+                end: decision.end,
+                line: decision.line,
+            });
+        }
+    }
+
+    for category in categories.iter().filter(|c| c.source != RootSource::Game) {
+        let message = match &category.icon {
+            Some(icon) if icon.contains(['[', '$']) => continue,
+            Some(icon) if sprites.map(|s| category_icon_defined(icon, s)).unwrap_or(true) => continue,
+            Some(icon) => format!("决议类别 {} 的图标 {} 没有对应的 spriteType", category.id, icon),
+            None => format!("决议类别 {} 没有设置图标", category.id),
+        };
+        warnings.push(DecisionWarning {
+            kind: DecisionWarningKind::MissingIcon,
+            id: category.id.clone(),
+            message,
+            file: category.file.clone(),
+            start: category.start,
+            end: category.end,
+            line: category.line,
+        });
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DECISIONS: &str = r#"
+TST_category = {
+    TST_build = {
+        icon = generic_construction
+        cost = 50
+        days_remove = 30
+        days_re_enable = 90
+        fire_only_once = yes
+        visible = { has_war = no }
+        available = { has_political_power > 50 }
+        ai_will_do = {
+            base = 1
+            factor = 2
+            modifier = { factor = 0 has_war = yes }
+        }
+    }
+    TST_demand = {
+        cost = @demand_cost
+        targets = { GER ENG }
+        target_array = global.countries
+    }
+}
+TST_unknown = {
+    TST_orphan = { }
+}
+"#;
+
+    #[test]
+    fn test_parse_decisions() {
+        let decisions = parse_decisions("/mod/common/decisions/a.txt", DECISIONS, RootSource::Project);
+        let ids: Vec<(&str, &str)> = decisions.iter().map(|d| (d.category.as_str(), d.id.as_str())).collect();
+        assert_eq!(ids, [("TST_category", "TST_build"), ("TST_category", "TST_demand"), ("TST_unknown", "TST_orphan")]);
+
+        let build = &decisions[0];
+        assert_eq!(build.cost.as_deref(), Some("50"));
+        assert_eq!((build.days_remove, build.days_re_enable, build.fire_only_once), (Some(30.0), Some(90.0), true));
+        assert_eq!(build.visible.as_deref(), Some("has_war = no"));
+        assert_eq!(build.available.as_deref(), Some("has_political_power > 50"));
+        assert_eq!(
+            build.ai_will_do,
+            Some(DecisionAiWeight {
+                base: Some(1.0),
+                factor: Some(2.0),
+                modifiers: 1
+            })
+        );
+        assert!(!build.targeted);
+
+        let demand = &decisions[1];
+        assert_eq!(demand.cost.as_deref(), Some("@demand_cost"));
+        assert!(demand.targeted);
+        assert_eq!(demand.targets, ["GER", "ENG"]);
+        assert_eq!(demand.target_array.as_deref(), Some("global.countries"));
+        assert!(DECISIONS[demand.start..demand.end].starts_with("TST_demand"));
+    }
+
+    #[test]
+    fn test_decision_warnings() {
+        let decisions = parse_decisions("/mod/common/decisions/a.txt", DECISIONS, RootSource::Project);
+        let categories_src = "TST_category = { icon = tst_icon }\nTST_bare = { }\nTST_broken = { icon = GFX_nothing }";
+        let mut categories = parse_categories("/mod/common/decisions/categories/a.txt", categories_src, RootSource::Project);
+        categories.extend(parse_categories("/game/common/decisions/categories/b.txt", "VANILLA = { }", RootSource::Game));
+        let sprites: HashSet<String> = HashSet::from(["GFX_decision_category_tst_icon".to_string()]);
+
+        let warnings: Vec<(DecisionWarningKind, String)> = collect_warnings(&categories, &decisions, Some(&sprites))
+            .into_iter()
+            .map(|w| (w.kind, w.message))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (DecisionWarningKind::UndefinedCategory, "决议 TST_orphan 所属的类别 TST_unknown 未定义".to_string()),
+                (DecisionWarningKind::MissingIcon, "决议类别 TST_bare 没有设置图标".to_string()),
+                (DecisionWarningKind::MissingIcon, "决议类别 TST_broken 的图标 GFX_nothing 没有对应的 spriteType".to_string()),
+            ]
+        );
+    }
+}
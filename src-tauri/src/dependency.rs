@@ -1,7 +1,17 @@
 #![deny(clippy::unwrap_used)]
+use crate::clausewitz;
+use crate::country_tags::{self, TagEntry, TagSource};
+use crate::gui_engine::SPRITE_TYPES;
+use crate::idea_registry;
+use crate::mod_descriptor::{self, ModDescriptor};
+use crate::symbol_index;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
+use std::time::SystemTime;
+use walkdir::WalkDir;
 
 /// 依赖项类型
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -22,6 +32,9 @@ pub struct Dependency {
     #[serde(rename = "addedAt")]
     pub added_at: String,
     pub enabled: bool,
+    /// 覆盖优先级，数值越大越优先（项目本身始终最高，游戏本体始终最低）
+    #[serde(default)]
+    pub priority: i32,
 }
 
 /// 依赖项验证结果
@@ -63,6 +76,56 @@ pub struct DependencyIndexResult {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "tagCount")]
     pub tag_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "localisationCount")]
+    pub localisation_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "spriteCount")]
+    pub sprite_count: Option<usize>,
+    #[serde(rename = "fileCount")]
+    pub file_count: usize,
+    /// 逐文件的读取 / 语法错误
+    pub errors: Vec<DependencyIndexError>,
+}
+
+/// 依赖项索引时单个文件的错误
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyIndexError {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// 依赖项索引的磁盘缓存，按文件 mtime 判断是否仍然有效
+#[derive(Debug, Serialize, Deserialize)]
+struct DependencyIndexCache {
+    path: String,
+    timestamps: HashMap<String, Option<SystemTime>>,
+    ideas: Vec<String>,
+    tags: Vec<TagEntry>,
+    localisation_keys: Vec<String>,
+    sprites: Vec<String>,
+    errors: Vec<DependencyIndexError>,
+}
+
+/// 依赖项中参与索引的文件类别
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IndexedFileKind {
+    Ideas,
+    CountryTags,
+    Localisation,
+    Sprites,
+}
+
+/// 单个文件的解析结果
+#[derive(Default)]
+struct FileIndexOutput {
+    ideas: Vec<String>,
+    tags: Vec<TagEntry>,
+    localisation_keys: Vec<String>,
+    sprites: Vec<String>,
+    errors: Vec<DependencyIndexError>,
 }
 
 /// 加载项目的依赖项列表
@@ -192,6 +255,11 @@ pub fn validate_dependency_path(path: String) -> DependencyValidation {
         };
     }
     
+    // zip 压缩包形式的 Mod（创意工坊常见），不解压直接读取
+    if is_archive(dep_path) {
+        return validate_archive(dep_path);
+    }
+
     // 检查是否是目录
     if !dep_path.is_dir() {
         return DependencyValidation {
@@ -275,14 +343,799 @@ pub fn validate_dependency_path(path: String) -> DependencyValidation {
     }
 }
 
-/// 索引依赖项的 Idea 和 Tag 数据
+/// 验证 zip 压缩包形式的 Mod：名称取包内或同目录的 descriptor.mod
+fn validate_archive(path: &Path) -> DependencyValidation {
+    let invalid = |message: String| DependencyValidation {
+        valid: false,
+        message,
+        name: None,
+        dependency_type: None,
+    };
+    let file = match fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) => return invalid(format!("打开压缩包失败: {}", e)),
+    };
+    let mut zip = match zip::ZipArchive::new(file) {
+        Ok(z) => z,
+        Err(e) => return invalid(format!("读取压缩包失败: {}", e)),
+    };
+
+    let content_dirs = ["common/", "events/", "history/", "interface/", "localisation/", "gfx/"];
+    let has_content = zip.file_names().any(|n| {
+        let n = n.replace('\\', "/").to_lowercase();
+        n == "descriptor.mod" || content_dirs.iter().any(|dir| n.starts_with(dir))
+    });
+    if !has_content {
+        return invalid("压缩包中没有 HOI4 Mod 内容".to_string());
+    }
+
+    let inner = zip.by_name("descriptor.mod").ok().and_then(|mut entry| {
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut entry, &mut content).ok()?;
+        Some(content)
+    });
+    let sibling = path
+        .parent()
+        .and_then(|p| fs::read_to_string(p.join("descriptor.mod")).ok());
+    let name = inner
+        .or(sibling)
+        .map(|c| mod_descriptor::parse_descriptor(&c).name)
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "未命名 Mod".to_string())
+        });
+
+    DependencyValidation {
+        valid: true,
+        message: "有效的 HOI4 Mod（压缩包）".to_string(),
+        name: Some(name),
+        dependency_type: Some(DependencyType::Hoi4mod),
+    }
+}
+
+/// 本地发现的 Mod 来源
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LocalModSource {
+    /// 用户文档目录下的 mod/ 文件夹
+    Documents,
+    /// Steam 创意工坊内容目录
+    Workshop,
+}
+
+/// 本地发现的 Mod
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalMod {
+    /// 描述文件路径（.mod 或 descriptor.mod）
+    pub descriptor_file: String,
+    pub source: LocalModSource,
+    pub descriptor: ModDescriptor,
+    /// 实际内容所在位置：目录或 zip 文件，无法定位时为 None
+    pub content_path: Option<String>,
+    pub is_archive: bool,
+    /// 可直接加入项目的依赖项条目
+    pub dependency: Option<Dependency>,
+}
+
+/// 本地 Mod 扫描结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoverModsResult {
+    pub success: bool,
+    pub message: String,
+    /// 实际扫描过的目录
+    pub scanned_dirs: Vec<String>,
+    pub mods: Vec<LocalMod>,
+}
+
+/// 扫描用户文档 mod/ 目录与创意工坊目录中的 .mod 描述文件
+///
+/// 未传入 `documents_dir` 时使用系统文档目录下的 `Paradox Interactive/Hearts of Iron IV`；
+/// 未传入 `workshop_dir` 时由游戏目录推导 `steamapps/workshop/content/394360`。
+#[tauri::command]
+pub fn discover_local_mods(
+    documents_dir: Option<String>,
+    workshop_dir: Option<String>,
+    game_directory: Option<String>,
+) -> DiscoverModsResult {
+    let user_dirs: Vec<std::path::PathBuf> = match documents_dir.filter(|d| !d.trim().is_empty()) {
+        Some(dir) => vec![std::path::PathBuf::from(dir)],
+        None => default_user_dirs(),
+    };
+    let workshop = workshop_dir
+        .filter(|d| !d.trim().is_empty())
+        .map(std::path::PathBuf::from)
+        .or_else(|| game_directory.as_deref().and_then(workshop_dir_from_game));
+
+    let mut scanned_dirs = Vec::new();
+    let mut mods = Vec::new();
+
+    for user_dir in &user_dirs {
+        let mod_dir = user_dir.join("mod");
+        let Ok(entries) = fs::read_dir(&mod_dir) else { continue };
+        scanned_dirs.push(mod_dir.to_string_lossy().replace('\\', "/"));
+        let mut files: Vec<_> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.is_file()
+                    && p.extension()
+                        .and_then(|e| e.to_str())
+                        .map(|e| e.eq_ignore_ascii_case("mod"))
+                        .unwrap_or(false)
+            })
+            .collect();
+        files.sort();
+        for file in files {
+            let Ok(content) = fs::read_to_string(&file) else { continue };
+            let descriptor = mod_descriptor::parse_descriptor(&content);
+            let content_path = descriptor
+                .path
+                .as_deref()
+                .or(descriptor.archive.as_deref())
+                .map(|p| resolve_user_path(user_dir, p));
+            mods.push(local_mod(&file, LocalModSource::Documents, descriptor, content_path));
+        }
+    }
+
+    if let Some(workshop) = workshop {
+        if let Ok(entries) = fs::read_dir(&workshop) {
+            scanned_dirs.push(workshop.to_string_lossy().replace('\\', "/"));
+            let mut dirs: Vec<_> = entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()).collect();
+            dirs.sort();
+            for dir in dirs {
+                let descriptor_file = dir.join("descriptor.mod");
+                let Ok(content) = fs::read_to_string(&descriptor_file) else { continue };
+                let mut descriptor = mod_descriptor::parse_descriptor(&content);
+                if descriptor.remote_file_id.is_none() {
+                    descriptor.remote_file_id = dir.file_name().map(|n| n.to_string_lossy().into_owned());
+                }
+                // 旧版创意工坊 Mod 只有一个 zip 与 descriptor.mod
+                let content_path = descriptor
+                    .archive
+                    .as_deref()
+                    .map(|a| resolve_user_path(&dir, a))
+                    .filter(|p| Path::new(p).exists())
+                    .or_else(|| single_archive_in(&dir))
+                    .unwrap_or_else(|| dir.to_string_lossy().replace('\\', "/"));
+                mods.push(local_mod(&descriptor_file, LocalModSource::Workshop, descriptor, Some(content_path)));
+            }
+        }
+    }
+
+    if scanned_dirs.is_empty() {
+        return DiscoverModsResult {
+            success: false,
+            message: "未找到 HOI4 用户 mod 目录或创意工坊目录".to_string(),
+            scanned_dirs,
+            mods,
+        };
+    }
+
+    DiscoverModsResult {
+        success: true,
+        message: format!("在 {} 个目录中发现 {} 个 Mod", scanned_dirs.len(), mods.len()),
+        scanned_dirs,
+        mods,
+    }
+}
+
+fn local_mod(
+    descriptor_file: &Path,
+    source: LocalModSource,
+    descriptor: ModDescriptor,
+    content_path: Option<String>,
+) -> LocalMod {
+    let content_path = content_path.filter(|p| Path::new(p).exists());
+    let is_archive = content_path.as_deref().map(|p| is_archive(Path::new(p))).unwrap_or(false);
+    let dependency = content_path.as_ref().map(|path| {
+        let dependency_type = if Path::new(path).join("project.json").exists() {
+            DependencyType::Hoics
+        } else {
+            DependencyType::Hoi4mod
+        };
+        let name = if descriptor.name.is_empty() {
+            Path::new(path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "未命名 Mod".to_string())
+        } else {
+            descriptor.name.clone()
+        };
+        Dependency {
+            id: format!("dep_{}", crate::hash_string(path)),
+            name,
+            path: path.clone(),
+            dependency_type,
+            added_at: chrono::Utc::now().to_rfc3339(),
+            enabled: true,
+            priority: 0,
+        }
+    });
+    LocalMod {
+        descriptor_file: descriptor_file.to_string_lossy().replace('\\', "/"),
+        source,
+        descriptor,
+        content_path,
+        is_archive,
+        dependency,
+    }
+}
+
+/// HOI4 用户目录候选（Windows/macOS 在文档目录下，Linux 在 ~/.local/share 下）
+fn default_user_dirs() -> Vec<std::path::PathBuf> {
+    let game_dir = Path::new("Paradox Interactive").join("Hearts of Iron IV");
+    [dirs::document_dir(), dirs::data_local_dir()]
+        .into_iter()
+        .flatten()
+        .map(|base| base.join(&game_dir))
+        .filter(|p| p.is_dir())
+        .collect()
+}
+
+/// 由 `steamapps/common/Hearts of Iron IV` 推导创意工坊内容目录
+fn workshop_dir_from_game(game_directory: &str) -> Option<std::path::PathBuf> {
+    let steamapps = Path::new(game_directory.trim()).parent()?.parent()?;
+    let dir = steamapps.join("workshop").join("content").join("394360");
+    dir.is_dir().then_some(dir)
+}
+
+/// 描述文件中的相对路径相对于用户目录（如 `mod/my_mod`）
+fn resolve_user_path(base: &Path, path: &str) -> String {
+    let normalized = path.trim().replace('\\', "/");
+    let candidate = Path::new(&normalized);
+    let full = if candidate.is_absolute() || normalized.chars().nth(1) == Some(':') {
+        candidate.to_path_buf()
+    } else {
+        base.join(candidate)
+    };
+    full.to_string_lossy().replace('\\', "/").trim_end_matches('/').to_string()
+}
+
+fn single_archive_in(dir: &Path) -> Option<String> {
+    let zips: Vec<_> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| is_archive(p))
+        .collect();
+    let has_content = dir.join("common").is_dir() || dir.join("events").is_dir();
+    match zips.as_slice() {
+        [only] if !has_content => Some(only.to_string_lossy().replace('\\', "/")),
+        _ => None,
+    }
+}
+
+/// 索引依赖项的 Idea、Tag、本地化与 Sprite 数据，结果写入磁盘缓存
 #[tauri::command]
-pub fn index_dependency(_dependency_path: String) -> DependencyIndexResult {
-    // 这个功能将在第二阶段实现，现在只返回一个占位结果
+pub fn index_dependency(dependency_path: String) -> DependencyIndexResult {
+    index_dependency_in(dependency_path, &crate::get_named_cache_dir("dependency-index"))
+}
+
+/// ：索引缓存写入 `cache_dir`，便于测试使用临时目录。
+fn index_dependency_in(dependency_path: String, cache_dir: &Path) -> DependencyIndexResult {
+    let root = dependency_path.trim().replace('\\', "/").trim_end_matches('/').to_string();
+    let root_path = Path::new(&root);
+    let archive = is_archive(root_path);
+    if root.is_empty() || !(root_path.is_dir() || archive) {
+        return DependencyIndexResult {
+            success: false,
+            message: "依赖项路径不存在或不是目录 / zip 压缩包".to_string(),
+            idea_count: None,
+            tag_count: None,
+            localisation_count: None,
+            sprite_count: None,
+            file_count: 0,
+            errors: Vec::new(),
+        };
+    }
+
+    // 压缩包整体只记录一个时间戳
+    let files = if archive { Vec::new() } else { collect_index_files(root_path) };
+    let timestamps: HashMap<String, Option<SystemTime>> = if archive {
+        let modified = fs::metadata(root_path).ok().and_then(|m| m.modified().ok());
+        HashMap::from([(root.clone(), modified)])
+    } else {
+        files
+            .iter()
+            .map(|(path, _, modified)| (path.clone(), *modified))
+            .collect()
+    };
+
+    let cache_path = cache_dir.join(format!("{}.json", crate::hash_string(&root)));
+    if let Some(cache) = load_index_cache(&cache_path) {
+        if cache.path == root && cache.timestamps == timestamps {
+            return index_result(&cache, "命中缓存");
+        }
+    }
+
+    let outputs: Vec<FileIndexOutput> = if archive {
+        match read_archive_files(root_path) {
+            Ok(entries) => entries
+                .par_iter()
+                .map(|(path, kind, content)| index_content(path, content, *kind))
+                .collect(),
+            Err(e) => {
+                return DependencyIndexResult {
+                    success: false,
+                    message: e,
+                    idea_count: None,
+                    tag_count: None,
+                    localisation_count: None,
+                    sprite_count: None,
+                    file_count: 0,
+                    errors: Vec::new(),
+                };
+            }
+        }
+    } else {
+        files
+            .par_iter()
+            .map(|(path, kind, _)| index_file(path, *kind))
+            .collect()
+    };
+
+    let mut ideas: HashSet<String> = HashSet::new();
+    let mut tags: HashMap<String, TagEntry> = HashMap::new();
+    let mut localisation_keys: HashSet<String> = HashSet::new();
+    let mut sprites: HashSet<String> = HashSet::new();
+    let mut errors: Vec<DependencyIndexError> = Vec::new();
+    for output in outputs {
+        ideas.extend(output.ideas);
+        for tag in output.tags {
+            tags.insert(tag.code.clone(), tag);
+        }
+        localisation_keys.extend(output.localisation_keys);
+        sprites.extend(output.sprites);
+        errors.extend(output.errors);
+    }
+
+    let mut cache = DependencyIndexCache {
+        path: root.clone(),
+        timestamps,
+        ideas: ideas.into_iter().collect(),
+        tags: tags.into_values().collect(),
+        localisation_keys: localisation_keys.into_iter().collect(),
+        sprites: sprites.into_iter().collect(),
+        errors,
+    };
+    cache.ideas.sort();
+    cache.tags.sort_by(|a, b| a.code.cmp(&b.code));
+    cache.localisation_keys.sort();
+    cache.sprites.sort();
+    cache.errors.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+
+    match serde_json::to_vec(&cache) {
+        Ok(bytes) => {
+            if let Err(e) = fs::write(&cache_path, bytes) {
+                println!("[dependency] 写入索引缓存失败: {}", e);
+            }
+        }
+        Err(e) => println!("[dependency] 序列化索引缓存失败: {}", e),
+    }
+
+    index_result(&cache, "索引完成")
+}
+
+fn index_result(cache: &DependencyIndexCache, prefix: &str) -> DependencyIndexResult {
+    let mut message = format!(
+        "{}：{} 个 idea，{} 个标签，{} 条本地化，{} 个 sprite",
+        prefix,
+        cache.ideas.len(),
+        cache.tags.len(),
+        cache.localisation_keys.len(),
+        cache.sprites.len()
+    );
+    if !cache.errors.is_empty() {
+        message.push_str(&format!("，{} 处解析错误", cache.errors.len()));
+    }
     DependencyIndexResult {
         success: true,
-        message: "依赖项索引功能将在第二阶段实现".to_string(),
-        idea_count: Some(0),
-        tag_count: Some(0),
+        message,
+        idea_count: Some(cache.ideas.len()),
+        tag_count: Some(cache.tags.len()),
+        localisation_count: Some(cache.localisation_keys.len()),
+        sprite_count: Some(cache.sprites.len()),
+        file_count: cache.timestamps.len(),
+        errors: cache.errors.clone(),
+    }
+}
+
+fn load_index_cache(path: &Path) -> Option<DependencyIndexCache> {
+    let bytes = fs::read(path).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// 参与索引的目录、扩展名与类别
+const INDEXED_DIRS: [(&str, &str, IndexedFileKind); 4] = [
+    ("common/ideas", "txt", IndexedFileKind::Ideas),
+    ("common/country_tags", "txt", IndexedFileKind::CountryTags),
+    ("localisation", "yml", IndexedFileKind::Localisation),
+    ("interface", "gfx", IndexedFileKind::Sprites),
+];
+
+/// 收集依赖项下需要索引的文件及其类别、修改时间
+fn collect_index_files(root: &Path) -> Vec<(String, IndexedFileKind, Option<SystemTime>)> {
+    let mut out = Vec::new();
+    for (dir, ext, kind) in INDEXED_DIRS {
+        let base = root.join(dir);
+        if !base.exists() {
+            continue;
+        }
+        for entry in WalkDir::new(&base).into_iter().filter_map(|e| e.ok()) {
+            let matches_ext = entry
+                .path()
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.eq_ignore_ascii_case(ext))
+                .unwrap_or(false);
+            if !entry.file_type().is_file() || !matches_ext {
+                continue;
+            }
+            let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+            out.push((entry.path().to_string_lossy().replace('\\', "/"), kind, modified));
+        }
+    }
+    out
+}
+
+/// 是否为 zip 压缩包形式的 Mod
+pub(crate) fn is_archive(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.eq_ignore_ascii_case("zip"))
+            .unwrap_or(false)
+}
+
+/// 直接从压缩包中读取需要索引的文件（不解压到磁盘），返回 (显示路径, 类别, 内容)
+fn read_archive_files(archive: &Path) -> Result<Vec<(String, IndexedFileKind, String)>, String> {
+    let kind_of = |relative: &str| {
+        INDEXED_DIRS.iter().find_map(|(dir, ext, kind)| {
+            let in_dir = relative.starts_with(dir) && relative[dir.len()..].starts_with('/');
+            (in_dir && relative.ends_with(&format!(".{}", ext))).then_some(*kind)
+        })
+    };
+    let entries = read_archive_entries(archive, |relative| kind_of(relative).is_some())?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| {
+            let kind = kind_of(&entry.relative)?;
+            Some((entry.path, kind, String::from_utf8_lossy(&entry.bytes).into_owned()))
+        })
+        .collect())
+}
+
+/// 压缩包形式依赖项中的一个文件
+pub(crate) struct ArchiveEntry {
+    /// `<压缩包路径>/<包内路径>`，用于显示与缓存
+    pub path: String,
+    /// 包内路径（小写、`/` 分隔）
+    pub relative: String,
+    pub bytes: Vec<u8>,
+}
+
+/// 压缩包内全部文件的 (显示路径, 小写包内路径)，不读取内容
+pub(crate) fn list_archive_files(archive: &Path) -> Result<Vec<(String, String)>, String> {
+    let file = fs::File::open(archive).map_err(|e| format!("打开压缩包失败: {}", e))?;
+    let zip = zip::ZipArchive::new(file).map_err(|e| format!("读取压缩包失败: {}", e))?;
+    let label = archive.to_string_lossy().replace('\\', "/");
+    Ok(zip
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(|name| {
+            let name = name.replace('\\', "/");
+            (format!("{}/{}", label, name), name.to_lowercase())
+        })
+        .collect())
+}
+
+/// 不解压直接读取压缩包内的文件，`wanted` 按小写包内路径筛选；读取失败的条目跳过
+pub(crate) fn read_archive_entries(
+    archive: &Path,
+    wanted: impl Fn(&str) -> bool,
+) -> Result<Vec<ArchiveEntry>, String> {
+    let file = fs::File::open(archive).map_err(|e| format!("打开压缩包失败: {}", e))?;
+    let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("读取压缩包失败: {}", e))?;
+    let label = archive.to_string_lossy().replace('\\', "/");
+
+    let mut out = Vec::new();
+    for i in 0..zip.len() {
+        let mut entry = match zip.by_index(i) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        if !entry.is_file() {
+            continue;
+        }
+        let name = entry.name().replace('\\', "/");
+        let relative = name.to_lowercase();
+        if !wanted(&relative) {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        if std::io::Read::read_to_end(&mut entry, &mut bytes).is_err() {
+            continue;
+        }
+        out.push(ArchiveEntry {
+            path: format!("{}/{}", label, name),
+            relative,
+            bytes,
+        });
+    }
+    Ok(out)
+}
+
+/// 读取并解析单个文件
+fn index_file(path: &str, kind: IndexedFileKind) -> FileIndexOutput {
+    match fs::read(path) {
+        Ok(bytes) => index_content(path, &String::from_utf8_lossy(&bytes), kind),
+        Err(e) => {
+            let mut output = FileIndexOutput::default();
+            output.errors.push(DependencyIndexError {
+                file: path.to_string(),
+                line: None,
+                message: format!("读取文件失败: {}", e),
+            });
+            output
+        }
+    }
+}
+
+/// 解析单个文件内容，脚本文件额外报告语法错误
+fn index_content(path: &str, content: &str, kind: IndexedFileKind) -> FileIndexOutput {
+    let mut output = FileIndexOutput::default();
+
+    if kind == IndexedFileKind::Localisation {
+        output.localisation_keys = symbol_index::localisation_keys(content);
+        return output;
+    }
+
+    let doc = clausewitz::parse(content);
+    if !doc.errors.is_empty() {
+        let lines = clausewitz::LineIndex::new(content);
+        for err in &doc.errors {
+            output.errors.push(DependencyIndexError {
+                file: path.to_string(),
+                line: Some(lines.line(err.span.start)),
+                message: err.message.clone(),
+            });
+        }
+    }
+
+    match kind {
+        IndexedFileKind::Ideas => output.ideas = idea_registry::extract_ideas(content),
+        IndexedFileKind::CountryTags => {
+            output.tags = country_tags::extract_tags(content, TagSource::Dependency)
+        }
+        IndexedFileKind::Sprites => {
+            doc.root.visit_fields(&mut |field, _| {
+                let is_sprite = SPRITE_TYPES.iter().any(|k| field.key_is(k));
+                if let Some(name) = field.value.as_block().filter(|_| is_sprite).and_then(|b| b.get_str("name")) {
+                    output.sprites.push(name.to_string());
+                }
+            });
+        }
+        IndexedFileKind::Localisation => {}
+    }
+    output
+}
+
+/// 将前端传入的依赖项路径与 project.json 中已启用的依赖合并（去重、保持顺序）
+pub(crate) fn with_project_dependencies(
+    project_root: Option<&str>,
+    explicit: Option<Vec<String>>,
+) -> Vec<String> {
+    let mut roots = explicit.unwrap_or_default();
+    if let Some(project) = project_root.filter(|p| !p.trim().is_empty()) {
+        roots.extend(enabled_dependency_roots(project));
+    }
+
+    let mut seen: HashSet<String> = HashSet::new();
+    roots
+        .into_iter()
+        .filter(|r| !r.trim().is_empty())
+        .filter(|r| seen.insert(r.replace('\\', "/").trim_end_matches('/').to_lowercase()))
+        .collect()
+}
+
+/// project.json 中已启用的依赖项（宽松解析，只要求 path 字段）
+#[derive(Debug, Clone)]
+pub(crate) struct EnabledDependency {
+    pub path: String,
+    pub name: String,
+    pub priority: i32,
+}
+
+/// 从 project.json 中读取已启用依赖，按优先级从高到低排序（同优先级保持原顺序）
+pub(crate) fn enabled_dependencies(project_root: &str) -> Vec<EnabledDependency> {
+    let mut result = Vec::new();
+    let project_json_path = Path::new(project_root).join("project.json");
+    if !project_json_path.exists() {
+        return result;
+    }
+
+    let content = match fs::read_to_string(&project_json_path) {
+        Ok(c) => c,
+        Err(e) => {
+            println!("读取 project.json 失败: {}", e);
+            return result;
+        }
+    };
+
+    let json: serde_json::Value = match serde_json::from_str(&content) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("解析 project.json 失败: {}", e);
+            return result;
+        }
+    };
+
+    if let Some(deps) = json.get("dependencies").and_then(|v| v.as_array()) {
+        for dep in deps {
+            let enabled = dep
+                .get("enabled")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true);
+            if !enabled {
+                continue;
+            }
+            if let Some(path) = dep.get("path").and_then(|v| v.as_str()) {
+                result.push(EnabledDependency {
+                    path: path.to_string(),
+                    name: dep
+                        .get("name")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or(path)
+                        .to_string(),
+                    priority: dep
+                        .get("priority")
+                        .and_then(|v| v.as_i64())
+                        .unwrap_or(0) as i32,
+                });
+            }
+        }
+    }
+
+    result.sort_by_key(|d| std::cmp::Reverse(d.priority));
+    result
+}
+
+/// 从 project.json 中读取已启用依赖的路径（按优先级从高到低）
+pub(crate) fn enabled_dependency_roots(project_root: &str) -> Vec<String> {
+    enabled_dependencies(project_root)
+        .into_iter()
+        .map(|d| d.path)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write(root: &Path, relative: &str, content: &str) {
+        let path = root.join(relative);
+        fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+        fs::write(path, content).expect("write file");
+    }
+
+    fn write_zip(path: &Path, entries: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).expect("create zip"));
+        for (name, content) in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default()).expect("start file");
+            zip.write_all(content.as_bytes()).expect("write entry");
+        }
+        zip.finish().expect("finish zip");
+    }
+
+    #[test]
+    fn test_descriptor_resolution() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let user = dir.path().join("user");
+        let workshop = dir.path().join("workshop");
+        write(&user, "mod/folder.mod", "name = \"Folder Mod\"\npath = \"mod/folder\"");
+        write(&user, "mod/folder/common/ideas/a.txt", "ideas = {}");
+        write(&user, "mod/zipped.mod", "name = \"Zipped Mod\"\narchive = \"mod/zipped.zip\"");
+        write_zip(&user.join("mod/zipped.zip"), &[("common/ideas/a.txt", "ideas = {}")]);
+        write(&user, "mod/missing.mod", "name = \"Missing\"\npath = \"mod/missing\"");
+        // 旧版创意工坊 Mod：descriptor.mod 不写 archive，目录下只有一个 zip
+        write(&workshop, "123/descriptor.mod", "name = \"Workshop Mod\"");
+        write_zip(&workshop.join("123/content.zip"), &[("descriptor.mod", "name = \"Inner\"")]);
+
+        let result = discover_local_mods(
+            Some(user.to_string_lossy().to_string()),
+            Some(workshop.to_string_lossy().to_string()),
+            None,
+        );
+        assert!(result.success);
+        let find = |name: &str| result.mods.iter().find(|m| m.descriptor.name == name).expect(name);
+
+        let folder = find("Folder Mod");
+        assert!(folder.content_path.as_deref().is_some_and(|p| p.ends_with("user/mod/folder")));
+        assert!(!folder.is_archive);
+        let dep = folder.dependency.as_ref().expect("dependency");
+        assert_eq!(dep.dependency_type, DependencyType::Hoi4mod);
+
+        assert!(find("Zipped Mod").is_archive);
+        let missing = find("Missing");
+        assert!(missing.content_path.is_none() && missing.dependency.is_none());
+
+        let workshop_mod = find("Workshop Mod");
+        assert_eq!(workshop_mod.source, LocalModSource::Workshop);
+        assert_eq!(workshop_mod.descriptor.remote_file_id.as_deref(), Some("123"));
+        assert!(workshop_mod.is_archive);
+
+        // 压缩包的名称优先取包内 descriptor.mod
+        let validation = validate_dependency_path(workshop.join("123/content.zip").to_string_lossy().to_string());
+        assert!(validation.valid);
+        assert_eq!(validation.name.as_deref(), Some("Inner"));
+        let empty = dir.path().join("empty.zip");
+        write_zip(&empty, &[("readme.txt", "")]);
+        assert!(!validate_dependency_path(empty.to_string_lossy().to_string()).valid);
+    }
+
+    #[test]
+    fn test_dependency_order_and_dedupe() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let project = dir.path().to_string_lossy().to_string();
+        write(
+            dir.path(),
+            "project.json",
+            r#"{"dependencies": [
+                {"path": "C:\\mods\\low", "priority": 0},
+                {"path": "/mods/off", "priority": 9, "enabled": false},
+                {"path": "/mods/high", "name": "High", "priority": 5},
+                {"path": "/mods/mid_a", "priority": 1},
+                {"path": "/mods/mid_b", "priority": 1}
+            ]}"#,
+        );
+
+        let deps = enabled_dependencies(&project);
+        let paths: Vec<&str> = deps.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, ["/mods/high", "/mods/mid_a", "/mods/mid_b", "C:\\mods\\low"]);
+        assert_eq!(deps[0].name, "High");
+        assert_eq!(deps[1].name, "/mods/mid_a");
+
+        // 前端传入的路径在前，与 project.json 重复的（忽略大小写、分隔符与末尾斜杠）只保留一次
+        let roots = with_project_dependencies(
+            Some(&project),
+            Some(vec!["C:/Mods/Low/".to_string(), String::new()]),
+        );
+        assert_eq!(roots, ["C:/Mods/Low/", "/mods/high", "/mods/mid_a", "/mods/mid_b"]);
+    }
+
+    #[test]
+    fn test_index_dependency_uses_cache_until_files_change() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        write(root, "common/ideas/a.txt", "ideas = { country = { dep_idea = { } } }");
+        write(root, "common/country_tags/00.txt", "DEP = \"countries/Dep.txt\"");
+        write(root, "localisation/dep_l_english.yml", "l_english:\n dep_idea:0 \"Idea\"\n");
+        write(root, "interface/dep.gfx", "spriteTypes = { spriteType = { name = \"GFX_dep\" texturefile = \"a.dds\" } }");
+        let path = root.to_string_lossy().to_string();
+        let cache_dir = tempfile::tempdir().expect("cache dir");
+
+        let first = index_dependency_in(path.clone(), cache_dir.path());
+        assert!(first.success && first.message.starts_with("索引完成"), "{}", first.message);
+        assert_eq!(first.idea_count, Some(1));
+        assert_eq!(first.tag_count, Some(1));
+        assert_eq!(first.localisation_count, Some(1));
+        assert_eq!(first.sprite_count, Some(1));
+        assert_eq!(first.file_count, 4);
+
+        let second = index_dependency_in(path.clone(), cache_dir.path());
+        assert!(second.message.starts_with("命中缓存"), "{}", second.message);
+        assert_eq!(second.idea_count, Some(1));
+
+        // 新增文件使时间戳集合变化，缓存失效
+        write(root, "common/ideas/b.txt", "ideas = { country = { other_idea = { } } }");
+        let third = index_dependency_in(path, cache_dir.path());
+        assert!(third.message.starts_with("索引完成"), "{}", third.message);
+        assert_eq!(third.idea_count, Some(2));
     }
 }
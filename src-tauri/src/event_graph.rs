@@ -0,0 +1,635 @@
+#![deny(clippy::unwrap_used)]
+
+//! 事件链图
+//!
+//! 解析项目与依赖项中的 `events/*.txt`，提取命名空间、事件定义（`is_triggered_only`、
+//! `mean_time_to_happen`、选项）以及所有 `country_event` / `news_event` / `state_event`
+//! 等调用及其延迟；同时扫描 on_actions、国策、决议与脚本效果，找出触发每个事件的来源。
+//! 结果附带字节区间，并给出不可达事件、调用了未定义事件与命名空间内重复 ID 的警告。
+
+use crate::clausewitz::{self, Block, Field, LineIndex, Value};
+use crate::file_cache::{self, FileSetCache, FileStamps};
+use crate::load_order::{self, RootSource};
+use crate::symbol_index::{self, IndexRoots, SymbolIndex, SymbolKind};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+/// ：事件类型，既是定义的键，也是触发事件的效果。
+const EVENT_TYPES: [&str; 5] = [
+    "country_event",
+    "news_event",
+    "state_event",
+    "unit_leader_event",
+    "operative_leader_event",
+];
+
+/// ：扫描的目录及其中调用的来源种类。
+const SCANNED_DIRS: [(&str, EventSourceKind); 5] = [
+    ("events", EventSourceKind::Event),
+    ("common/on_actions", EventSourceKind::OnAction),
+    ("common/national_focus", EventSourceKind::Focus),
+    ("common/decisions", EventSourceKind::Decision),
+    ("common/scripted_effects", EventSourceKind::ScriptedEffect),
+];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum EventSourceKind {
+    Event,
+    OnAction,
+    Focus,
+    Decision,
+    ScriptedEffect,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventNamespace {
+    pub name: String,
+    pub file: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventOptionDto {
+    pub name: Option<String>,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventNode {
+    pub id: String,
+    /// `id` 中 `.` 之前的部分
+    pub namespace: Option<String>,
+    /// `country_event`、`news_event` 等
+    pub event_type: String,
+    pub title: Option<String>,
+    pub is_triggered_only: bool,
+    pub fire_only_once: bool,
+    pub hidden: bool,
+    /// `mean_time_to_happen` 折算成天（月按 30 天、年按 365 天）
+    pub mean_time_to_happen: Option<f64>,
+    pub options: Vec<EventOptionDto>,
+    pub file: String,
+    pub source: RootSource,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+/// ：一次触发事件，`source` 为事件 ID、on_action 名、国策 ID、决议 ID 或脚本效果名。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventCall {
+    pub source_kind: EventSourceKind,
+    pub source: String,
+    /// 调用位于事件的哪个选项中
+    pub option: Option<String>,
+    pub target: String,
+    pub event_type: String,
+    pub days: Option<f64>,
+    pub random_days: Option<f64>,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum EventWarningKind {
+    /// `is_triggered_only` 的事件没有任何来源触发
+    Unreachable,
+    /// 触发了未定义的事件
+    Undefined,
+    /// 事件 ID 重复定义
+    DuplicateId,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventWarning {
+    pub kind: EventWarningKind,
+    pub event: String,
+    pub message: String,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventGraphResult {
+    pub success: bool,
+    pub message: String,
+    pub namespaces: Vec<EventNamespace>,
+    pub events: Vec<EventNode>,
+    pub calls: Vec<EventCall>,
+    pub warnings: Vec<EventWarning>,
+}
+
+/// 构建项目与依赖项的事件链图；提供游戏目录时，调用原版事件不算未定义
+#[tauri::command]
+pub fn event_graph(
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> EventGraphResult {
+    let roots = load_order::load_order(project_root.as_deref(), dependency_roots.clone(), None);
+    if roots.is_empty() {
+        return EventGraphResult {
+            success: false,
+            message: "未提供项目或依赖目录".to_string(),
+            namespaces: Vec::new(),
+            events: Vec::new(),
+            calls: Vec::new(),
+            warnings: Vec::new(),
+        };
+    }
+
+    let facts = load_facts(&roots);
+    let index_roots = IndexRoots::new(project_root, game_root, dependency_roots);
+    // 扫描目录以外的脚本（如 common/ 下的其它文件、history/）也可能触发事件，借助符号索引排除这类误报
+    let warnings = symbol_index::with_index(&index_roots, |index| {
+        let referenced = referenced_elsewhere(&facts, index);
+        collect_warnings(
+            &facts,
+            |id| index.is_defined_as(id, SymbolKind::Event),
+            |id| referenced.contains(id),
+        )
+    })
+    .map(|(warnings, _)| warnings)
+    .unwrap_or_else(|_| collect_warnings(&facts, |_| false, |_| false));
+
+    let message = format!(
+        "{} 个事件，{} 处触发，{} 条警告",
+        facts.events.len(),
+        facts.calls.len(),
+        warnings.len()
+    );
+    EventGraphResult {
+        success: true,
+        message,
+        namespaces: facts.namespaces.clone(),
+        events: facts.events.clone(),
+        calls: facts.calls.clone(),
+        warnings,
+    }
+}
+
+// ==================== 收集 ====================
+
+#[derive(Debug, Default)]
+struct GraphFacts {
+    namespaces: Vec<EventNamespace>,
+    events: Vec<EventNode>,
+    calls: Vec<EventCall>,
+}
+
+static FACTS_CACHE: Lazy<FileSetCache<GraphFacts>> = Lazy::new(FileSetCache::new);
+
+/// ：按加载顺序收集生效的文件，同一相对路径只取优先级最高的根目录。
+fn load_facts(roots: &[load_order::LoadOrderRoot]) -> Arc<GraphFacts> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut files: FileStamps = Vec::new();
+    let mut meta: Vec<(EventSourceKind, RootSource)> = Vec::new();
+    for (index, root) in roots.iter().enumerate() {
+        for (dir, kind) in SCANNED_DIRS {
+            if load_order::blanked_by(roots, index, dir).is_some() {
+                continue;
+            }
+            let base = Path::new(&root.path).join(dir);
+            if !base.exists() {
+                continue;
+            }
+            // 决议类别不含决议本身
+            let max_depth = if kind == EventSourceKind::Decision { 1 } else { usize::MAX };
+            for entry in WalkDir::new(&base)
+                .max_depth(max_depth)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(|e| e.ok())
+            {
+                let path = entry.path().to_string_lossy().replace('\\', "/");
+                if !entry.file_type().is_file() || !path.to_lowercase().ends_with(".txt") {
+                    continue;
+                }
+                let inner = entry.path().strip_prefix(&base).unwrap_or(entry.path());
+                let relative = format!("{}/{}", dir, inner.to_string_lossy().replace('\\', "/")).to_lowercase();
+                if !seen.insert(relative) {
+                    continue;
+                }
+                let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+                files.push((path, modified));
+                meta.push((kind, root.source));
+            }
+        }
+    }
+
+    FACTS_CACHE.get_or_build(file_cache::roots_key(roots), files, |files| {
+        let parsed: Vec<GraphFacts> = files
+            .par_iter()
+            .zip(meta.par_iter())
+            .map(|((path, _), (kind, source))| {
+                let content = fs::read(path)
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                    .unwrap_or_default();
+                parse_file(*kind, *source, path, &content)
+            })
+            .collect();
+        let mut facts = GraphFacts::default();
+        for file in parsed {
+            facts.namespaces.extend(file.namespaces);
+            facts.events.extend(file.events);
+            facts.calls.extend(file.calls);
+        }
+        facts
+    })
+}
+
+// ==================== 解析 ====================
+
+fn is_event_type(field: &Field) -> bool {
+    EVENT_TYPES.iter().any(|k| field.key_is(k))
+}
+
+/// ：事件 ID 形如 `namespace.1`，变量与脚本参数无法静态解析。
+fn is_static_id(text: &str) -> bool {
+    !text.is_empty() && !text.contains(['[', '$', '@', ':'])
+}
+
+/// ：`mean_time_to_happen` 中的 `days` / `months` / `years` 折算成天。
+fn mean_time_days(block: &Block) -> Option<f64> {
+    let parts = [("days", 1.0), ("months", 30.0), ("years", 365.0)];
+    let values: Vec<f64> = parts
+        .iter()
+        .filter_map(|(key, factor)| block.get_f64(key).map(|v| v * factor))
+        .collect();
+    (!values.is_empty()).then(|| values.iter().sum())
+}
+
+fn parse_file(kind: EventSourceKind, source: RootSource, file: &str, content: &str) -> GraphFacts {
+    let doc = clausewitz::parse(content);
+    let index = LineIndex::new(content);
+    let mut facts = GraphFacts::default();
+
+    if kind == EventSourceKind::Event {
+        for field in doc.root.fields() {
+            if field.key_is("add_namespace") {
+                if let Some(name) = field.value.as_str() {
+                    facts.namespaces.push(EventNamespace {
+                        name: name.to_string(),
+                        file: file.to_string(),
+                        line: index.line(field.span.start),
+                    });
+                }
+                continue;
+            }
+            let Some(block) = field.value.as_block().filter(|_| is_event_type(field)) else { continue };
+            let Some(id) = block.get_str("id") else { continue };
+            facts.events.push(EventNode {
+                id: id.to_string(),
+                namespace: id.split_once('.').map(|(ns, _)| ns.to_string()),
+                event_type: field.key.as_str().to_lowercase(),
+                title: block.get_str("title").map(|s| s.to_string()),
+                is_triggered_only: block.get_bool("is_triggered_only").unwrap_or(false),
+                fire_only_once: block.get_bool("fire_only_once").unwrap_or(false),
+                hidden: block.get_bool("hidden").unwrap_or(false),
+                mean_time_to_happen: block.get_block("mean_time_to_happen").and_then(mean_time_days),
+                options: block
+                    .fields_named("option")
+                    .filter_map(|f| f.value.as_block().map(|b| (f, b)))
+                    .map(|(f, b)| EventOptionDto {
+                        name: b.get_str("name").map(|s| s.to_string()),
+                        start: f.span.start,
+                        end: f.span.end,
+                        line: index.line(f.span.start),
+                    })
+                    .collect(),
+                file: file.to_string(),
+                source,
+                start: field.span.start,
+                end: field.span.end,
+                line: index.line(field.span.start),
+            });
+        }
+    }
+
+    doc.root.visit_fields(&mut |field, ancestors| {
+        let Some((source_name, option)) = call_source(kind, ancestors) else { return };
+        let mut push = |target: &str, event_type: &str, days: Option<f64>, random_days: Option<f64>, span: clausewitz::Span| {
+            if is_static_id(target) {
+                facts.calls.push(EventCall {
+                    source_kind: kind,
+                    source: source_name.clone(),
+                    option: option.clone(),
+                    target: target.to_string(),
+                    event_type: event_type.to_string(),
+                    days,
+                    random_days,
+                    file: file.to_string(),
+                    start: span.start,
+                    end: span.end,
+                    line: index.line(span.start),
+                });
+            }
+        };
+
+        if is_event_type(field) {
+            let event_type = field.key.as_str().to_lowercase();
+            match &field.value {
+                Value::Scalar(s) => push(s.as_str(), &event_type, None, None, s.span),
+                Value::Block(b) => {
+                    if let Some(id) = b.field("id").and_then(|f| f.value.as_scalar()) {
+                        let hours = b.get_f64("hours").map(|h| h / 24.0);
+                        let days = match (b.get_f64("days"), hours) {
+                            (None, None) => None,
+                            (d, h) => Some(d.unwrap_or(0.0) + h.unwrap_or(0.0)),
+                        };
+                        push(id.as_str(), &event_type, days, b.get_f64("random_days"), id.span);
+                    }
+                }
+                Value::Color(_) => {}
+            }
+        } else if kind == EventSourceKind::OnAction {
+            // on_actions 中的 `events = { a.1 }` 与 `random_events = { 100 = a.2 }`
+            let Some(block) = field.value.as_block() else { return };
+            if field.key_is("events") {
+                for s in block.scalars() {
+                    push(s.as_str(), "on_action", None, None, s.span);
+                }
+            } else if field.key_is("random_events") {
+                for f in block.fields() {
+                    if let Some(s) = f.value.as_scalar().filter(|s| s.as_str() != "0") {
+                        push(s.as_str(), "on_action", None, None, s.span);
+                    }
+                }
+            }
+        }
+    });
+
+    facts
+}
+
+/// ：调用所在的来源。事件文件顶层的事件块是定义本身，其内部的调用才算触发。
+fn call_source(kind: EventSourceKind, ancestors: &[&Field]) -> Option<(String, Option<String>)> {
+    let block_id = |f: &Field| f.value.as_block().and_then(|b| b.get_str("id")).map(|s| s.to_string());
+    match kind {
+        EventSourceKind::Event => {
+            let event = ancestors.first().filter(|f| is_event_type(f))?;
+            let option = ancestors
+                .get(1)
+                .filter(|f| f.key_is("option"))
+                .and_then(|f| f.value.as_block())
+                .and_then(|b| b.get_str("name"))
+                .map(|s| s.to_string());
+            Some((block_id(event)?, option))
+        }
+        EventSourceKind::OnAction => {
+            // on_actions = { on_startup = { ... } }
+            let name = ancestors.get(1).map(|f| f.key.as_str().to_string())?;
+            Some((name, None))
+        }
+        EventSourceKind::Focus => {
+            let focus = ancestors
+                .iter()
+                .find(|f| f.key_is("focus") || f.key_is("shared_focus") || f.key_is("joint_focus"))?;
+            Some((block_id(focus)?, None))
+        }
+        EventSourceKind::Decision => {
+            // category = { decision = { ... } }
+            let name = ancestors.get(1).map(|f| f.key.as_str().to_string())?;
+            Some((name, None))
+        }
+        EventSourceKind::ScriptedEffect => {
+            let name = ancestors.first().map(|f| f.key.as_str().to_string())?;
+            Some((name, None))
+        }
+    }
+}
+
+// ==================== 警告 ====================
+
+/// ：`is_triggered_only` 的事件中，在自身定义以外还被符号索引记录到引用的事件。
+fn referenced_elsewhere(facts: &GraphFacts, index: &SymbolIndex) -> HashSet<String> {
+    let names: HashSet<&str> = facts
+        .events
+        .iter()
+        .filter(|e| e.is_triggered_only)
+        .map(|e| e.id.as_str())
+        .collect();
+    let inside_definition = |name: &str, file: &str, start: usize| {
+        facts
+            .events
+            .iter()
+            .any(|e| e.id == name && e.file == file && (e.start..e.end).contains(&start))
+    };
+    index
+        .reference_sites(&names)
+        .into_iter()
+        .filter(|(name, sites)| sites.iter().any(|(file, start)| !inside_definition(name, file, *start)))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// ：`defined_elsewhere` 判断事件是否在扫描范围以外（如游戏本体）定义，
+/// `referenced_elsewhere` 判断事件是否在扫描范围以外被引用。
+fn collect_warnings(
+    facts: &GraphFacts,
+    defined_elsewhere: impl Fn(&str) -> bool,
+    referenced_elsewhere: impl Fn(&str) -> bool,
+) -> Vec<EventWarning> {
+    let mut warnings: Vec<EventWarning> = Vec::new();
+    let event_warning = |kind, event: &EventNode, message| EventWarning {
+        kind,
+        event: event.id.clone(),
+        message,
+        file: event.file.clone(),
+        start: event.start,
+        end: event.end,
+        line: event.line,
+    };
+
+    // 同路径文件的覆盖已在收集时处理，剩下的同名事件都会被游戏加载
+    let mut seen: HashMap<&str, &EventNode> = HashMap::new();
+    for event in &facts.events {
+        match seen.get(event.id.as_str()) {
+            Some(first) => warnings.push(event_warning(
+                EventWarningKind::DuplicateId,
+                event,
+                format!(
+                    "命名空间 {} 中的事件 ID {} 重复（首次定义于 {}:{}）",
+                    event.namespace.as_deref().unwrap_or("-"),
+                    event.id,
+                    first.file,
+                    first.line
+                ),
+            )),
+            None => {
+                seen.insert(&event.id, event);
+            }
+        }
+    }
+
+    let defined: HashSet<&str> = facts.events.iter().map(|e| e.id.as_str()).collect();
+    for call in &facts.calls {
+        if defined.contains(call.target.as_str()) || defined_elsewhere(&call.target) {
+            continue;
+        }
+        warnings.push(EventWarning {
+            kind: EventWarningKind::Undefined,
+            event: call.target.clone(),
+            message: format!("触发了未定义的事件 {}", call.target),
+            file: call.file.clone(),
+            start: call.start,
+            end: call.end,
+            line: call.line,
+        });
+    }
+
+    // 只被自身触发的事件同样不可达
+    let fired: HashSet<&str> = facts
+        .calls
+        .iter()
+        .filter(|c| c.source_kind != EventSourceKind::Event || c.source != c.target)
+        .map(|c| c.target.as_str())
+        .collect();
+    for event in facts.events.iter().filter(|e| e.is_triggered_only) {
+        if !fired.contains(event.id.as_str()) && !referenced_elsewhere(&event.id) {
+            warnings.push(event_warning(
+                EventWarningKind::Unreachable,
+                event,
+                format!("事件 {} 只能被触发，但没有找到任何触发或引用它的脚本", event.id),
+            ));
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENTS: &str = r#"
+add_namespace = test
+country_event = {
+    id = test.1
+    title = test.1.t
+    is_triggered_only = yes
+    immediate = { news_event = { id = test.2 days = 3 hours = 12 random_days = 2 } }
+    option = {
+        name = test.1.a
+        country_event = test.3
+    }
+    option = { name = test.1.b }
+}
+news_event = {
+    id = test.2
+    is_triggered_only = yes
+}
+country_event = {
+    id = test.3
+    mean_time_to_happen = { months = 1 days = 5 }
+}
+country_event = {
+    id = test.3
+    is_triggered_only = yes
+    option = { country_event = { id = test.3 } country_event = missing.1 }
+}
+"#;
+
+    fn facts() -> GraphFacts {
+        let mut facts = parse_file(EventSourceKind::Event, RootSource::Project, "/mod/events/test.txt", EVENTS);
+        let on_actions = "on_actions = { on_startup = { effect = { country_event = test.1 } random_events = { 100 = test.4 0 = 0 } } }";
+        let focuses = "focus_tree = { focus = { id = TST_focus completion_reward = { country_event = { id = test.2 days = 1 } } } }";
+        for (kind, file, content) in [
+            (EventSourceKind::OnAction, "/mod/common/on_actions/a.txt", on_actions),
+            (EventSourceKind::Focus, "/mod/common/national_focus/f.txt", focuses),
+        ] {
+            facts.calls.extend(parse_file(kind, RootSource::Project, file, content).calls);
+        }
+        facts
+    }
+
+    #[test]
+    fn test_event_extraction() {
+        let facts = facts();
+        assert_eq!(facts.namespaces[0].name, "test");
+        let first = &facts.events[0];
+        assert_eq!((first.event_type.as_str(), first.is_triggered_only), ("country_event", true));
+        let options: Vec<Option<&str>> = first.options.iter().map(|o| o.name.as_deref()).collect();
+        assert_eq!(options, [Some("test.1.a"), Some("test.1.b")]);
+        assert_eq!(facts.events[2].mean_time_to_happen, Some(35.0));
+
+        type CallSummary<'a> = (EventSourceKind, &'a str, Option<&'a str>, &'a str, Option<f64>);
+        let calls: Vec<CallSummary> = facts
+            .calls
+            .iter()
+            .map(|c| (c.source_kind, c.source.as_str(), c.option.as_deref(), c.target.as_str(), c.days))
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                (EventSourceKind::Event, "test.1", None, "test.2", Some(3.5)),
+                (EventSourceKind::Event, "test.1", Some("test.1.a"), "test.3", None),
+                (EventSourceKind::Event, "test.3", None, "test.3", None),
+                (EventSourceKind::Event, "test.3", None, "missing.1", None),
+                (EventSourceKind::OnAction, "on_startup", None, "test.1", None),
+                (EventSourceKind::OnAction, "on_startup", None, "test.4", None),
+                (EventSourceKind::Focus, "TST_focus", None, "test.2", Some(1.0)),
+            ]
+        );
+        assert_eq!(facts.calls[0].random_days, Some(2.0));
+        assert_eq!(&EVENTS[facts.calls[1].start..facts.calls[1].end], "test.3");
+    }
+
+    #[test]
+    fn test_event_warnings() {
+        let mut facts = facts();
+        facts.events.push(EventNode {
+            id: "test.9".to_string(),
+            file: "/mod/events/other.txt".to_string(),
+            ..facts.events[1].clone()
+        });
+        let warnings: Vec<(EventWarningKind, String)> = collect_warnings(&facts, |id| id == "test.4", |_| false)
+            .into_iter()
+            .map(|w| (w.kind, w.event))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (EventWarningKind::DuplicateId, "test.3".to_string()),
+                (EventWarningKind::Undefined, "missing.1".to_string()),
+                (EventWarningKind::Unreachable, "test.9".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_references_outside_scanned_dirs() {
+        let events = "add_namespace = gui\ncountry_event = { id = gui.1 is_triggered_only = yes option = { country_event = gui.1 } }\ncountry_event = { id = gui.2 is_triggered_only = yes }\n";
+        let gui = "scripted_gui = { gui_window = { effects = { button_click = { country_event = gui.2 } } } }";
+        let facts = parse_file(EventSourceKind::Event, RootSource::Project, "/mod/events/gui.txt", events);
+        let index = SymbolIndex::from_project_files(&[("events/gui.txt", events), ("common/scripted_guis/gui.txt", gui)]);
+        let referenced = referenced_elsewhere(&facts, &index);
+        assert_eq!(referenced, HashSet::from(["gui.2".to_string()]));
+        let unreachable: Vec<String> = collect_warnings(&facts, |_| false, |id| referenced.contains(id))
+            .into_iter()
+            .filter(|w| w.kind == EventWarningKind::Unreachable)
+            .map(|w| w.event)
+            .collect();
+        assert_eq!(unreachable, ["gui.1"]);
+    }
+}
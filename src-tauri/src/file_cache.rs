@@ -0,0 +1,133 @@
+#![deny(clippy::unwrap_used)]
+
+//! 按文件集合校验的内存缓存
+//!
+//! 各类注册表都由“一组按加载顺序收集的文件”计算得出：缓存记录这组文件及其 mtime，
+//! 文件增删、顺序或任一 mtime 变化时重新计算。
+
+use crate::load_order::LoadOrderRoot;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+/// ：参与计算的文件及其 mtime，顺序即加载顺序。
+pub(crate) type FileStamps = Vec<(String, Option<SystemTime>)>;
+
+struct FileSetEntry<T> {
+    files: FileStamps,
+    value: Arc<T>,
+}
+
+/// ：以 key 区分的缓存，key 通常为按加载顺序拼接的根目录。
+pub(crate) struct FileSetCache<T> {
+    entries: RwLock<HashMap<String, FileSetEntry<T>>>,
+}
+
+impl<T> FileSetCache<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// ：文件集合与缓存记录完全一致时返回缓存值。
+    pub(crate) fn get(&self, key: &str, files: &[(String, Option<SystemTime>)]) -> Option<Arc<T>> {
+        let entries = self.entries.read().ok()?;
+        entries
+            .get(key)
+            .filter(|entry| entry.files == files)
+            .map(|entry| entry.value.clone())
+    }
+
+    pub(crate) fn insert(&self, key: String, files: FileStamps, value: T) -> Arc<T> {
+        let value = Arc::new(value);
+        if let Ok(mut entries) = self.entries.write() {
+            entries.insert(key, FileSetEntry { files, value: value.clone() });
+        }
+        value
+    }
+
+    /// ：命中时直接返回，否则由 `build` 根据文件列表重新计算并写入缓存。
+    pub(crate) fn get_or_build(
+        &self,
+        key: String,
+        files: FileStamps,
+        build: impl FnOnce(&FileStamps) -> T,
+    ) -> Arc<T> {
+        if let Some(value) = self.get(&key, &files) {
+            return value;
+        }
+        let value = build(&files);
+        self.insert(key, files, value)
+    }
+
+    pub(crate) fn clear(&self) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.clear();
+        }
+    }
+}
+
+/// ：按加载顺序拼接根目录路径，作为缓存 key。
+pub(crate) fn roots_key(roots: &[LoadOrderRoot]) -> String {
+    roots.iter().map(|r| r.path.as_str()).collect::<Vec<_>>().join("|")
+}
+
+/// ：文件的 mtime，无法读取时为 None。
+pub(crate) fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).ok().and_then(|m| m.modified().ok())
+}
+
+/// ：游戏目录的廉价文件戳：launcher-settings.json 与各顶层目录自身的 mtime。
+/// 游戏更新时前者随之变化，不必为判断失效而遍历整个游戏目录。
+pub(crate) fn game_root_stamps<S: AsRef<str>>(root: &Path, dirs: impl IntoIterator<Item = S>) -> FileStamps {
+    let mut stamps: FileStamps = vec![(
+        "launcher-settings.json".to_string(),
+        modified(&root.join("launcher-settings.json")),
+    )];
+    stamps.extend(dirs.into_iter().map(|dir| {
+        let dir = dir.as_ref();
+        (dir.to_string(), modified(&root.join(dir)))
+    }));
+    stamps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_invalidates_when_file_set_changes() {
+        let cache: FileSetCache<usize> = FileSetCache::new();
+        let stamp = Some(SystemTime::UNIX_EPOCH);
+        let files = vec![("a.txt".to_string(), stamp)];
+        cache.insert("k".to_string(), files.clone(), 1);
+        assert_eq!(cache.get("k", &files).as_deref(), Some(&1));
+
+        // mtime 变化、文件增删或 key 不同都视为失效
+        let touched = vec![("a.txt".to_string(), stamp.map(|t| t + Duration::from_secs(1)))];
+        assert!(cache.get("k", &touched).is_none());
+        let added = vec![("a.txt".to_string(), stamp), ("b.txt".to_string(), None)];
+        assert!(cache.get("k", &added).is_none());
+        assert!(cache.get("other", &files).is_none());
+
+        cache.clear();
+        assert!(cache.get("k", &files).is_none());
+    }
+
+    #[test]
+    fn test_get_or_build_only_builds_on_miss() {
+        let cache: FileSetCache<usize> = FileSetCache::new();
+        let files = vec![("a.txt".to_string(), Some(SystemTime::UNIX_EPOCH))];
+        let mut builds = 0;
+        for _ in 0..2 {
+            cache.get_or_build("k".to_string(), files.clone(), |files| {
+                builds += 1;
+                files.len()
+            });
+        }
+        assert_eq!(builds, 1);
+    }
+}
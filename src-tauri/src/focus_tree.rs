@@ -0,0 +1,1034 @@
+#![deny(clippy::unwrap_used)]
+
+//! 国策树解析
+//!
+//! 把 `common/national_focus` 中的 `focus_tree` 解析为带类型的结构：坐标（含
+//! `relative_position_id` 换算后的绝对坐标）、前置条件分组、互斥、花费、`allow_branch`，
+//! 以及通过 `shared_focus` / `joint_focus` 引入的共享国策。每个国策附带源文件中的字节区间，
+//! 并给出循环引用、位置重叠、悬空前置与缺失图标等警告。
+
+use crate::clausewitz::{self, Block, LineIndex, Value};
+use crate::file_cache::{self, FileSetCache, FileStamps};
+use crate::graph::find_cycles;
+use crate::load_order;
+use crate::localisation_lint;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+/// ：国策的来源。
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FocusKind {
+    /// `focus_tree` 中的 `focus = { }`
+    Focus,
+    /// 顶层 `shared_focus = { }`
+    Shared,
+    /// 顶层 `joint_focus = { }`
+    Joint,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusDto {
+    pub id: String,
+    pub kind: FocusKind,
+    pub icon: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    /// 按 `relative_position_id` 累加后的坐标
+    pub absolute_x: i32,
+    pub absolute_y: i32,
+    pub relative_position_id: Option<String>,
+    /// 每组内为“或”，组之间为“且”
+    pub prerequisites: Vec<Vec<String>>,
+    pub mutually_exclusive: Vec<String>,
+    pub cost: Option<f64>,
+    /// `allow_branch` 块的原文
+    pub allow_branch: Option<String>,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FocusWarningKind {
+    /// 前置条件或相对定位成环
+    Cycle,
+    /// 多个国策位于同一格
+    Overlap,
+    /// 前置条件引用了不存在的国策
+    DanglingPrerequisite,
+    /// `relative_position_id`、互斥或 `shared_focus` 引用了不存在的国策
+    DanglingReference,
+    MissingIcon,
+    DuplicateId,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusWarning {
+    pub kind: FocusWarningKind,
+    pub focus: String,
+    pub message: String,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusTreeDto {
+    pub id: String,
+    pub default: bool,
+    /// 树内国策加上 `shared_focus` / `joint_focus` 引入的国策
+    pub focuses: Vec<FocusDto>,
+    pub shared_focuses: Vec<String>,
+    pub warnings: Vec<FocusWarning>,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusTreeData {
+    pub source_file: String,
+    pub trees: Vec<FocusTreeDto>,
+    /// 本文件中定义的共享国策（不属于任何一棵树）
+    pub shared_focuses: Vec<FocusDto>,
+    pub warnings: Vec<FocusWarning>,
+}
+
+const FOCUS_DIR: &str = "common/national_focus";
+
+/// 解析国策树文件，`content_override` 为编辑器中尚未保存的内容
+#[tauri::command]
+pub fn parse_focus_tree(
+    file_path: String,
+    content_override: Option<String>,
+    project_path: Option<String>,
+    game_directory: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> Result<FocusTreeData, String> {
+    let source = file_path.replace('\\', "/");
+    let content = match content_override {
+        Some(c) => c,
+        None => fs::read(&source)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .map_err(|e| format!("读取文件失败: {} ({})", source, e))?,
+    };
+
+    let project = project_path.filter(|p| !p.trim().is_empty());
+    let game = game_directory.filter(|p| !p.trim().is_empty());
+    let has_roots = project.is_some() || game.is_some();
+    let shared = load_shared_focuses(project.as_deref(), game.as_deref(), dependency_roots.clone());
+    let gfx = has_roots.then(|| localisation_lint::load_catalogue(project.as_deref(), game.as_deref(), dependency_roots));
+
+    Ok(build_focus_trees(&source, &content, &shared, gfx.as_deref().map(|g| &g.sprites)))
+}
+
+// ==================== 共享国策 ====================
+
+static SHARED_CACHE: Lazy<FileSetCache<HashMap<String, FocusDto>>> = Lazy::new(FileSetCache::new);
+
+/// ：按加载顺序收集全部根目录中定义的 `shared_focus` / `joint_focus`，高优先级的定义生效。
+fn load_shared_focuses(
+    project_root: Option<&str>,
+    game_root: Option<&str>,
+    dependency_roots: Option<Vec<String>>,
+) -> Arc<HashMap<String, FocusDto>> {
+    let roots = load_order::load_order(project_root, dependency_roots, game_root);
+    let mut files: FileStamps = Vec::new();
+    for (index, root) in roots.iter().enumerate() {
+        if load_order::blanked_by(&roots, index, FOCUS_DIR).is_some() {
+            continue;
+        }
+        let dir = Path::new(&root.path).join(FOCUS_DIR);
+        if !dir.exists() {
+            continue;
+        }
+        for entry in WalkDir::new(&dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            if entry.file_type().is_file() && name.ends_with(".txt") {
+                let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+                files.push((entry.path().to_string_lossy().replace('\\', "/"), modified));
+            }
+        }
+    }
+
+    SHARED_CACHE.get_or_build(file_cache::roots_key(&roots), files, |files| {
+        let mut focuses: HashMap<String, FocusDto> = HashMap::new();
+        for (path, _) in files {
+            let Ok(bytes) = fs::read(path) else { continue };
+            let content = String::from_utf8_lossy(&bytes);
+            if !content.contains("shared_focus") && !content.contains("joint_focus") {
+                continue;
+            }
+            for focus in parse_file(path, &content).shared {
+                focuses.entry(focus.id.clone()).or_insert(focus);
+            }
+        }
+        focuses
+    })
+}
+
+// ==================== 解析 ====================
+
+struct ParsedTree {
+    id: String,
+    default: bool,
+    focuses: Vec<FocusDto>,
+    /// `shared_focus = X` / `joint_focus = X` 引用及其字节区间
+    includes: Vec<(String, usize, usize)>,
+    start: usize,
+    end: usize,
+    line: usize,
+}
+
+struct ParsedFile {
+    trees: Vec<ParsedTree>,
+    shared: Vec<FocusDto>,
+}
+
+fn parse_file(file: &str, content: &str) -> ParsedFile {
+    let doc = clausewitz::parse(content);
+    let index = LineIndex::new(content);
+    let mut parsed = ParsedFile {
+        trees: Vec::new(),
+        shared: Vec::new(),
+    };
+
+    for field in doc.root.fields() {
+        let Some(block) = field.value.as_block() else { continue };
+        let kind = if field.key_is("focus_tree") {
+            None
+        } else if field.key_is("shared_focus") {
+            Some(FocusKind::Shared)
+        } else if field.key_is("joint_focus") {
+            Some(FocusKind::Joint)
+        } else {
+            continue;
+        };
+        match kind {
+            Some(kind) => parsed.shared.extend(parse_focus(kind, file, content, &index, block)),
+            None => parsed.trees.push(ParsedTree {
+                id: block.get_str("id").unwrap_or("unknown").to_string(),
+                default: block.get_bool("default").unwrap_or(false),
+                focuses: block
+                    .fields_named("focus")
+                    .filter_map(|f| f.value.as_block())
+                    .filter_map(|b| parse_focus(FocusKind::Focus, file, content, &index, b))
+                    .collect(),
+                includes: block
+                    .fields()
+                    .filter(|f| f.key_is("shared_focus") || f.key_is("joint_focus"))
+                    .filter_map(|f| f.value.as_scalar())
+                    .map(|s| (s.as_str().to_string(), s.span.start, s.span.end))
+                    .collect(),
+                start: field.span.start,
+                end: field.span.end,
+                line: index.line(field.span.start),
+            }),
+        }
+    }
+    parsed
+}
+
+fn parse_focus(kind: FocusKind, file: &str, content: &str, index: &LineIndex, block: &Block) -> Option<FocusDto> {
+    let id = block.get_str("id")?.to_string();
+    let x = block.get_f64("x").map(|v| v.round() as i32).unwrap_or(0);
+    let y = block.get_f64("y").map(|v| v.round() as i32).unwrap_or(0);
+    Some(FocusDto {
+        id,
+        kind,
+        icon: focus_icon(block),
+        x,
+        y,
+        absolute_x: x,
+        absolute_y: y,
+        relative_position_id: block.get_str("relative_position_id").map(|s| s.to_string()),
+        prerequisites: block
+            .fields_named("prerequisite")
+            .filter_map(|f| f.value.as_block())
+            .map(focus_list)
+            .filter(|group| !group.is_empty())
+            .collect(),
+        mutually_exclusive: block
+            .fields_named("mutually_exclusive")
+            .filter_map(|f| f.value.as_block())
+            .flat_map(focus_list)
+            .collect(),
+        cost: block.get_f64("cost"),
+        allow_branch: block
+            .get_block("allow_branch")
+            .map(|b| b.inner_span().slice(content).trim().to_string()),
+        file: file.to_string(),
+        start: block.span.start,
+        end: block.span.end,
+        line: index.line(block.span.start),
+    })
+}
+
+/// ：`icon = GFX_x`，或按条件切换的 `icon = { trigger = { } value = GFX_x }` 中的第一个。
+fn focus_icon(block: &Block) -> Option<String> {
+    block.fields_named("icon").find_map(|f| match &f.value {
+        Value::Scalar(s) => Some(s.as_str().to_string()),
+        Value::Block(b) => b.get_str("value").map(|s| s.to_string()),
+        Value::Color(_) => None,
+    })
+}
+
+fn focus_list(block: &Block) -> Vec<String> {
+    block
+        .fields_named("focus")
+        .filter_map(|f| f.value.as_str())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+// ==================== 组装与校验 ====================
+
+/// ：解析文件并组装每棵树，`shared` 为可供引用的共享国策，`sprites` 为 None 时不检查图标。
+fn build_focus_trees(
+    file: &str,
+    content: &str,
+    shared: &HashMap<String, FocusDto>,
+    sprites: Option<&HashSet<String>>,
+) -> FocusTreeData {
+    let parsed = parse_file(file, content);
+
+    // 本文件中的共享国策优先于磁盘上的版本
+    let mut available: HashMap<String, FocusDto> = shared.clone();
+    for focus in &parsed.shared {
+        available.insert(focus.id.clone(), focus.clone());
+    }
+
+    let trees = parsed
+        .trees
+        .into_iter()
+        .map(|tree| build_tree(file, tree, &available, sprites))
+        .collect();
+
+    let mut shared_focuses = parsed.shared;
+    let mut warnings = Vec::new();
+    check_duplicates(&shared_focuses, &mut warnings);
+    check_icons(&shared_focuses, sprites, &mut warnings);
+    resolve_positions(&mut shared_focuses, &available, &mut warnings);
+
+    FocusTreeData {
+        source_file: file.to_string(),
+        trees,
+        shared_focuses,
+        warnings,
+    }
+}
+
+fn build_tree(
+    file: &str,
+    tree: ParsedTree,
+    shared: &HashMap<String, FocusDto>,
+    sprites: Option<&HashSet<String>>,
+) -> FocusTreeDto {
+    let mut warnings: Vec<FocusWarning> = Vec::new();
+    let mut focuses = tree.focuses;
+
+    // `shared_focus = X` 引入 X 以及以其为（间接）前置的全部共享国策
+    let mut included: HashSet<String> = HashSet::new();
+    let mut shared_focuses: Vec<String> = Vec::new();
+    for (id, start, end) in &tree.includes {
+        if !shared.contains_key(id) {
+            warnings.push(FocusWarning {
+                kind: FocusWarningKind::DanglingReference,
+                focus: id.clone(),
+                message: format!("国策树 {} 引用了不存在的共享国策 {}", tree.id, id),
+                file: file.to_string(),
+                start: *start,
+                end: *end,
+                line: tree.line,
+            });
+            continue;
+        }
+        shared_focuses.push(id.clone());
+        included.insert(id.clone());
+    }
+    let mut changed = !included.is_empty();
+    while changed {
+        changed = false;
+        for focus in shared.values() {
+            if !included.contains(&focus.id)
+                && focus.prerequisites.iter().flatten().any(|p| included.contains(p))
+            {
+                included.insert(focus.id.clone());
+                changed = true;
+            }
+        }
+    }
+    let mut extra: Vec<FocusDto> = included.iter().filter_map(|id| shared.get(id).cloned()).collect();
+    extra.sort_by(|a, b| a.id.cmp(&b.id));
+    focuses.extend(extra);
+
+    check_duplicates(&focuses, &mut warnings);
+    let ids: HashSet<&str> = focuses.iter().map(|f| f.id.as_str()).collect();
+    for focus in &focuses {
+        for prerequisite in focus.prerequisites.iter().flatten() {
+            if !ids.contains(prerequisite.as_str()) {
+                warnings.push(warning(
+                    FocusWarningKind::DanglingPrerequisite,
+                    focus,
+                    format!("{} 的前置国策 {} 不存在", focus.id, prerequisite),
+                ));
+            }
+        }
+        for other in &focus.mutually_exclusive {
+            if !ids.contains(other.as_str()) {
+                warnings.push(warning(
+                    FocusWarningKind::DanglingReference,
+                    focus,
+                    format!("{} 的互斥国策 {} 不存在", focus.id, other),
+                ));
+            }
+        }
+    }
+
+    let edges: BTreeMap<&str, Vec<&str>> = focuses
+        .iter()
+        .map(|f| (f.id.as_str(), f.prerequisites.iter().flatten().map(|p| p.as_str()).collect()))
+        .collect();
+    for cycle in find_cycles(&edges) {
+        if let Some(focus) = focuses.iter().find(|f| f.id == cycle[0]) {
+            warnings.push(warning(
+                FocusWarningKind::Cycle,
+                focus,
+                format!("前置条件成环: {}", cycle.join(" → ")),
+            ));
+        }
+    }
+
+    check_icons(&focuses, sprites, &mut warnings);
+    resolve_positions(&mut focuses, &HashMap::new(), &mut warnings);
+    check_overlaps(&focuses, &mut warnings);
+
+    FocusTreeDto {
+        id: tree.id,
+        default: tree.default,
+        focuses,
+        shared_focuses,
+        warnings,
+        start: tree.start,
+        end: tree.end,
+        line: tree.line,
+    }
+}
+
+fn warning(kind: FocusWarningKind, focus: &FocusDto, message: String) -> FocusWarning {
+    FocusWarning {
+        kind,
+        focus: focus.id.clone(),
+        message,
+        file: focus.file.clone(),
+        start: focus.start,
+        end: focus.end,
+        line: focus.line,
+    }
+}
+
+fn check_duplicates(focuses: &[FocusDto], warnings: &mut Vec<FocusWarning>) {
+    let mut seen: HashSet<&str> = HashSet::new();
+    for focus in focuses {
+        if !seen.insert(&focus.id) {
+            warnings.push(warning(FocusWarningKind::DuplicateId, focus, format!("国策 ID 重复: {}", focus.id)));
+        }
+    }
+}
+
+fn check_icons(focuses: &[FocusDto], sprites: Option<&HashSet<String>>, warnings: &mut Vec<FocusWarning>) {
+    let Some(sprites) = sprites else { return };
+    for focus in focuses {
+        let Some(icon) = &focus.icon else { continue };
+        if icon.contains(['[', '$']) || sprites.contains(icon) {
+            continue;
+        }
+        warnings.push(warning(
+            FocusWarningKind::MissingIcon,
+            focus,
+            format!("{} 的图标 {} 没有对应的 spriteType", focus.id, icon),
+        ));
+    }
+}
+
+/// ：按 `relative_position_id` 计算绝对坐标，基准可以是 `outside` 中的国策（共享国策挂在树上时）。
+/// 成环或基准缺失时保留原始坐标。
+fn resolve_positions(focuses: &mut [FocusDto], outside: &HashMap<String, FocusDto>, warnings: &mut Vec<FocusWarning>) {
+    let by_id: HashMap<String, (i32, i32, Option<String>)> = outside
+        .values()
+        .chain(focuses.iter())
+        .map(|f| (f.id.clone(), (f.x, f.y, f.relative_position_id.clone())))
+        .collect();
+
+    let edges: BTreeMap<&str, Vec<&str>> = by_id
+        .iter()
+        .map(|(id, (_, _, rel))| (id.as_str(), rel.iter().map(|r| r.as_str()).collect()))
+        .collect();
+    let cyclic: HashSet<String> = find_cycles(&edges).into_iter().flatten().collect();
+
+    for focus in focuses.iter_mut() {
+        let mut x = focus.x;
+        let mut y = focus.y;
+        let mut current = focus.relative_position_id.clone();
+        while let Some(base) = current {
+            // 环上的国策各自报告，链条接到环上时停在环外
+            if cyclic.contains(&focus.id) || cyclic.contains(&base) {
+                break;
+            }
+            let Some((bx, by, next)) = by_id.get(&base) else {
+                warnings.push(warning(
+                    FocusWarningKind::DanglingReference,
+                    focus,
+                    format!("{} 的 relative_position_id 引用了不存在的国策 {}", focus.id, base),
+                ));
+                break;
+            };
+            x += bx;
+            y += by;
+            current = next.clone();
+        }
+        if cyclic.contains(&focus.id) {
+            warnings.push(warning(
+                FocusWarningKind::Cycle,
+                focus,
+                format!("{} 的 relative_position_id 成环", focus.id),
+            ));
+        }
+        focus.absolute_x = x;
+        focus.absolute_y = y;
+    }
+}
+
+fn check_overlaps(focuses: &[FocusDto], warnings: &mut Vec<FocusWarning>) {
+    let mut occupied: HashMap<(i32, i32), &str> = HashMap::new();
+    for focus in focuses {
+        let position = (focus.absolute_x, focus.absolute_y);
+        match occupied.get(&position) {
+            Some(other) => warnings.push(warning(
+                FocusWarningKind::Overlap,
+                focus,
+                format!("{} 与 {} 位置重叠 ({}, {})", focus.id, other, position.0, position.1),
+            )),
+            None => {
+                occupied.insert(position, &focus.id);
+            }
+        }
+    }
+}
+
+// ==================== 布局写回 ====================
+
+/// ：一次移动，`x`/`y` 为写入脚本的值，给出 `relative_to` 时是相对该国策的偏移。
+/// `relative_to` 为空时保留原有的 `relative_position_id`，只有 `detach` 为真时才删除。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusMove {
+    pub focus_id: String,
+    pub x: i32,
+    pub y: i32,
+    #[serde(default)]
+    pub relative_to: Option<String>,
+    #[serde(default)]
+    pub detach: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusLayoutResult {
+    pub success: bool,
+    pub message: String,
+    /// 实际改动了的国策
+    pub moved: Vec<String>,
+    /// 写回后的文件内容，供编辑器刷新
+    pub content: Option<String>,
+}
+
+/// 移动单个国策，只改写该国策的 x / y / relative_position_id 行
+#[tauri::command]
+pub fn move_focus(
+    file_path: String,
+    focus_id: String,
+    x: i32,
+    y: i32,
+    relative_to: Option<String>,
+    detach: Option<bool>,
+) -> FocusLayoutResult {
+    move_focuses(
+        file_path,
+        vec![FocusMove {
+            focus_id,
+            x,
+            y,
+            relative_to,
+            detach: detach.unwrap_or(false),
+        }],
+    )
+}
+
+/// 批量移动国策
+#[tauri::command]
+pub fn move_focuses(file_path: String, moves: Vec<FocusMove>) -> FocusLayoutResult {
+    rewrite_file(&file_path, |_| Ok(moves))
+}
+
+/// 自动排列国策树：按前置条件分层，消除空隙与重叠；`tree_id` 缺省时取文件中的第一棵树
+#[tauri::command]
+pub fn auto_layout_focus_tree(file_path: String, tree_id: Option<String>) -> FocusLayoutResult {
+    rewrite_file(&file_path, |content| layout_moves(content, tree_id.as_deref()))
+}
+
+fn layout_failure(message: String) -> FocusLayoutResult {
+    FocusLayoutResult {
+        success: false,
+        message,
+        moved: Vec::new(),
+        content: None,
+    }
+}
+
+fn rewrite_file(file_path: &str, plan: impl FnOnce(&str) -> Result<Vec<FocusMove>, String>) -> FocusLayoutResult {
+    let bytes = match fs::read(file_path) {
+        Ok(b) => b,
+        Err(e) => return layout_failure(format!("读取文件失败: {}", e)),
+    };
+    let Ok(content) = String::from_utf8(bytes) else {
+        return layout_failure("文件不是 UTF-8 编码，未写入".to_string());
+    };
+    let moves = match plan(&content) {
+        Ok(m) => m,
+        Err(e) => return layout_failure(e),
+    };
+    let (updated, moved) = match apply_moves(&content, &moves) {
+        Ok(r) => r,
+        Err(e) => return layout_failure(e),
+    };
+    if updated != content {
+        if let Err(e) = crate::write_text_file(Path::new(file_path), &updated, encoding_rs::UTF_8) {
+            return layout_failure(e);
+        }
+    }
+    FocusLayoutResult {
+        success: true,
+        message: format!("移动了 {} 个国策", moved.len()),
+        moved,
+        content: Some(updated),
+    }
+}
+
+/// ：文件中全部国策块（树内、共享与联合国策），按 id 索引。
+fn focus_blocks(root: &Block) -> HashMap<&str, &Block> {
+    let mut blocks: HashMap<&str, &Block> = HashMap::new();
+    for field in root.fields() {
+        let Some(block) = field.value.as_block() else { continue };
+        let candidates: Vec<&Block> = if field.key_is("focus_tree") {
+            block.fields_named("focus").filter_map(|f| f.value.as_block()).collect()
+        } else if field.key_is("shared_focus") || field.key_is("joint_focus") {
+            vec![block]
+        } else {
+            continue;
+        };
+        for candidate in candidates {
+            if let Some(id) = candidate.get_str("id") {
+                blocks.entry(id).or_insert(candidate);
+            }
+        }
+    }
+    blocks
+}
+
+struct TextEdit {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+/// ：把移动转换为文本替换，其余内容原样保留。返回新内容与实际改动的国策。
+fn apply_moves(content: &str, moves: &[FocusMove]) -> Result<(String, Vec<String>), String> {
+    let doc = clausewitz::parse(content);
+    let blocks = focus_blocks(&doc.root);
+    let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+
+    let mut edits: Vec<TextEdit> = Vec::new();
+    let mut moved: Vec<String> = Vec::new();
+    for mv in moves {
+        let block = blocks
+            .get(mv.focus_id.as_str())
+            .ok_or_else(|| format!("未找到国策 {}", mv.focus_id))?;
+        let id_field = block.field("id").ok_or_else(|| format!("国策 {} 缺少 id", mv.focus_id))?;
+        let anchor = match (&mv.relative_to, mv.detach) {
+            (Some(id), _) => Some(("relative_position_id", Some(id.clone()))),
+            (None, true) => Some(("relative_position_id", None)),
+            (None, false) => None,
+        };
+        let wanted = anchor
+            .into_iter()
+            .chain([("x", Some(mv.x.to_string())), ("y", Some(mv.y.to_string()))]);
+
+        let mut inserted: Vec<(&str, String)> = Vec::new();
+        let before = edits.len();
+        for (key, value) in wanted {
+            match (block.field(key), value) {
+                (Some(field), Some(value)) => {
+                    let span = field.value.span();
+                    if span.slice(content) != value {
+                        edits.push(TextEdit {
+                            start: span.start,
+                            end: span.end,
+                            text: value,
+                        });
+                    }
+                }
+                (Some(field), None) => edits.push(removal(content, field.span.start, field.span.end)),
+                (None, Some(value)) => inserted.push((key, value)),
+                (None, None) => {}
+            }
+        }
+
+        // 缺少的字段插在 id 之后，单行写法的块就地追加
+        if !inserted.is_empty() {
+            let anchor = id_field.span.end;
+            let line_start = content[..id_field.span.start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let indent = &content[line_start..id_field.span.start];
+            let text: String = if indent.trim().is_empty() {
+                inserted.iter().map(|(k, v)| format!("{}{}{} = {}", newline, indent, k, v)).collect()
+            } else {
+                inserted.iter().map(|(k, v)| format!(" {} = {}", k, v)).collect()
+            };
+            edits.push(TextEdit {
+                start: anchor,
+                end: anchor,
+                text,
+            });
+        }
+        if edits.len() > before {
+            moved.push(mv.focus_id.clone());
+        }
+    }
+
+    edits.sort_by(|a, b| b.start.cmp(&a.start).then(b.end.cmp(&a.end)));
+    let mut updated = content.to_string();
+    let mut limit = usize::MAX;
+    for edit in edits {
+        if edit.end > limit {
+            return Err("移动之间存在重叠的改动（同一国策出现了多次）".to_string());
+        }
+        updated.replace_range(edit.start..edit.end, &edit.text);
+        limit = edit.start;
+    }
+    Ok((updated, moved))
+}
+
+/// ：删除字段；字段独占一行时连同该行一起删除。
+fn removal(content: &str, start: usize, end: usize) -> TextEdit {
+    let line_start = content[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = content[end..].find('\n').map(|i| end + i + 1).unwrap_or(content.len());
+    let whole_line = content[line_start..start].trim().is_empty() && content[end..line_end].trim().is_empty();
+    let trailing = content[end..line_end].len() - content[end..line_end].trim_start_matches([' ', '\t']).len();
+    let (start, end) = if whole_line { (line_start, line_end) } else { (start, end + trailing) };
+    TextEdit {
+        start,
+        end,
+        text: String::new(),
+    }
+}
+
+/// ：按前置条件分层排列一棵树。y 为层数（最长前置链），每个国策挂在层数最深的前置下，
+/// 叶子依次占列、父节点居中于子节点之上，因此不同子树的列区间互不相交，不会重叠。
+/// 同层兄弟保持原有的左右顺序；相对定位的基准不在本树时改为绝对坐标。
+fn layout_moves(content: &str, tree_id: Option<&str>) -> Result<Vec<FocusMove>, String> {
+    let parsed = parse_file("", content);
+    let tree = parsed
+        .trees
+        .into_iter()
+        .find(|t| tree_id.map(|id| t.id == id).unwrap_or(true))
+        .ok_or_else(|| format!("未找到国策树 {}", tree_id.unwrap_or("")))?;
+    let mut focuses = tree.focuses;
+    resolve_positions(&mut focuses, &HashMap::new(), &mut Vec::new());
+
+    let index: HashMap<&str, usize> = focuses.iter().enumerate().map(|(i, f)| (f.id.as_str(), i)).collect();
+    let parents: Vec<Vec<usize>> = focuses
+        .iter()
+        .map(|f| f.prerequisites.iter().flatten().filter_map(|p| index.get(p.as_str()).copied()).collect())
+        .collect();
+
+    fn depth_of(i: usize, parents: &[Vec<usize>], memo: &mut [Option<i32>], visiting: &mut HashSet<usize>) -> i32 {
+        if let Some(d) = memo[i] {
+            return d;
+        }
+        if !visiting.insert(i) {
+            return 0;
+        }
+        let d = parents[i]
+            .iter()
+            .map(|&p| depth_of(p, parents, memo, visiting) + 1)
+            .max()
+            .unwrap_or(0);
+        visiting.remove(&i);
+        memo[i] = Some(d);
+        d
+    }
+    let mut memo: Vec<Option<i32>> = vec![None; focuses.len()];
+    let depths: Vec<i32> = (0..focuses.len())
+        .map(|i| depth_of(i, &parents, &mut memo, &mut HashSet::new()))
+        .collect();
+
+    let order = |list: &mut Vec<usize>| {
+        list.sort_by(|&a, &b| {
+            let (fa, fb) = (&focuses[a], &focuses[b]);
+            (fa.absolute_x, fa.absolute_y, &fa.id).cmp(&(fb.absolute_x, fb.absolute_y, &fb.id))
+        })
+    };
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); focuses.len()];
+    let mut roots: Vec<usize> = Vec::new();
+    for i in 0..focuses.len() {
+        let primary = parents[i]
+            .iter()
+            .copied()
+            .filter(|&p| depths[p] < depths[i])
+            .max_by_key(|&p| (depths[p], std::cmp::Reverse(p)));
+        match primary {
+            Some(p) => children[p].push(i),
+            None => roots.push(i),
+        }
+    }
+    children.iter_mut().for_each(&order);
+    order(&mut roots);
+
+    fn place(node: usize, children: &[Vec<usize>], cursor: &mut i32, xs: &mut [i32]) {
+        let kids = &children[node];
+        match (kids.first(), kids.last()) {
+            (Some(&first), Some(&last)) => {
+                for &kid in kids {
+                    place(kid, children, cursor, xs);
+                }
+                xs[node] = (xs[first] + xs[last]).div_euclid(2);
+            }
+            _ => {
+                xs[node] = *cursor;
+                *cursor += 1;
+            }
+        }
+    }
+    let mut xs: Vec<i32> = vec![0; focuses.len()];
+    let mut cursor = 0;
+    for &root in &roots {
+        place(root, &children, &mut cursor, &mut xs);
+    }
+
+    let edges: BTreeMap<&str, Vec<&str>> = focuses
+        .iter()
+        .map(|f| (f.id.as_str(), f.relative_position_id.iter().map(|r| r.as_str()).collect()))
+        .collect();
+    let cyclic: HashSet<String> = find_cycles(&edges).into_iter().flatten().collect();
+
+    Ok(focuses
+        .iter()
+        .enumerate()
+        .filter_map(|(i, focus)| {
+            let base = focus
+                .relative_position_id
+                .as_deref()
+                .filter(|id| !cyclic.contains(*id))
+                .and_then(|id| index.get(id).copied());
+            let (x, y) = match base {
+                Some(b) => (xs[i] - xs[b], depths[i] - depths[b]),
+                None => (xs[i], depths[i]),
+            };
+            let relative_to = base.map(|b| focuses[b].id.clone());
+            let unchanged = (x, y) == (focus.x, focus.y) && relative_to == focus.relative_position_id;
+            (!unchanged).then(|| FocusMove {
+                focus_id: focus.id.clone(),
+                x,
+                y,
+                detach: relative_to.is_none(),
+                relative_to,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TREE: &str = r#"
+focus_tree = {
+    id = test_tree
+    default = yes
+    shared_focus = SHARED_root
+    focus = {
+        id = TST_a
+        icon = GFX_a
+        x = 5
+        y = 0
+        cost = 10
+    }
+    focus = {
+        id = TST_b
+        icon = { trigger = { always = yes } value = GFX_b }
+        relative_position_id = TST_a
+        x = -1
+        y = 1
+        prerequisite = { focus = TST_a }
+        mutually_exclusive = { focus = TST_c }
+        allow_branch = { has_dlc = "La Resistance" }
+    }
+    focus = {
+        id = TST_c
+        icon = GFX_missing
+        relative_position_id = TST_b
+        x = 2
+        y = 0
+        prerequisite = { focus = TST_a focus = TST_b }
+        prerequisite = { focus = TST_gone }
+    }
+}
+"#;
+
+    #[test]
+    fn test_tree_model_and_shared_focuses() {
+        let shared_src = "shared_focus = { id = SHARED_root x = 0 y = 0 }\n\
+            shared_focus = { id = SHARED_next relative_position_id = SHARED_root x = 0 y = 1 prerequisite = { focus = SHARED_root } }\n\
+            shared_focus = { id = SHARED_other x = 9 y = 9 }";
+        let shared: HashMap<String, FocusDto> =
+            parse_file("shared.txt", shared_src).shared.into_iter().map(|f| (f.id.clone(), f)).collect();
+        let data = build_focus_trees("tree.txt", TREE, &shared, None);
+        let tree = &data.trees[0];
+        assert_eq!((tree.id.as_str(), tree.default), ("test_tree", true));
+        assert_eq!(tree.shared_focuses, ["SHARED_root"]);
+
+        let ids: Vec<&str> = tree.focuses.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, ["TST_a", "TST_b", "TST_c", "SHARED_next", "SHARED_root"]);
+        let b = &tree.focuses[1];
+        assert_eq!(b.icon.as_deref(), Some("GFX_b"));
+        assert_eq!((b.absolute_x, b.absolute_y), (4, 1));
+        assert_eq!(b.allow_branch.as_deref(), Some(r#"has_dlc = "La Resistance""#));
+        assert_eq!(&TREE[b.start..b.start + 1], "{");
+        let c = &tree.focuses[2];
+        assert_eq!((c.absolute_x, c.absolute_y), (6, 1));
+        assert_eq!(c.prerequisites, [vec!["TST_a", "TST_b"], vec!["TST_gone"]]);
+        assert_eq!(tree.focuses[0].cost, Some(10.0));
+        assert_eq!((tree.focuses[3].absolute_x, tree.focuses[3].absolute_y), (0, 1));
+    }
+
+    #[test]
+    fn test_tree_warnings() {
+        let content = format!(
+            "{}\nfocus_tree = {{ id = loop\n\
+                focus = {{ id = L_a x = 1 y = 1 prerequisite = {{ focus = L_b }} }}\n\
+                focus = {{ id = L_b x = 1 y = 1 prerequisite = {{ focus = L_a }} relative_position_id = L_c }}\n\
+                focus = {{ id = L_c relative_position_id = L_b }}\n\
+            }}",
+            TREE
+        );
+        let sprites: HashSet<String> = ["GFX_a", "GFX_b"].iter().map(|s| s.to_string()).collect();
+        let data = build_focus_trees("tree.txt", &content, &HashMap::new(), Some(&sprites));
+
+        let kinds = |tree: &FocusTreeDto| -> Vec<(FocusWarningKind, String)> {
+            tree.warnings.iter().map(|w| (w.kind, w.message.clone())).collect()
+        };
+        assert_eq!(
+            kinds(&data.trees[0]),
+            [
+                (FocusWarningKind::DanglingReference, "国策树 test_tree 引用了不存在的共享国策 SHARED_root".to_string()),
+                (FocusWarningKind::DanglingPrerequisite, "TST_c 的前置国策 TST_gone 不存在".to_string()),
+                (FocusWarningKind::MissingIcon, "TST_c 的图标 GFX_missing 没有对应的 spriteType".to_string()),
+            ]
+        );
+        assert_eq!(
+            kinds(&data.trees[1]),
+            [
+                (FocusWarningKind::Cycle, "前置条件成环: L_a → L_b → L_a".to_string()),
+                (FocusWarningKind::Cycle, "L_b 的 relative_position_id 成环".to_string()),
+                (FocusWarningKind::Cycle, "L_c 的 relative_position_id 成环".to_string()),
+                (FocusWarningKind::Overlap, "L_b 与 L_a 位置重叠 (1, 1)".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_move_focuses_keeps_formatting() {
+        let content = "focus_tree = {\n\
+            \tid = t\n\
+            \tfocus = {\n\
+            \t\tid = A\n\
+            \t\tx = 1 # left column\n\
+            \t\ty = 0\n\
+            \t}\n\
+            \tfocus = { id = B relative_position_id = A x = 1 y = 1 }\n\
+            \tfocus = {\n\
+            \t\tid = C\n\
+            \t\ticon = GFX_c\n\
+            \t}\n\
+            }\n";
+        let moves = [
+            FocusMove { focus_id: "A".into(), x: 3, y: 0, relative_to: None, detach: false },
+            FocusMove { focus_id: "B".into(), x: 4, y: 1, relative_to: None, detach: true },
+            FocusMove { focus_id: "C".into(), x: 0, y: 2, relative_to: Some("A".into()), detach: false },
+        ];
+        let (updated, moved) = apply_moves(content, &moves).expect("moves apply");
+        assert_eq!(moved, ["A", "B", "C"]);
+        assert_eq!(
+            updated,
+            "focus_tree = {\n\
+            \tid = t\n\
+            \tfocus = {\n\
+            \t\tid = A\n\
+            \t\tx = 3 # left column\n\
+            \t\ty = 0\n\
+            \t}\n\
+            \tfocus = { id = B x = 4 y = 1 }\n\
+            \tfocus = {\n\
+            \t\tid = C\n\
+            \t\trelative_position_id = A\n\
+            \t\tx = 0\n\
+            \t\ty = 2\n\
+            \t\ticon = GFX_c\n\
+            \t}\n\
+            }\n"
+        );
+        assert!(apply_moves(content, &[FocusMove { focus_id: "Z".into(), x: 0, y: 0, relative_to: None, detach: false }]).is_err());
+
+        // 不给出 relative_to 时保留原有的锚点
+        let keep = FocusMove { focus_id: "B".into(), x: 2, y: 1, relative_to: None, detach: false };
+        let (updated, moved) = apply_moves(content, &[keep]).expect("move applies");
+        assert_eq!(moved, ["B"]);
+        assert!(updated.contains("\tfocus = { id = B relative_position_id = A x = 2 y = 1 }\n"));
+    }
+
+    #[test]
+    fn test_auto_layout_packs_tree() {
+        let content = "focus_tree = { id = t\n\
+            focus = { id = R x = 10 y = 0 }\n\
+            focus = { id = L1 x = 3 y = 5 prerequisite = { focus = R } }\n\
+            focus = { id = L2 x = 20 y = 1 prerequisite = { focus = R } }\n\
+            focus = { id = M relative_position_id = L1 x = 0 y = 1 prerequisite = { focus = L1 } prerequisite = { focus = L2 } }\n\
+            focus = { id = S x = 0 y = 0 }\n\
+            }";
+        let moves = layout_moves(content, Some("t")).expect("tree exists");
+        let (updated, moved) = apply_moves(content, &moves).expect("moves apply");
+        assert_eq!(moved, ["R", "L1", "L2"]);
+
+        let data = build_focus_trees("tree.txt", &updated, &HashMap::new(), None);
+        let positions: Vec<(&str, i32, i32)> = data.trees[0]
+            .focuses
+            .iter()
+            .map(|f| (f.id.as_str(), f.absolute_x, f.absolute_y))
+            .collect();
+        assert_eq!(positions, [("R", 1, 0), ("L1", 1, 1), ("L2", 2, 1), ("M", 1, 2), ("S", 0, 0)]);
+        assert!(data.trees[0].warnings.is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+#![deny(clippy::unwrap_used)]
+
+//! 有向图工具
+//!
+//! 国策树的前置条件与 `relative_position_id`、脚本效果/触发器之间的调用关系
+//! 都以 `名称 -> [指向的名称]` 的邻接表表示，这里提供它们共用的环检测。
+
+use std::collections::{BTreeMap, HashSet};
+
+/// ：在有向图中查找环，每个环只报告一次，形如 `[A, B, A]`。
+pub(crate) fn find_cycles(edges: &BTreeMap<&str, Vec<&str>>) -> Vec<Vec<String>> {
+    fn visit<'a>(
+        node: &'a str,
+        edges: &BTreeMap<&'a str, Vec<&'a str>>,
+        stack: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        if done.contains(node) {
+            return;
+        }
+        if let Some(pos) = stack.iter().position(|n| *n == node) {
+            let mut cycle: Vec<String> = stack[pos..].iter().map(|n| n.to_string()).collect();
+            cycle.push(node.to_string());
+            cycles.push(cycle);
+            return;
+        }
+        stack.push(node);
+        for next in edges.get(node).into_iter().flatten() {
+            if edges.contains_key(next) {
+                visit(next, edges, stack, done, cycles);
+            }
+        }
+        stack.pop();
+        done.insert(node);
+    }
+
+    let mut cycles = Vec::new();
+    let mut done: HashSet<&str> = HashSet::new();
+    for node in edges.keys() {
+        visit(node, edges, &mut Vec::new(), &mut done, &mut cycles);
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_cycles() {
+        let edges: BTreeMap<&str, Vec<&str>> = BTreeMap::from([
+            ("A", vec!["B"]),
+            ("B", vec!["C", "missing"]),
+            ("C", vec!["A"]),
+            ("D", vec!["D"]),
+            ("E", vec!["A"]),
+        ]);
+        assert_eq!(
+            find_cycles(&edges),
+            vec![
+                vec!["A".to_string(), "B".to_string(), "C".to_string(), "A".to_string()],
+                vec!["D".to_string(), "D".to_string()],
+            ]
+        );
+    }
+}
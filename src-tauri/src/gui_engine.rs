@@ -2,7 +2,8 @@ use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use std::fs;
 use std::path::Path;
-use regex::Regex;
+use crate::clausewitz::{self, Block, Field};
+use crate::load_order;
 
 /// GUI 节点类型
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -64,35 +65,49 @@ pub struct GuiNode {
     pub children: Vec<GuiNode>,
 }
 
+/// 窗口类节点的键名
+pub(crate) const WINDOW_TYPES: [&str; 2] = ["containerWindowType", "windowType"];
+
+/// .gfx 中定义 sprite 的键名
+pub(crate) const SPRITE_TYPES: [&str; 3] = ["spriteType", "frameAnimatedSpriteType", "corneredTileSpriteType"];
+
+/// 可作为子节点的键名
+const CHILD_TYPES: [&str; 6] = [
+    "containerWindowType",
+    "windowType",
+    "iconType",
+    "buttonType",
+    "instantTextBoxType",
+    "gridBoxType",
+];
+
 /// 解析 GUI 文件内容并返回所有顶层窗口
 #[tauri::command]
 pub fn parse_gui_content(content: String) -> Result<Value, String> {
-    let content = strip_comments(&content);
-    // 基础解析：查找 containerWindowType 或 windowType
+    let doc = clausewitz::parse(&content);
     let mut windows = Vec::new();
-    let re_window = Regex::new(r"(?i)(containerWindowType|windowType)\s*=\s*\{").unwrap();
-    let mut current_pos = 0;
-    
-    while let Some(mat) = re_window.find_at(&content, current_pos) {
-        let start = mat.start();
-        // 查找匹配的闭合括号
-        if let Some(end) = find_matching_bracket(&content, mat.end()) {
-            let block = &content[start..end];
-            if let Some(node) = parse_node(block) {
-                windows.push(node);
-            }
-            current_pos = end;
-        } else {
-            current_pos = mat.end();
-        }
-    }
-    
+    collect_nodes(&doc.root, &WINDOW_TYPES, &mut windows);
+
     Ok(json!({
         "success": true,
         "windows": windows
     }))
 }
 
+/// 辅助函数：收集块内最外层的指定类型节点（不深入已匹配的节点）
+fn collect_nodes(block: &Block, types: &[&str], out: &mut Vec<GuiNode>) {
+    for field in block.fields() {
+        let Some(inner) = field.value.as_block() else {
+            continue;
+        };
+        if types.iter().any(|t| field.key_is(t)) {
+            out.push(parse_node(field, inner));
+        } else {
+            collect_nodes(inner, types, out);
+        }
+    }
+}
+
 /// 解析 GUI 文件并返回所有顶层窗口
 #[tauri::command]
 pub fn parse_gui_file(path: String) -> Result<Value, String> {
@@ -104,42 +119,54 @@ pub fn parse_gui_file(path: String) -> Result<Value, String> {
     Ok(res)
 }
 
+/// ：.gfx 中的一个 sprite 定义。
+#[derive(Debug, Clone)]
+pub(crate) struct GfxSprite {
+    pub(crate) name: String,
+    pub(crate) texturefile: Option<String>,
+    pub(crate) no_of_frames: i32,
+    /// 用于 9 宫格渲染的 borderSize
+    pub(crate) border_size: Option<Value>,
+}
+
+/// ：已解析的 .gfx 文档中的全部 sprite 定义，parse_gfx_file 与本地化检查共用。
+pub(crate) fn gfx_sprites(doc: &clausewitz::Document) -> Vec<GfxSprite> {
+    let mut sprites = Vec::new();
+    doc.root.visit_fields(&mut |field, _| {
+        let is_sprite = SPRITE_TYPES.iter().any(|k| field.key_is(k));
+        let Some(block) = field.value.as_block().filter(|_| is_sprite) else {
+            return;
+        };
+        let Some(name) = block.get_str("name") else {
+            return;
+        };
+        sprites.push(GfxSprite {
+            name: name.to_string(),
+            texturefile: block.get_str("texturefile").map(str::to_string),
+            no_of_frames: extract_int_value(block, "noOfFrames").unwrap_or(1),
+            border_size: extract_xy_value(block, "borderSize"),
+        });
+    });
+    sprites
+}
+
 /// 解析 GFX 文件获取 Sprite 定义
 #[tauri::command]
 pub fn parse_gfx_file(path: String) -> Result<Value, String> {
     let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    let content = strip_comments(&content);
-    let mut sprites = std::collections::HashMap::new();
-    
-    let re_sprite = Regex::new(r"(?i)(spriteType|frameAnimatedSpriteType|corneredTileSpriteType)\s*=\s*\{").unwrap();
-    let mut current_pos = 0;
-    
-    while let Some(mat) = re_sprite.find_at(&content, current_pos) {
-        let start = mat.start();
-        if let Some(end) = find_matching_bracket(&content, mat.end()) {
-            let block = &content[start..end];
-            let name = extract_value(block, "name");
-            let texturefile = extract_value(block, "texturefile");
-            let noofframes = extract_value(block, "noOfFrames")
-                .and_then(|v| v.parse::<i32>().ok())
-                .unwrap_or(1);
-            
-            // 提取 borderSize (用于 9 宫格渲染)
-            let border_size = extract_xy_value(block, "borderSize");
-            
-            if let Some(n) = name {
-                sprites.insert(n, json!({
-                    "texturefile": texturefile,
-                    "noOfFrames": noofframes,
-                    "borderSize": border_size
-                }));
-            }
-            current_pos = end;
-        } else {
-            current_pos = mat.end();
-        }
-    }
-    
+    let doc = clausewitz::parse(&content);
+    let sprites: std::collections::HashMap<String, Value> = gfx_sprites(&doc)
+        .into_iter()
+        .map(|sprite| {
+            let value = json!({
+                "texturefile": sprite.texturefile,
+                "noOfFrames": sprite.no_of_frames,
+                "borderSize": sprite.border_size
+            });
+            (sprite.name, value)
+        })
+        .collect();
+
     Ok(json!({
         "success": true,
         "sprites": sprites
@@ -154,18 +181,19 @@ pub async fn resolve_gui_resource(
     game_directory: String,
     dependency_roots: Vec<String>
 ) -> Result<Value, String> {
-    // 1. 搜寻所有可能的根目录：项目 > 依赖 > 游戏目录
-    let mut search_roots = vec![Path::new(&project_path).to_path_buf()];
-    for root in dependency_roots {
-        search_roots.push(Path::new(&root).to_path_buf());
-    }
-    if !game_directory.is_empty() {
-        search_roots.push(Path::new(&game_directory).to_path_buf());
-    }
-
-    // 2. 搜寻所有的 .gfx 文件，寻找名为 name 的 spriteType
-    for root in &search_roots {
-        let interface_dir = root.join("interface");
+    // 1. 按加载顺序排列根目录：项目 > 依赖（按 priority）> 游戏目录
+    let roots = load_order::load_order(
+        Some(&project_path),
+        Some(dependency_roots),
+        Some(&game_directory),
+    );
+
+    // 2. 搜寻所有的 .gfx 文件，寻找名为 name 的 spriteType；被 replace_path 清空的目录跳过
+    for (index, root) in roots.iter().enumerate() {
+        if load_order::blanked_by(&roots, index, "interface").is_some() {
+            continue;
+        }
+        let interface_dir = Path::new(&root.path).join("interface");
         if !interface_dir.exists() { continue; }
 
         if let Ok(entries) = fs::read_dir(interface_dir) {
@@ -179,9 +207,17 @@ pub async fn resolve_gui_resource(
                             let res = parse_gfx_file(path.to_string_lossy().to_string())?;
                             if let Some(sprite) = res["sprites"].get(&name) {
                                 if let Some(texture_rel_path) = sprite["texturefile"].as_str() {
-                                    // 3. 在所有根目录下寻找这个 texturefile
-                                    for t_root in &search_roots {
-                                        let full_path = t_root.join(texture_rel_path.replace("\\", "/"));
+                                    // 3. 按同样的顺序寻找这个 texturefile
+                                    let texture_rel_path = texture_rel_path.replace("\\", "/");
+                                    let texture_dir = texture_rel_path
+                                        .rsplit_once('/')
+                                        .map(|(dir, _)| dir)
+                                        .unwrap_or("");
+                                    for (t_index, t_root) in roots.iter().enumerate() {
+                                        if load_order::blanked_by(&roots, t_index, texture_dir).is_some() {
+                                            continue;
+                                        }
+                                        let full_path = Path::new(&t_root.path).join(&texture_rel_path);
                                         if full_path.exists() {
                                             return Ok(json!({
                                                 "success": true,
@@ -203,148 +239,78 @@ pub async fn resolve_gui_resource(
     Err(format!("Resource not found: {}", name))
 }
 
-/// 辅助函数：去除注释
-fn strip_comments(content: &str) -> String {
-    content.lines()
-        .map(|line| {
-            if let Some(pos) = line.find('#') {
-                &line[..pos]
-            } else {
-                line
-            }
-        })
-        .collect::<Vec<_>>()
-        .join("\n")
-}
-
-/// 辅助函数：查找匹配的括号
-fn find_matching_bracket(content: &str, start_pos: usize) -> Option<usize> {
-    let mut count = 1;
-    let bytes = content.as_bytes();
-    for i in start_pos..bytes.len() {
-        if bytes[i] == b'{' {
-            count += 1;
-        } else if bytes[i] == b'}' {
-            count -= 1;
-            if count == 0 {
-                return Some(i + 1);
-            }
-        }
-    }
-    None
-}
-
 /// 辅助函数：解析单个 GUI 节点
-fn parse_node(content: &str) -> Option<GuiNode> {
-    let node_type_match = Regex::new(
-        r"(?i)^\s*(containerWindowType|windowType|iconType|buttonType|instantTextBoxType|gridBoxType)\s*=",
-    )
-    .unwrap()
-    .captures(content);
-
-    let node_type = node_type_match
-        .and_then(|cap| cap.get(1).map(|m| m.as_str().to_ascii_lowercase()))
-        .map(|t| match t.as_str() {
-            "containerwindowtype" => GuiNodeType::ContainerWindow,
-            "windowtype" => GuiNodeType::WindowType,
-            "icontype" => GuiNodeType::Icon,
-            "buttontype" => GuiNodeType::Button,
-            "instanttextboxtype" => GuiNodeType::InstantTextBox,
-            "gridboxtype" => GuiNodeType::GridBox,
-            _ => GuiNodeType::Window,
-        })
-        .unwrap_or(GuiNodeType::Window);
+fn parse_node(field: &Field, block: &Block) -> GuiNode {
+    let node_type = match field.key.as_str().to_ascii_lowercase().as_str() {
+        "containerwindowtype" => GuiNodeType::ContainerWindow,
+        "windowtype" => GuiNodeType::WindowType,
+        "icontype" => GuiNodeType::Icon,
+        "buttontype" => GuiNodeType::Button,
+        "instanttextboxtype" => GuiNodeType::InstantTextBox,
+        "gridboxtype" => GuiNodeType::GridBox,
+        _ => GuiNodeType::Window,
+    };
 
     let mut children = Vec::new();
-    let mut child_spans: Vec<(usize, usize)> = Vec::new();
-
-    let re_child = Regex::new(
-        r"(?i)(containerWindowType|windowType|iconType|buttonType|instantTextBoxType|gridBoxType)\s*=\s*\{",
-    )
-    .unwrap();
-
-    let mut current_pos = 0;
-    while let Some(mat) = re_child.find_at(content, current_pos) {
-        // 如果匹配的是当前节点本身（起始位置），跳过
-        if mat.start() < 5 && current_pos == 0 {
-            current_pos = mat.end();
-            continue;
-        }
-
-        if let Some(end) = find_matching_bracket(content, mat.end()) {
-            let block = &content[mat.start()..end];
-            if let Some(child) = parse_node(block) {
-                children.push(child);
-            }
-            child_spans.push((mat.start(), end));
-            current_pos = end;
-        } else {
-            current_pos = mat.end();
-        }
-    }
-
-    let mut stripped = content.to_string();
-    child_spans.sort_by(|a, b| b.0.cmp(&a.0));
-    for (start, end) in child_spans {
-        let len = end.saturating_sub(start);
-        stripped.replace_range(start..end, &" ".repeat(len));
-    }
+    collect_nodes(block, &CHILD_TYPES, &mut children);
 
     let properties = GuiProperties {
-        name: extract_value(&stripped, "name"),
-        position: extract_position(&stripped),
-        size: extract_size(&stripped),
-        orientation: extract_value(&stripped, "orientation"),
-        origo: extract_value(&stripped, "origo"),
-        sprite_type: extract_value(&stripped, "spriteType").or_else(|| extract_value(&stripped, "sprite_type")),
-        quad_texture_sprite: extract_value(&stripped, "quadTextureSprite"),
-        background: extract_background_sprite(&stripped),
-        font: extract_value(&stripped, "font").or_else(|| extract_value(&stripped, "buttonFont")),
-        text: extract_value(&stripped, "text").or_else(|| extract_value(&stripped, "buttonText")),
-        format: extract_value(&stripped, "format"),
-        vertical_alignment: extract_value(&stripped, "vertical_alignment").or_else(|| extract_value(&stripped, "verticalAlignment")),
-        max_width: extract_int_value(&stripped, "maxWidth").or_else(|| extract_int_value(&stripped, "max_width")),
-        max_height: extract_int_value(&stripped, "maxHeight").or_else(|| extract_int_value(&stripped, "max_height")),
-        scale: extract_value(&stripped, "scale").and_then(|v| v.parse().ok()),
-        frame: extract_int_value(&stripped, "frame"),
-        clipping: extract_value(&stripped, "clipping").map(|v| v.to_lowercase() == "yes"),
-        fixedsize: extract_value(&stripped, "fixedsize").map(|v| v.to_lowercase() == "yes"),
-        slotsize: extract_slotsize(&stripped),
-        add_horizontal: extract_value(&stripped, "add_horizontal").map(|v| v.to_lowercase() == "yes"),
-        max_slots_horizontal: extract_int_value(&stripped, "max_slots_horizontal"),
-        max_slots_vertical: extract_int_value(&stripped, "max_slots_vertical"),
+        name: extract_value(block, "name"),
+        position: extract_position(block),
+        size: extract_size(block),
+        orientation: extract_value(block, "orientation"),
+        origo: extract_value(block, "origo"),
+        sprite_type: extract_value(block, "spriteType").or_else(|| extract_value(block, "sprite_type")),
+        quad_texture_sprite: extract_value(block, "quadTextureSprite"),
+        background: extract_background_sprite(block),
+        font: extract_value(block, "font").or_else(|| extract_value(block, "buttonFont")),
+        text: extract_value(block, "text").or_else(|| extract_value(block, "buttonText")),
+        format: extract_value(block, "format"),
+        vertical_alignment: extract_value(block, "vertical_alignment").or_else(|| extract_value(block, "verticalAlignment")),
+        max_width: extract_int_value(block, "maxWidth").or_else(|| extract_int_value(block, "max_width")),
+        max_height: extract_int_value(block, "maxHeight").or_else(|| extract_int_value(block, "max_height")),
+        scale: extract_value(block, "scale").and_then(|v| v.parse().ok()),
+        frame: extract_int_value(block, "frame"),
+        clipping: extract_value(block, "clipping").map(|v| v.to_lowercase() == "yes"),
+        fixedsize: extract_value(block, "fixedsize").map(|v| v.to_lowercase() == "yes"),
+        slotsize: extract_slotsize(block),
+        add_horizontal: extract_value(block, "add_horizontal").map(|v| v.to_lowercase() == "yes"),
+        max_slots_horizontal: extract_int_value(block, "max_slots_horizontal"),
+        max_slots_vertical: extract_int_value(block, "max_slots_vertical"),
     };
 
-    Some(GuiNode {
+    GuiNode {
         node_type,
         properties,
         children,
-    })
+    }
 }
 
 /// 辅助函数：提取属性值
-fn extract_value(content: &str, key: &str) -> Option<String> {
-    // 支持 key = value, key = "value"
-    let re = Regex::new(&format!(r#"(?i){}\s*=\s*("(?:[^"\\]|\\.)*"|[^\s{{}}]+)"#, key)).unwrap();
-    re.captures(content).map(|cap| {
-        cap[1].trim_matches('"').to_string()
-    })
+fn extract_value(block: &Block, key: &str) -> Option<String> {
+    block.get_str(key).map(|v| v.to_string())
+}
+
+/// 辅助函数：提取宽高块，兼容 `{ width height }` 与 `{ x y }` 两种写法
+fn extract_size_block(block: &Block, key: &str) -> Option<Size> {
+    let inner = block.get_block(key)?;
+    let width = extract_int_value(inner, "width").or_else(|| extract_int_value(inner, "x")).unwrap_or(0);
+    let height = extract_int_value(inner, "height").or_else(|| extract_int_value(inner, "y")).unwrap_or(0);
+    Some(Size { width, height })
 }
 
 /// 辅助函数：提取位置
-fn extract_position(content: &str) -> Option<Position> {
+fn extract_position(block: &Block) -> Option<Position> {
     // 1. 尝试解析 position = { x = 10 y = 20 }
-    let re_block = Regex::new(r"(?i)position\s*=\s*\{([^{}]*)\}").unwrap();
-    if let Some(cap) = re_block.captures(content) {
-        let block = &cap[1];
-        let x = extract_int_value(block, "x").unwrap_or(0);
-        let y = extract_int_value(block, "y").unwrap_or(0);
+    if let Some(inner) = block.get_block("position") {
+        let x = extract_int_value(inner, "x").unwrap_or(0);
+        let y = extract_int_value(inner, "y").unwrap_or(0);
         return Some(Position { x, y });
     }
-    
+
     // 2. 尝试直接解析顶级 x = ... 和 y = ...
-    let x = extract_int_value(content, "x");
-    let y = extract_int_value(content, "y");
+    let x = extract_int_value(block, "x");
+    let y = extract_int_value(block, "y");
     if x.is_some() || y.is_some() {
         return Some(Position { x: x.unwrap_or(0), y: y.unwrap_or(0) });
     }
@@ -353,19 +319,15 @@ fn extract_position(content: &str) -> Option<Position> {
 }
 
 /// 辅助函数：提取尺寸
-fn extract_size(content: &str) -> Option<Size> {
+fn extract_size(block: &Block) -> Option<Size> {
     // 1. 尝试解析 size = { width = 100 height = 200 } 或 { x = 100 y = 200 }
-    let re_block = Regex::new(r"(?i)size\s*=\s*\{([^{}]*)\}").unwrap();
-    if let Some(cap) = re_block.captures(content) {
-        let block = &cap[1];
-        let width = extract_int_value(block, "width").or_else(|| extract_int_value(block, "x")).unwrap_or(0);
-        let height = extract_int_value(block, "height").or_else(|| extract_int_value(block, "y")).unwrap_or(0);
-        return Some(Size { width, height });
+    if let Some(size) = extract_size_block(block, "size") {
+        return Some(size);
     }
 
     // 2. 尝试直接解析顶级 width = ... 和 height = ...
-    let width = extract_int_value(content, "width");
-    let height = extract_int_value(content, "height");
+    let width = extract_int_value(block, "width");
+    let height = extract_int_value(block, "height");
     if width.is_some() || height.is_some() {
         return Some(Size { width: width.unwrap_or(0), height: height.unwrap_or(0) });
     }
@@ -374,43 +336,33 @@ fn extract_size(content: &str) -> Option<Size> {
 }
 
 /// 辅助函数：提取格子尺寸
-fn extract_slotsize(content: &str) -> Option<Size> {
-    let re_block = Regex::new(r"(?i)slotsize\s*=\s*\{([^{}]*)\}").unwrap();
-    if let Some(cap) = re_block.captures(content) {
-        let block = &cap[1];
-        let width = extract_int_value(block, "width").or_else(|| extract_int_value(block, "x")).unwrap_or(0);
-        let height = extract_int_value(block, "height").or_else(|| extract_int_value(block, "y")).unwrap_or(0);
-        return Some(Size { width, height });
-    }
-    None
+fn extract_slotsize(block: &Block) -> Option<Size> {
+    extract_size_block(block, "slotsize")
 }
 
-/// 辅助函数：从块中提取整数值
-fn extract_int_value(content: &str, key: &str) -> Option<i32> {
-    let re = Regex::new(&format!(r"(?i){}\s*=\s*(-?\d+)", key)).unwrap();
-    re.captures(content).and_then(|cap| cap[1].parse().ok())
+/// 辅助函数：从块中提取整数值（取数值前缀，兼容 `10%` 等写法）
+fn extract_int_value(block: &Block, key: &str) -> Option<i32> {
+    let raw = block.get_str(key)?.trim();
+    let digits_end = raw
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-')))
+        .map(|(i, _)| i)
+        .unwrap_or(raw.len());
+    raw[..digits_end].parse().ok()
 }
 
 /// 辅助函数：提取背景中的 Sprite
-fn extract_background_sprite(content: &str) -> Option<String> {
-    let re_bg = Regex::new(r"(?i)background\s*=\s*\{([^{}]*)\}").unwrap();
-    if let Some(cap) = re_bg.captures(content) {
-        let block = &cap[1];
-        return extract_value(block, "spriteType")
-            .or_else(|| extract_value(block, "quadTextureSprite"))
-            .or_else(|| extract_value(block, "sprite_type"));
-    }
-    None
+fn extract_background_sprite(block: &Block) -> Option<String> {
+    let inner = block.get_block("background")?;
+    extract_value(inner, "spriteType")
+        .or_else(|| extract_value(inner, "quadTextureSprite"))
+        .or_else(|| extract_value(inner, "sprite_type"))
 }
 
 /// 辅助函数：提取 x/y 结构的值 (常用于 borderSize 或 size)
-fn extract_xy_value(content: &str, key: &str) -> Option<Value> {
-    let re = Regex::new(&format!(r"(?i){}\s*=\s*\{{([^{{}}]*)\}}", key)).unwrap();
-    if let Some(cap) = re.captures(content) {
-        let block = &cap[1];
-        let x = extract_int_value(block, "x").unwrap_or(0);
-        let y = extract_int_value(block, "y").unwrap_or(0);
-        return Some(json!({ "x": x, "y": y }));
-    }
-    None
+fn extract_xy_value(block: &Block, key: &str) -> Option<Value> {
+    let inner = block.get_block(key)?;
+    let x = extract_int_value(inner, "x").unwrap_or(0);
+    let y = extract_int_value(inner, "y").unwrap_or(0);
+    Some(json!({ "x": x, "y": y }))
 }
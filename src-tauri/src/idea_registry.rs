@@ -1,12 +1,13 @@
 #![deny(clippy::unwrap_used)]
 use once_cell::sync::Lazy;
 use rayon::prelude::*;
+use crate::clausewitz;
+use crate::file_cache::{FileSetCache, FileStamps};
 use serde::Serialize;
 use std::collections::HashMap;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
-use std::sync::RwLock;
 use std::time::SystemTime;
 
 /// ：标记点位的来源，区分项目、游戏目录与依赖项，便于前端显示覆盖关系。
@@ -35,16 +36,8 @@ pub struct IdeaLoadResponse {
     pub ideas: Option<Vec<IdeaEntry>>,
 }
 
-/// ：缓存条目，记录idea集合及文件时间戳，便于判断缓存是否失效。
-#[derive(Debug, Clone)]
-struct IdeaCacheEntry {
-    ideas: Vec<IdeaEntry>,
-    timestamps: HashMap<String, Option<SystemTime>>,
-}
-
 /// ：全局缓存容器，key 为“项目路径||游戏路径”。
-static IDEA_CACHE: Lazy<RwLock<HashMap<String, IdeaCacheEntry>>> =
-    Lazy::new(|| RwLock::new(HashMap::new()));
+static IDEA_CACHE: Lazy<FileSetCache<Vec<IdeaEntry>>> = Lazy::new(FileSetCache::new);
 
 /// ：描述单个候选文件与来源及修改时间。
 #[derive(Debug, Clone)]
@@ -52,18 +45,16 @@ struct IdeaFileInfo {
     path: PathBuf,
     source: IdeaSource,
     modified: Option<SystemTime>,
+    /// 压缩包形式的依赖项，整体作为一个条目，解析时读取包内 `common/ideas`
+    archive: bool,
 }
 
 /// ：对外暴露的清理接口，可在调试时手动失效缓存。
 #[tauri::command]
 pub fn reset_idea_cache() -> bool {
-    if let Ok(mut cache) = IDEA_CACHE.write() {
-        cache.clear();
-        println!("[idea_registry] cache cleared by frontend command");
-        true
-    } else {
-        false
-    }
+    IDEA_CACHE.clear();
+    println!("[idea_registry] cache cleared by frontend command");
+    true
 }
 
 /// ：加载idea列表，必要时重新扫描文件。支持依赖项路径列表。
@@ -75,8 +66,9 @@ pub fn load_ideas(
 ) -> IdeaLoadResponse {
     let normalized_project = normalize_root(project_root.as_deref());
     let normalized_game = normalize_root(game_root.as_deref());
+    let dependency_roots =
+        crate::dependency::with_project_dependencies(project_root.as_deref(), dependency_roots);
     let normalized_deps: Vec<String> = dependency_roots
-        .unwrap_or_default()
         .into_iter()
         .filter_map(|s| normalize_root(Some(&s)))
         .collect();
@@ -170,7 +162,13 @@ fn add_files_under_root(
     out: &mut Vec<IdeaFileInfo>,
 ) -> io::Result<()> {
     if let Some(root_dir) = root {
-        let base = Path::new(root_dir).join("common/ideas");
+        let root_path = Path::new(root_dir);
+        if crate::dependency::is_archive(root_path) {
+            let modified = fs::metadata(root_path).ok().and_then(|m| m.modified().ok());
+            out.push(IdeaFileInfo { path: root_path.to_path_buf(), source, modified, archive: true });
+            return Ok(());
+        }
+        let base = root_path.join("common/ideas");
         if base.exists() {
             let mut stack = vec![base];
             while let Some(current) = stack.pop() {
@@ -181,7 +179,7 @@ fn add_files_under_root(
                         stack.push(path);
                     } else if is_script_file(&path) {
                         let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
-                        out.push(IdeaFileInfo { path, source, modified });
+                        out.push(IdeaFileInfo { path, source, modified, archive: false });
                     }
                 }
             }
@@ -200,50 +198,20 @@ fn is_script_file(path: &Path) -> bool {
 
 /// 尝试读取缓存，若文件时间戳均一致则返回克隆数据。
 fn try_use_cache(cache_key: &str, files: &[IdeaFileInfo]) -> Option<Vec<IdeaEntry>> {
-    let cache_map = IDEA_CACHE.read().ok()?;
-    let cached = cache_map.get(cache_key)?;
-    if !is_cache_valid(cached, files) {
-        return None;
-    }
-    Some(cached.ideas.clone())
+    IDEA_CACHE.get(cache_key, &file_stamps(files)).map(|cached| cached.to_vec())
 }
 
-/// 校验缓存内的时间戳是否与当前文件列表一致。
-fn is_cache_valid(entry: &IdeaCacheEntry, files: &[IdeaFileInfo]) -> bool {
-    if entry.timestamps.len() != files.len() {
-        return false;
-    }
-    for info in files {
-        let path_str = match info.path.to_str() {
-            Some(v) => v,
-            None => return false,
-        };
-        match entry.timestamps.get(path_str) {
-            Some(stored) if stored == &info.modified => continue,
-            Some(_) => return false,
-            None => return false,
-        }
-    }
-    true
+/// ：缓存校验用的文件路径与时间戳。
+fn file_stamps(files: &[IdeaFileInfo]) -> FileStamps {
+    files
+        .iter()
+        .map(|info| (info.path.to_string_lossy().into_owned(), info.modified))
+        .collect()
 }
 
 /// 将最新扫描的idea列表写入缓存。
 fn store_cache(cache_key: &str, ideas: &[IdeaEntry], files: &[IdeaFileInfo]) {
-    if let Ok(mut cache) = IDEA_CACHE.write() {
-        let mut timestamps = HashMap::new();
-        for info in files {
-            if let Some(path_str) = info.path.to_str() {
-                timestamps.insert(path_str.to_string(), info.modified);
-            }
-        }
-        cache.insert(
-            cache_key.to_string(),
-            IdeaCacheEntry {
-                ideas: ideas.to_vec(),
-                timestamps,
-            },
-        );
-    }
+    IDEA_CACHE.insert(cache_key.to_string(), file_stamps(files), ideas.to_vec());
 }
 
 /// 并行解析所有文件的idea定义。
@@ -251,9 +219,19 @@ fn parse_ideas(files: &[IdeaFileInfo]) -> io::Result<Vec<IdeaEntry>> {
     let parsed: Result<Vec<Vec<(String, IdeaSource)>>, io::Error> = files
         .par_iter()
         .map(|info| {
-            let content = fs::read_to_string(&info.path)?;
+            let contents = if info.archive {
+                crate::dependency::read_archive_entries(&info.path, |relative| {
+                    relative.starts_with("common/ideas/") && relative.ends_with(".txt")
+                })
+                .map_err(io::Error::other)?
+                .into_iter()
+                .map(|entry| String::from_utf8_lossy(&entry.bytes).into_owned())
+                .collect()
+            } else {
+                vec![fs::read_to_string(&info.path)?]
+            };
             let mut local = Vec::new();
-            for idea in extract_ideas(&content) {
+            for idea in contents.iter().flat_map(|content| extract_ideas(content)) {
                 local.push((idea, info.source));
             }
             Ok(local)
@@ -288,75 +266,27 @@ fn parse_ideas(files: &[IdeaFileInfo]) -> io::Result<Vec<IdeaEntry>> {
     Ok(ideas)
 }
 
-/// 从单个脚本内容中提取idea标识符。
-fn extract_ideas(content: &str) -> Vec<String> {
+/// 从单个脚本内容中提取idea标识符（`ideas = { 分类 = { idea = { } } }`）。
+pub(crate) fn extract_ideas(content: &str) -> Vec<String> {
+    let doc = clausewitz::parse(content);
     let mut ideas = Vec::new();
-    let mut chars = content.chars().peekable();
-    let mut stack: Vec<Option<String>> = Vec::new();
-    let mut current_ident: Option<String> = None;
-    let mut in_comment = false;
-
-    while let Some(ch) = chars.next() {
-        if in_comment {
-            if ch == '\n' {
-                in_comment = false;
-            }
-            continue;
+    doc.root.visit_fields(&mut |field, _| {
+        if !field.key_is("ideas") {
+            return;
         }
-
-        match ch {
-            '#' => {
-                in_comment = true;
-                current_ident = None;
-            }
-            '{' => {
-                let ident = current_ident.take();
-                stack.push(ident.clone());
-                if stack.len() >= 3 {
-                    if let (Some(Some(ideas_key)), Some(Some(_category)), Some(Some(idea_name))) = (
-                        stack.get(stack.len() - 3),
-                        stack.get(stack.len() - 2),
-                        stack.last(),
-                    ) {
-                        if ideas_key.eq_ignore_ascii_case("ideas") {
-                            ideas.push(idea_name.clone());
-                        }
-                    }
-                }
-            }
-            '}' => {
-                current_ident = None;
-                stack.pop();
-            }
-            '=' => {
-                // 忽略，等待下一个符号以决定是否进入块
-            }
-            c if c.is_whitespace() => {
-                // 空白直接忽略
-            }
-            c if is_ident_char(c) => {
-                let mut ident = String::new();
-                ident.push(c);
-                while let Some(&next) = chars.peek() {
-                    if is_ident_char(next) {
-                        ident.push(next);
-                        chars.next();
-                    } else {
-                        break;
-                    }
+        let Some(categories) = field.value.as_block() else {
+            return;
+        };
+        for category in categories.fields() {
+            let Some(entries) = category.value.as_block() else {
+                continue;
+            };
+            for idea in entries.fields() {
+                if idea.value.as_block().is_some() {
+                    ideas.push(idea.key.as_str().to_string());
                 }
-                current_ident = Some(ident);
-            }
-            _ => {
-                current_ident = None;
             }
         }
-    }
-
+    });
     ideas
 }
-
-/// 判断字符是否属于idea标识符。
-fn is_ident_char(ch: char) -> bool {
-    ch.is_ascii_alphanumeric() || matches!(ch, '_' | '.' | '-')
-}
\ No newline at end of file
@@ -11,18 +11,38 @@ use std::sync::{Arc, Mutex};
 
 // 本地模块
 mod json_decoder;
+mod clausewitz;
 mod file_tree;
 mod bracket_matcher;
+mod character_registry;
 mod country_tags;
 mod idea_registry;
 mod tag_validator;
+mod decisions;
 mod dependency;
+mod event_graph;
+mod file_cache;
 mod focus_localization;
+mod focus_tree;
+mod graph;
+mod localisation;
+mod localisation_lint;
+mod localisation_transfer;
+mod scripted_registry;
 mod map_engine;
 mod gui_engine;
 mod mio_parser;
 mod plugin_manager;
 mod theme_manager;
+mod script_formatter;
+mod script_lint;
+mod modifier_db;
+mod symbol_index;
+mod symbol_rename;
+mod load_order;
+mod mod_descriptor;
+mod packaging;
+mod package_validation;
 
 use json_decoder::{
     get_json_path,
@@ -455,22 +475,6 @@ pub struct LaunchGameResult {
     message: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PackageResult {
-    success: bool,
-    message: String,
-    output_path: Option<String>,
-    file_size: Option<u64>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PackageOptions {
-    pub project_path: String,
-    pub output_name: String,
-    pub exclude_dependencies: bool,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ImageReadResult {
@@ -661,27 +665,14 @@ fn create_new_project(
         };
     }
 
-    let replace_path_str = if !replace_path.is_empty() {
-        replace_path
-            .iter()
-            .map(|s| format!("replace_path=\"{}\"", s))
-            .collect::<Vec<_>>()
-            .join("\n")
-            + "\n"
-    } else {
-        String::new()
-    };
-
-    let descriptor_content = format!(
-        r#"version="{}"
-tags={{
-    "Graphics"
-}}
-name="{}"
-{}supported_version="1.14.*"
-"#,
-        version, project_name, replace_path_str
-    );
+    let descriptor_content = mod_descriptor::render_descriptor(&mod_descriptor::ModDescriptor {
+        name: project_name.clone(),
+        version: Some(version.clone()),
+        tags: vec!["Graphics".to_string()],
+        supported_version: Some("1.14.*".to_string()),
+        replace_path: replace_path.clone(),
+        ..Default::default()
+    });
 
     let descriptor_path = full_path.join("descriptor.mod");
 
@@ -730,30 +721,12 @@ fn initialize_project(project_path: String) -> OpenProjectResult {
 
     // 读取descriptor.mod文件
     let descriptor_path = project_dir.join("descriptor.mod");
-    let mod_name = if descriptor_path.exists() {
-        match fs::read_to_string(&descriptor_path) {
-            Ok(content) => {
-                // 解析name属性
-                if let Some(name_match) = content.lines()
-                    .find(|line| line.trim().starts_with("name="))
-                    .and_then(|line| {
-                        let line = line.trim();
-                        if line.starts_with("name=\"") && line.ends_with('\"') && line.len() > 7 {
-                            Some(line[6..line.len()-1].to_string())
-                        } else {
-                            None
-                        }
-                    }) {
-                    name_match
-                } else {
-                    "Unknown Mod".to_string()
-                }
-            }
-            Err(_) => "Unknown Mod".to_string(),
-        }
-    } else {
-        "Unknown Mod".to_string()
-    };
+    let mod_name = fs::read(&descriptor_path)
+        .ok()
+        .and_then(|bytes| decode_text_bytes(&bytes))
+        .map(|(content, _)| mod_descriptor::parse_descriptor(&content).name)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Unknown Mod".to_string());
 
     // 创建项目配置
     let mut config = serde_json::json!({
@@ -1344,6 +1317,21 @@ fn get_gfx_preview_cache_dir() -> std::path::PathBuf {
     dir
 }
 
+/// 与图标缓存同级的具名缓存目录（符号索引、依赖项索引等）
+fn get_named_cache_dir(name: &str) -> std::path::PathBuf {
+    let base = get_cache_dir();
+    let dir = base
+        .parent()
+        .map(|p| p.join(name))
+        .unwrap_or_else(|| base.join(name));
+
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        println!("创建缓存目录 {} 失败: {}", name, e);
+    }
+
+    dir
+}
+
 /// 计算字符串的哈希值，用于缓存文件名
 fn hash_string(s: &str) -> String {
     use std::collections::hash_map::DefaultHasher;
@@ -1803,6 +1791,57 @@ fn read_file_content(file_path: String) -> serde_json::Value {
     })
 }
 
+/// 按 read_file_content 相同的顺序解码文件字节（UTF-8 → 自动检测 → 常见编码）。
+/// UTF-8 BOM 保留在返回的文本开头；只能有损解码时返回 None。
+fn decode_text_bytes(bytes: &[u8]) -> Option<(String, &'static encoding_rs::Encoding)> {
+    if let Ok(content) = std::str::from_utf8(bytes) {
+        return Some((content.to_string(), encoding_rs::UTF_8));
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    let detected = detector.guess(None, true);
+    let candidates = [
+        detected,
+        encoding_rs::GBK,
+        encoding_rs::BIG5,
+        encoding_rs::SHIFT_JIS,
+        encoding_rs::EUC_KR,
+        encoding_rs::WINDOWS_1252,
+    ];
+    for encoding in candidates {
+        let (decoded, used, had_errors) = encoding.decode(bytes);
+        if !had_errors {
+            return Some((decoded.into_owned(), used));
+        }
+    }
+    None
+}
+
+/// 以指定编码写入文本文件，write_file_content 与重构类命令共用。
+/// 先写入同目录下的临时文件再替换，写入中途失败不会留下半截文件。
+fn write_text_file(
+    path: &std::path::Path,
+    content: &str,
+    encoding: &'static encoding_rs::Encoding,
+) -> Result<(), String> {
+    let (bytes, _, had_unmappable) = encoding.encode(content);
+    if had_unmappable {
+        return Err(format!("内容包含 {} 编码无法表示的字符", encoding.name()));
+    }
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| format!("无效的文件路径: {}", path.display()))?;
+    let tmp = path.with_file_name(format!(".{}.tmp", file_name));
+    std::fs::write(&tmp, bytes)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            format!("保存文件失败: {}", e)
+        })
+}
+
 /// 写入文件内容
 /// 参数:
 /// - file_path: 文件路径
@@ -1810,7 +1849,6 @@ fn read_file_content(file_path: String) -> serde_json::Value {
 /// 返回: JSON 对象，包含 success, message 字段
 #[tauri::command]
 fn write_file_content(file_path: String, content: String) -> serde_json::Value {
-    use std::fs;
     use std::path::Path;
 
     println!("写入文件: {}", file_path);
@@ -1818,14 +1856,14 @@ fn write_file_content(file_path: String, content: String) -> serde_json::Value {
     let path = Path::new(&file_path);
     
     // 写入文件
-    match fs::write(path, content) {
+    match write_text_file(path, &content, encoding_rs::UTF_8) {
         Ok(_) => serde_json::json!({
             "success": true,
             "message": "保存成功"
         }),
         Err(e) => serde_json::json!({
             "success": false,
-            "message": format!("保存文件失败: {}", e)
+            "message": e
         })
     }
 }
@@ -2283,274 +2321,9 @@ fn get_bracket_depths(content: String) -> Vec<usize> {
     get_bracket_depth_map(&content)
 }
 
-// ==================== 项目打包命令 ====================
-/// 打包项目到 ZIP 文件的内部实现
-/// 
-/// # 参数
-/// * `project_path` - 项目路径
-/// * `output_name` - 输出文件名（如 project.zip）
-/// * `exclude_dependencies` - 是否排除依赖项
-fn package_project_impl(opts: PackageOptions) -> PackageResult {
-    use std::fs::{self, File};
-    use std::io::Write;
-    use std::path::{Path, PathBuf};
-    use walkdir::WalkDir;
-    use zip::write::SimpleFileOptions;
-    use zip::ZipWriter;
-
-    println!("开始打包项目: {}", opts.project_path);
-    println!("输出文件名: {}", opts.output_name);
-
-    let project_path_obj = Path::new(&opts.project_path);
-    if !project_path_obj.exists() {
-        return PackageResult {
-            success: false,
-            message: "项目路径不存在".to_string(),
-            output_path: None,
-            file_size: None,
-        };
-    }
-
-    // 创建 package 目录
-    let package_dir = project_path_obj.join("package");
-    if let Err(e) = fs::create_dir_all(&package_dir) {
-        return PackageResult {
-            success: false,
-            message: format!("创建 package 目录失败: {}", e),
-            output_path: None,
-            file_size: None,
-        };
-    }
-
-    // 输出文件路径
-    let output_path = package_dir.join(&opts.output_name);
-    
-    // 如果文件已存在，尝试删除
-    if output_path.exists() {
-        if let Err(e) = fs::remove_file(&output_path) {
-            return PackageResult {
-                success: false,
-                message: format!("无法覆盖已存在的文件: {}", e),
-                output_path: None,
-                file_size: None,
-            };
-        }
-    }
-
-    // 创建 ZIP 文件
-    let file = match File::create(&output_path) {
-        Ok(f) => f,
-        Err(e) => {
-            return PackageResult {
-                success: false,
-                message: format!("创建 ZIP 文件失败: {}", e),
-                output_path: None,
-                file_size: None,
-            };
-        }
-    };
-
-    let mut zip = ZipWriter::new(file);
-    let file_options = SimpleFileOptions::default()
-        .compression_method(zip::CompressionMethod::Deflated)
-        .compression_level(Some(6));
-
-    // 定义要排除的目录和文件
-    let exclude_dirs = vec![
-        "node_modules",
-        "target",
-        ".git",
-        ".idea",
-        ".vscode",
-        ".windsurf",
-        "package",
-    ];
-
-    // 读取 dependencies.json 获取依赖项路径（如果需要排除）
-    let dependency_paths: Vec<PathBuf> = if opts.exclude_dependencies {
-        let deps_file = project_path_obj.join("dependencies.json");
-        if deps_file.exists() {
-            match fs::read_to_string(&deps_file) {
-                Ok(content) => {
-                    if let Ok(deps) = serde_json::from_str::<serde_json::Value>(&content) {
-                        if let Some(arr) = deps.as_array() {
-                            arr.iter()
-                                .filter_map(|dep| {
-                                    dep.get("path")
-                                        .and_then(|p| p.as_str())
-                                        .map(|s| PathBuf::from(s))
-                                })
-                                .collect()
-                        } else {
-                            Vec::new()
-                        }
-                    } else {
-                        Vec::new()
-                    }
-                }
-                Err(_) => Vec::new(),
-            }
-        } else {
-            Vec::new()
-        }
-    } else {
-        Vec::new()
-    };
-
-    // 遍历项目文件夹
-    let mut file_count = 0;
-    for entry in WalkDir::new(project_path_obj)
-        .follow_links(false)
-        .into_iter()
-        .filter_entry(|e| {
-            let path = e.path();
-            let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-            
-            // 排除指定目录
-            if e.file_type().is_dir() && exclude_dirs.contains(&file_name) {
-                return false;
-            }
-
-            // 排除依赖项路径（如果依赖项在项目内部）
-            for dep_path in &dependency_paths {
-                if path.starts_with(dep_path) {
-                    return false;
-                }
-            }
-
-            true
-        })
-    {
-        let entry = match entry {
-            Ok(e) => e,
-            Err(_) => continue,
-        };
-
-        let path = entry.path();
-        
-        // 跳过项目根目录本身
-        if path == project_path_obj {
-            continue;
-        }
-
-        // 跳过目录，只打包文件
-        if !path.is_file() {
-            continue;
-        }
-
-        // 计算相对路径
-        let relative_path = match path.strip_prefix(project_path_obj) {
-            Ok(p) => p,
-            Err(_) => continue,
-        };
-
-        // 转换为字符串路径（使用 / 作为分隔符）
-        let zip_path = relative_path
-            .to_str()
-            .unwrap_or("")
-            .replace("\\", "/");
-
-        // 读取文件内容
-        let file_content = match fs::read(path) {
-            Ok(content) => content,
-            Err(e) => {
-                println!("警告: 无法读取文件 {}: {}", path.display(), e);
-                continue;
-            }
-        };
-
-        // 添加到 ZIP
-        if let Err(e) = zip.start_file(&zip_path, file_options) {
-            println!("警告: 无法添加文件到 ZIP {}: {}", zip_path, e);
-            continue;
-        }
-
-        if let Err(e) = zip.write_all(&file_content) {
-            println!("警告: 无法写入文件内容 {}: {}", zip_path, e);
-            continue;
-        }
-
-        file_count += 1;
-    }
-
-    // 完成 ZIP 文件
-    if let Err(e) = zip.finish() {
-        return PackageResult {
-            success: false,
-            message: format!("完成 ZIP 文件失败: {}", e),
-            output_path: None,
-            file_size: None,
-        };
-    }
-
-    // 获取文件大小
-    let file_size = fs::metadata(&output_path)
-        .ok()
-        .map(|m| m.len());
-
-    println!("打包完成: {} 个文件", file_count);
-
-    PackageResult {
-        success: true,
-        message: format!("打包成功！已打包 {} 个文件", file_count),
-        output_path: Some(output_path.to_string_lossy().to_string()),
-        file_size,
-    }
-}
-
-/// 打包项目 Tauri 命令
-#[tauri::command]
-fn pack_project(opts: PackageOptions) -> PackageResult {
-    package_project_impl(opts)
-}
-
-/// 从 project.json 中读取已启用依赖的路径
-fn get_dependency_paths_from_project(project_root: &str) -> Vec<String> {
-    use std::fs;
-    use std::path::Path;
-
-    let mut result = Vec::new();
-    let project_json_path = Path::new(project_root).join("project.json");
-    if !project_json_path.exists() {
-        return result;
-    }
-
-    let content = match fs::read_to_string(&project_json_path) {
-        Ok(c) => c,
-        Err(e) => {
-            println!("读取 project.json 失败: {}", e);
-            return result;
-        }
-    };
-
-    let json: serde_json::Value = match serde_json::from_str(&content) {
-        Ok(v) => v,
-        Err(e) => {
-            println!("解析 project.json 失败: {}", e);
-            return result;
-        }
-    };
-
-    if let Some(deps) = json.get("dependencies").and_then(|v| v.as_array()) {
-        for dep in deps {
-            let enabled = dep
-                .get("enabled")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(true);
-            if !enabled {
-                continue;
-            }
-            if let Some(path) = dep.get("path").and_then(|v| v.as_str()) {
-                result.push(path.to_string());
-            }
-        }
-    }
-
-    result
-}
-
-/// 在 .gfx 文件内容中查找指定图标的纹理文件路径
-fn find_texture_for_icon_in_gfx(content: &str, icon_name: &str) -> Option<String> {
+/// 提取 .gfx 文件内容中所有 SpriteType 的 (name, texturefile)
+fn sprite_textures_in_gfx(content: &str) -> Vec<(String, String)> {
+    let mut sprites = Vec::new();
     let mut in_block = false;
     let mut block_lines: Vec<String> = Vec::new();
 
@@ -2597,12 +2370,8 @@ fn find_texture_for_icon_in_gfx(content: &str, icon_name: &str) -> Option<String
                 }
             }
 
-            if let Some(name) = name_value {
-                if name == icon_name {
-                    if let Some(texture) = texture_value {
-                        return Some(texture);
-                    }
-                }
+            if let (Some(name), Some(texture)) = (name_value, texture_value) {
+                sprites.push((name, texture));
             }
 
             in_block = false;
@@ -2610,7 +2379,76 @@ fn find_texture_for_icon_in_gfx(content: &str, icon_name: &str) -> Option<String
         }
     }
 
-    None
+    sprites
+}
+
+/// 批量解析图标名称对应的纹理文件绝对路径
+///
+/// 按 `roots` 的顺序（优先级从高到低）扫描 `gfx/**/*.gfx` 与 `interface/**/*.gfx`，
+/// 每个名称取第一个命中的 SpriteType，全部命中后提前结束。
+pub(crate) fn resolve_sprite_textures(
+    names: &std::collections::HashSet<String>,
+    roots: &[std::path::PathBuf],
+) -> std::collections::HashMap<String, String> {
+    use std::fs;
+    use walkdir::WalkDir;
+
+    let mut resolved = std::collections::HashMap::new();
+    if names.is_empty() {
+        return resolved;
+    }
+
+    for root in roots.iter() {
+        // HOI4 习惯把 gfx 定义放在 root/gfx/**/**.gfx
+        // 旧逻辑只扫 root/interface/*.gfx，导致 MIO trait 等图标无法命中。
+        let mut scan_roots: Vec<std::path::PathBuf> = vec![root.join("gfx")];
+        // 兼容某些工程把 gfx 直接放在 interface 下的情况
+        scan_roots.push(root.join("interface"));
+
+        for scan_root in scan_roots {
+            if !scan_root.exists() || !scan_root.is_dir() {
+                continue;
+            }
+
+            for entry in WalkDir::new(&scan_root)
+                .follow_links(false)
+                .into_iter()
+                .filter_map(|e| e.ok())
+            {
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let path = entry.path();
+                let ext = path
+                    .extension()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("")
+                    .to_lowercase();
+                if ext != "gfx" {
+                    continue;
+                }
+
+                let content = match fs::read_to_string(path) {
+                    Ok(c) => c,
+                    Err(_) => continue,
+                };
+
+                for (name, texture_rel) in sprite_textures_in_gfx(&content) {
+                    if !names.contains(&name) || resolved.contains_key(&name) {
+                        continue;
+                    }
+                    let normalized_rel = texture_rel.replace('\\', "/");
+                    let texture_path = root.join(normalized_rel);
+                    resolved.insert(name, texture_path.to_string_lossy().to_string());
+                }
+                if resolved.len() == names.len() {
+                    return resolved;
+                }
+            }
+        }
+    }
+
+    resolved
 }
 
 /// 根据 icon 名称加载国策图标的内部实现
@@ -2622,9 +2460,7 @@ fn load_focus_icon_impl(
     project_root: Option<String>,
     game_root: Option<String>,
 ) -> ImageReadResult {
-    use std::fs;
     use std::path::PathBuf;
-    use walkdir::WalkDir;
 
     let icon_name_trimmed = icon_name.trim().to_string();
     
@@ -2644,7 +2480,7 @@ fn load_focus_icon_impl(
             let root_path = PathBuf::from(&root);
             roots.push(root_path.clone());
 
-            let dep_paths = get_dependency_paths_from_project(&root);
+            let dep_paths = dependency::enabled_dependency_roots(root);
             for dep in dep_paths {
                 if !dep.is_empty() {
                     roots.push(PathBuf::from(dep));
@@ -2668,51 +2504,9 @@ fn load_focus_icon_impl(
         };
     }
 
-    for root in roots.iter() {
-        // HOI4 习惯把 gfx 定义放在 root/gfx/**/**.gfx
-        // 旧逻辑只扫 root/interface/*.gfx，导致 MIO trait 等图标无法命中。
-        let mut scan_roots: Vec<PathBuf> = vec![root.join("gfx")];
-        // 兼容某些工程把 gfx 直接放在 interface 下的情况
-        scan_roots.push(root.join("interface"));
-
-        for scan_root in scan_roots {
-            if !scan_root.exists() || !scan_root.is_dir() {
-                continue;
-            }
-
-            for entry in WalkDir::new(&scan_root)
-                .follow_links(false)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                if !entry.file_type().is_file() {
-                    continue;
-                }
-                let path = entry.path();
-                let ext = path
-                    .extension()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("")
-                    .to_lowercase();
-                if ext != "gfx" {
-                    continue;
-                }
-
-                let content = match fs::read_to_string(path) {
-                    Ok(c) => c,
-                    Err(_) => continue,
-                };
-
-                if let Some(texture_rel) =
-                    find_texture_for_icon_in_gfx(&content, &icon_name_trimmed)
-                {
-                    let normalized_rel = texture_rel.replace('\\', "/");
-                    let texture_path = root.join(normalized_rel);
-                    let texture_path_str = texture_path.to_string_lossy().to_string();
-                    return read_image_as_base64(texture_path_str);
-                }
-            }
-        }
+    let names = std::collections::HashSet::from([icon_name_trimmed.clone()]);
+    if let Some(texture_path) = resolve_sprite_textures(&names, &roots).remove(&icon_name_trimmed) {
+        return read_image_as_base64(texture_path);
     }
     
     ImageReadResult {
@@ -2959,6 +2753,9 @@ pub fn run() {
             dependency::save_dependencies,
             dependency::validate_dependency_path,
             dependency::index_dependency,
+            dependency::discover_local_mods,
+            mod_descriptor::read_descriptor,
+            mod_descriptor::write_descriptor,
             plugin_manager::install_plugin,
             plugin_manager::uninstall_plugin,
             plugin_manager::list_installed_plugins,
@@ -2966,14 +2763,35 @@ pub fn run() {
             theme_manager::list_themes,
             theme_manager::upsert_theme,
             theme_manager::delete_theme,
-            pack_project,
+            script_lint::list_lint_rules,
+            script_lint::lint_file,
+            script_lint::lint_project,
+            packaging::pack_project,
+            packaging::cancel_packaging,
+            packaging::load_package_profiles,
+            packaging::save_package_profiles,
             read_image_as_base64,
             load_focus_icon,
             read_icon_cache,
             write_icon_cache,
             clear_icon_cache,
             focus_localization::load_focus_localizations,
+            focus_tree::parse_focus_tree,
+            focus_tree::move_focus,
+            focus_tree::move_focuses,
+            focus_tree::auto_layout_focus_tree,
+            event_graph::event_graph,
+            decisions::load_decisions,
+            scripted_registry::load_scripted_definitions,
+            character_registry::load_characters,
+            character_registry::reset_character_cache,
+            localisation::lookup_localisation,
+            localisation::get_missing_localisation,
+            localisation::upsert_localisation_keys,
+            localisation_transfer::export_localisation,
+            localisation_transfer::import_localisation,
             get_modifier_list,
+            modifier_db::get_modifier_database,
             map_engine::load_map_definitions,
             map_engine::load_default_map,
             map_engine::load_provinces_bmp,
@@ -2995,6 +2813,12 @@ pub fn run() {
             gui_engine::parse_gfx_file,
             gui_engine::resolve_gui_resource,
             mio_parser::parse_mio_preview,
+            script_formatter::format_script,
+            symbol_index::build_symbol_index,
+            symbol_index::goto_definition,
+            symbol_index::find_references,
+            symbol_rename::rename_symbol,
+            load_order::compute_override_report,
             parse_gfx_preview,
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,619 @@
+#![deny(clippy::unwrap_used)]
+
+//! 加载顺序与覆盖关系
+//!
+//! 统一计算“项目 > 依赖项（按 priority 从高到低）> 游戏本体”的根目录顺序，
+//! 读取各根目录 descriptor.mod 中的 `replace_path`，并生成覆盖报告：
+//! 列出被多个根目录同时提供的文件与顶层定义，以及最终生效的一方。
+
+use crate::clausewitz;
+use crate::dependency;
+use crate::file_cache::{game_root_stamps, FileSetCache};
+use crate::gui_engine::SPRITE_TYPES;
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+/// ：游戏目录的文件列表，按 launcher-settings.json 与顶层目录的 mtime 失效。
+static GAME_FILES: Lazy<FileSetCache<Vec<(String, String)>>> = Lazy::new(FileSetCache::new);
+
+/// ：根目录来源。
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RootSource {
+    Project,
+    Dependency,
+    Game,
+}
+
+/// ：加载顺序中的一个根目录，列表按生效优先级从高到低排列。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadOrderRoot {
+    pub path: String,
+    pub name: String,
+    pub source: RootSource,
+    /// 依赖项的 priority，项目与游戏本体为 None
+    pub priority: Option<i32>,
+    /// 该根目录声明的 replace_path（小写、`/` 分隔）
+    pub replace_paths: Vec<String>,
+}
+
+/// ：某个文件或定义的提供方状态。
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderStatus {
+    /// 最终生效
+    Winner,
+    /// 被更高优先级的根目录覆盖
+    Overridden,
+    /// 所在目录被更高优先级根目录的 replace_path 清空
+    Replaced,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverrideProvider {
+    pub root: String,
+    pub root_name: String,
+    pub source: RootSource,
+    pub file: String,
+    pub status: ProviderStatus,
+}
+
+/// ：被多个根目录提供的同一相对路径。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileOverride {
+    pub path: String,
+    pub winner: Option<OverrideProvider>,
+    pub overridden: Vec<OverrideProvider>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum DefinitionKind {
+    Idea,
+    FocusTree,
+    EventNamespace,
+    Sprite,
+}
+
+/// ：被多个根目录定义的同名顶层定义。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DefinitionOverride {
+    pub kind: DefinitionKind,
+    pub name: String,
+    pub winner: OverrideProvider,
+    pub overridden: Vec<OverrideProvider>,
+}
+
+/// ：被 replace_path 清空的目录及其中失效的文件。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplacedPath {
+    pub directory: String,
+    pub replaced_by: String,
+    pub replaced_by_name: String,
+    pub blanked_files: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverrideReport {
+    pub success: bool,
+    pub message: String,
+    pub roots: Vec<LoadOrderRoot>,
+    pub files: Vec<FileOverride>,
+    pub definitions: Vec<DefinitionOverride>,
+    pub replaced: Vec<ReplacedPath>,
+}
+
+/// ：计算加载顺序。依赖项取自 project.json 中已启用的条目（按 priority 排序），
+/// 前端额外传入但不在 project.json 中的依赖项以 priority 0 参与排序。
+pub(crate) fn load_order(
+    project_root: Option<&str>,
+    dependency_roots: Option<Vec<String>>,
+    game_root: Option<&str>,
+) -> Vec<LoadOrderRoot> {
+    let mut roots: Vec<LoadOrderRoot> = Vec::new();
+    let project = project_root.and_then(normalize_root);
+
+    if let Some(project) = &project {
+        let mut replace_paths = read_descriptor_replace_paths(project);
+        for path in read_project_replace_paths(project) {
+            if !replace_paths.contains(&path) {
+                replace_paths.push(path);
+            }
+        }
+        roots.push(LoadOrderRoot {
+            path: project.clone(),
+            name: read_project_name(project).unwrap_or_else(|| display_name(project)),
+            source: RootSource::Project,
+            priority: None,
+            replace_paths,
+        });
+    }
+
+    let mut deps: Vec<(String, String, i32)> = project
+        .as_deref()
+        .map(dependency::enabled_dependencies)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|d| normalize_root(&d.path).map(|p| (p, d.name, d.priority)))
+        .collect();
+    for extra in dependency_roots.unwrap_or_default() {
+        if let Some(path) = normalize_root(&extra) {
+            if !deps.iter().any(|(p, _, _)| same_path(p, &path)) {
+                let name = display_name(&path);
+                deps.push((path, name, 0));
+            }
+        }
+    }
+    deps.sort_by_key(|d| std::cmp::Reverse(d.2));
+
+    for (path, name, priority) in deps {
+        if roots.iter().any(|r| same_path(&r.path, &path)) {
+            continue;
+        }
+        roots.push(LoadOrderRoot {
+            replace_paths: read_descriptor_replace_paths(&path),
+            path,
+            name,
+            source: RootSource::Dependency,
+            priority: Some(priority),
+        });
+    }
+
+    if let Some(game) = game_root.and_then(normalize_root) {
+        if !roots.iter().any(|r| same_path(&r.path, &game)) {
+            roots.push(LoadOrderRoot {
+                path: game,
+                name: "游戏本体".to_string(),
+                source: RootSource::Game,
+                priority: None,
+                replace_paths: Vec::new(),
+            });
+        }
+    }
+
+    roots
+}
+
+/// ：若第 `index` 个根目录下的 `directory` 被更高优先级根目录的 replace_path 清空，
+/// 返回清空它的根目录下标。replace_path 只作用于该目录本身，不含子目录。
+pub(crate) fn blanked_by(roots: &[LoadOrderRoot], index: usize, directory: &str) -> Option<usize> {
+    let dir = normalize_relative(directory);
+    roots
+        .iter()
+        .take(index)
+        .position(|r| r.replace_paths.contains(&dir))
+}
+
+/// ：单个生效文件中提取到的定义：(根目录下标, 相对路径, 定义列表)。
+type ExtractedFile = (usize, String, Vec<(DefinitionKind, String)>);
+
+/// ：生成覆盖报告。
+#[tauri::command]
+pub fn compute_override_report(
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> OverrideReport {
+    let roots = load_order(project_root.as_deref(), dependency_roots, game_root.as_deref());
+    if roots.is_empty() {
+        return OverrideReport {
+            success: false,
+            message: "未提供任何根目录".to_string(),
+            roots,
+            files: Vec::new(),
+            definitions: Vec::new(),
+            replaced: Vec::new(),
+        };
+    }
+
+    // 相对路径（小写） -> [(根目录下标, 相对路径原文)]
+    let listed: Vec<Arc<Vec<(String, String)>>> = roots.par_iter().map(list_root_files).collect();
+    let mut by_path: HashMap<String, Vec<(usize, String)>> = HashMap::new();
+    for (idx, files) in listed.iter().enumerate() {
+        for (key, relative) in files.iter() {
+            by_path.entry(key.clone()).or_default().push((idx, relative.clone()));
+        }
+    }
+
+    let provider = |idx: usize, relative: &str, status: ProviderStatus| OverrideProvider {
+        root: roots[idx].path.clone(),
+        root_name: roots[idx].name.clone(),
+        source: roots[idx].source,
+        file: format!("{}/{}", roots[idx].path, relative),
+        status,
+    };
+
+    let mut files: Vec<FileOverride> = Vec::new();
+    let mut replaced: HashMap<(String, usize), Vec<String>> = HashMap::new();
+    // 最终生效的文件：(根目录下标, 相对路径)
+    let mut effective: Vec<(usize, String)> = Vec::new();
+
+    for (key, mut providers) in by_path {
+        providers.sort_by_key(|(idx, _)| *idx);
+        let directory = key.rsplit_once('/').map(|(d, _)| d.to_string()).unwrap_or_default();
+
+        let mut winner: Option<OverrideProvider> = None;
+        let mut overridden: Vec<OverrideProvider> = Vec::new();
+        for (idx, relative) in &providers {
+            if let Some(by) = blanked_by(&roots, *idx, &directory) {
+                replaced
+                    .entry((directory.clone(), by))
+                    .or_default()
+                    .push(format!("{}/{}", roots[*idx].path, relative));
+                overridden.push(provider(*idx, relative, ProviderStatus::Replaced));
+            } else if winner.is_none() {
+                winner = Some(provider(*idx, relative, ProviderStatus::Winner));
+                effective.push((*idx, relative.clone()));
+            } else {
+                overridden.push(provider(*idx, relative, ProviderStatus::Overridden));
+            }
+        }
+
+        if providers.len() > 1 {
+            files.push(FileOverride {
+                path: providers[0].1.clone(),
+                winner,
+                overridden,
+            });
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut replaced: Vec<ReplacedPath> = replaced
+        .into_iter()
+        .map(|((directory, by), mut blanked_files)| {
+            blanked_files.sort();
+            ReplacedPath {
+                directory,
+                replaced_by: roots[by].path.clone(),
+                replaced_by_name: roots[by].name.clone(),
+                blanked_files,
+            }
+        })
+        .collect();
+    replaced.sort_by(|a, b| a.directory.cmp(&b.directory));
+
+    // 只统计实际生效文件中的定义
+    let (archived, plain): (Vec<_>, Vec<_>) = effective
+        .into_iter()
+        .filter(|(_, relative)| definition_file_kind(relative).is_some())
+        .partition(|(idx, _)| dependency::is_archive(Path::new(&roots[*idx].path)));
+    let mut extracted: Vec<ExtractedFile> = plain
+        .par_iter()
+        .filter_map(|(idx, relative)| {
+            let kind = definition_file_kind(relative)?;
+            let path = format!("{}/{}", roots[*idx].path, relative);
+            let bytes = fs::read(&path).ok()?;
+            let content = String::from_utf8_lossy(&bytes);
+            Some((*idx, relative.clone(), extract_definitions(kind, &content)))
+        })
+        .collect();
+    extracted.extend(extract_archived(&roots, &archived));
+
+    let mut by_definition: HashMap<(DefinitionKind, String), Vec<(usize, String)>> = HashMap::new();
+    for (idx, relative, defs) in extracted {
+        for def in defs {
+            by_definition.entry(def).or_default().push((idx, relative.clone()));
+        }
+    }
+
+    let mut definitions: Vec<DefinitionOverride> = by_definition
+        .into_iter()
+        .filter_map(|((kind, name), mut providers)| {
+            providers.sort();
+            providers.dedup_by_key(|(idx, _)| *idx);
+            if providers.len() < 2 {
+                return None;
+            }
+            let (first_idx, first_file) = &providers[0];
+            Some(DefinitionOverride {
+                kind,
+                name,
+                winner: provider(*first_idx, first_file, ProviderStatus::Winner),
+                overridden: providers[1..]
+                    .iter()
+                    .map(|(idx, file)| provider(*idx, file, ProviderStatus::Overridden))
+                    .collect(),
+            })
+        })
+        .collect();
+    definitions.sort_by(|a, b| a.kind.cmp(&b.kind).then(a.name.cmp(&b.name)));
+
+    OverrideReport {
+        success: true,
+        message: format!(
+            "{} 个根目录：{} 个文件、{} 个定义存在覆盖，{} 个目录被 replace_path 清空",
+            roots.len(),
+            files.len(),
+            definitions.len(),
+            replaced.len()
+        ),
+        roots,
+        files,
+        definitions,
+        replaced,
+    }
+}
+
+/// ：每个压缩包只打开一次，读取其中生效的定义文件。
+fn extract_archived(roots: &[LoadOrderRoot], files: &[(usize, String)]) -> Vec<ExtractedFile> {
+    let mut by_root: HashMap<usize, HashMap<String, &str>> = HashMap::new();
+    for (idx, relative) in files {
+        by_root.entry(*idx).or_default().insert(relative.to_lowercase(), relative);
+    }
+    let mut out = Vec::new();
+    for (idx, wanted) in by_root {
+        let archive = Path::new(&roots[idx].path);
+        let Ok(entries) = dependency::read_archive_entries(archive, |r| wanted.contains_key(r)) else {
+            continue;
+        };
+        for entry in entries {
+            let (Some(relative), Some(kind)) = (wanted.get(&entry.relative), definition_file_kind(&entry.relative)) else {
+                continue;
+            };
+            let content = String::from_utf8_lossy(&entry.bytes);
+            out.push((idx, relative.to_string(), extract_definitions(kind, &content)));
+        }
+    }
+    out
+}
+
+/// ：列出根目录提供的文件。游戏目录体量大，文件列表按廉价的目录戳缓存；
+/// 压缩包形式的依赖项直接列出包内文件。
+fn list_root_files(root: &LoadOrderRoot) -> Arc<Vec<(String, String)>> {
+    let path = Path::new(&root.path);
+    if root.source == RootSource::Game {
+        let dirs: Vec<String> = fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let stamps = game_root_stamps(path, dirs);
+        return GAME_FILES.get_or_build(root.path.clone(), stamps, |_| list_files(&root.path));
+    }
+    if dependency::is_archive(path) {
+        return Arc::new(list_archive(&root.path));
+    }
+    Arc::new(list_files(&root.path))
+}
+
+/// ：压缩包内子目录中的文件，规则与 `list_files` 相同。
+fn list_archive(archive: &str) -> Vec<(String, String)> {
+    let Ok(names) = dependency::list_archive_files(Path::new(archive)) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}/", archive);
+    names
+        .into_iter()
+        .filter_map(|(path, key)| {
+            let relative = path.strip_prefix(&prefix)?.to_string();
+            let hidden = relative.split('/').any(|part| part.starts_with('.'));
+            (relative.contains('/') && !hidden).then_some((key, relative))
+        })
+        .collect()
+}
+
+/// ：列出根目录下所有子目录中的文件，返回 (小写相对路径, 相对路径原文)。
+/// 根目录本身的文件（descriptor.mod、project.json 等）不参与覆盖。
+fn list_files(root: &str) -> Vec<(String, String)> {
+    WalkDir::new(root)
+        .min_depth(2)
+        .into_iter()
+        .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let path = e.path().to_string_lossy().replace('\\', "/");
+            let relative = path.strip_prefix(root)?.trim_start_matches('/').to_string();
+            Some((relative.to_lowercase(), relative))
+        })
+        .collect()
+}
+
+fn definition_file_kind(relative: &str) -> Option<DefinitionKind> {
+    let lower = relative.to_lowercase();
+    if lower.starts_with("common/ideas/") && lower.ends_with(".txt") {
+        Some(DefinitionKind::Idea)
+    } else if lower.starts_with("common/national_focus/") && lower.ends_with(".txt") {
+        Some(DefinitionKind::FocusTree)
+    } else if lower.starts_with("events/") && lower.ends_with(".txt") {
+        Some(DefinitionKind::EventNamespace)
+    } else if lower.starts_with("interface/") && lower.ends_with(".gfx") {
+        Some(DefinitionKind::Sprite)
+    } else {
+        None
+    }
+}
+
+fn extract_definitions(kind: DefinitionKind, content: &str) -> Vec<(DefinitionKind, String)> {
+    let names: Vec<String> = match kind {
+        DefinitionKind::Idea => crate::idea_registry::extract_ideas(content),
+        DefinitionKind::FocusTree => {
+            let doc = clausewitz::parse(content);
+            doc.root
+                .fields_named("focus_tree")
+                .filter_map(|f| f.value.as_block())
+                .filter_map(|b| b.get_str("id").map(|s| s.to_string()))
+                .collect()
+        }
+        DefinitionKind::EventNamespace => {
+            let doc = clausewitz::parse(content);
+            doc.root
+                .fields_named("add_namespace")
+                .filter_map(|f| f.value.as_str().map(|s| s.to_string()))
+                .collect()
+        }
+        DefinitionKind::Sprite => {
+            let doc = clausewitz::parse(content);
+            let mut names = Vec::new();
+            doc.root.visit_fields(&mut |field, _| {
+                let is_sprite = SPRITE_TYPES.iter().any(|k| field.key_is(k));
+                if let Some(name) = field.value.as_block().filter(|_| is_sprite).and_then(|b| b.get_str("name")) {
+                    names.push(name.to_string());
+                }
+            });
+            names
+        }
+    };
+    names.into_iter().map(|n| (kind, n)).collect()
+}
+
+/// ：读取根目录 descriptor.mod 中的 replace_path。
+fn read_descriptor_replace_paths(root: &str) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(Path::new(root).join("descriptor.mod")) else {
+        return Vec::new();
+    };
+    let doc = clausewitz::parse(&content);
+    let mut paths: Vec<String> = Vec::new();
+    for field in doc.root.fields_named("replace_path") {
+        if let Some(p) = field.value.as_str() {
+            let p = normalize_relative(p);
+            if !p.is_empty() && !paths.contains(&p) {
+                paths.push(p);
+            }
+        }
+    }
+    paths
+}
+
+/// ：读取 project.json 中的 replace_path 数组。
+fn read_project_replace_paths(root: &str) -> Vec<String> {
+    read_project_json(root)
+        .and_then(|json| {
+            json.get("replace_path").and_then(|v| v.as_array()).map(|arr| {
+                arr.iter()
+                    .filter_map(|v| v.as_str())
+                    .map(normalize_relative)
+                    .filter(|p| !p.is_empty())
+                    .collect()
+            })
+        })
+        .unwrap_or_default()
+}
+
+fn read_project_name(root: &str) -> Option<String> {
+    read_project_json(root)?
+        .get("name")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+fn read_project_json(root: &str) -> Option<serde_json::Value> {
+    let content = fs::read_to_string(Path::new(root).join("project.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn display_name(path: &str) -> String {
+    path.rsplit('/').next().unwrap_or(path).to_string()
+}
+
+fn normalize_root(path: &str) -> Option<String> {
+    let replaced = path.trim().replace('\\', "/");
+    let trimmed = replaced.trim_end_matches('/');
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+fn normalize_relative(path: &str) -> String {
+    path.trim().replace('\\', "/").trim_matches('/').to_lowercase()
+}
+
+fn same_path(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(path: &str, source: RootSource, replace_paths: &[&str]) -> LoadOrderRoot {
+        LoadOrderRoot {
+            path: path.to_string(),
+            name: path.to_string(),
+            source,
+            priority: None,
+            replace_paths: replace_paths.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_blanked_by_only_affects_lower_roots() {
+        let roots = vec![
+            root("/project", RootSource::Project, &["common/ideas"]),
+            root("/dep", RootSource::Dependency, &[]),
+            root("/game", RootSource::Game, &[]),
+        ];
+        assert_eq!(blanked_by(&roots, 0, "common/ideas"), None);
+        assert_eq!(blanked_by(&roots, 1, "common/ideas"), Some(0));
+        assert_eq!(blanked_by(&roots, 2, "Common\\Ideas"), Some(0));
+        // 子目录不受影响
+        assert_eq!(blanked_by(&roots, 2, "common/ideas/sub"), None);
+    }
+
+    #[test]
+    fn test_extract_definitions() {
+        let focus = "focus_tree = { id = ger_tree focus = { id = a } }";
+        assert_eq!(
+            extract_definitions(DefinitionKind::FocusTree, focus),
+            vec![(DefinitionKind::FocusTree, "ger_tree".to_string())]
+        );
+        let events = "add_namespace = ger\ncountry_event = { id = ger.1 }";
+        assert_eq!(
+            extract_definitions(DefinitionKind::EventNamespace, events),
+            vec![(DefinitionKind::EventNamespace, "ger".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_override_report_reads_zipped_dependency() {
+        use std::io::Write;
+        let dir = tempfile::tempdir().expect("tempdir");
+        let project = dir.path().join("project");
+        fs::create_dir_all(project.join("common/ideas")).expect("mkdir");
+        fs::write(project.join("common/ideas/a.txt"), "ideas = { country = { shared_idea = { } } }").expect("write");
+
+        let archive = dir.path().join("dep.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&archive).expect("create zip"));
+        for (name, content) in [
+            ("common/ideas/a.txt", "ideas = { country = { other_idea = { } } }"),
+            ("common/ideas/B.txt", "ideas = { country = { shared_idea = { } } }"),
+            ("descriptor.mod", "name = \"dep\""),
+        ] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default()).expect("start file");
+            zip.write_all(content.as_bytes()).expect("write entry");
+        }
+        zip.finish().expect("finish zip");
+
+        let archive = archive.to_string_lossy().replace('\\', "/");
+        let report = compute_override_report(
+            Some(project.to_string_lossy().to_string()),
+            None,
+            Some(vec![archive.clone()]),
+        );
+        assert!(report.success, "{}", report.message);
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.files[0].overridden[0].file, format!("{}/common/ideas/a.txt", archive));
+        assert_eq!(report.definitions.len(), 1);
+        assert_eq!(report.definitions[0].name, "shared_idea");
+        assert_eq!(report.definitions[0].overridden[0].file, format!("{}/common/ideas/B.txt", archive));
+    }
+}
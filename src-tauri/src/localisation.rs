@@ -0,0 +1,854 @@
+#![deny(clippy::unwrap_used)]
+
+//! 本地化数据库
+//!
+//! 解析各根目录 `localisation` 下全部 `l_<语言>:` 文件，记录每个键在各语言下的
+//! 文本、版本号、所在文件与行号，以及文本中的 `[Scope.GetName]`、`$KEY$`、`£icon` 引用。
+//! 同一键同一语言按加载顺序取最高优先级的定义；`replace/` 目录下的文件优先于一切
+//! 普通文件。结果按文件 mtime 缓存，焦点树与 MIO 预览也从这里取文本。
+//! 写入时只改动引号内的文本，新键追加到目标文件末尾。
+
+use crate::file_cache::{self, FileSetCache, FileStamps};
+use crate::load_order::{self, LoadOrderRoot, RootSource};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// ：本地化文本中的引用类型。
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LocalisationReferenceKind {
+    /// `[ROOT.GetName]`
+    Scope,
+    /// `$KEY$`
+    Key,
+    /// `£icon`
+    Icon,
+}
+
+/// ：文本中的一处引用，`start`/`end` 为名称在文本内的字节区间。
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalisationReference {
+    pub kind: LocalisationReferenceKind,
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// ：某个键在某种语言下的定义。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalisationValue {
+    pub language: String,
+    pub value: String,
+    pub version: Option<u32>,
+    /// 同一文件的定义共用一份路径
+    pub file: Arc<str>,
+    pub line: usize,
+    pub source: RootSource,
+    /// 来自 `replace/` 目录
+    pub replace: bool,
+    pub references: Vec<LocalisationReference>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalisationEntry {
+    pub key: String,
+    pub values: Vec<LocalisationValue>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalisationLookupResult {
+    pub success: bool,
+    pub message: String,
+    pub languages: Vec<String>,
+    pub entries: Vec<LocalisationEntry>,
+}
+
+/// ：某种语言下缺失的键。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingLocalisation {
+    pub language: String,
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingLocalisationReport {
+    pub success: bool,
+    pub message: String,
+    pub languages: Vec<String>,
+    pub missing: Vec<MissingLocalisation>,
+}
+
+/// ：合并后的本地化表：键 → 语言 → 生效的定义。
+#[derive(Debug, Default)]
+pub(crate) struct LocalisationDatabase {
+    keys: HashMap<String, BTreeMap<String, LocalisationValue>>,
+    languages: BTreeSet<String>,
+}
+
+impl LocalisationDatabase {
+    pub(crate) fn get(&self, key: &str, language: &str) -> Option<&LocalisationValue> {
+        self.keys.get(key)?.get(language)
+    }
+
+    /// 某个键在各语言下的定义。
+    pub(crate) fn values(&self, key: &str) -> Option<&BTreeMap<String, LocalisationValue>> {
+        self.keys.get(key)
+    }
+
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&String, &BTreeMap<String, LocalisationValue>)> {
+        self.keys.iter()
+    }
+
+    /// 某种语言下的 `键 → 文本` 表。
+    pub(crate) fn language_map(&self, language: &str) -> HashMap<String, String> {
+        self.keys
+            .iter()
+            .filter_map(|(key, values)| values.get(language).map(|v| (key.clone(), v.value.clone())))
+            .collect()
+    }
+
+    /// 在 `sources` 提供的键中，找出各语言下缺失的键。
+    pub(crate) fn missing_keys(&self, languages: &[String], sources: &[RootSource]) -> Vec<MissingLocalisation> {
+        languages
+            .iter()
+            .map(|language| {
+                let mut keys: Vec<String> = self
+                    .keys
+                    .iter()
+                    .filter(|(_, values)| {
+                        !values.contains_key(language) && values.values().any(|v| sources.contains(&v.source))
+                    })
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                keys.sort();
+                MissingLocalisation { language: language.clone(), keys }
+            })
+            .collect()
+    }
+}
+
+/// ：数据库缓存，记录参与合并的文件及其 mtime。
+static DATABASE_CACHE: Lazy<FileSetCache<LocalisationDatabase>> = Lazy::new(FileSetCache::new);
+
+const LOCALISATION_DIR: &str = "localisation";
+
+/// 查询若干本地化键在各语言下的定义；指定语言时只加载这些语言
+#[tauri::command]
+pub fn lookup_localisation(
+    keys: Vec<String>,
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+    languages: Option<Vec<String>>,
+) -> LocalisationLookupResult {
+    let languages: Option<Vec<&str>> = languages.as_ref().map(|l| l.iter().map(String::as_str).collect());
+    let database = load_database(project_root.as_deref(), game_root.as_deref(), dependency_roots, languages.as_deref());
+    let entries: Vec<LocalisationEntry> = keys
+        .into_iter()
+        .filter_map(|key| {
+            let values = database.keys.get(&key)?.values().cloned().collect();
+            Some(LocalisationEntry { key, values })
+        })
+        .collect();
+    LocalisationLookupResult {
+        success: true,
+        message: format!("找到 {} 个键（共 {} 个）", entries.len(), database.keys.len()),
+        languages: database.languages.iter().cloned().collect(),
+        entries,
+    }
+}
+
+/// 列出项目中各语言缺失的本地化键。未指定语言时取项目本身用到的全部语言。
+#[tauri::command]
+pub fn get_missing_localisation(
+    languages: Option<Vec<String>>,
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> MissingLocalisationReport {
+    if project_root.as_deref().map(str::trim).unwrap_or_default().is_empty() {
+        return MissingLocalisationReport {
+            success: false,
+            message: "未打开项目".to_string(),
+            languages: Vec::new(),
+            missing: Vec::new(),
+        };
+    }
+    // 项目键只可能出现在项目用到的语言里，因此只需加载这些语言与要检查的语言
+    let used = project_languages(project_root.as_deref().unwrap_or_default());
+    let languages = languages.unwrap_or_else(|| used.iter().cloned().collect());
+    let wanted: Vec<&str> = used.iter().chain(&languages).map(String::as_str).collect();
+    let database = load_database(project_root.as_deref(), game_root.as_deref(), dependency_roots, Some(&wanted));
+    let missing = database.missing_keys(&languages, &[RootSource::Project]);
+    let total: usize = missing.iter().map(|m| m.keys.len()).sum();
+    MissingLocalisationReport {
+        success: true,
+        message: if total == 0 {
+            "各语言本地化完整".to_string()
+        } else {
+            format!("{} 种语言共缺失 {} 个键", languages.len(), total)
+        },
+        languages,
+        missing,
+    }
+}
+
+/// ：项目 `localisation` 下各文件使用的语言，文件名中没有语言时读取文件头。
+fn project_languages(project_root: &str) -> BTreeSet<String> {
+    let root = LoadOrderRoot {
+        name: project_root.to_string(),
+        path: project_root.trim().replace('\\', "/").trim_end_matches('/').to_string(),
+        source: RootSource::Project,
+        priority: None,
+        replace_paths: Vec::new(),
+    };
+    collect_files(std::slice::from_ref(&root), None)
+        .into_iter()
+        .filter_map(|file| {
+            file.language.or_else(|| {
+                let bytes = fs::read(&file.path).ok()?;
+                parse_localisation_header(&String::from_utf8_lossy(&bytes))
+            })
+        })
+        .collect()
+}
+
+/// ：按项目的加载顺序加载本地化表。`languages` 为空时加载全部语言。
+pub(crate) fn load_database(
+    project_root: Option<&str>,
+    game_root: Option<&str>,
+    dependency_roots: Option<Vec<String>>,
+    languages: Option<&[&str]>,
+) -> Arc<LocalisationDatabase> {
+    let roots = load_order::load_order(project_root, dependency_roots, game_root);
+    load_from_roots(&roots, languages)
+}
+
+/// ：按给定顺序（优先级从高到低）加载若干根目录，来源一律视为依赖项。
+/// 供只持有路径列表的焦点树、MIO 预览使用。
+pub(crate) fn load_from_paths(paths: &[String], languages: Option<&[&str]>) -> Arc<LocalisationDatabase> {
+    let roots: Vec<LoadOrderRoot> = paths
+        .iter()
+        .map(|p| p.trim().replace('\\', "/").trim_end_matches('/').to_string())
+        .filter(|p| !p.is_empty())
+        .map(|path| LoadOrderRoot {
+            name: path.clone(),
+            path,
+            source: RootSource::Dependency,
+            priority: None,
+            replace_paths: Vec::new(),
+        })
+        .collect();
+    load_from_roots(&roots, languages)
+}
+
+/// ：待解析的本地化文件。
+struct LocalisationFile {
+    path: String,
+    modified: Option<SystemTime>,
+    root: usize,
+    source: RootSource,
+    replace: bool,
+    /// 由文件名 `_l_<语言>.yml` 推断，解析时以文件头为准
+    language: Option<String>,
+}
+
+fn load_from_roots(roots: &[LoadOrderRoot], languages: Option<&[&str]>) -> Arc<LocalisationDatabase> {
+    let mut key = file_cache::roots_key(roots);
+    if let Some(languages) = languages {
+        key.push_str("||");
+        key.push_str(&languages.join("|"));
+    }
+
+    let files = collect_files(roots, languages);
+    let stamps: FileStamps = files.iter().map(|f| (f.path.clone(), f.modified)).collect();
+
+    DATABASE_CACHE.get_or_build(key, stamps, |_| {
+        let mut parsed: Vec<(&LocalisationFile, String, Vec<LocalisationLine>)> = files
+            .par_iter()
+            .filter_map(|file| {
+                let bytes = fs::read(&file.path).ok()?;
+                let content = String::from_utf8_lossy(&bytes);
+                let (header, lines) = parse_localisation(&content);
+                let language = header.or_else(|| file.language.clone())?;
+                let wanted = languages.is_none_or(|l| l.contains(&language.as_str()));
+                wanted.then_some((file, language, lines))
+            })
+            .collect();
+        // 低优先级先写入、高优先级覆盖：replace 目录最后，其次是加载顺序靠前的根目录
+        parsed.sort_by_key(|(file, _, _)| (file.replace, std::cmp::Reverse(file.root)));
+
+        let mut database = LocalisationDatabase::default();
+        for (file, language, lines) in parsed {
+            database.languages.insert(language.clone());
+            let path: Arc<str> = Arc::from(file.path.as_str());
+            for line in lines {
+                database.keys.entry(line.key).or_default().insert(
+                    language.clone(),
+                    LocalisationValue {
+                        language: language.clone(),
+                        references: parse_references(&line.value),
+                        value: line.value,
+                        version: line.version,
+                        file: path.clone(),
+                        line: line.line,
+                        source: file.source,
+                        replace: file.replace,
+                    },
+                );
+            }
+        }
+        database
+    })
+}
+
+/// ：按加载顺序收集各根目录下的 `.yml` 文件，跳过被 replace_path 清空的目录。
+fn collect_files(roots: &[LoadOrderRoot], languages: Option<&[&str]>) -> Vec<LocalisationFile> {
+    let mut files: Vec<LocalisationFile> = Vec::new();
+    for (index, root) in roots.iter().enumerate() {
+        let base = Path::new(&root.path).join(LOCALISATION_DIR);
+        if !base.is_dir() {
+            continue;
+        }
+        for entry in WalkDir::new(&base).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            if !entry.file_type().is_file() || !name.ends_with(".yml") {
+                continue;
+            }
+            let relative = entry
+                .path()
+                .strip_prefix(&root.path)
+                .map(|p| p.to_string_lossy().replace('\\', "/").to_lowercase())
+                .unwrap_or_default();
+            let directory = relative.rsplit_once('/').map(|(dir, _)| dir).unwrap_or_default();
+            if load_order::blanked_by(roots, index, directory).is_some() {
+                continue;
+            }
+            let language = file_name_language(&name);
+            if let (Some(filter), Some(language)) = (languages, language.as_deref()) {
+                if !filter.contains(&language) {
+                    continue;
+                }
+            }
+            files.push(LocalisationFile {
+                path: entry.path().to_string_lossy().replace('\\', "/"),
+                modified: entry.metadata().ok().and_then(|m| m.modified().ok()),
+                root: index,
+                source: root.source,
+                replace: relative.split('/').any(|part| part == "replace"),
+                language,
+            });
+        }
+    }
+    files
+}
+
+/// 从 `xxx_l_english.yml` 中取出语言名。
+pub(crate) fn file_name_language(name: &str) -> Option<String> {
+    let stem = name.strip_suffix(".yml")?;
+    let (_, language) = stem.rsplit_once("_l_")?;
+    (!language.is_empty()).then(|| language.to_string())
+}
+
+/// ：yml 中的一行定义，`value_start` 为文本在文件内容中的字节偏移。
+#[derive(Debug, PartialEq)]
+pub(crate) struct LocalisationLine {
+    pub(crate) key: String,
+    pub(crate) version: Option<u32>,
+    pub(crate) value: String,
+    pub(crate) line: usize,
+    pub(crate) value_start: usize,
+}
+
+/// ：只取文件头 `l_<语言>:` 中的语言。
+fn parse_localisation_header(content: &str) -> Option<String> {
+    content
+        .lines()
+        .map(|line| line.trim_start_matches(['\u{feff}', ' ', '\t']).trim_end())
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .and_then(|line| line.strip_suffix(':'))
+        .and_then(|key| key.strip_prefix("l_"))
+        .map(|language| language.to_string())
+}
+
+/// ：解析本地化文件，返回文件头中的语言与全部定义。
+/// 文本中允许出现未转义的引号，闭引号见 [`closing_quote`]。
+pub(crate) fn parse_localisation(content: &str) -> (Option<String>, Vec<LocalisationLine>) {
+    let mut language: Option<String> = None;
+    let mut lines: Vec<LocalisationLine> = Vec::new();
+    let mut offset = 0usize;
+    for (i, piece) in content.split('\n').enumerate() {
+        let line_start = offset;
+        offset += piece.len() + 1;
+        let raw = piece.strip_suffix('\r').unwrap_or(piece);
+        let line = raw.trim_start_matches(['\u{feff}', ' ', '\t']);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, rest)) = line.split_once(':') else {
+            continue;
+        };
+        if key.is_empty() || !key.chars().all(is_key_char) {
+            continue;
+        }
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let version = rest[..digits].parse::<u32>().ok();
+        let Some(text) = rest[digits..].trim_start().strip_prefix('"') else {
+            // `l_english:` 头部
+            if language.is_none() && rest.trim().is_empty() {
+                language = key.strip_prefix("l_").map(|l| l.to_string());
+            }
+            continue;
+        };
+        let value = closing_quote(text).map(|end| &text[..end]).unwrap_or(text);
+        lines.push(LocalisationLine {
+            key: key.to_string(),
+            version,
+            value: value.to_string(),
+            line: i + 1,
+            value_start: line_start + raw.len() - text.len(),
+        });
+    }
+    (language, lines)
+}
+
+/// ：开引号之后文本的闭引号位置：第一个其后只剩空白或 `#` 注释的未转义引号，
+/// 找不到时退回最后一个引号。
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if c == '"' && !escaped {
+            let rest = text[i + 1..].trim_start();
+            if rest.is_empty() || rest.starts_with('#') {
+                return Some(i);
+            }
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    text.rfind('"')
+}
+
+fn is_icon_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// ：提取文本中的 `[...]` 作用域、`$KEY$`（含 `$KEY|Y$` 格式）与 `£icon` 引用。
+pub(crate) fn parse_references(text: &str) -> Vec<LocalisationReference> {
+    let mut refs: Vec<LocalisationReference> = Vec::new();
+    let mut push = |kind, start: usize, name: &str| {
+        if !name.is_empty() {
+            refs.push(LocalisationReference {
+                kind,
+                name: name.to_string(),
+                start,
+                end: start + name.len(),
+            });
+        }
+    };
+    let mut pos = 0usize;
+    while let Some((offset, c)) = text[pos..].char_indices().next() {
+        let at = pos + offset;
+        let body = &text[at + c.len_utf8()..];
+        let consumed = match c {
+            '[' => body.find(']').map(|end| {
+                push(LocalisationReferenceKind::Scope, at + 1, &body[..end]);
+                end + 2
+            }),
+            '$' => body.find('$').map(|end| {
+                let name = body[..end].split('|').next().unwrap_or_default();
+                push(LocalisationReferenceKind::Key, at + 1, name);
+                end + 2
+            }),
+            '£' => {
+                let len = body.find(|ch: char| !is_icon_char(ch)).unwrap_or(body.len());
+                push(LocalisationReferenceKind::Icon, at + c.len_utf8(), &body[..len]);
+                Some(len + c.len_utf8())
+            }
+            _ => None,
+        };
+        pos = at + consumed.unwrap_or(c.len_utf8());
+    }
+    refs
+}
+
+// ==================== 写入 ====================
+
+/// ：批量写入中的单个键。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalisationUpsert {
+    pub key: String,
+    pub value: String,
+    /// 指定时同时改写版本号，否则保留原有版本号（新键为 0）
+    #[serde(default)]
+    pub version: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalisationWriteResult {
+    pub success: bool,
+    pub message: String,
+    pub updated: usize,
+    pub added: usize,
+    /// 被修改或新建的文件
+    pub files: Vec<String>,
+}
+
+impl LocalisationWriteResult {
+    fn error(message: String) -> Self {
+        Self {
+            success: false,
+            message,
+            updated: 0,
+            added: 0,
+            files: Vec::new(),
+        }
+    }
+}
+
+/// 批量写入本地化键：已有的键就地修改所在行（保留版本号与行尾注释），
+/// 其余追加到 `target_file`（相对项目根目录），默认写入 `localisation/<语言>/<项目名>_l_<语言>.yml`。
+#[tauri::command]
+pub fn upsert_localisation_keys(
+    project_root: String,
+    language: String,
+    entries: Vec<LocalisationUpsert>,
+    target_file: Option<String>,
+) -> LocalisationWriteResult {
+    upsert_keys(Path::new(project_root.trim()), &language, entries, target_file)
+}
+
+/// ：[`upsert_localisation_keys`] 的实现，供导入译文复用。
+pub(crate) fn upsert_keys(
+    root: &Path,
+    language: &str,
+    entries: Vec<LocalisationUpsert>,
+    target_file: Option<String>,
+) -> LocalisationWriteResult {
+    if !root.is_dir() {
+        return LocalisationWriteResult::error(format!("项目目录不存在: {}", root.display()));
+    }
+    if language.is_empty() || !language.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+        return LocalisationWriteResult::error(format!("无效的语言名: {}", language));
+    }
+    if let Some(bad) = entries.iter().find(|e| e.key.is_empty() || !e.key.chars().all(is_key_char)) {
+        return LocalisationWriteResult::error(format!("无效的本地化键: {}", bad.key));
+    }
+
+    // 写入前先校验目标文件，避免部分键已写入后才失败
+    let relative = target_file
+        .filter(|f| !f.trim().is_empty())
+        .unwrap_or_else(|| default_file_name(root, language));
+    let path = match target_path(root, &relative) {
+        Ok(path) => path,
+        Err(message) => return LocalisationWriteResult::error(message),
+    };
+
+    // 同一个键出现多次时取版本号最高的一次，版本相同时以最后一次为准，保留首次出现的顺序
+    let mut pending: Vec<LocalisationUpsert> = Vec::new();
+    for entry in entries {
+        match pending.iter_mut().find(|p| p.key == entry.key) {
+            Some(existing) if entry.version >= existing.version => *existing = entry,
+            Some(_) => {}
+            None => pending.push(entry),
+        }
+    }
+
+    let mut result = LocalisationWriteResult {
+        success: true,
+        message: String::new(),
+        updated: 0,
+        added: 0,
+        files: Vec::new(),
+    };
+
+    let base = root.join(LOCALISATION_DIR);
+    if base.is_dir() {
+        for entry in WalkDir::new(&base).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            if pending.is_empty() || !entry.file_type().is_file() || !name.ends_with(".yml") {
+                continue;
+            }
+            // 非 UTF-8 文件游戏同样无法读取，不做修改
+            let Ok(content) = fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            let (header, _) = parse_localisation(&content);
+            if header.or_else(|| file_name_language(&name)).as_deref() != Some(language) {
+                continue;
+            }
+            let (rewritten, updated) = update_existing_keys(&content, &mut pending);
+            if updated > 0 {
+                if let Err(err) = crate::write_text_file(entry.path(), &rewritten, encoding_rs::UTF_8) {
+                    return LocalisationWriteResult::error(format!("写入 {} 失败: {}", entry.path().display(), err));
+                }
+                result.updated += updated;
+                result.files.push(entry.path().to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+
+    if !pending.is_empty() {
+        let existing = match fs::read(&path) {
+            Ok(bytes) => match String::from_utf8(bytes) {
+                Ok(content) => Some(content),
+                Err(_) => return LocalisationWriteResult::error(format!("{} 不是 UTF-8 编码", path.display())),
+            },
+            Err(_) => None,
+        };
+        let content = append_keys(existing.as_deref(), language, &pending);
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .map_err(|e| e.to_string())
+            .and_then(|_| crate::write_text_file(&path, &content, encoding_rs::UTF_8));
+        if let Err(err) = written {
+            return LocalisationWriteResult::error(format!("写入 {} 失败: {}", path.display(), err));
+        }
+        result.added = pending.len();
+        result.files.push(path.to_string_lossy().replace('\\', "/"));
+    }
+
+    result.message = format!("更新 {} 个键，新增 {} 个键", result.updated, result.added);
+    result
+}
+
+/// ：校验写入目标（可能来自导入的译文文件），只允许 `localisation/` 下的 `.yml`，
+/// 拒绝 `..`、绝对路径与盘符。
+fn target_path(root: &Path, relative: &str) -> Result<PathBuf, String> {
+    let invalid = || format!("无效的目标文件: {}", relative);
+    let trimmed = relative.trim();
+    if trimmed.starts_with(['/', '\\']) {
+        return Err(invalid());
+    }
+    let mut parts: Vec<&str> = Vec::new();
+    for part in trimmed.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => return Err(invalid()),
+            _ if part.contains(':') => return Err(invalid()),
+            _ => parts.push(part),
+        }
+    }
+    let valid = parts.len() >= 2
+        && parts[0].eq_ignore_ascii_case(LOCALISATION_DIR)
+        && parts.last().is_some_and(|name| name.to_lowercase().ends_with(".yml"));
+    if !valid {
+        return Err(invalid());
+    }
+    Ok(parts.iter().fold(root.to_path_buf(), |path, part| path.join(part)))
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_.-".contains(c)
+}
+
+/// 默认写入的文件：`localisation/<语言>/<项目名>_l_<语言>.yml`。
+fn default_file_name(root: &Path, language: &str) -> String {
+    let project: String = root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let project = project.trim_matches('_');
+    let project = if project.is_empty() { "mod" } else { project };
+    format!("{}/{}/{}_l_{}.yml", LOCALISATION_DIR, language, project, language)
+}
+
+/// 转义写入引号内的文本：引号与换行。
+fn escape_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // 已转义的序列原样保留
+            '\\' if chars.peek().is_some_and(|n| matches!(n, '"' | 'n' | '\\')) => {
+                out.push(c);
+                out.extend(chars.next());
+            }
+            '"' => out.push_str("\\\""),
+            '\r' => {}
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// ：就地修改已存在的键，返回新内容与修改数量；命中的键从 `pending` 中移除。
+/// 只替换引号内的文本，缩进、版本号、行尾注释与换行符保持不变。
+fn update_existing_keys(content: &str, pending: &mut Vec<LocalisationUpsert>) -> (String, usize) {
+    let mut out = String::with_capacity(content.len());
+    let mut updated = 0usize;
+    for raw in content.split_inclusive('\n') {
+        let line = raw.trim_start_matches(['\u{feff}', ' ', '\t']);
+        let indent = raw.len() - line.len();
+        let replaced = line.split_once(':').and_then(|(key, rest)| {
+            let index = pending.iter().position(|p| p.key == key)?;
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let after_version = &rest[digits..];
+            let text = after_version.trim_start().strip_prefix('"')?;
+            let open = indent + key.len() + 1 + digits + (after_version.len() - text.len());
+            let close = open + closing_quote(text)?;
+            let entry = pending.remove(index);
+            let version_start = indent + key.len() + 1;
+            let version = match entry.version {
+                Some(version) => version.to_string(),
+                None => raw[version_start..version_start + digits].to_string(),
+            };
+            Some(format!(
+                "{}{}{}{}{}",
+                &raw[..version_start],
+                version,
+                &raw[version_start + digits..open],
+                escape_value(&entry.value),
+                &raw[close..]
+            ))
+        });
+        match replaced {
+            Some(line) => {
+                out.push_str(&line);
+                updated += 1;
+            }
+            None => out.push_str(raw),
+        }
+    }
+    (out, updated)
+}
+
+/// ：把新键追加到文件末尾；文件不存在时创建带 BOM 与 `l_<语言>:` 头部的新文件。
+fn append_keys(existing: Option<&str>, language: &str, entries: &[LocalisationUpsert]) -> String {
+    let mut out = match existing {
+        Some(content) => content.to_string(),
+        None => format!("\u{feff}l_{}:\n", language),
+    };
+    let newline = if out.contains("\r\n") { "\r\n" } else { "\n" };
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push_str(newline);
+    }
+    for entry in entries {
+        out.push_str(&format!(
+            " {}:{} \"{}\"{}",
+            entry.key,
+            entry.version.unwrap_or(0),
+            escape_value(&entry.value),
+            newline
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lines_and_references() {
+        let content = "\u{feff}l_english:\n # comment\n GER_focus:0 \"Say \"hi\" to [ROOT.GetName]\" # note \"quoted\"\n KEY: \"$OTHER|Y$ £pol_power costs\"\n";
+        let (language, lines) = parse_localisation(content);
+        assert_eq!(language.as_deref(), Some("english"));
+        assert_eq!(lines[0].key, "GER_focus");
+        assert_eq!(lines[0].version, Some(0));
+        assert_eq!(lines[0].value, "Say \"hi\" to [ROOT.GetName]");
+        assert!(content[lines[0].value_start..].starts_with("Say"));
+        assert_eq!((lines[1].line, lines[1].version), (4, None));
+
+        let refs = parse_references(&lines[1].value);
+        let names: Vec<(LocalisationReferenceKind, &str)> = refs.iter().map(|r| (r.kind, r.name.as_str())).collect();
+        assert_eq!(names, [(LocalisationReferenceKind::Key, "OTHER"), (LocalisationReferenceKind::Icon, "pol_power")]);
+        assert_eq!(&lines[1].value[refs[1].start..refs[1].end], "pol_power");
+        assert_eq!(parse_references(&lines[0].value)[0].name, "ROOT.GetName");
+    }
+
+    #[test]
+    fn test_file_name_language() {
+        assert_eq!(file_name_language("localisation/ger_loc_l_english.yml").as_deref(), Some("english"));
+        assert_eq!(file_name_language("localisation/x_l_simp_chinese.yml").as_deref(), Some("simp_chinese"));
+        // 文件名中其他位置的 `l_` 不是语言后缀
+        assert_eq!(file_name_language("localisation/general_loc.yml"), None);
+    }
+
+    #[test]
+    fn test_replace_folder_and_missing_languages() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let write = |relative: &str, content: &str| {
+            let path = dir.path().join(relative);
+            fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+            fs::write(path, content).expect("write file");
+        };
+        write("mod/localisation/english/a_l_english.yml", "l_english:\n A:0 \"mod\"\n B:0 \"b\"\n");
+        write("mod/localisation/simp_chinese/a_l_simp_chinese.yml", "l_simp_chinese:\n A:0 \"模组\"\n");
+        write("game/localisation/english/replace/a_l_english.yml", "l_english:\n A:1 \"replaced\"\n");
+
+        let root = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        let database = load_from_paths(&[root("mod"), root("game")], None);
+        let a = &database.keys["A"]["english"];
+        assert_eq!((a.value.as_str(), a.version, a.replace), ("replaced", Some(1), true));
+        assert_eq!(database.keys["A"]["simp_chinese"].value, "模组");
+
+        let missing = database.missing_keys(&["simp_chinese".to_string()], &[RootSource::Dependency]);
+        assert_eq!(missing[0].keys, ["B"]);
+        let chinese = load_from_paths(&[root("mod"), root("game")], Some(&["simp_chinese"]));
+        assert_eq!(chinese.language_map("simp_chinese").len(), 1);
+    }
+
+    #[test]
+    fn test_update_preserves_layout_and_appends() {
+        let content = "\u{feff}l_english:\r\n # keep me\r\n  A:1 \"old\" # trailing \"q\"\r\n B:0 \"b\"\r\n";
+        let mut pending = vec![
+            LocalisationUpsert { key: "A".into(), value: "say \"hi\"\nthere".into(), version: None },
+            LocalisationUpsert { key: "C".into(), value: "c".into(), version: Some(2) },
+        ];
+        let (rewritten, updated) = update_existing_keys(content, &mut pending);
+        assert_eq!(updated, 1);
+        assert_eq!(
+            rewritten,
+            "\u{feff}l_english:\r\n # keep me\r\n  A:1 \"say \\\"hi\\\"\\nthere\" # trailing \"q\"\r\n B:0 \"b\"\r\n"
+        );
+        assert_eq!(pending.len(), 1);
+
+        let appended = append_keys(Some(&rewritten), "english", &pending);
+        assert!(appended.ends_with(" B:0 \"b\"\r\n C:2 \"c\"\r\n"));
+        assert_eq!(append_keys(None, "simp_chinese", &pending), "\u{feff}l_simp_chinese:\n C:2 \"c\"\n");
+    }
+
+    #[test]
+    fn test_upsert_keeps_highest_version_of_duplicates() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("localisation/english/a_l_english.yml");
+        fs::create_dir_all(file.parent().expect("parent")).expect("mkdir");
+        fs::write(&file, "l_english:\n A:0 \"old\"\n").expect("write");
+        let upsert = |key: &str, value: &str, version: Option<u32>| LocalisationUpsert {
+            key: key.into(),
+            value: value.into(),
+            version,
+        };
+        let entries = vec![
+            upsert("A", "newer", Some(2)),
+            upsert("A", "stale", Some(1)),
+            upsert("B", "first", None),
+            upsert("B", "last", None),
+        ];
+        let result = upsert_keys(dir.path(), "english", entries, Some("localisation/english/a_l_english.yml".into()));
+        assert!(result.success, "{}", result.message);
+        assert_eq!(fs::read_to_string(&file).expect("read"), "l_english:\n A:2 \"newer\"\n B:0 \"last\"\n");
+        let leftovers = fs::read_dir(file.parent().expect("parent")).expect("read dir").count();
+        assert_eq!(leftovers, 1);
+    }
+}
@@ -0,0 +1,400 @@
+#![deny(clippy::unwrap_used)]
+
+//! 本地化文本检查
+//!
+//! 供诊断引擎对 `.yml` 文件执行的规则：`§Y…§!` 颜色代码未闭合或颜色未定义、
+//! `£icon` 找不到对应的 spriteType、`$KEY$` 引用不存在的键以及 `$KEY$` 互相嵌套成环。
+//! 颜色除原版内置的以外，还取自 `.gfx` 中的 `textcolors`；sprite 名称从各根目录
+//! `gfx` 与 `interface` 下的 `.gfx` 中收集，按文件 mtime 缓存。`$VALUE$` 等由游戏填入的
+//! 占位符不算本地化键。
+
+use crate::clausewitz::{self, Span};
+use crate::file_cache::{self, FileSetCache, FileStamps};
+use crate::gui_engine;
+use crate::load_order::{self, LoadOrderRoot, RootSource};
+use crate::localisation::{self, LocalisationDatabase, LocalisationLine, LocalisationReferenceKind};
+use crate::script_lint::Finding;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+/// ：原版 `textcolors` 中定义的颜色代码。
+pub(crate) const BUILTIN_COLOURS: &str = "CLWBGRbgYHTO0123456789";
+
+/// ：从 `.gfx` 收集的 sprite 与自定义文字颜色。
+#[derive(Debug, Default)]
+pub(crate) struct GfxCatalogue {
+    pub(crate) sprites: HashSet<String>,
+    /// sprite 名称 → 纹理的完整路径（所在根目录 + texturefile）
+    pub(crate) textures: HashMap<String, String>,
+    pub(crate) colours: HashSet<char>,
+}
+
+static CATALOGUE_CACHE: Lazy<FileSetCache<GfxCatalogue>> = Lazy::new(FileSetCache::new);
+
+/// ：存放 `.gfx` 的顶层目录，与 `crate::resolve_sprite_textures` 一致。
+const GFX_DIRS: [&str; 2] = ["gfx", "interface"];
+
+/// ：由游戏在显示时填入的 `$TOKEN$`，不是本地化键。
+const ENGINE_TOKENS: [&str; 46] = [
+    "VALUE", "COUNT", "TARGET", "WHO", "NAME", "DATE", "DAY", "DAYS", "HOURS", "MONTHS", "YEARS",
+    "AMOUNT", "COST", "LEVEL", "TEXT", "TOOLTIP", "DESC", "TITLE", "ICON", "PERCENT", "MAX", "MIN",
+    "CURRENT", "TOTAL", "CHANGE", "BONUS", "RATIO", "NUM", "COUNTRY", "STATE", "PROVINCE", "LEADER",
+    "UNIT", "IDEOLOGY", "PARTY", "BUILDING", "RESOURCE", "EQUIPMENT", "TECH", "FOCUS", "DECISION",
+    "MODIFIER", "TRAIT", "REASON", "EFFECT", "TRIGGER",
+];
+
+/// ：按加载顺序收集各根目录 `gfx` 与 `interface` 下的 `.gfx`，文件未变化时复用缓存。
+pub(crate) fn load_catalogue(
+    project_root: Option<&str>,
+    game_root: Option<&str>,
+    dependency_roots: Option<Vec<String>>,
+) -> Arc<GfxCatalogue> {
+    let roots = load_order::load_order(project_root, dependency_roots, game_root);
+    catalogue_for_roots(&roots)
+}
+
+/// ：项目与依赖项按各 `.gfx` 的 mtime 校验；游戏目录只比较廉价的目录戳，
+/// 其文件列表在重新构建时才遍历。
+pub(crate) fn catalogue_for_roots(roots: &[LoadOrderRoot]) -> Arc<GfxCatalogue> {
+    let mut stamps: FileStamps = Vec::new();
+    for (index, root) in roots.iter().enumerate() {
+        if root.source == RootSource::Game {
+            stamps.extend(file_cache::game_root_stamps(Path::new(&root.path), GFX_DIRS));
+        } else {
+            stamps.extend(gfx_files(roots, index));
+        }
+    }
+
+    CATALOGUE_CACHE.get_or_build(file_cache::roots_key(roots), stamps, |_| {
+        let mut catalogue = GfxCatalogue::default();
+        for (index, root) in roots.iter().enumerate() {
+            for (path, _) in gfx_files(roots, index) {
+                let Ok(bytes) = fs::read(&path) else { continue };
+                let content = String::from_utf8_lossy(&bytes);
+                let doc = clausewitz::parse(&content);
+                for sprite in gui_engine::gfx_sprites(&doc) {
+                    // 同名 sprite 以加载顺序中靠前的定义为准
+                    if let Some(texture) = sprite.texturefile.filter(|t| !t.is_empty()) {
+                        let texture = format!("{}/{}", root.path, texture.replace('\\', "/"));
+                        catalogue.textures.entry(sprite.name.clone()).or_insert(texture);
+                    }
+                    catalogue.sprites.insert(sprite.name);
+                }
+                // textcolors 通常嵌套在 bitmapfonts 中
+                doc.root.visit_fields(&mut |field, _| {
+                    let Some(block) = field.value.as_block().filter(|_| field.key_is("textcolors")) else { return };
+                    let codes = block.fields().filter_map(|f| single_char(f.key.as_str()));
+                    catalogue.colours.extend(codes);
+                });
+            }
+        }
+        catalogue
+    })
+}
+
+/// ：某个根目录下未被 replace_path 清空的 `.gfx` 及其 mtime。
+fn gfx_files(roots: &[LoadOrderRoot], index: usize) -> FileStamps {
+    let root = &roots[index];
+    let mut files: FileStamps = Vec::new();
+    for dir in GFX_DIRS {
+        let base = Path::new(&root.path).join(dir);
+        if !base.exists() {
+            continue;
+        }
+        for entry in WalkDir::new(&base).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            if !entry.file_type().is_file() || !name.ends_with(".gfx") {
+                continue;
+            }
+            let path = entry.path().to_string_lossy().replace('\\', "/");
+            // replace_path 只清空所列目录本身
+            let directory = path
+                .strip_prefix(root.path.as_str())
+                .and_then(|rel| rel.trim_start_matches('/').rsplit_once('/'))
+                .map(|(d, _)| d.to_string())
+                .unwrap_or_default();
+            if load_order::blanked_by(roots, index, &directory).is_some() {
+                continue;
+            }
+            let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+            files.push((path, modified));
+        }
+    }
+    files
+}
+
+fn single_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    let first = chars.next()?;
+    chars.next().is_none().then_some(first)
+}
+
+/// ：单个 yml 文件的检查上下文。`sprites` / `database` 在缺少根目录时为 None，
+/// 对应的规则随之跳过。
+pub(crate) struct LocalisationContext<'a> {
+    pub(crate) language: Option<&'a str>,
+    pub(crate) entries: &'a [LocalisationLine],
+    pub(crate) colours: &'a HashSet<char>,
+    pub(crate) sprites: Option<&'a HashSet<String>>,
+    pub(crate) database: Option<&'a LocalisationDatabase>,
+}
+
+impl LocalisationContext<'_> {
+    /// 键在当前语言下的文本，本文件中的定义优先于数据库（可能尚未保存）。
+    fn value_of(&self, key: &str) -> Option<&str> {
+        if let Some(entry) = self.entries.iter().find(|e| e.key == key) {
+            return Some(&entry.value);
+        }
+        let database = self.database?;
+        match self.language {
+            Some(language) => database.get(key, language).map(|v| v.value.as_str()),
+            None => database.values(key)?.values().next().map(|v| v.value.as_str()),
+        }
+    }
+}
+
+fn span_in(entry: &LocalisationLine, start: usize, end: usize) -> Span {
+    Span::new(entry.value_start + start, entry.value_start + end)
+}
+
+/// ：颜色代码 `§X` 的位置与字符，`None` 表示 `§` 位于文本末尾。
+fn colour_codes(value: &str) -> Vec<(usize, Option<char>)> {
+    value
+        .char_indices()
+        .filter(|(_, c)| *c == '§')
+        .map(|(i, c)| (i, value[i + c.len_utf8()..].chars().next()))
+        .collect()
+}
+
+pub(crate) fn check_unclosed_colour(ctx: &LocalisationContext, out: &mut Vec<Finding>) {
+    for entry in ctx.entries {
+        // 颜色可以直接切换，只要最后一次设置颜色之后有 `§!` 即可
+        let mut open: Option<(usize, char)> = None;
+        for (pos, code) in colour_codes(&entry.value) {
+            match code {
+                Some('!') => open = None,
+                Some(c) if open.is_none() => open = Some((pos, c)),
+                _ => {}
+            }
+        }
+        if let Some((pos, c)) = open {
+            let len = '§'.len_utf8() + c.len_utf8();
+            out.push(Finding {
+                span: span_in(entry, pos, pos + len),
+                message: format!("{}: 颜色代码 §{} 缺少结束的 §!", entry.key, c),
+                fixes: Vec::new(),
+            });
+        }
+    }
+}
+
+pub(crate) fn check_unknown_colour(ctx: &LocalisationContext, out: &mut Vec<Finding>) {
+    for entry in ctx.entries {
+        for (pos, code) in colour_codes(&entry.value) {
+            let message = match code {
+                Some('!') => continue,
+                Some(c) if ctx.colours.contains(&c) => continue,
+                Some(c) => format!("{}: 未定义的颜色代码 §{}", entry.key, c),
+                None => format!("{}: § 后缺少颜色代码", entry.key),
+            };
+            let len = '§'.len_utf8() + code.map(char::len_utf8).unwrap_or(0);
+            out.push(Finding {
+                span: span_in(entry, pos, pos + len),
+                message,
+                fixes: Vec::new(),
+            });
+        }
+    }
+}
+
+pub(crate) fn check_unknown_icon(ctx: &LocalisationContext, out: &mut Vec<Finding>) {
+    let Some(sprites) = ctx.sprites else { return };
+    for entry in ctx.entries {
+        for reference in localisation::parse_references(&entry.value) {
+            if reference.kind != LocalisationReferenceKind::Icon {
+                continue;
+            }
+            let name = &reference.name;
+            if sprites.contains(name) || sprites.contains(&format!("GFX_{}", name)) {
+                continue;
+            }
+            out.push(Finding {
+                span: span_in(entry, reference.start, reference.end),
+                message: format!("{}: 图标 £{} 没有对应的 spriteType（GFX_{}）", entry.key, name, name),
+                fixes: Vec::new(),
+            });
+        }
+    }
+}
+
+fn key_references(value: &str) -> impl Iterator<Item = localisation::LocalisationReference> {
+    localisation::parse_references(value).into_iter().filter(|r| {
+        r.kind == LocalisationReferenceKind::Key
+            && r.name.chars().all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c))
+    })
+}
+
+pub(crate) fn check_missing_key(ctx: &LocalisationContext, out: &mut Vec<Finding>) {
+    if ctx.database.is_none() {
+        return;
+    }
+    for entry in ctx.entries {
+        for reference in key_references(&entry.value) {
+            if !ENGINE_TOKENS.contains(&reference.name.as_str()) && ctx.value_of(&reference.name).is_none() {
+                out.push(Finding {
+                    span: span_in(entry, reference.start, reference.end),
+                    message: format!("{}: 引用了不存在的本地化键 ${}$", entry.key, reference.name),
+                    fixes: Vec::new(),
+                });
+            }
+        }
+    }
+}
+
+/// ：从 `key` 出发沿 `$KEY$` 引用深度优先查找回到 `target` 的路径。
+fn find_cycle(ctx: &LocalisationContext, key: &str, target: &str, path: &mut Vec<String>, seen: &mut HashSet<String>) -> bool {
+    if !seen.insert(key.to_string()) {
+        return false;
+    }
+    let Some(value) = ctx.value_of(key) else { return false };
+    for reference in key_references(value) {
+        path.push(reference.name.clone());
+        if reference.name == target || find_cycle(ctx, &reference.name, target, path, seen) {
+            return true;
+        }
+        path.pop();
+    }
+    false
+}
+
+pub(crate) fn check_recursion(ctx: &LocalisationContext, out: &mut Vec<Finding>) {
+    for entry in ctx.entries {
+        let mut seen: HashSet<String> = HashSet::new();
+        for reference in key_references(&entry.value) {
+            let mut path = vec![entry.key.clone(), reference.name.clone()];
+            let cyclic = reference.name == entry.key || find_cycle(ctx, &reference.name, &entry.key, &mut path, &mut seen);
+            if cyclic {
+                out.push(Finding {
+                    span: span_in(entry, reference.start, reference.end),
+                    message: format!("本地化引用成环: {}", path.join(" → ")),
+                    fixes: Vec::new(),
+                });
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context<'a>(entries: &'a [LocalisationLine], colours: &'a HashSet<char>, sprites: &'a HashSet<String>) -> LocalisationContext<'a> {
+        LocalisationContext {
+            language: Some("english"),
+            entries,
+            colours,
+            sprites: Some(sprites),
+            database: None,
+        }
+    }
+
+    fn messages(check: fn(&LocalisationContext, &mut Vec<Finding>), ctx: &LocalisationContext) -> Vec<String> {
+        let mut out = Vec::new();
+        check(ctx, &mut out);
+        out.into_iter().map(|f| f.message).collect()
+    }
+
+    #[test]
+    fn test_catalogue_reads_gfx_and_interface() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let write = |relative: &str, content: &str| {
+            let path = dir.path().join(relative);
+            fs::create_dir_all(path.parent().expect("parent")).expect("mkdir");
+            fs::write(path, content).expect("write");
+        };
+        write("gfx/texticons/icons.gfx", "spriteTypes = { spriteType = { name = GFX_gfx_icon texturefile = a.dds } }");
+        write("interface/fonts.gfx", "bitmapfonts = { textcolors = { Q = { 1 2 3 } } }\nspriteTypes = { frameAnimatedSpriteType = { name = GFX_ui_icon } }");
+        let project = dir.path().to_string_lossy().replace('\\', "/");
+
+        let catalogue = load_catalogue(Some(&project), None, None);
+        assert!(catalogue.sprites.contains("GFX_gfx_icon") && catalogue.sprites.contains("GFX_ui_icon"));
+        assert!(catalogue.colours.contains(&'Q'));
+    }
+
+    #[test]
+    fn test_catalogue_textures_and_game_stamps() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let write = |relative: &str, content: &str| {
+            let path = dir.path().join(relative);
+            fs::create_dir_all(path.parent().expect("parent")).expect("mkdir");
+            fs::write(path, content).expect("write");
+        };
+        let sprite = |name: &str, texture: &str| format!("spriteTypes = {{ spriteType = {{ name = {} texturefile = \"{}\" }} }}", name, texture);
+        write("project/interface/a.gfx", &sprite("GFX_shared", "gfx/project.dds"));
+        write("game/interface/a.gfx", &sprite("GFX_shared", "gfx\\game.dds"));
+        write("game/gfx/leaders/b.gfx", &sprite("GFX_game", "gfx/leaders/b.dds"));
+        let path = |name: &str| dir.path().join(name).to_string_lossy().replace('\\', "/");
+        let (project, game) = (path("project"), path("game"));
+
+        let catalogue = load_catalogue(Some(&project), Some(&game), None);
+        assert_eq!(catalogue.textures["GFX_shared"], format!("{}/gfx/project.dds", project));
+        assert_eq!(catalogue.textures["GFX_game"], format!("{}/gfx/leaders/b.dds", game));
+
+        // 游戏目录只比较目录戳：嵌套目录中的新文件要等游戏更新后才生效
+        write("game/gfx/leaders/c.gfx", &sprite("GFX_new", "gfx/leaders/c.dds"));
+        assert!(!load_catalogue(Some(&project), Some(&game), None).sprites.contains("GFX_new"));
+        write("game/launcher-settings.json", "{}");
+        assert!(load_catalogue(Some(&project), Some(&game), None).sprites.contains("GFX_new"));
+    }
+
+    #[test]
+    fn test_colour_codes_and_icons() {
+        let content = "l_english:\n A:0 \"§Yyellow§! and §Rred\"\n B:0 \"§Ggreen §Qodd§! £pol_power £nope §\"\n";
+        let (_, entries) = localisation::parse_localisation(content);
+        let colours: HashSet<char> = BUILTIN_COLOURS.chars().collect();
+        let sprites: HashSet<String> = HashSet::from(["GFX_pol_power".to_string()]);
+        let ctx = context(&entries, &colours, &sprites);
+
+        let mut unclosed = Vec::new();
+        check_unclosed_colour(&ctx, &mut unclosed);
+        assert_eq!(unclosed.len(), 1);
+        assert_eq!(&content[unclosed[0].span.start..unclosed[0].span.end], "§R");
+        assert_eq!(messages(check_unknown_colour, &ctx), ["B: 未定义的颜色代码 §Q", "B: § 后缺少颜色代码"]);
+        assert_eq!(messages(check_unknown_icon, &ctx), ["B: 图标 £nope 没有对应的 spriteType（GFX_nope）"]);
+    }
+
+    #[test]
+    fn test_missing_key_skips_engine_tokens() {
+        let content = "l_english:\n A:0 \"$VALUE$ $COUNT|Y$ $TARGET$ $B$ $MISSING$\"\n B:0 \"b\"\n";
+        let (_, entries) = localisation::parse_localisation(content);
+        let (colours, sprites) = (HashSet::new(), HashSet::new());
+        let database = LocalisationDatabase::default();
+        let ctx = LocalisationContext {
+            database: Some(&database),
+            ..context(&entries, &colours, &sprites)
+        };
+        assert_eq!(messages(check_missing_key, &ctx), ["A: 引用了不存在的本地化键 $MISSING$"]);
+    }
+
+    #[test]
+    fn test_key_recursion() {
+        let content = "l_english:\n A:0 \"see $B$\"\n B:0 \"see $C|Y$\"\n C:0 \"back to $A$\"\n D:0 \"$D$\"\n E:0 \"$A$ is fine\"\n";
+        let (_, entries) = localisation::parse_localisation(content);
+        let (colours, sprites) = (HashSet::new(), HashSet::new());
+        let ctx = context(&entries, &colours, &sprites);
+        assert_eq!(
+            messages(check_recursion, &ctx),
+            [
+                "本地化引用成环: A → B → C → A",
+                "本地化引用成环: B → C → A → B",
+                "本地化引用成环: C → A → B → C",
+                "本地化引用成环: D → D",
+            ]
+        );
+    }
+}
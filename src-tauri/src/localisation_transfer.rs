@@ -0,0 +1,683 @@
+#![deny(clippy::unwrap_used)]
+
+//! 本地化导入导出
+//!
+//! 把项目本地化按目标语言导出为 CSV（`key,source,target,file,base`）或 XLIFF 1.2，
+//! 供不熟悉 yml 的译者使用；`base` 记录导出时的译文，导入时据此发现冲突：
+//! 导出后 yml 中的译文又被改动过，则不覆盖并列入冲突。
+//! 目标语言由 `file` 列的 `_l_<语言>.yml` 文件名决定（XLIFF 另有 `target-language`）。
+//! 译文版本号低于源语言时视为过期，可以只导出缺失或过期的条目。
+
+use crate::localisation::{self, LocalisationDatabase, LocalisationUpsert, LocalisationValue};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// ：交换文件格式。
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LocalisationFormat {
+    Csv,
+    Xliff,
+}
+
+impl LocalisationFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            LocalisationFormat::Csv => "csv",
+            LocalisationFormat::Xliff => "xlf",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalisationExportResult {
+    pub success: bool,
+    pub message: String,
+    pub files: Vec<String>,
+    pub count: usize,
+}
+
+/// ：导出后译文被改动过的条目。
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalisationConflict {
+    pub key: String,
+    pub language: String,
+    /// 导出时的译文
+    pub exported: String,
+    /// yml 中当前的译文
+    pub current: String,
+    /// 导入文件中的译文
+    pub imported: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalisationImportResult {
+    pub success: bool,
+    pub message: String,
+    pub updated: usize,
+    pub added: usize,
+    pub unchanged: usize,
+    /// 无法确定目标语言或缺少必要列的行
+    pub skipped: usize,
+    pub conflicts: Vec<LocalisationConflict>,
+    /// 项目中已不存在的键
+    pub removed: Vec<String>,
+    pub files: Vec<String>,
+}
+
+impl LocalisationImportResult {
+    fn error(message: String) -> Self {
+        Self {
+            success: false,
+            message,
+            updated: 0,
+            added: 0,
+            unchanged: 0,
+            skipped: 0,
+            conflicts: Vec::new(),
+            removed: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+}
+
+/// ：译文状态。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TranslationState {
+    Missing,
+    Outdated,
+    Translated,
+}
+
+impl TranslationState {
+    /// XLIFF 1.2 的 `state` 属性值
+    fn xliff_state(self) -> &'static str {
+        match self {
+            TranslationState::Missing => "new",
+            TranslationState::Outdated => "needs-review-translation",
+            TranslationState::Translated => "translated",
+        }
+    }
+}
+
+/// ：交换文件中的一行。`file` 为目标语言 yml 相对项目根目录的路径。
+#[derive(Debug, Clone, PartialEq)]
+struct TransferRow {
+    key: String,
+    source: String,
+    target: String,
+    file: String,
+    base: String,
+    state: TranslationState,
+}
+
+const CSV_HEADER: [&str; 5] = ["key", "source", "target", "file", "base"];
+
+/// 导出项目本地化，每种目标语言一个文件，写入 `output_dir/<项目名>_<语言>.<csv|xlf>`。
+#[tauri::command]
+pub fn export_localisation(
+    project_root: String,
+    format: LocalisationFormat,
+    source_language: String,
+    languages: Vec<String>,
+    output_dir: String,
+    only_pending: Option<bool>,
+) -> LocalisationExportResult {
+    let root = normalize_root(&project_root);
+    let error = |message: String| LocalisationExportResult {
+        success: false,
+        message,
+        files: Vec::new(),
+        count: 0,
+    };
+    if !Path::new(&root).is_dir() {
+        return error(format!("项目目录不存在: {}", project_root));
+    }
+    if let Err(err) = fs::create_dir_all(&output_dir) {
+        return error(format!("创建输出目录失败: {}", err));
+    }
+
+    let database = localisation::load_database(Some(&root), None, None, None);
+    let project_name = Path::new(&root)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "localisation".to_string());
+
+    let mut files: Vec<String> = Vec::new();
+    let mut count = 0usize;
+    for language in languages.iter().filter(|l| **l != source_language) {
+        let rows = export_rows(&database, &root, &source_language, language, only_pending.unwrap_or(false));
+        let content = match format {
+            LocalisationFormat::Csv => write_csv(&rows),
+            LocalisationFormat::Xliff => write_xliff(&rows, &source_language, language),
+        };
+        let path = Path::new(&output_dir).join(format!("{}_{}.{}", project_name, language, format.extension()));
+        if let Err(err) = fs::write(&path, content) {
+            return error(format!("写入 {} 失败: {}", path.display(), err));
+        }
+        count += rows.len();
+        files.push(path.to_string_lossy().replace('\\', "/"));
+    }
+
+    LocalisationExportResult {
+        success: true,
+        message: format!("导出 {} 个文件，共 {} 条", files.len(), count),
+        files,
+        count,
+    }
+}
+
+/// 导入译者返回的 CSV / XLIFF，按格式由扩展名决定。
+#[tauri::command]
+pub fn import_localisation(project_root: String, file: String) -> LocalisationImportResult {
+    let root = normalize_root(&project_root);
+    if !Path::new(&root).is_dir() {
+        return LocalisationImportResult::error(format!("项目目录不存在: {}", project_root));
+    }
+    let content = match fs::read(&file) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).trim_start_matches('\u{feff}').to_string(),
+        Err(err) => return LocalisationImportResult::error(format!("读取 {} 失败: {}", file, err)),
+    };
+    let extension = Path::new(&file)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let (rows, skipped) = match extension.as_str() {
+        "csv" => match read_csv(&content) {
+            Ok(rows) => rows,
+            Err(message) => return LocalisationImportResult::error(message),
+        },
+        "xlf" | "xliff" => read_xliff(&content),
+        _ => return LocalisationImportResult::error(format!("不支持的文件格式: .{}", extension)),
+    };
+
+    let database = localisation::load_database(Some(&root), None, None, None);
+    let plan = plan_import(rows, &database, &root);
+
+    let mut result = LocalisationImportResult {
+        success: true,
+        message: String::new(),
+        updated: 0,
+        added: 0,
+        unchanged: plan.unchanged,
+        skipped,
+        conflicts: plan.conflicts,
+        removed: plan.removed,
+        files: Vec::new(),
+    };
+    for ((language, target_file), entries) in plan.writes {
+        let written = localisation::upsert_keys(Path::new(&root), &language, entries, Some(target_file));
+        if !written.success {
+            return LocalisationImportResult { message: written.message, success: false, ..result };
+        }
+        result.updated += written.updated;
+        result.added += written.added;
+        result.files.extend(written.files);
+    }
+    result.message = format!(
+        "更新 {} 条，新增 {} 条，冲突 {} 条，已删除的键 {} 个",
+        result.updated,
+        result.added,
+        result.conflicts.len(),
+        result.removed.len()
+    );
+    result
+}
+
+fn normalize_root(path: &str) -> String {
+    path.trim().replace('\\', "/").trim_end_matches('/').to_string()
+}
+
+/// 项目内文件相对项目根目录的路径，不在项目内时返回 None。
+fn project_relative(value: &LocalisationValue, root: &str) -> Option<String> {
+    value
+        .file
+        .strip_prefix(root)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .map(str::to_string)
+}
+
+/// 把源语言文件路径换成目标语言：目录名与 `_l_<语言>.yml` 后缀都替换。
+fn target_file_for(source_file: &str, source_language: &str, language: &str) -> String {
+    let mut parts: Vec<String> = source_file
+        .split('/')
+        .map(|part| if part == source_language { language.to_string() } else { part.to_string() })
+        .collect();
+    if let Some(name) = parts.last_mut() {
+        let suffix = format!("l_{}.yml", source_language);
+        if let Some(stem) = name.strip_suffix(&suffix) {
+            *name = format!("{}l_{}.yml", stem, language);
+        }
+    }
+    parts.join("/")
+}
+
+/// ：生成某种目标语言的导出行，只包含项目中定义了源语言文本的键。
+fn export_rows(
+    database: &LocalisationDatabase,
+    root: &str,
+    source_language: &str,
+    language: &str,
+    only_pending: bool,
+) -> Vec<TransferRow> {
+    let mut rows: Vec<TransferRow> = database
+        .entries()
+        .filter_map(|(key, values)| {
+            let source = values.get(source_language)?;
+            let source_file = project_relative(source, root)?;
+            let target = values.get(language).filter(|t| !t.value.is_empty());
+            let state = match target {
+                None => TranslationState::Missing,
+                Some(t) if t.version.unwrap_or(0) < source.version.unwrap_or(0) => TranslationState::Outdated,
+                Some(_) => TranslationState::Translated,
+            };
+            if only_pending && state == TranslationState::Translated {
+                return None;
+            }
+            let file = target
+                .and_then(|t| project_relative(t, root))
+                .unwrap_or_else(|| target_file_for(&source_file, source_language, language));
+            let base = target.map(|t| t.value.clone()).unwrap_or_default();
+            Some(TransferRow {
+                key: key.clone(),
+                source: source.value.clone(),
+                target: base.clone(),
+                file,
+                base,
+                state,
+            })
+        })
+        .collect();
+    rows.sort_by(|a, b| a.file.cmp(&b.file).then_with(|| a.key.cmp(&b.key)));
+    rows
+}
+
+/// ：导入计划：按 (语言, 目标文件) 分组的写入，以及不写入的条目。
+#[derive(Debug, Default)]
+struct ImportPlan {
+    writes: BTreeMap<(String, String), Vec<LocalisationUpsert>>,
+    unchanged: usize,
+    conflicts: Vec<LocalisationConflict>,
+    removed: Vec<String>,
+}
+
+/// ：比对导入行与当前本地化，决定写入、跳过或报告冲突。
+/// 写入时版本号与项目中其它语言的最高版本对齐，避免刚导入的译文仍被判为过期。
+fn plan_import(rows: Vec<(String, TransferRow)>, database: &LocalisationDatabase, root: &str) -> ImportPlan {
+    let mut plan = ImportPlan::default();
+    for (language, row) in rows {
+        if row.target.is_empty() {
+            continue;
+        }
+        let project_values: Vec<&LocalisationValue> = database
+            .values(&row.key)
+            .map(|values| values.values().filter(|v| project_relative(v, root).is_some()).collect())
+            .unwrap_or_default();
+        if project_values.is_empty() {
+            plan.removed.push(row.key);
+            continue;
+        }
+        let current = project_values.iter().find(|v| v.language == language);
+        let current_text = current.map(|v| v.value.as_str()).unwrap_or_default();
+        if current_text == row.target {
+            plan.unchanged += 1;
+            continue;
+        }
+        if current_text != row.base {
+            plan.conflicts.push(LocalisationConflict {
+                key: row.key,
+                language,
+                exported: row.base,
+                current: current_text.to_string(),
+                imported: row.target,
+            });
+            continue;
+        }
+        let latest = project_values.iter().filter_map(|v| v.version).max();
+        let version = latest.filter(|v| *v > current.and_then(|c| c.version).unwrap_or(0));
+        plan.writes.entry((language, row.file)).or_default().push(LocalisationUpsert {
+            key: row.key,
+            value: row.target,
+            version,
+        });
+    }
+    plan
+}
+
+// ==================== CSV ====================
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 写出带 BOM 的 CSV，方便表格软件识别 UTF-8。
+fn write_csv(rows: &[TransferRow]) -> String {
+    let mut out = String::from("\u{feff}");
+    out.push_str(&CSV_HEADER.join(","));
+    out.push_str("\r\n");
+    for row in rows {
+        let fields = [&row.key, &row.source, &row.target, &row.file, &row.base];
+        out.push_str(&fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// 按 RFC 4180 拆分记录，支持引号内的逗号、换行与 `""` 转义。
+fn parse_csv_records(content: &str) -> Vec<Vec<String>> {
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// ：读取 CSV，按表头定位列，允许译者调整列顺序或增加列。返回 (语言, 行) 与跳过的行数。
+fn read_csv(content: &str) -> Result<(Vec<(String, TransferRow)>, usize), String> {
+    let mut records = parse_csv_records(content).into_iter();
+    let header: Vec<String> = records
+        .next()
+        .ok_or_else(|| "CSV 文件为空".to_string())?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let (Some(key), Some(target), Some(file)) = (column("key"), column("target"), column("file")) else {
+        return Err("CSV 缺少 key、target 或 file 列".to_string());
+    };
+    let (source, base) = (column("source"), column("base"));
+
+    let mut rows: Vec<(String, TransferRow)> = Vec::new();
+    let mut skipped = 0usize;
+    for record in records {
+        let get = |index: Option<usize>| index.and_then(|i| record.get(i)).cloned().unwrap_or_default();
+        let row = TransferRow {
+            key: get(Some(key)).trim().to_string(),
+            source: get(source),
+            target: get(Some(target)),
+            file: get(Some(file)).trim().to_string(),
+            base: get(base),
+            state: TranslationState::Translated,
+        };
+        if row.key.is_empty() && row.target.is_empty() {
+            continue;
+        }
+        let name = row.file.rsplit('/').next().unwrap_or_default().to_lowercase();
+        match localisation::file_name_language(&name) {
+            Some(language) if !row.key.is_empty() => rows.push((language, row)),
+            _ => skipped += 1,
+        }
+    }
+    Ok((rows, skipped))
+}
+
+// ==================== XLIFF ====================
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+static ENTITY_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"&(#x[0-9A-Fa-f]+|#[0-9]+|[a-z]+);").expect("failed to compile entity regex"));
+
+fn xml_unescape(value: &str) -> String {
+    ENTITY_REGEX
+        .replace_all(value, |caps: &regex::Captures| {
+            let entity = &caps[1];
+            let decoded = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity
+                    .strip_prefix("#x")
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse::<u32>))
+                    .and_then(|n| n.ok())
+                    .and_then(char::from_u32),
+            };
+            decoded.map(String::from).unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// 写出 XLIFF 1.2，每个目标 yml 文件对应一个 `<file>`，导出时的译文放在 `<note from="base">`。
+fn write_xliff(rows: &[TransferRow], source_language: &str, language: &str) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n",
+    );
+    let mut by_file: BTreeMap<&str, Vec<&TransferRow>> = BTreeMap::new();
+    for row in rows {
+        by_file.entry(row.file.as_str()).or_default().push(row);
+    }
+    for (file, rows) in by_file {
+        out.push_str(&format!(
+            "  <file original=\"{}\" source-language=\"{}\" target-language=\"{}\" datatype=\"plaintext\">\n    <body>\n",
+            xml_escape(file),
+            xml_escape(source_language),
+            xml_escape(language)
+        ));
+        for row in rows {
+            out.push_str(&format!(
+                "      <trans-unit id=\"{}\" xml:space=\"preserve\">\n        <source>{}</source>\n        <target state=\"{}\">{}</target>\n        <note from=\"base\">{}</note>\n      </trans-unit>\n",
+                xml_escape(&row.key),
+                xml_escape(&row.source),
+                row.state.xliff_state(),
+                xml_escape(&row.target),
+                xml_escape(&row.base)
+            ));
+        }
+        out.push_str("    </body>\n  </file>\n");
+    }
+    out.push_str("</xliff>\n");
+    out
+}
+
+static FILE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<file\b([^>]*)>(.*?)</file>").expect("failed to compile xliff file regex"));
+static UNIT_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)<trans-unit\b([^>]*)>(.*?)</trans-unit>").expect("failed to compile xliff unit regex")
+});
+static ATTR_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"([\w:-]+)\s*=\s*"([^"]*)""#).expect("failed to compile xml attribute regex"));
+
+fn attributes(text: &str) -> HashMap<String, String> {
+    ATTR_REGEX
+        .captures_iter(text)
+        .map(|caps| (caps[1].to_string(), xml_unescape(&caps[2])))
+        .collect()
+}
+
+/// 取出 `<name ...>内容</name>` 的内容，`<name/>` 视为空。`attr` 用于筛选 `<note from="base">`。
+fn element_text(body: &str, name: &str, attr: Option<(&str, &str)>) -> Option<String> {
+    let mut rest = body;
+    while let Some(start) = rest.find(&format!("<{}", name)) {
+        let after = &rest[start + name.len() + 1..];
+        let tag_end = after.find('>')?;
+        let tag = &after[..tag_end];
+        let is_match = !tag.starts_with(|c: char| c.is_alphanumeric() || c == '-')
+            && attr.is_none_or(|(k, v)| attributes(tag).get(k).is_some_and(|value| value == v));
+        if is_match {
+            if tag.ends_with('/') {
+                return Some(String::new());
+            }
+            let content = &after[tag_end + 1..];
+            let close = content.find(&format!("</{}>", name))?;
+            return Some(xml_unescape(&content[..close]));
+        }
+        rest = &after[tag_end..];
+    }
+    None
+}
+
+/// ：读取 XLIFF 1.2，返回 (语言, 行) 与跳过的行数。
+fn read_xliff(content: &str) -> (Vec<(String, TransferRow)>, usize) {
+    let mut rows: Vec<(String, TransferRow)> = Vec::new();
+    let mut skipped = 0usize;
+    for file in FILE_REGEX.captures_iter(content) {
+        let attrs = attributes(&file[1]);
+        let original = attrs.get("original").cloned().unwrap_or_default();
+        let name = original.rsplit('/').next().unwrap_or_default().to_lowercase();
+        let language = attrs
+            .get("target-language")
+            .cloned()
+            .or_else(|| localisation::file_name_language(&name));
+        for unit in UNIT_REGEX.captures_iter(&file[2]) {
+            let key = attributes(&unit[1]).get("id").cloned().unwrap_or_default();
+            let (Some(language), false) = (language.clone(), key.is_empty()) else {
+                skipped += 1;
+                continue;
+            };
+            let body = &unit[2];
+            rows.push((
+                language,
+                TransferRow {
+                    key,
+                    source: element_text(body, "source", None).unwrap_or_default(),
+                    target: element_text(body, "target", None).unwrap_or_default(),
+                    file: original.clone(),
+                    base: element_text(body, "note", Some(("from", "base"))).unwrap_or_default(),
+                    state: TranslationState::Translated,
+                },
+            ));
+        }
+    }
+    (rows, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(key: &str, target: &str, base: &str) -> TransferRow {
+        TransferRow {
+            key: key.to_string(),
+            source: "Say \"hi\", <b>".to_string(),
+            target: target.to_string(),
+            file: "localisation/simp_chinese/a_l_simp_chinese.yml".to_string(),
+            base: base.to_string(),
+            state: TranslationState::Missing,
+        }
+    }
+
+    #[test]
+    fn test_csv_and_xliff_round_trip() {
+        let rows = vec![row("A", "你好,\n世界", ""), row("B", "旧 & 新", "旧")];
+        let (csv_rows, skipped) = read_csv(write_csv(&rows).trim_start_matches('\u{feff}')).expect("csv");
+        assert_eq!(skipped, 0);
+        let (xliff_rows, _) = read_xliff(&write_xliff(&rows, "english", "simp_chinese"));
+        for parsed in [csv_rows, xliff_rows] {
+            assert_eq!(parsed.len(), 2);
+            for ((language, parsed), original) in parsed.into_iter().zip(&rows) {
+                assert_eq!(language, "simp_chinese");
+                assert_eq!((&parsed.key, &parsed.source, &parsed.target), (&original.key, &original.source, &original.target));
+                assert_eq!((&parsed.file, &parsed.base), (&original.file, &original.base));
+            }
+        }
+        assert_eq!(
+            target_file_for("localisation/english/a_l_english.yml", "english", "simp_chinese"),
+            "localisation/simp_chinese/a_l_simp_chinese.yml"
+        );
+    }
+
+    #[test]
+    fn test_export_pending_and_import_conflicts() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let write = |relative: &str, content: &str| {
+            let path = dir.path().join(relative);
+            fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+            fs::write(path, content).expect("write file");
+        };
+        write("localisation/english/a_l_english.yml", "l_english:\n A:1 \"a\"\n B:0 \"b\"\n C:0 \"c\"\n");
+        write("localisation/simp_chinese/a_l_simp_chinese.yml", "l_simp_chinese:\n A:0 \"甲\"\n B:0 \"乙\"\n");
+        let root = normalize_root(&dir.path().to_string_lossy());
+        let database = localisation::load_from_paths(std::slice::from_ref(&root), None);
+
+        let pending = export_rows(&database, &root, "english", "simp_chinese", true);
+        let states: Vec<(&str, TranslationState)> = pending.iter().map(|r| (r.key.as_str(), r.state)).collect();
+        assert_eq!(states, [("A", TranslationState::Outdated), ("C", TranslationState::Missing)]);
+        assert_eq!(pending[1].file, "localisation/simp_chinese/a_l_simp_chinese.yml");
+
+        let language = || "simp_chinese".to_string();
+        let rows = vec![
+            (language(), row("A", "新甲", "甲")),
+            (language(), row("B", "新乙", "旧乙")),
+            (language(), row("C", "丙", "")),
+            (language(), row("GONE", "无", "")),
+        ];
+        let plan = plan_import(rows, &database, &root);
+        assert_eq!(plan.removed, ["GONE"]);
+        assert_eq!(plan.conflicts.len(), 1);
+        assert_eq!((plan.conflicts[0].key.as_str(), plan.conflicts[0].current.as_str()), ("B", "乙"));
+        let writes = &plan.writes[&(language(), "localisation/simp_chinese/a_l_simp_chinese.yml".to_string())];
+        let written: Vec<(&str, Option<u32>)> = writes.iter().map(|w| (w.key.as_str(), w.version)).collect();
+        assert_eq!(written, [("A", Some(1)), ("C", None)]);
+    }
+
+    #[test]
+    fn test_import_rejects_path_traversal() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let project = dir.path().join("project");
+        let file = project.join("localisation/simp_chinese/a_l_simp_chinese.yml");
+        let english = project.join("localisation/english/a_l_english.yml");
+        for parent in [file.parent(), english.parent()] {
+            fs::create_dir_all(parent.expect("parent")).expect("create dir");
+        }
+        fs::write(&file, "l_simp_chinese:\n A:0 \"甲\"\n").expect("write file");
+        fs::write(&english, "l_english:\n A:0 \"a\"\n B:0 \"b\"\n").expect("write file");
+
+        for target in ["../../../escape_l_simp_chinese.yml", "/tmp/escape_l_simp_chinese.yml", "common/a_l_simp_chinese.yml"] {
+            let rows: Vec<TransferRow> = [row("A", "新甲", "甲"), row("B", "乙", "")]
+                .into_iter()
+                .map(|r| TransferRow { file: target.to_string(), ..r })
+                .collect();
+            let csv = dir.path().join("import.csv");
+            fs::write(&csv, write_csv(&rows)).expect("write csv");
+
+            let result = import_localisation(project.to_string_lossy().to_string(), csv.to_string_lossy().to_string());
+            assert!(!result.success, "{} should be rejected", target);
+            assert!(result.files.is_empty());
+        }
+        assert!(!dir.path().join("escape_l_simp_chinese.yml").exists());
+        assert!(!project.join("common").exists());
+        assert_eq!(fs::read_to_string(&file).expect("read"), "l_simp_chinese:\n A:0 \"甲\"\n");
+    }
+}
@@ -9,12 +9,8 @@ use rayon::prelude::*;
 use std::sync::Mutex;
 use once_cell::sync::Lazy;
 use memmap2::Mmap;
+use crate::clausewitz;
 
-static RE_STATE_ID: Lazy<Regex> = Lazy::new(|| Regex::new(r"id\s*=\s*(\d+)").unwrap());
-static RE_STATE_NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r#"name\s*=\s*"([^"]*)""#).unwrap());
-static RE_STATE_OWNER: Lazy<Regex> = Lazy::new(|| Regex::new(r"owner\s*=\s*([A-Z0-9]{3})").unwrap());
-static RE_STATE_CORE: Lazy<Regex> = Lazy::new(|| Regex::new(r"add_core_of\s*=\s*([A-Z0-9]{3})").unwrap());
-static RE_STATE_CLAIM: Lazy<Regex> = Lazy::new(|| Regex::new(r"add_claim_by\s*=\s*([A-Z0-9]{3})").unwrap());
 static RE_COUNTRY_COLOR: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^([A-Z0-9]{3})\s*=\s*\{\s*color\s*=\s*(?:rgb)?\s*\{\s*(\d+)\s+(\d+)\s+(\d+)\s*\}").unwrap());
 
 /// 地图上下文状态 (常驻内存)
@@ -601,48 +597,36 @@ pub struct StateDefinition {
 pub fn parse_state_file(path: &Path) -> Result<StateDefinition, String> {
     let content = read_file_with_encoding(path)?;
     
-    let mut id = 0;
-    let mut name = String::new();
-    let mut provinces = Vec::new();
+    let doc = clausewitz::parse(&content);
+    let state = doc.root.get_block("state").unwrap_or(&doc.root);
+
+    let id = state
+        .get_str("id")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let name = state.get_str("name").unwrap_or_default().to_string();
+    let provinces: Vec<u32> = state
+        .get_block("provinces")
+        .map(|b| b.scalars().filter_map(|s| s.as_str().parse().ok()).collect())
+        .unwrap_or_default();
+
+    // owner 取第一次出现的值（基础 history 块先于日期块），核心与宣称收集全部
     let mut owner = String::new();
     let mut cores = Vec::new();
     let mut claims = Vec::new();
-    
-    // 使用预编译的正则解析 (提升大量小文件解析速度)
-    if let Some(caps) = RE_STATE_ID.captures(&content) {
-        id = caps[1].parse().unwrap_or(0);
-    }
-    
-    if let Some(caps) = RE_STATE_NAME.captures(&content) {
-        name = caps[1].to_string();
-    }
-    
-    if let Some(caps) = RE_STATE_OWNER.captures(&content) {
-        owner = caps[1].to_string();
-    }
-
-    for cap in RE_STATE_CORE.captures_iter(&content) {
-        cores.push(cap[1].to_string());
-    }
-
-    for cap in RE_STATE_CLAIM.captures_iter(&content) {
-        claims.push(cap[1].to_string());
-    }
-    
-    // 查找 provinces = { 1 2 3 }
-    if let Some(start_idx) = content.find("provinces") {
-        if let Some(open_brace) = content[start_idx..].find('{') {
-            if let Some(close_brace) = content[start_idx + open_brace..].find('}') {
-                let province_str = &content[start_idx + open_brace + 1..start_idx + open_brace + close_brace];
-                for p in province_str.split_whitespace() {
-                    if let Ok(p_id) = p.parse::<u32>() {
-                        provinces.push(p_id);
-                    }
-                }
-            }
+    state.visit_fields(&mut |field, _| {
+        let Some(value) = field.value.as_str() else {
+            return;
+        };
+        if field.key_is("owner") && owner.is_empty() {
+            owner = value.to_string();
+        } else if field.key_is("add_core_of") {
+            cores.push(value.to_string());
+        } else if field.key_is("add_claim_by") {
+            claims.push(value.to_string());
         }
-    }
-    
+    });
+
     Ok(StateDefinition { id, name, provinces, owner, cores, claims })
 }
 
@@ -2,7 +2,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use crate::clausewitz::{self, Block, LineIndex, Value};
+use crate::localisation;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MioPreviewData {
@@ -400,93 +401,35 @@ fn read_all_mio_files_under(folder: &Path, out: &mut HashMap<String, MioDef>) {
 }
 
 fn parse_mio_file(file: &str, content: &str) -> Vec<MioDef> {
-    let mut result = Vec::new();
+    let doc = clausewitz::parse(content);
+    let index = LineIndex::new(content);
 
-    let bytes = content.as_bytes();
-    let mut i: usize = 0;
-
-    while i < bytes.len() {
-        // 跳过空白
-        while i < bytes.len() && is_space(bytes[i]) {
-            i += 1;
-        }
-        if i >= bytes.len() {
-            break;
-        }
-
-        // 行注释
-        if bytes[i] == b'#' {
-            while i < bytes.len() && bytes[i] != b'\n' {
-                i += 1;
-            }
-            continue;
-        }
-
-        // 读取 key（mio id）
-        let key_start = i;
-        if !is_ident_start(bytes[i]) {
-            i += 1;
-            continue;
-        }
-        i += 1;
-        while i < bytes.len() && is_ident_continue(bytes[i]) {
-            i += 1;
-        }
-        let key = content[key_start..i].trim().to_string();
-
-        // 跳过空白
-        while i < bytes.len() && is_space(bytes[i]) {
-            i += 1;
-        }
+    doc.root
+        .fields()
+        .filter_map(|field| {
+            let block = field.value.as_block()?;
+            Some(parse_mio_block(field.key.as_str(), file, &index, block))
+        })
+        .collect()
+}
 
-        if i >= bytes.len() || bytes[i] != b'=' {
-            continue;
-        }
-        i += 1;
+fn parse_mio_block(mio_id: &str, file: &str, index: &LineIndex, block: &Block) -> MioDef {
+    let include = block.get_str("include").map(|s| s.to_string());
 
-        while i < bytes.len() && is_space(bytes[i]) {
-            i += 1;
-        }
+    let trait_defs = parse_trait_list(file, index, block, "trait");
+    let add_trait_defs = parse_trait_list(file, index, block, "add_trait");
+    let override_trait_defs = parse_trait_list(file, index, block, "override_trait");
 
-        if i >= bytes.len() || bytes[i] != b'{' {
-            continue;
+    // remove_trait 支持 `remove_trait = token` 与 `remove_trait = { a b }` 两种写法
+    let mut remove_trait_ids = Vec::new();
+    for field in block.fields_named("remove_trait") {
+        match &field.value {
+            Value::Scalar(s) => remove_trait_ids.push(s.as_str().to_string()),
+            Value::Block(b) => remove_trait_ids.extend(parse_string_list(b)),
+            Value::Color(_) => {}
         }
-
-        let block_start = i;
-        let block_end = match find_matching_brace(content, block_start) {
-            Some(v) => v,
-            None => {
-                // 无法闭合，停止
-                break;
-            }
-        };
-
-        let block_content = &content[(block_start + 1)..block_end];
-
-        let mio_def = parse_mio_block(&key, file, content, block_start, block_end, block_content);
-        result.push(mio_def);
-
-        i = block_end + 1;
     }
 
-    result
-}
-
-fn parse_mio_block(
-    mio_id: &str,
-    file: &str,
-    full_content: &str,
-    block_start: usize,
-    _block_end: usize,
-    block_content: &str,
-) -> MioDef {
-    let include = extract_field_value(block_content, "include");
-
-    let trait_defs = parse_trait_list(file, full_content, block_start, block_content, "trait");
-    let add_trait_defs = parse_trait_list(file, full_content, block_start, block_content, "add_trait");
-    let override_trait_defs = parse_trait_list(file, full_content, block_start, block_content, "override_trait");
-    let remove_trait_ids = parse_string_list(block_content, "remove_trait");
-
     MioDef {
         id: mio_id.to_string(),
         include,
@@ -497,95 +440,42 @@ fn parse_mio_block(
     }
 }
 
-fn parse_trait_list(
-    file: &str,
-    full_content: &str,
-    mio_block_abs_start: usize,
-    mio_block_content: &str,
-    field: &str,
-) -> Vec<MioTraitDef> {
-    let mut traits = Vec::new();
-
-    let pattern = format!("{}", field);
-    let bytes = mio_block_content.as_bytes();
-    let mut i = 0usize;
-
-    while i < bytes.len() {
-        // 查找字段名
-        if starts_with_word(mio_block_content, i, &pattern) {
-            let mut j = i + pattern.len();
-            while j < bytes.len() && is_space(bytes[j]) {
-                j += 1;
-            }
-            if j >= bytes.len() || bytes[j] != b'=' {
-                i += 1;
-                continue;
-            }
-            j += 1;
-            while j < bytes.len() && is_space(bytes[j]) {
-                j += 1;
-            }
-            if j >= bytes.len() || bytes[j] != b'{' {
-                i += 1;
-                continue;
-            }
-
-            let abs_trait_block_start = mio_block_abs_start + 1 + j;
-            let trait_block_end_abs = match find_matching_brace(full_content, abs_trait_block_start) {
-                Some(v) => v,
-                None => break,
-            };
-
-            let trait_block_content = &full_content[(abs_trait_block_start + 1)..trait_block_end_abs];
-
-            let def = parse_trait_block(file, full_content, abs_trait_block_start, trait_block_end_abs, trait_block_content);
-            traits.push(def);
-
-            // 前进到块结束
-            let rel_end = (trait_block_end_abs + 1).saturating_sub(mio_block_abs_start + 1);
-            i = rel_end;
-            continue;
-        }
-
-        i += 1;
-    }
-
-    traits
+fn parse_trait_list(file: &str, index: &LineIndex, mio_block: &Block, field: &str) -> Vec<MioTraitDef> {
+    mio_block
+        .fields_named(field)
+        .filter_map(|f| f.value.as_block())
+        .map(|b| parse_trait_block(file, index, b))
+        .collect()
 }
 
-fn parse_trait_block(
-    file: &str,
-    full_content: &str,
-    abs_start: usize,
-    abs_end: usize,
-    block: &str,
-) -> MioTraitDef {
-    let id = extract_field_value(block, "token");
-    let name = extract_field_value(block, "name");
-    let icon = extract_field_value(block, "icon");
+fn parse_trait_block(file: &str, index: &LineIndex, block: &Block) -> MioTraitDef {
+    let id = block.get_str("token").map(|s| s.to_string());
+    let name = block.get_str("name").map(|s| s.to_string());
+    let icon = block.get_str("icon").map(|s| s.to_string());
 
     let (x, y) = extract_position(block);
-    let relative_position_id = extract_field_value(block, "relative_position_id");
+    let relative_position_id = block.get_str("relative_position_id").map(|s| s.to_string());
 
-    let any_parent = parse_string_list(block, "any_parent");
-    let all_parents = parse_string_list(block, "all_parents");
-    let mutually_exclusive = parse_string_list(block, "mutually_exclusive");
+    let any_parent = block.get_block("any_parent").map(parse_string_list).unwrap_or_default();
+    let all_parents = block.get_block("all_parents").map(parse_string_list).unwrap_or_default();
+    let mutually_exclusive = block
+        .get_block("mutually_exclusive")
+        .map(parse_string_list)
+        .unwrap_or_default();
 
     let parent = parse_parent(block);
 
     let mut effects_present: Vec<String> = Vec::new();
-    if contains_top_level_assignment(block, "equipment_bonus") {
+    if block.field("equipment_bonus").is_some() {
         effects_present.push("equipment".to_string());
     }
-    if contains_top_level_assignment(block, "production_bonus") {
+    if block.field("production_bonus").is_some() {
         effects_present.push("production".to_string());
     }
-    if contains_top_level_assignment(block, "organization_modifier") {
+    if block.field("organization_modifier").is_some() {
         effects_present.push("organization".to_string());
     }
 
-    let line = get_line_number(full_content, abs_start);
-
     MioTraitDef {
         id,
         name,
@@ -599,277 +489,44 @@ fn parse_trait_block(
         mutually_exclusive,
         effects_present,
         file: file.to_string(),
-        start: abs_start,
-        end: abs_end + 1,
-        line,
+        start: block.span.start,
+        end: block.span.end,
+        line: index.line(block.span.start),
     }
 }
 
-fn parse_parent(block: &str) -> Option<MioParentDto> {
-    let parent_block = extract_block(block, "parent")?;
-    let traits = parse_string_list(&parent_block, "traits");
+fn parse_parent(block: &Block) -> Option<MioParentDto> {
+    let parent_block = block.get_block("parent")?;
+    let traits = parent_block
+        .get_block("traits")
+        .map(parse_string_list)
+        .unwrap_or_default();
     if traits.is_empty() {
         return None;
     }
-    let num_needed = extract_i32(&parent_block, "num_parents_needed").unwrap_or(1);
+    let num_needed = extract_i32(parent_block, "num_parents_needed").unwrap_or(1);
     Some(MioParentDto { traits, num_needed })
 }
 
-fn extract_position(block: &str) -> (i32, i32) {
-    let pos_block = extract_block(block, "position");
-    if let Some(p) = pos_block {
-        let x = extract_i32(&p, "x").unwrap_or(0);
-        let y = extract_i32(&p, "y").unwrap_or(0);
+fn extract_position(block: &Block) -> (i32, i32) {
+    if let Some(p) = block.get_block("position") {
+        let x = extract_i32(p, "x").unwrap_or(0);
+        let y = extract_i32(p, "y").unwrap_or(0);
         return (x, y);
     }
     (0, 0)
 }
 
-fn extract_i32(block: &str, key: &str) -> Option<i32> {
-    let v = extract_field_value(block, key)?;
-    v.parse::<f64>().ok().map(|f| f.round() as i32)
-}
-
-fn extract_field_value(block: &str, key: &str) -> Option<String> {
-    // 非严格：匹配 `key = value`，value 截到行尾/注释/右花括号
-    // 支持 quoted/unquoted
-    let mut i = 0usize;
-    let bytes = block.as_bytes();
-
-    while i < bytes.len() {
-        if starts_with_word(block, i, key) {
-            let mut j = i + key.len();
-            while j < bytes.len() && is_space(bytes[j]) {
-                j += 1;
-            }
-            if j >= bytes.len() || bytes[j] != b'=' {
-                i += 1;
-                continue;
-            }
-            j += 1;
-            while j < bytes.len() && is_space(bytes[j]) {
-                j += 1;
-            }
-            if j >= bytes.len() {
-                return None;
-            }
-
-            // quoted
-            if bytes[j] == b'"' {
-                j += 1;
-                let start = j;
-                while j < bytes.len() {
-                    if bytes[j] == b'"' && (j == 0 || bytes[j - 1] != b'\\') {
-                        return Some(block[start..j].trim().to_string());
-                    }
-                    j += 1;
-                }
-                return Some(block[start..].trim().to_string());
-            }
-
-            let start = j;
-            while j < bytes.len() {
-                let c = bytes[j];
-                // 未加引号的 value：
-                // 1) 需要支持 `x=1 y=2` 这种同一行写法（否则会把 `1 y=2` 当成 x 的值）
-                // 2) 大多数字段值（数字、token、路径）都不需要包含空格
-                if c == b'\n' || c == b'\r' || c == b'#' || c == b'}' || c == b' ' || c == b'\t' {
-                    break;
-                }
-                j += 1;
-            }
-            return Some(block[start..j].trim().to_string());
-        }
-
-        i += 1;
-    }
-
-    None
-}
-
-fn parse_string_list(block: &str, key: &str) -> Vec<String> {
-    let Some(inner) = extract_block(block, key) else {
-        return Vec::new();
-    };
-
-    let mut result = Vec::new();
-    let bytes = inner.as_bytes();
-    let mut i = 0usize;
-
-    while i < bytes.len() {
-        // skip
-        while i < bytes.len() && (is_space(bytes[i]) || bytes[i] == b',' || bytes[i] == b';') {
-            i += 1;
-        }
-        if i >= bytes.len() {
-            break;
-        }
-        if bytes[i] == b'#' {
-            while i < bytes.len() && bytes[i] != b'\n' {
-                i += 1;
-            }
-            continue;
-        }
-
-        if is_ident_start(bytes[i]) {
-            let start = i;
-            i += 1;
-            while i < bytes.len() && is_ident_continue(bytes[i]) {
-                i += 1;
-            }
-            let v = inner[start..i].trim();
-            if !v.is_empty() {
-                result.push(v.to_string());
-            }
-            continue;
-        }
-
-        i += 1;
-    }
-
-    result
-}
-
-fn extract_block(block: &str, key: &str) -> Option<String> {
-    let bytes = block.as_bytes();
-    let mut i = 0usize;
-
-    while i < bytes.len() {
-        if starts_with_word(block, i, key) {
-            let mut j = i + key.len();
-            while j < bytes.len() && is_space(bytes[j]) {
-                j += 1;
-            }
-            if j >= bytes.len() || bytes[j] != b'=' {
-                i += 1;
-                continue;
-            }
-            j += 1;
-            while j < bytes.len() && is_space(bytes[j]) {
-                j += 1;
-            }
-            if j >= bytes.len() || bytes[j] != b'{' {
-                i += 1;
-                continue;
-            }
-            let start = j;
-            let end = find_matching_brace_local(block, start)?;
-            return Some(block[(start + 1)..end].to_string());
-        }
-
-        i += 1;
-    }
-
-    None
-}
-
-fn find_matching_brace_local(content: &str, open_brace_index: usize) -> Option<usize> {
-    find_matching_brace(content, open_brace_index)
-}
-
-fn find_matching_brace(content: &str, open_brace_index: usize) -> Option<usize> {
-    let bytes = content.as_bytes();
-    if open_brace_index >= bytes.len() || bytes[open_brace_index] != b'{' {
-        return None;
-    }
-
-    let mut depth = 1i32;
-    let mut i = open_brace_index + 1;
-    let mut in_string = false;
-    let mut in_line_comment = false;
-
-    while i < bytes.len() {
-        let c = bytes[i];
-
-        if !in_string && c == b'#' {
-            in_line_comment = true;
-            i += 1;
-            continue;
-        }
-
-        if in_line_comment {
-            if c == b'\n' {
-                in_line_comment = false;
-            }
-            i += 1;
-            continue;
-        }
-
-        if c == b'"' {
-            if i == 0 || bytes[i - 1] != b'\\' {
-                in_string = !in_string;
-            }
-            i += 1;
-            continue;
-        }
-
-        if in_string {
-            i += 1;
-            continue;
-        }
-
-        if c == b'{' {
-            depth += 1;
-        } else if c == b'}' {
-            depth -= 1;
-            if depth == 0 {
-                return Some(i);
-            }
-        }
-
-        i += 1;
-    }
-
-    None
-}
-
-fn get_line_number(content: &str, position: usize) -> usize {
-    let mut line = 1usize;
-    for (idx, ch) in content.char_indices() {
-        if idx >= position {
-            break;
-        }
-        if ch == '\n' {
-            line += 1;
-        }
-    }
-    line
+fn extract_i32(block: &Block, key: &str) -> Option<i32> {
+    block.get_f64(key).map(|f| f.round() as i32)
 }
 
-fn is_space(c: u8) -> bool {
-    c == b' ' || c == b'\t' || c == b'\r' || c == b'\n'
-}
-
-fn is_ident_start(c: u8) -> bool {
-    (c >= b'a' && c <= b'z') || (c >= b'A' && c <= b'Z') || c == b'_' || c == b'@'
-}
-
-fn is_ident_continue(c: u8) -> bool {
-    is_ident_start(c) || (c >= b'0' && c <= b'9') || c == b'.' || c == b'-'
-}
-
-fn starts_with_word(s: &str, idx: usize, word: &str) -> bool {
-    if idx + word.len() > s.len() {
-        return false;
-    }
-    let slice = &s[idx..idx + word.len()];
-    if slice != word {
-        return false;
-    }
-
-    let before = if idx == 0 { None } else { s.as_bytes().get(idx - 1).copied() };
-    let after = s.as_bytes().get(idx + word.len()).copied();
-
-    let before_ok = before.map(|b| !is_ident_continue(b)).unwrap_or(true);
-    let after_ok = after.map(|b| !is_ident_continue(b)).unwrap_or(true);
-
-    before_ok && after_ok
-}
-
-fn contains_top_level_assignment(block: &str, key: &str) -> bool {
-    // 宽松：只要出现 key = 就算存在（忽略深度差异）
-    let needle = format!("{}", key);
-    block.contains(&needle) && block.contains('=')
+fn parse_string_list(block: &Block) -> Vec<String> {
+    block
+        .scalars()
+        .map(|s| s.as_str().trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
 }
 
 fn simple_hash(s: &str) -> String {
@@ -900,131 +557,33 @@ fn localize_trait_name(name: Option<String>, loc: &HashMap<String, String>) -> O
     Some(n)
 }
 
+/// 读取简体中文本地化，`roots` 靠前的优先；空文本视为未翻译，退回到其后根目录中的文本。
 fn load_simp_chinese_localization(roots: &[String]) -> HashMap<String, String> {
-    let mut map: HashMap<String, String> = HashMap::new();
+    let mut map = localisation::load_from_paths(roots, Some(&["simp_chinese"])).language_map("simp_chinese");
+    let untranslated: Vec<String> = map
+        .iter()
+        .filter(|(_, value)| value.trim().is_empty())
+        .map(|(key, _)| key.clone())
+        .collect();
+    map.retain(|_, value| !value.trim().is_empty());
+    if untranslated.is_empty() {
+        return map;
+    }
 
     for root in roots {
-        if root.trim().is_empty() {
-            continue;
-        }
-        let base = Path::new(root)
-            .join("localisation")
-            .join("simp_chinese");
-        if !base.exists() || !base.is_dir() {
-            continue;
-        }
-
-        for entry in WalkDir::new(&base)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            if !entry.file_type().is_file() {
+        let database = localisation::load_from_paths(std::slice::from_ref(root), Some(&["simp_chinese"]));
+        for key in &untranslated {
+            if map.contains_key(key) {
                 continue;
             }
-            let path = entry.path();
-            let ext = path
-                .extension()
-                .and_then(|s| s.to_str())
-                .unwrap_or("")
-                .to_lowercase();
-            if ext != "yml" {
-                continue;
+            if let Some(value) = database.get(key, "simp_chinese").filter(|v| !v.value.trim().is_empty()) {
+                map.insert(key.clone(), value.value.clone());
             }
-
-            let content = match fs::read_to_string(path) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
-
-            parse_hoi4_localization_yml(&content, &mut map);
         }
     }
-
     map
 }
 
-fn parse_hoi4_localization_yml(content: &str, out: &mut HashMap<String, String>) {
-    // 支持：
-    // key: "文本"
-    // key:0 "文本"
-    // 并忽略：l_simp_chinese:
-    for raw_line in content.lines() {
-        let mut line = raw_line.trim();
-        if line.is_empty() {
-            continue;
-        }
-
-        // 去 BOM
-        if line.starts_with('\u{feff}') {
-            line = line.trim_start_matches('\u{feff}');
-        }
-
-        if line.is_empty() {
-            continue;
-        }
-        if line.starts_with('#') {
-            continue;
-        }
-
-        // header
-        if line.ends_with(':') {
-            continue;
-        }
-
-        let Some(colon_pos) = line.find(':') else {
-            continue;
-        };
-
-        let key = line[..colon_pos].trim();
-        if key.is_empty() {
-            continue;
-        }
-
-        let mut rest = line[colon_pos + 1..].trim_start();
-
-        // optional numeric like :0
-        if let Some(first) = rest.as_bytes().first().copied() {
-            if first.is_ascii_digit() {
-                let mut idx = 0usize;
-                let bytes = rest.as_bytes();
-                while idx < bytes.len() && bytes[idx].is_ascii_digit() {
-                    idx += 1;
-                }
-                rest = rest[idx..].trim_start();
-            }
-        }
-
-        if !rest.starts_with('"') {
-            continue;
-        }
-        rest = &rest[1..];
-
-        let mut value = String::new();
-        let mut chars = rest.chars().peekable();
-        while let Some(ch) = chars.next() {
-            if ch == '"' {
-                break;
-            }
-            if ch == '\\' {
-                // 简单处理转义，保留后续字符
-                if let Some(next) = chars.next() {
-                    value.push(next);
-                    continue;
-                }
-            }
-            value.push(ch);
-        }
-
-        if value.trim().is_empty() {
-            continue;
-        }
-
-        // 不覆盖已有 key（优先让前面的文件生效，保持稳定）
-        out.entry(key.to_string()).or_insert(value);
-    }
-}
-
 fn try_find_root_containing_org_dir(file: &str) -> Option<String> {
     let p = PathBuf::from(file);
     let mut cur = p.parent()?.to_path_buf();
@@ -1038,3 +597,28 @@ fn try_find_root_containing_org_dir(file: &str) -> Option<String> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_localisation_falls_back_to_lower_root() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        for (root, content) in [
+            ("mod", "l_simp_chinese:\n A:0 \"\"\n B:0 \"乙\"\n"),
+            ("game", "l_simp_chinese:\n A:0 \"甲\"\n B:0 \"原版\"\n"),
+        ] {
+            let path = dir.path().join(root).join("localisation/simp_chinese/a_l_simp_chinese.yml");
+            fs::create_dir_all(path.parent().expect("parent")).expect("mkdir");
+            fs::write(path, content).expect("write");
+        }
+        let roots: Vec<String> = ["mod", "game"]
+            .iter()
+            .map(|r| dir.path().join(r).to_string_lossy().to_string())
+            .collect();
+        let map = load_simp_chinese_localization(&roots);
+        assert_eq!(map.get("A").map(String::as_str), Some("甲"));
+        assert_eq!(map.get("B").map(String::as_str), Some("乙"));
+    }
+}
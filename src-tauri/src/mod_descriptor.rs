@@ -0,0 +1,374 @@
+#![deny(clippy::unwrap_used)]
+
+//! `.mod` 描述文件
+//!
+//! 解析 descriptor.mod 以及启动器使用的 `<documents>/mod/*.mod`，
+//! 提取名称、版本、路径、依赖等字段；写回时只改动变化的字段，
+//! 未知字段、注释与原有排版保持不变。
+
+use crate::clausewitz::{self, Block, Field, Span};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// ：描述文件中的已知字段。
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ModDescriptor {
+    pub name: String,
+    pub version: Option<String>,
+    pub tags: Vec<String>,
+    pub picture: Option<String>,
+    pub supported_version: Option<String>,
+    /// 解包后的 Mod 目录（启动器 .mod 文件中常为相对于用户目录的路径）
+    pub path: Option<String>,
+    /// 压缩包形式的 Mod
+    pub archive: Option<String>,
+    pub replace_path: Vec<String>,
+    pub dependencies: Vec<String>,
+    pub remote_file_id: Option<String>,
+}
+
+/// ：解析描述文件内容，忽略未知字段与语法错误。
+pub(crate) fn parse_descriptor(content: &str) -> ModDescriptor {
+    let doc = clausewitz::parse(content.trim_start_matches('\u{feff}'));
+    let root = &doc.root;
+    ModDescriptor {
+        name: string_field(root, "name").unwrap_or_default(),
+        version: string_field(root, "version"),
+        tags: list_field(root, "tags"),
+        picture: string_field(root, "picture"),
+        supported_version: string_field(root, "supported_version"),
+        path: string_field(root, "path"),
+        archive: string_field(root, "archive"),
+        replace_path: root
+            .fields_named("replace_path")
+            .filter_map(|f| f.value.as_str())
+            .map(|s| s.to_string())
+            .collect(),
+        dependencies: list_field(root, "dependencies"),
+        remote_file_id: string_field(root, "remote_file_id"),
+    }
+}
+
+/// ：read_descriptor / write_descriptor 的返回值。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DescriptorResult {
+    pub success: bool,
+    pub message: String,
+    pub descriptor_path: String,
+    pub descriptor: Option<ModDescriptor>,
+    /// project.json 中记录的 replace_path
+    pub project_replace_path: Vec<String>,
+    /// project.json 与 descriptor.mod 的 replace_path 是否一致
+    pub replace_path_in_sync: bool,
+}
+
+/// ：读取项目的 descriptor.mod。
+#[tauri::command]
+pub fn read_descriptor(project_path: String) -> DescriptorResult {
+    let descriptor_path = Path::new(&project_path).join("descriptor.mod");
+    let path_str = descriptor_path.to_string_lossy().to_string();
+    let project_replace_path = read_project_replace_path(Path::new(&project_path));
+
+    let content = match fs::read(&descriptor_path) {
+        Ok(bytes) => match crate::decode_text_bytes(&bytes) {
+            Some((content, _)) => content,
+            None => return descriptor_error(path_str, "无法识别 descriptor.mod 的编码".to_string()),
+        },
+        Err(e) => return descriptor_error(path_str, format!("读取 descriptor.mod 失败: {}", e)),
+    };
+
+    let descriptor = parse_descriptor(&content);
+    let in_sync = project_replace_path
+        .as_ref()
+        .map(|p| *p == descriptor.replace_path)
+        .unwrap_or(true);
+    DescriptorResult {
+        success: true,
+        message: "读取成功".to_string(),
+        descriptor_path: path_str,
+        descriptor: Some(descriptor),
+        project_replace_path: project_replace_path.unwrap_or_default(),
+        replace_path_in_sync: in_sync,
+    }
+}
+
+/// ：写回 descriptor.mod（不存在时新建），并同步 project.json 的 replace_path。
+#[tauri::command]
+pub fn write_descriptor(project_path: String, descriptor: ModDescriptor) -> DescriptorResult {
+    let project_dir = Path::new(&project_path);
+    let descriptor_path = project_dir.join("descriptor.mod");
+    let path_str = descriptor_path.to_string_lossy().to_string();
+
+    if descriptor.name.trim().is_empty() {
+        return descriptor_error(path_str, "Mod 名称不能为空".to_string());
+    }
+
+    let (original, encoding) = match fs::read(&descriptor_path) {
+        Ok(bytes) => match crate::decode_text_bytes(&bytes) {
+            Some(decoded) => decoded,
+            None => return descriptor_error(path_str, "无法识别 descriptor.mod 的编码".to_string()),
+        },
+        Err(_) => (String::new(), encoding_rs::UTF_8),
+    };
+
+    let updated = update_descriptor(&original, &descriptor);
+    if updated != original {
+        if let Err(e) = crate::write_text_file(&descriptor_path, &updated, encoding) {
+            return descriptor_error(path_str, e);
+        }
+    }
+
+    if let Err(e) = sync_project_replace_path(project_dir, &descriptor.replace_path) {
+        return descriptor_error(path_str, e);
+    }
+
+    DescriptorResult {
+        success: true,
+        message: "descriptor.mod 已保存".to_string(),
+        descriptor_path: path_str,
+        project_replace_path: descriptor.replace_path.clone(),
+        descriptor: Some(parse_descriptor(&updated)),
+        replace_path_in_sync: true,
+    }
+}
+
+fn descriptor_error(descriptor_path: String, message: String) -> DescriptorResult {
+    DescriptorResult {
+        success: false,
+        message,
+        descriptor_path,
+        descriptor: None,
+        project_replace_path: Vec::new(),
+        replace_path_in_sync: false,
+    }
+}
+
+/// ：project.json 中的 replace_path；没有 project.json 或字段缺失时为 None。
+fn read_project_replace_path(project_dir: &Path) -> Option<Vec<String>> {
+    let content = fs::read_to_string(project_dir.join("project.json")).ok()?;
+    let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+    let list = json.get("replace_path")?.as_array()?;
+    Some(list.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
+}
+
+/// ：把 replace_path 写入 project.json（文件不存在时跳过）。
+fn sync_project_replace_path(project_dir: &Path, replace_path: &[String]) -> Result<(), String> {
+    let config_path = project_dir.join("project.json");
+    if !config_path.exists() {
+        return Ok(());
+    }
+    if read_project_replace_path(project_dir).as_deref() == Some(replace_path) {
+        return Ok(());
+    }
+    let content = fs::read_to_string(&config_path).map_err(|e| format!("读取 project.json 失败: {}", e))?;
+    let mut json: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| format!("解析 project.json 失败: {}", e))?;
+    let Some(obj) = json.as_object_mut() else {
+        return Err("project.json 格式无效".to_string());
+    };
+    obj.insert("replace_path".to_string(), serde_json::json!(replace_path));
+    let out = serde_json::to_string_pretty(&json).map_err(|e| format!("序列化 project.json 失败: {}", e))?;
+    crate::write_text_file(&config_path, &out, encoding_rs::UTF_8)
+}
+
+/// ：字段写回方式。
+enum FieldValue<'a> {
+    Scalar(Option<&'a str>),
+    List(&'a [String]),
+    /// 可重复出现的键，如 replace_path
+    Repeated(&'a [String]),
+}
+
+/// ：按新描述更新原文。只改动取值变化的字段，新增字段追加在末尾。
+pub(crate) fn update_descriptor(original: &str, descriptor: &ModDescriptor) -> String {
+    let (bom, body) = match original.strip_prefix('\u{feff}') {
+        Some(rest) => ("\u{feff}", rest),
+        None => ("", original),
+    };
+    let newline = if body.contains("\r\n") { "\r\n" } else { "\n" };
+    let doc = clausewitz::parse(body);
+
+    let fields: [(&str, FieldValue); 10] = [
+        ("version", FieldValue::Scalar(descriptor.version.as_deref())),
+        ("tags", FieldValue::List(&descriptor.tags)),
+        ("name", FieldValue::Scalar(Some(descriptor.name.as_str()))),
+        ("picture", FieldValue::Scalar(descriptor.picture.as_deref())),
+        ("replace_path", FieldValue::Repeated(&descriptor.replace_path)),
+        ("dependencies", FieldValue::List(&descriptor.dependencies)),
+        ("supported_version", FieldValue::Scalar(descriptor.supported_version.as_deref())),
+        ("path", FieldValue::Scalar(descriptor.path.as_deref())),
+        ("archive", FieldValue::Scalar(descriptor.archive.as_deref())),
+        ("remote_file_id", FieldValue::Scalar(descriptor.remote_file_id.as_deref())),
+    ];
+
+    let mut edits: Vec<(Span, String)> = Vec::new();
+    let mut appended: Vec<String> = Vec::new();
+    for (key, value) in fields {
+        let existing: Vec<&Field> = doc.root.fields_named(key).collect();
+        match value {
+            FieldValue::Scalar(new) => match (existing.first(), new.filter(|v| !v.is_empty())) {
+                (Some(field), Some(v)) => {
+                    if field.value.as_str() != Some(escape(v).as_str()) {
+                        edits.push((field.value.span(), quote(v)));
+                    }
+                }
+                (Some(_), None) => {
+                    edits.extend(existing.iter().map(|f| (removal_span(body, f.span), String::new())));
+                }
+                (None, Some(v)) => appended.push(format!("{}={}", key, quote(v))),
+                (None, None) => {}
+            },
+            FieldValue::List(items) => match existing.first() {
+                Some(field) => {
+                    let current: Vec<&str> = field
+                        .value
+                        .as_block()
+                        .map(|b| b.scalars().map(|s| s.as_str()).collect())
+                        .unwrap_or_default();
+                    let wanted: Vec<String> = items.iter().map(|i| escape(i)).collect();
+                    if items.is_empty() {
+                        edits.push((removal_span(body, field.span), String::new()));
+                    } else if current != wanted {
+                        edits.push((field.value.span(), render_list(items, newline)));
+                    }
+                }
+                None if !items.is_empty() => {
+                    appended.push(format!("{}={}", key, render_list(items, newline)));
+                }
+                None => {}
+            },
+            FieldValue::Repeated(items) => {
+                let current: Vec<&str> = existing.iter().filter_map(|f| f.value.as_str()).collect();
+                let wanted: Vec<String> = items.iter().map(|i| escape(i)).collect();
+                if current == wanted {
+                    continue;
+                }
+                let lines: Vec<String> = items.iter().map(|i| format!("{}={}", key, quote(i))).collect();
+                match existing.split_first() {
+                    // 新列表放在第一处原有位置，其余删除
+                    Some((first, rest)) => {
+                        if lines.is_empty() {
+                            edits.push((removal_span(body, first.span), String::new()));
+                        } else {
+                            edits.push((first.span, lines.join(newline)));
+                        }
+                        edits.extend(rest.iter().map(|f| (removal_span(body, f.span), String::new())));
+                    }
+                    None => appended.extend(lines),
+                }
+            }
+        }
+    }
+
+    let mut out = body.to_string();
+    edits.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+    for (span, text) in edits {
+        out.replace_range(span.start..span.end, &text);
+    }
+    if !appended.is_empty() {
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push_str(newline);
+        }
+        for line in appended {
+            out.push_str(&line);
+            out.push_str(newline);
+        }
+    }
+    format!("{}{}", bom, out)
+}
+
+/// ：生成全新的描述文件内容。
+pub(crate) fn render_descriptor(descriptor: &ModDescriptor) -> String {
+    update_descriptor("", descriptor)
+}
+
+fn escape(value: &str) -> String {
+    value.replace('"', "\\\"")
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", escape(value))
+}
+
+fn render_list(items: &[String], newline: &str) -> String {
+    let mut out = format!("{{{}", newline);
+    for item in items {
+        out.push('\t');
+        out.push_str(&quote(item));
+        out.push_str(newline);
+    }
+    out.push('}');
+    out
+}
+
+/// ：删除字段时连同所在行一起删除（行内还有其他内容时只删字段本身）。
+fn removal_span(source: &str, span: Span) -> Span {
+    let line_start = source[..span.start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let before_blank = source[line_start..span.start].trim().is_empty();
+    let rest = &source[span.end..];
+    let line_end = rest.find('\n').map(|i| span.end + i + 1).unwrap_or(source.len());
+    let after_blank = source[span.end..line_end].trim().is_empty();
+    if before_blank && after_blank {
+        Span::new(line_start, line_end)
+    } else {
+        span
+    }
+}
+
+fn string_field(block: &Block, key: &str) -> Option<String> {
+    block
+        .get_str(key)
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+fn list_field(block: &Block, key: &str) -> Vec<String> {
+    block
+        .fields_named(key)
+        .filter_map(|f| f.value.as_block())
+        .flat_map(|b| b.scalars().map(|s| s.as_str().to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_descriptor() {
+        let content = "\u{feff}version=\"1.2\"\ntags={\n\t\"Graphics\"\n\t\"Gameplay\"\n}\nname=\"My Mod\" # 注释\n\
+            replace_path=\"common/ideas\"\nreplace_path=\"history/states\"\n\
+            dependencies={ \"Base Mod\" }\nsupported_version=\"1.14.*\"\narchive=\"C:/mods/123/mod.zip\"\nremote_file_id=\"123\"\n";
+        let d = parse_descriptor(content);
+        assert_eq!(d.name, "My Mod");
+        assert_eq!(d.version.as_deref(), Some("1.2"));
+        assert_eq!(d.tags, vec!["Graphics", "Gameplay"]);
+        assert_eq!(d.replace_path, vec!["common/ideas", "history/states"]);
+        assert_eq!(d.dependencies, vec!["Base Mod"]);
+        assert_eq!(d.supported_version.as_deref(), Some("1.14.*"));
+        assert_eq!(d.archive.as_deref(), Some("C:/mods/123/mod.zip"));
+        assert_eq!(d.remote_file_id.as_deref(), Some("123"));
+        assert_eq!(d.path, None);
+    }
+
+    #[test]
+    fn test_update_descriptor_round_trip() {
+        let original = "# 手写注释\nversion=\"1.0\"\ntags={\n\t\"Graphics\"\n}\nname=\"Old\" # 名称\nreplace_path=\"common/ideas\"\nuser_dir=\"keep\"\nsupported_version=\"1.14.*\"\n";
+        let mut d = parse_descriptor(original);
+        assert_eq!(update_descriptor(original, &d), original);
+
+        d.name = "New".to_string();
+        d.replace_path = vec!["common/ideas".to_string(), "history/states".to_string()];
+        d.supported_version = None;
+        d.remote_file_id = Some("42".to_string());
+        let out = update_descriptor(original, &d);
+        assert_eq!(
+            out,
+            "# 手写注释\nversion=\"1.0\"\ntags={\n\t\"Graphics\"\n}\nname=\"New\" # 名称\nreplace_path=\"common/ideas\"\nreplace_path=\"history/states\"\nuser_dir=\"keep\"\nremote_file_id=\"42\"\n"
+        );
+        assert_eq!(parse_descriptor(&out), d);
+    }
+}
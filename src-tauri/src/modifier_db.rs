@@ -0,0 +1,381 @@
+#![deny(clippy::unwrap_used)]
+
+//! 修正数据库
+//!
+//! 内置 modifier.txt 是游戏修正的本地化文本（`MODIFIER_X - 描述`），这里把它解析为
+//! 结构化的修正表：名称、分类、数值类型与可用作用域。分类与作用域取自整理好的
+//! modifier_scopes.txt，表中没有的修正分类为 `unknown`、作用域为空（未知）。
+//! 模组在 `common/modifier_definitions` 中自定义的修正按加载顺序合并进来，
+//! 同名时以定义文件为准。结果按文件 mtime 缓存。
+
+use crate::clausewitz::{self, Block};
+use crate::file_cache::{self, FileSetCache, FileStamps};
+use crate::load_order::{self, RootSource};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// ：修正数值的显示方式。
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ModifierValueType {
+    Percent,
+    Flat,
+    Bool,
+}
+
+/// ：修正可以生效的作用域。
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ModifierScope {
+    Country,
+    State,
+    UnitLeader,
+}
+
+impl ModifierScope {
+    pub fn label(self) -> &'static str {
+        match self {
+            ModifierScope::Country => "国家",
+            ModifierScope::State => "州",
+            ModifierScope::UnitLeader => "将领",
+        }
+    }
+}
+
+/// ：修正来源。
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModifierSource {
+    Builtin,
+    Project,
+    Dependency,
+    Game,
+}
+
+/// ：单个修正。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifierInfo {
+    pub name: String,
+    /// 本地化描述，自定义修正为空
+    pub description: String,
+    pub category: String,
+    pub value_type: ModifierValueType,
+    /// 为空时表示作用域未知
+    pub scopes: Vec<ModifierScope>,
+    pub source: ModifierSource,
+    /// 自定义修正所在文件
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifierDatabaseResult {
+    pub success: bool,
+    pub message: String,
+    pub modifiers: Vec<ModifierInfo>,
+}
+
+/// ：合并后的修正表，键为小写名称。
+#[derive(Debug, Default)]
+pub(crate) struct ModifierDatabase {
+    by_name: HashMap<String, ModifierInfo>,
+}
+
+impl ModifierDatabase {
+    pub(crate) fn get(&self, name: &str) -> Option<&ModifierInfo> {
+        self.by_name.get(&name.to_lowercase())
+    }
+
+    /// ：名称与大小写完全一致的修正。
+    pub(crate) fn get_exact(&self, name: &str) -> Option<&ModifierInfo> {
+        self.by_name.get(name).filter(|m| m.name == name)
+    }
+}
+
+/// ：内置修正表。
+static BUILTIN: Lazy<Vec<ModifierInfo>> = Lazy::new(|| {
+    let scopes = parse_scope_table(include_str!("modifier_scopes.txt"));
+    parse_builtin(include_str!("modifier.txt"), &scopes)
+});
+
+/// ：分类未知的内置修正。
+const UNKNOWN_CATEGORY: &str = "unknown";
+
+/// ：自定义修正缓存，记录参与合并的文件及其 mtime。
+static DATABASE_CACHE: Lazy<FileSetCache<ModifierDatabase>> = Lazy::new(FileSetCache::new);
+
+const DEFINITIONS_DIR: &str = "common/modifier_definitions";
+
+/// 获取结构化的修正表（内置 + 自定义）
+#[tauri::command]
+pub fn get_modifier_database(
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> ModifierDatabaseResult {
+    let database = load_database(project_root.as_deref(), game_root.as_deref(), dependency_roots);
+    let mut modifiers: Vec<ModifierInfo> = database.by_name.values().cloned().collect();
+    modifiers.sort_by(|a, b| a.category.cmp(&b.category).then(a.name.cmp(&b.name)));
+    let custom = modifiers.iter().filter(|m| m.source != ModifierSource::Builtin).count();
+    ModifierDatabaseResult {
+        success: true,
+        message: format!("共 {} 个修正（自定义 {} 个）", modifiers.len(), custom),
+        modifiers,
+    }
+}
+
+/// ：加载合并后的修正表，定义文件未变化时复用缓存。
+pub(crate) fn load_database(
+    project_root: Option<&str>,
+    game_root: Option<&str>,
+    dependency_roots: Option<Vec<String>>,
+) -> Arc<ModifierDatabase> {
+    let roots = load_order::load_order(project_root, dependency_roots, game_root);
+    let key = file_cache::roots_key(&roots);
+
+    // 按加载顺序收集定义文件，同名文件只取最先出现的根目录
+    let mut files: Vec<(String, Option<SystemTime>, RootSource)> = Vec::new();
+    let mut seen_names: HashSet<String> = HashSet::new();
+    for (index, root) in roots.iter().enumerate() {
+        if load_order::blanked_by(&roots, index, DEFINITIONS_DIR).is_some() {
+            continue;
+        }
+        let dir = Path::new(&root.path).join(DEFINITIONS_DIR);
+        if !dir.exists() {
+            continue;
+        }
+        for entry in WalkDir::new(&dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            if !entry.file_type().is_file() || !name.ends_with(".txt") || !seen_names.insert(name) {
+                continue;
+            }
+            let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+            files.push((entry.path().to_string_lossy().replace('\\', "/"), modified, root.source));
+        }
+    }
+    let stamps: FileStamps = files.iter().map(|(p, m, _)| (p.clone(), *m)).collect();
+
+    DATABASE_CACHE.get_or_build(key, stamps, |_| {
+        let mut by_name: HashMap<String, ModifierInfo> = BUILTIN
+            .iter()
+            .map(|m| (m.name.to_lowercase(), m.clone()))
+            .collect();
+        // 低优先级先写入，高优先级覆盖
+        for (path, _, source) in files.iter().rev() {
+            let Ok(bytes) = fs::read(path) else { continue };
+            let content = String::from_utf8_lossy(&bytes);
+            for info in parse_definitions(&content, path, *source) {
+                by_name.insert(info.name.to_lowercase(), info);
+            }
+        }
+        ModifierDatabase { by_name }
+    })
+}
+
+// ==================== 内置表 ====================
+
+/// ：解析 modifier.txt。只保留修正本身的本地化键，跳过提示、前缀等其它文本。
+fn parse_builtin(text: &str, table: &HashMap<String, (String, Vec<ModifierScope>)>) -> Vec<ModifierInfo> {
+    let mut modifiers: Vec<ModifierInfo> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for line in text.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        let (key, description) = match line.split_once(" - ") {
+            Some((k, d)) => (k.trim(), d.trim()),
+            None => continue,
+        };
+        let Some(name) = modifier_name(key) else { continue };
+        // MODIFIER_X 与 x 同时存在时保留先出现的描述
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        let (category, scopes) = table
+            .get(&name)
+            .cloned()
+            .unwrap_or_else(|| (UNKNOWN_CATEGORY.to_string(), Vec::new()));
+        modifiers.push(ModifierInfo {
+            category,
+            value_type: infer_value_type(&name, description),
+            scopes,
+            description: description.to_string(),
+            name,
+            source: ModifierSource::Builtin,
+            file: None,
+        });
+    }
+    modifiers
+}
+
+/// ：从本地化键得到修正名。大写键只接受 `MODIFIER_` / `MODIFIERS_` 前缀。
+fn modifier_name(key: &str) -> Option<String> {
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    let name = if let Some(rest) = key.strip_prefix("MODIFIERS_").or_else(|| key.strip_prefix("MODIFIER_")) {
+        rest.to_lowercase()
+    } else if let Some(rest) = key.strip_prefix("modifier_") {
+        rest.to_string()
+    } else if key.chars().any(|c| c.is_ascii_uppercase()) {
+        return None;
+    } else {
+        key.to_string()
+    };
+    let is_text = ["_prefix", "_tooltip", "_desc", "_tt", "_added", "_removed", "_unchanged"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix));
+    (!name.is_empty() && !is_text).then_some(name)
+}
+
+/// ：解析 modifier_scopes.txt：`[分类 作用域,作用域]` 开始一组，其后每行一个修正名。
+fn parse_scope_table(text: &str) -> HashMap<String, (String, Vec<ModifierScope>)> {
+    let mut table = HashMap::new();
+    let mut group: Option<(String, Vec<ModifierScope>)> = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let mut parts = header.split_whitespace();
+            let category = parts.next().unwrap_or(UNKNOWN_CATEGORY).to_string();
+            let scopes = parts
+                .next()
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|scope| match scope {
+                    "country" => Some(ModifierScope::Country),
+                    "state" => Some(ModifierScope::State),
+                    "unit_leader" => Some(ModifierScope::UnitLeader),
+                    _ => None,
+                })
+                .collect();
+            group = Some((category, scopes));
+        } else if let Some(group) = &group {
+            table.insert(line.to_string(), group.clone());
+        }
+    }
+    table
+}
+
+fn infer_value_type(name: &str, description: &str) -> ModifierValueType {
+    let is_bool = ["can_", "no_", "disable_", "enable_", "is_"].iter().any(|p| name.starts_with(p))
+        || ["_enabled", "_disabled", "_allowed"].iter().any(|s| name.ends_with(s));
+    if is_bool {
+        ModifierValueType::Bool
+    } else if name.contains("factor")
+        || ["_efficiency", "_chance", "_research", "_speed"].iter().any(|s| name.ends_with(s))
+        || description.contains('%')
+    {
+        ModifierValueType::Percent
+    } else {
+        ModifierValueType::Flat
+    }
+}
+
+// ==================== 自定义修正 ====================
+
+/// ：解析 `common/modifier_definitions` 中的定义：
+/// `name = { value_type = percentage category = country ... }`。
+fn parse_definitions(content: &str, path: &str, source: RootSource) -> Vec<ModifierInfo> {
+    let doc = clausewitz::parse(content);
+    doc.root
+        .fields()
+        .filter_map(|field| {
+            let block = field.value.as_block()?;
+            let name = field.key.as_str().to_string();
+            Some(ModifierInfo {
+                category: block.get_str("category").unwrap_or("general").to_lowercase(),
+                value_type: match block.get_str("value_type").map(str::to_lowercase).as_deref() {
+                    Some("yes_no") => ModifierValueType::Bool,
+                    Some("number") => ModifierValueType::Flat,
+                    Some(_) => ModifierValueType::Percent,
+                    None => infer_value_type(&name, ""),
+                },
+                scopes: definition_scopes(block),
+                description: String::new(),
+                name,
+                source: match source {
+                    RootSource::Project => ModifierSource::Project,
+                    RootSource::Dependency => ModifierSource::Dependency,
+                    RootSource::Game => ModifierSource::Game,
+                },
+                file: Some(path.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// ：`category` 可以是单个值或列表，缺省或 `all` 时视为任意作用域。
+fn definition_scopes(block: &Block) -> Vec<ModifierScope> {
+    let categories: Vec<String> = block
+        .fields_named("category")
+        .flat_map(|f| match f.value.as_block() {
+            Some(list) => list.scalars().map(|s| s.text.to_lowercase()).collect::<Vec<_>>(),
+            None => f.value.as_str().map(|s| vec![s.to_lowercase()]).unwrap_or_default(),
+        })
+        .collect();
+
+    let mut scopes: Vec<ModifierScope> = Vec::new();
+    for category in &categories {
+        let scope = match category.as_str() {
+            "country" => Some(ModifierScope::Country),
+            "state" => Some(ModifierScope::State),
+            "unit_leader" | "army" | "naval" | "air" => Some(ModifierScope::UnitLeader),
+            _ => None,
+        };
+        if let Some(scope) = scope.filter(|s| !scopes.contains(s)) {
+            scopes.push(scope);
+        }
+    }
+    if scopes.is_empty() || categories.iter().any(|c| c == "all") {
+        vec![ModifierScope::Country, ModifierScope::State, ModifierScope::UnitLeader]
+    } else {
+        scopes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_table() {
+        let find = |name: &str| BUILTIN.iter().find(|m| m.name == name);
+        let pp = find("political_power_factor").expect("political_power_factor");
+        assert_eq!(pp.category, "politics");
+        assert_eq!(pp.value_type, ModifierValueType::Percent);
+        assert_eq!(pp.scopes, vec![ModifierScope::Country]);
+
+        let local = find("local_building_slots").expect("local_building_slots");
+        assert_eq!(local.scopes, vec![ModifierScope::State]);
+        // 占领相关的国家修正不能因名称含 resistance / compliance 被当作州修正
+        let occupied = find("resistance_growth_on_our_occupied_states").expect("resistance_growth_on_our_occupied_states");
+        assert_eq!(occupied.scopes, vec![ModifierScope::Country]);
+        assert!(find("compliance_growth").expect("compliance_growth").scopes.contains(&ModifierScope::Country));
+        // 表中没有的修正作用域未知
+        let unknown = find("spy_attempt_coup_efficiency").expect("spy_attempt_coup_efficiency");
+        assert_eq!((unknown.category.as_str(), unknown.scopes.len()), (UNKNOWN_CATEGORY, 0));
+        let table = parse_scope_table(include_str!("modifier_scopes.txt"));
+        assert!(table.keys().all(|name| find(name).is_some()));
+        assert!(find("tech_prefix").is_none());
+        assert!(find("resource_sabotage_decrease_tooltip").is_none());
+    }
+
+    #[test]
+    fn test_parse_definitions() {
+        let content = "my_mod = { value_type = number category = state }\nmy_flag = { value_type = yes_no }\n";
+        let parsed = parse_definitions(content, "a.txt", RootSource::Project);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].value_type, ModifierValueType::Flat);
+        assert_eq!(parsed[0].scopes, vec![ModifierScope::State]);
+        assert_eq!(parsed[1].value_type, ModifierValueType::Bool);
+        assert_eq!(parsed[1].scopes.len(), 3);
+    }
+}
@@ -0,0 +1,412 @@
+#![deny(clippy::unwrap_used)]
+
+//! 打包前校验
+//!
+//! 对即将打包的文件执行检查：未定义的国家标签、花括号不平衡、
+//! .gfx 引用的贴图缺失、本地化文件缺少 BOM 或语言头错误、非 UTF-8 编码。
+//! 结果以诊断列表返回，由打包流程决定警告还是阻止。
+
+use crate::bracket_matcher;
+use crate::clausewitz;
+use crate::gui_engine::SPRITE_TYPES;
+use crate::load_order::{self, LoadOrderRoot, RootSource};
+use crate::tag_validator;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// ：游戏支持的本地化语言。
+const LOCALISATION_LANGUAGES: [&str; 10] = [
+    "english",
+    "french",
+    "german",
+    "polish",
+    "russian",
+    "spanish",
+    "braz_por",
+    "japanese",
+    "simp_chinese",
+    "korean",
+];
+
+/// ：需要按文本检查编码的扩展名。
+const TEXT_EXTENSIONS: [&str; 7] = ["txt", "yml", "gfx", "gui", "mod", "asset", "csv"];
+
+/// ：校验模式。
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PackageValidation {
+    /// 不校验
+    Off,
+    /// 校验，出现错误时仍然打包
+    #[default]
+    Warn,
+    /// 校验，出现错误时中止打包
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+/// ：单条诊断。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageDiagnostic {
+    pub severity: DiagnosticSeverity,
+    /// 规则名：undefinedTag / unbalancedBraces / missingTexture / localisationBom /
+    /// localisationHeader / encoding / tagsUnavailable
+    pub rule: String,
+    /// 相对于项目根目录的路径
+    pub file: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// ：校验所需的上下文。
+pub(crate) struct ValidationContext {
+    pub project_root: String,
+    pub game_root: Option<String>,
+    pub dependency_roots: Option<Vec<String>>,
+}
+
+/// ：对待打包文件 `(相对路径, 内容)` 执行全部检查，结果按文件与行号排序。
+pub(crate) fn validate_files(files: &[(&str, &[u8])], ctx: &ValidationContext) -> Vec<PackageDiagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let tags = tag_validator::ensure_tag_cache(
+        Some(ctx.project_root.clone()),
+        ctx.game_root.clone(),
+        ctx.dependency_roots.clone(),
+    );
+    if tags.is_empty() {
+        diagnostics.push(PackageDiagnostic {
+            severity: DiagnosticSeverity::Warning,
+            rule: "tagsUnavailable".to_string(),
+            file: String::new(),
+            line: None,
+            message: "未能加载国家标签，已跳过标签校验".to_string(),
+        });
+    }
+
+    let roots = load_order::load_order(
+        Some(&ctx.project_root),
+        ctx.dependency_roots.clone(),
+        ctx.game_root.as_deref(),
+    );
+    let packed: HashSet<String> = files.iter().map(|(rel, _)| rel.to_lowercase()).collect();
+
+    let per_file: Vec<Vec<PackageDiagnostic>> = files
+        .par_iter()
+        .map(|(relative, bytes)| validate_file(relative, bytes, &tags, &roots, &packed))
+        .collect();
+    diagnostics.extend(per_file.into_iter().flatten());
+    diagnostics.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+    diagnostics
+}
+
+/// ：是否存在会阻止打包的错误。
+pub(crate) fn has_errors(diagnostics: &[PackageDiagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Error)
+}
+
+fn validate_file(
+    relative: &str,
+    bytes: &[u8],
+    tags: &HashSet<String>,
+    roots: &[LoadOrderRoot],
+    packed: &HashSet<String>,
+) -> Vec<PackageDiagnostic> {
+    let mut out = Vec::new();
+    let lower = relative.to_lowercase();
+    let Some(ext) = lower.rsplit_once('.').map(|(_, e)| e) else {
+        return out;
+    };
+    if !TEXT_EXTENSIONS.contains(&ext) {
+        return out;
+    }
+
+    let diagnostic = |severity, rule: &str, line: Option<usize>, message: String| PackageDiagnostic {
+        severity,
+        rule: rule.to_string(),
+        file: relative.to_string(),
+        line,
+        message,
+    };
+
+    let content = match std::str::from_utf8(bytes) {
+        Ok(c) => c,
+        Err(e) => {
+            let encoding = crate::decode_text_bytes(bytes)
+                .map(|(_, enc)| enc.name().to_string())
+                .unwrap_or_else(|| "未知编码".to_string());
+            // 本地化文件必须是 UTF-8，脚本文件只给出警告
+            let severity = if ext == "yml" { DiagnosticSeverity::Error } else { DiagnosticSeverity::Warning };
+            out.push(diagnostic(
+                severity,
+                "encoding",
+                None,
+                format!("文件不是 UTF-8 编码（检测为 {}，首个无效字节位于 {}）", encoding, e.valid_up_to()),
+            ));
+            return out;
+        }
+    };
+
+    if ext == "yml" && lower.starts_with("localisation/") {
+        out.extend(check_localisation(relative, content));
+        return out;
+    }
+
+    if matches!(ext, "txt" | "gfx" | "gui") {
+        out.extend(check_braces(content).into_iter().map(|(line, message)| {
+            diagnostic(DiagnosticSeverity::Error, "unbalancedBraces", Some(line), message)
+        }));
+    }
+
+    let is_script = ext == "txt"
+        && (lower.starts_with("common/") || lower.starts_with("events/") || lower.starts_with("history/"));
+    if is_script && !tags.is_empty() {
+        out.extend(tag_validator::validate_tags_internal(content, tags).into_iter().map(|e| {
+            diagnostic(DiagnosticSeverity::Error, "undefinedTag", Some(e.line), e.message)
+        }));
+    }
+
+    if ext == "gfx" {
+        out.extend(check_textures(content, roots, packed).into_iter().map(|(line, message)| {
+            diagnostic(DiagnosticSeverity::Error, "missingTexture", Some(line), message)
+        }));
+    }
+    out
+}
+
+/// ：用 bracket_matcher 检查花括号；注释与字符串中的字符先替换为空格，保持字符位置不变。
+fn check_braces(content: &str) -> Vec<(usize, String)> {
+    let masked = mask_comments_and_strings(content);
+    let chars: Vec<char> = masked.chars().collect();
+    let result = bracket_matcher::find_bracket_matches(&masked);
+
+    let mut line_of_char = Vec::with_capacity(chars.len());
+    let mut line = 1;
+    for ch in &chars {
+        line_of_char.push(line);
+        if *ch == '\n' {
+            line += 1;
+        }
+    }
+
+    result
+        .unmatched
+        .iter()
+        .filter_map(|&pos| {
+            let ch = *chars.get(pos)?;
+            let message = match ch {
+                '{' => "未闭合的 {",
+                '}' => "多余的 }",
+                _ => return None,
+            };
+            Some((line_of_char[pos], message.to_string()))
+        })
+        .collect()
+}
+
+fn mask_comments_and_strings(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut escaped = false;
+    for ch in content.chars() {
+        if ch == '\n' {
+            in_comment = false;
+            in_string = false;
+            out.push(ch);
+            continue;
+        }
+        if in_comment {
+            out.push(' ');
+        } else if in_string {
+            if !escaped && ch == '"' {
+                in_string = false;
+            }
+            escaped = !escaped && ch == '\\';
+            out.push(' ');
+        } else if ch == '#' {
+            in_comment = true;
+            out.push(' ');
+        } else if ch == '"' {
+            in_string = true;
+            escaped = false;
+            out.push(' ');
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// ：sprite 引用的贴图须在打包内容、依赖项或游戏目录中存在。
+fn check_textures(content: &str, roots: &[LoadOrderRoot], packed: &HashSet<String>) -> Vec<(usize, String)> {
+    let doc = clausewitz::parse(content);
+    let lines = clausewitz::LineIndex::new(content);
+    let mut out = Vec::new();
+    doc.root.visit_fields(&mut |field, _| {
+        if !SPRITE_TYPES.iter().any(|k| field.key_is(k)) {
+            return;
+        }
+        let Some(block) = field.value.as_block() else { return };
+        let Some(texture) = block.get_str("texturefile") else { return };
+        let texture = texture.replace('\\', "/");
+        if texture.is_empty() || texture_exists(&texture, roots, packed) {
+            return;
+        }
+        let name = block.get_str("name").unwrap_or("?");
+        out.push((lines.line(field.span.start), format!("{} 引用的贴图不存在: {}", name, texture)));
+    });
+    out
+}
+
+fn texture_exists(texture: &str, roots: &[LoadOrderRoot], packed: &HashSet<String>) -> bool {
+    if packed.contains(&texture.to_lowercase()) {
+        return true;
+    }
+    let directory = texture.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+    roots.iter().enumerate().any(|(index, root)| {
+        // 项目本身以打包内容为准
+        root.source != RootSource::Project
+            && load_order::blanked_by(roots, index, directory).is_none()
+            && Path::new(&root.path).join(texture).is_file()
+    })
+}
+
+/// ：本地化文件须带 UTF-8 BOM，首个有效行为与文件名一致的 `l_<language>:`。
+fn check_localisation(relative: &str, content: &str) -> Vec<PackageDiagnostic> {
+    let mut out = Vec::new();
+    let diagnostic = |rule: &str, line: Option<usize>, message: String| PackageDiagnostic {
+        severity: DiagnosticSeverity::Error,
+        rule: rule.to_string(),
+        file: relative.to_string(),
+        line,
+        message,
+    };
+
+    if !content.starts_with('\u{feff}') {
+        out.push(diagnostic("localisationBom", Some(1), "本地化文件缺少 UTF-8 BOM，游戏将忽略该文件".to_string()));
+    }
+
+    let file_language = relative
+        .to_lowercase()
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.strip_suffix(".yml"))
+        .and_then(|stem| LOCALISATION_LANGUAGES.iter().find(|lang| stem.ends_with(&format!("_l_{}", lang))))
+        .map(|lang| lang.to_string());
+
+    let header = content
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .find(|(_, l)| !l.is_empty() && !l.starts_with('#'));
+    let header_language = header.and_then(|(_, l)| {
+        l.split('#')
+            .next()
+            .map(str::trim)
+            .and_then(|h| h.strip_prefix("l_"))
+            .and_then(|h| h.strip_suffix(':'))
+            .map(|h| h.to_string())
+    });
+
+    match (header, header_language) {
+        (None, _) => out.push(diagnostic("localisationHeader", Some(1), "本地化文件为空，缺少 l_<language>: 头".to_string())),
+        (Some((line, text)), None) => out.push(diagnostic(
+            "localisationHeader",
+            Some(line),
+            format!("首行应为 l_<language>:，实际为 {}", text),
+        )),
+        (Some((line, _)), Some(lang)) => {
+            if !LOCALISATION_LANGUAGES.contains(&lang.as_str()) {
+                out.push(diagnostic("localisationHeader", Some(line), format!("未知的本地化语言: l_{}", lang)));
+            } else if let Some(expected) = file_language.filter(|f| *f != lang) {
+                out.push(diagnostic(
+                    "localisationHeader",
+                    Some(line),
+                    format!("语言头 l_{} 与文件名中的 l_{} 不一致", lang, expected),
+                ));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_braces_ignores_comments_and_strings() {
+        assert!(check_braces("a = { b = \"}\" } # {").is_empty());
+        assert_eq!(check_braces("a = {\n b = { }\n"), vec![(1, "未闭合的 {".to_string())]);
+        assert_eq!(check_braces("a = { }\n}\n"), vec![(2, "多余的 }".to_string())]);
+    }
+
+    #[test]
+    fn test_check_localisation() {
+        let ok = "\u{feff}l_english:\n KEY:0 \"x\"\n";
+        assert!(check_localisation("localisation/english/a_l_english.yml", ok).is_empty());
+
+        let rules: Vec<String> = check_localisation("localisation/a_l_english.yml", "l_german:\n")
+            .into_iter()
+            .map(|d| d.rule)
+            .collect();
+        assert_eq!(rules, vec!["localisationBom", "localisationHeader"]);
+    }
+
+    #[test]
+    fn test_check_textures() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let dep = dir.path().join("dep");
+        std::fs::create_dir_all(dep.join("gfx/interface")).expect("mkdir");
+        std::fs::write(dep.join("gfx/interface/dep.dds"), "").expect("write");
+        let roots = vec![
+            LoadOrderRoot {
+                path: dir.path().join("project").to_string_lossy().replace('\\', "/"),
+                name: "project".to_string(),
+                source: RootSource::Project,
+                priority: None,
+                replace_paths: Vec::new(),
+            },
+            LoadOrderRoot {
+                path: dep.to_string_lossy().replace('\\', "/"),
+                name: "dep".to_string(),
+                source: RootSource::Dependency,
+                priority: Some(0),
+                replace_paths: Vec::new(),
+            },
+        ];
+        let packed: HashSet<String> = ["gfx/interface/packed.dds".to_string()].into_iter().collect();
+
+        let content = "spriteTypes = {\n\tspriteType = { name = GFX_packed texturefile = \"gfx\\interface\\Packed.dds\" }\n\tspriteType = { name = GFX_dep texturefile = gfx/interface/dep.dds }\n\tspriteType = { name = GFX_missing texturefile = gfx/interface/missing.dds }\n}\n";
+        assert_eq!(
+            check_textures(content, &roots, &packed),
+            vec![(4, "GFX_missing 引用的贴图不存在: gfx/interface/missing.dds".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_encoding_rule() {
+        let (gbk, _, _) = encoding_rs::GBK.encode("l_english:\n KEY:0 \"德国\"\n");
+        let none = HashSet::new();
+        let severity = |relative: &str| {
+            let diagnostics = validate_file(relative, &gbk, &none, &[], &none);
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(diagnostics[0].rule, "encoding");
+            diagnostics[0].severity
+        };
+        // 本地化文件必须是 UTF-8，脚本只给出警告
+        assert_eq!(severity("localisation/a_l_english.yml"), DiagnosticSeverity::Error);
+        assert_eq!(severity("common/ideas/a.txt"), DiagnosticSeverity::Warning);
+    }
+}
@@ -0,0 +1,1068 @@
+#![deny(clippy::unwrap_used)]
+
+//! 项目打包
+//!
+//! 打包配置（profile）保存在 project.json 的 `package_profiles` 中，
+//! 每个配置包含 include/exclude 通配符、输出布局与可选的版本号递增。
+//! 每次打包都会在输出旁写入 manifest，记录每个文件的大小与 SHA-256。
+//!
+//! 打包是增量的：缓存目录下按项目保存文件哈希索引（大小与修改时间不变时不再读取），
+//! 以及按内容哈希命名的已压缩条目；未变化的文件直接复制压缩数据，新文件用 rayon 并行压缩。
+//! 进度通过 `package-progress` 事件推送给前端，可随时取消。
+
+use crate::mod_descriptor;
+use crate::package_validation::{self, PackageDiagnostic, PackageValidation, ValidationContext};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+use walkdir::WalkDir;
+
+/// ：前端监听的进度事件名。
+const PROGRESS_EVENT: &str = "package-progress";
+
+/// ：扩展名属于文本时读取内容参与校验。
+const TEXT_EXTENSIONS: [&str; 7] = ["txt", "yml", "gfx", "gui", "mod", "asset", "csv"];
+
+/// ：进行中的打包任务的取消标记，key 为前端生成的 pack id，由 cancel_packaging 设置。
+static CANCEL_TOKENS: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// ：未配置时使用的排除规则。
+const DEFAULT_EXCLUDES: [&str; 14] = [
+    ".git/**",
+    ".idea/**",
+    ".vscode/**",
+    ".windsurf/**",
+    "node_modules/**",
+    "target/**",
+    "package/**",
+    "notes/**",
+    "project.json",
+    "dependencies.json",
+    "**/*.psd",
+    "**/*.xcf",
+    "**/*.md",
+    "**/Thumbs.db",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageOptions {
+    /// 前端生成的任务 id，取消时传给 cancel_packaging
+    pub pack_id: String,
+    pub project_path: String,
+    pub output_name: String,
+    pub exclude_dependencies: bool,
+    /// 使用的打包配置名称，缺省时使用第一个配置或内置默认配置
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// 覆盖配置中的校验模式
+    #[serde(default)]
+    pub validation: Option<PackageValidation>,
+    /// 校验标签与贴图时使用的游戏目录
+    #[serde(default)]
+    pub game_root: Option<String>,
+    #[serde(default)]
+    pub dependency_roots: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageResult {
+    success: bool,
+    message: String,
+    output_path: Option<String>,
+    file_size: Option<u64>,
+    file_count: usize,
+    manifest_path: Option<String>,
+    /// 打包时使用的版本号（递增后）
+    version: Option<String>,
+    /// 打包前校验的结果
+    diagnostics: Vec<PackageDiagnostic>,
+}
+
+/// ：输出布局。
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PackageLayout {
+    /// `package/<name>.zip`，文件位于压缩包根目录
+    #[default]
+    Zip,
+    /// `package/<name>/`，可直接作为创意工坊上传目录
+    Workshop,
+    /// `package/<name>.mod` + `package/<name>/`，可直接放入文档 mod/ 目录
+    ModFolder,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum VersionBump {
+    Major,
+    Minor,
+    Patch,
+}
+
+/// ：打包配置。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PackageProfile {
+    pub name: String,
+    /// 为空时包含全部文件
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub layout: PackageLayout,
+    pub version_bump: Option<VersionBump>,
+    /// 输出名称（不含扩展名），为空时使用打包对话框中填写的名称
+    pub output_name: Option<String>,
+    pub validation: PackageValidation,
+}
+
+impl Default for PackageProfile {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            include: Vec::new(),
+            exclude: DEFAULT_EXCLUDES.iter().map(|s| s.to_string()).collect(),
+            layout: PackageLayout::Zip,
+            version_bump: None,
+            output_name: None,
+            validation: PackageValidation::Warn,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageProfilesResult {
+    pub success: bool,
+    pub message: String,
+    pub profiles: Vec<PackageProfile>,
+}
+
+/// ：manifest 中的单个文件。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PackageManifest<'a> {
+    name: &'a str,
+    profile: &'a str,
+    layout: PackageLayout,
+    version: Option<&'a str>,
+    created_at: String,
+    total_size: u64,
+    files: &'a [ManifestEntry],
+}
+
+/// ：待打包的文件。内容按需读取，哈希优先取自缓存索引。
+struct PackFile {
+    relative: String,
+    path: PathBuf,
+    size: u64,
+    modified: Option<u128>,
+    sha256: String,
+    /// 与上次打包相比内容有变化（或没有缓存）
+    changed: bool,
+}
+
+/// ：缓存索引中的单个文件。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedFile {
+    size: u64,
+    modified: Option<u128>,
+    sha256: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PackageCacheIndex {
+    files: HashMap<String, CachedFile>,
+}
+
+/// ：打包进度。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageProgress {
+    /// scan / hash / validate / compress / write / done
+    pub stage: String,
+    pub current: usize,
+    pub total: usize,
+    pub message: String,
+}
+
+/// ：向前端推送进度，约每 1% 推送一次；同时携带本任务的取消标记。
+struct Progress {
+    app: Option<AppHandle>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Progress {
+    fn check_cancelled(&self) -> Result<(), String> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            Err("打包已取消".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn emit(&self, stage: &str, current: usize, total: usize, message: &str) {
+        let Some(app) = &self.app else { return };
+        let step = (total / 100).max(1);
+        if current != 0 && current != total && !current.is_multiple_of(step) {
+            return;
+        }
+        let payload = PackageProgress {
+            stage: stage.to_string(),
+            current,
+            total,
+            message: message.to_string(),
+        };
+        if let Err(e) = app.emit(PROGRESS_EVENT, payload) {
+            println!("[packaging] 推送进度失败: {}", e);
+        }
+    }
+}
+
+/// ：登记在 CANCEL_TOKENS 中的取消标记，任务结束时随之注销。
+struct CancelToken {
+    pack_id: String,
+    flag: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    fn register(pack_id: &str) -> Self {
+        let flag = Arc::new(AtomicBool::new(false));
+        if let Ok(mut tokens) = CANCEL_TOKENS.lock() {
+            tokens.insert(pack_id.to_string(), flag.clone());
+        }
+        CancelToken {
+            pack_id: pack_id.to_string(),
+            flag,
+        }
+    }
+}
+
+impl Drop for CancelToken {
+    fn drop(&mut self) {
+        if let Ok(mut tokens) = CANCEL_TOKENS.lock() {
+            // 同一 id 已被新任务占用时保留新任务的标记
+            if tokens.get(&self.pack_id).is_some_and(|flag| Arc::ptr_eq(flag, &self.flag)) {
+                tokens.remove(&self.pack_id);
+            }
+        }
+    }
+}
+
+/// ：读取项目的打包配置，未配置时返回内置默认配置。
+#[tauri::command]
+pub fn load_package_profiles(project_path: String) -> PackageProfilesResult {
+    match read_profiles(Path::new(&project_path)) {
+        Ok(profiles) => PackageProfilesResult {
+            success: true,
+            message: format!("{} 个打包配置", profiles.len()),
+            profiles,
+        },
+        Err(e) => PackageProfilesResult {
+            success: false,
+            message: e,
+            profiles: vec![PackageProfile::default()],
+        },
+    }
+}
+
+/// ：保存打包配置到 project.json。
+#[tauri::command]
+pub fn save_package_profiles(project_path: String, profiles: Vec<PackageProfile>) -> PackageProfilesResult {
+    let fail = |message: String| PackageProfilesResult {
+        success: false,
+        message,
+        profiles: Vec::new(),
+    };
+    if let Some(bad) = profiles.iter().find(|p| p.name.trim().is_empty()) {
+        return fail(format!("打包配置名称不能为空: {:?}", bad));
+    }
+    for profile in &profiles {
+        if let Err(e) = build_globset(&profile.include).and(build_globset(&profile.exclude)) {
+            return fail(format!("配置 {} 的通配符无效: {}", profile.name, e));
+        }
+    }
+
+    let value = match serde_json::to_value(&profiles) {
+        Ok(v) => v,
+        Err(e) => return fail(format!("序列化打包配置失败: {}", e)),
+    };
+    if let Err(e) = update_project_json(Path::new(&project_path), |obj| {
+        obj.insert("package_profiles".to_string(), value);
+    }) {
+        return fail(e);
+    }
+
+    PackageProfilesResult {
+        success: true,
+        message: "打包配置已保存".to_string(),
+        profiles,
+    }
+}
+
+/// 打包项目 Tauri 命令，在后台线程执行
+#[tauri::command]
+pub async fn pack_project(app: AppHandle, opts: PackageOptions) -> PackageResult {
+    tauri::async_runtime::spawn_blocking(move || {
+        let token = CancelToken::register(&opts.pack_id);
+        let progress = Progress {
+            app: Some(app),
+            cancelled: token.flag.clone(),
+        };
+        package_project_impl(opts, &progress)
+    })
+        .await
+        .unwrap_or_else(|e| PackageResult {
+            success: false,
+            message: format!("打包线程异常: {}", e),
+            ..Default::default()
+        })
+}
+
+/// 取消指定的打包任务，任务不存在（未开始或已结束）时返回 false
+#[tauri::command]
+pub fn cancel_packaging(pack_id: String) -> bool {
+    let Ok(tokens) = CANCEL_TOKENS.lock() else {
+        return false;
+    };
+    match tokens.get(&pack_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// 打包项目的内部实现
+fn package_project_impl(opts: PackageOptions, progress: &Progress) -> PackageResult {
+    let fail = |message: String| PackageResult {
+        success: false,
+        message,
+        ..Default::default()
+    };
+
+    println!("开始打包项目: {}", opts.project_path);
+    let project = Path::new(&opts.project_path);
+    if !project.is_dir() {
+        return fail("项目路径不存在".to_string());
+    }
+
+    let profile = match select_profile(project, opts.profile.as_deref()) {
+        Ok(p) => p,
+        Err(e) => return fail(e),
+    };
+    let name = match output_stem(profile.output_name.as_deref().unwrap_or(&opts.output_name)) {
+        Ok(n) => n,
+        Err(e) => return fail(e),
+    };
+
+    let dependency_paths = if opts.exclude_dependencies {
+        project_dependency_paths(project)
+    } else {
+        Vec::new()
+    };
+    let cache_dir = crate::get_named_cache_dir("package").join(crate::hash_string(&opts.project_path));
+    if let Err(e) = fs::create_dir_all(&cache_dir) {
+        return fail(format!("创建打包缓存目录失败: {}", e));
+    }
+    let cache_index = load_cache_index(&cache_dir);
+
+    progress.emit("scan", 0, 0, "正在扫描文件...");
+    let mut files = match collect_files(project, &profile, &dependency_paths)
+        .and_then(|f| hash_files(f, &cache_index, progress))
+    {
+        Ok(f) => f,
+        Err(e) => return fail(e),
+    };
+    if files.is_empty() {
+        return fail("没有符合打包配置的文件".to_string());
+    }
+
+    let validation = opts.validation.unwrap_or(profile.validation);
+    let diagnostics = if validation == PackageValidation::Off {
+        Vec::new()
+    } else {
+        progress.emit("validate", 0, 0, "正在校验...");
+        // 只有文本文件需要内容，二进制文件仅参与贴图存在性检查
+        let contents: Result<Vec<Vec<u8>>, String> = files
+            .par_iter()
+            .map(|f| {
+                if !is_text_file(&f.relative) {
+                    return Ok(Vec::new());
+                }
+                fs::read(&f.path).map_err(|e| format!("读取文件失败 {}: {}", f.relative, e))
+            })
+            .collect();
+        let contents = match contents {
+            Ok(c) => c,
+            Err(e) => return fail(e),
+        };
+        let inputs: Vec<(&str, &[u8])> = files
+            .iter()
+            .zip(&contents)
+            .map(|(f, bytes)| (f.relative.as_str(), bytes.as_slice()))
+            .collect();
+        package_validation::validate_files(
+            &inputs,
+            &ValidationContext {
+                project_root: opts.project_path.clone(),
+                game_root: opts.game_root.clone(),
+                dependency_roots: opts.dependency_roots.clone(),
+            },
+        )
+    };
+    if let Err(e) = progress.check_cancelled() {
+        return fail(e);
+    }
+    if validation == PackageValidation::Block && package_validation::has_errors(&diagnostics) {
+        let errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == package_validation::DiagnosticSeverity::Error)
+            .count();
+        return PackageResult {
+            success: false,
+            message: format!("校验发现 {} 个错误，已中止打包", errors),
+            diagnostics,
+            ..Default::default()
+        };
+    }
+
+    // 新版本号只写进打包产物，打包成功后才写回项目
+    let bumped = profile
+        .version_bump
+        .map(|bump| bump_version(&read_project_version(project).unwrap_or_else(|| "1.0.0".to_string()), bump));
+    if let Some(next) = &bumped {
+        if let Err(e) = stage_descriptor(&mut files, &cache_dir, next) {
+            return fail(e);
+        }
+    }
+    let version = bumped.clone().or_else(|| read_project_version(project));
+
+    let package_dir = project.join("package");
+    if let Err(e) = fs::create_dir_all(&package_dir) {
+        return fail(format!("创建 package 目录失败: {}", e));
+    }
+
+    let written = match profile.layout {
+        PackageLayout::Zip => write_zip(&package_dir.join(format!("{}.zip", name)), &files, &cache_dir, progress),
+        PackageLayout::Workshop => write_folder(&package_dir.join(&name), &files, progress),
+        PackageLayout::ModFolder => write_folder(&package_dir.join(&name), &files, progress)
+            .and_then(|out| write_mod_file(project, &package_dir, &name, bumped.as_deref()).map(|_| out)),
+    };
+    let output_path = match written {
+        Ok(p) => p,
+        Err(e) => return fail(e),
+    };
+    save_cache_index(&cache_dir, &files);
+
+    let entries: Vec<ManifestEntry> = files
+        .iter()
+        .map(|f| ManifestEntry {
+            path: f.relative.clone(),
+            size: f.size,
+            sha256: f.sha256.clone(),
+        })
+        .collect();
+    let total_size: u64 = entries.iter().map(|e| e.size).sum();
+    let manifest_path = package_dir.join(format!("{}.manifest.json", name));
+    let manifest = PackageManifest {
+        name: &name,
+        profile: &profile.name,
+        layout: profile.layout,
+        version: version.as_deref(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        total_size,
+        files: &entries,
+    };
+    let manifest_written = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| e.to_string())
+        .and_then(|s| fs::write(&manifest_path, s).map_err(|e| e.to_string()));
+    if let Err(e) = manifest_written {
+        return fail(format!("写入 manifest 失败: {}", e));
+    }
+    if let Some(next) = &bumped {
+        if let Err(e) = write_project_version(project, next) {
+            return fail(format!("打包已完成，但写回版本号失败: {}", e));
+        }
+    }
+
+    let file_size = match profile.layout {
+        PackageLayout::Zip => fs::metadata(&output_path).ok().map(|m| m.len()),
+        _ => Some(total_size),
+    };
+    println!("打包完成: {} 个文件", files.len());
+
+    progress.emit("done", files.len(), files.len(), "打包完成");
+    let changed = files.iter().filter(|f| f.changed).count();
+    let mut message = format!("打包成功！已打包 {} 个文件（{} 个有变化）", files.len(), changed);
+    if !diagnostics.is_empty() {
+        message.push_str(&format!("，校验发现 {} 个问题", diagnostics.len()));
+    }
+
+    PackageResult {
+        success: true,
+        message,
+        output_path: Some(output_path.to_string_lossy().to_string()),
+        file_size,
+        file_count: files.len(),
+        manifest_path: Some(manifest_path.to_string_lossy().to_string()),
+        version,
+        diagnostics,
+    }
+}
+
+fn read_project_json(project: &Path) -> Option<serde_json::Value> {
+    let content = fs::read_to_string(project.join("project.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// ：修改 project.json 的顶层字段并写回，保留其它字段。
+fn update_project_json(
+    project: &Path,
+    update: impl FnOnce(&mut serde_json::Map<String, serde_json::Value>),
+) -> Result<(), String> {
+    let config_path = project.join("project.json");
+    let content = fs::read_to_string(&config_path).map_err(|e| format!("读取 project.json 失败: {}", e))?;
+    let mut json: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| format!("解析 project.json 失败: {}", e))?;
+    let obj = json.as_object_mut().ok_or_else(|| "project.json 格式无效".to_string())?;
+    update(obj);
+    let out = serde_json::to_string_pretty(&json).map_err(|e| format!("序列化 project.json 失败: {}", e))?;
+    crate::write_text_file(&config_path, &out, encoding_rs::UTF_8)
+}
+
+fn read_profiles(project: &Path) -> Result<Vec<PackageProfile>, String> {
+    let Some(json) = read_project_json(project) else {
+        return Ok(vec![PackageProfile::default()]);
+    };
+    match json.get("package_profiles") {
+        Some(value) => {
+            let profiles: Vec<PackageProfile> =
+                serde_json::from_value(value.clone()).map_err(|e| format!("解析打包配置失败: {}", e))?;
+            if profiles.is_empty() {
+                Ok(vec![PackageProfile::default()])
+            } else {
+                Ok(profiles)
+            }
+        }
+        None => Ok(vec![PackageProfile::default()]),
+    }
+}
+
+fn select_profile(project: &Path, name: Option<&str>) -> Result<PackageProfile, String> {
+    let profiles = read_profiles(project)?;
+    match name.filter(|n| !n.trim().is_empty()) {
+        Some(name) => profiles
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("找不到打包配置: {}", name)),
+        None => Ok(profiles.into_iter().next().unwrap_or_default()),
+    }
+}
+
+/// ：输出名称去掉 `.zip` 后缀，不允许包含路径分隔符。
+fn output_stem(name: &str) -> Result<String, String> {
+    let trimmed = name.trim();
+    let stem = if trimmed.to_lowercase().ends_with(".zip") {
+        &trimmed[..trimmed.len() - 4]
+    } else {
+        trimmed
+    };
+    if stem.is_empty() || stem == "." || stem == ".." {
+        return Err("输出名称不能为空".to_string());
+    }
+    if stem.chars().any(|c| matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')) {
+        return Err("输出名称包含非法字符".to_string());
+    }
+    Ok(stem.to_string())
+}
+
+fn build_globset(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let pattern = pattern.trim().replace('\\', "/");
+        if pattern.is_empty() {
+            continue;
+        }
+        builder.add(Glob::new(&pattern).map_err(|e| format!("{}: {}", pattern, e))?);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// ：project.json 中记录的全部依赖项路径，位于项目内部的依赖不参与打包。
+fn project_dependency_paths(project: &Path) -> Vec<PathBuf> {
+    read_project_json(project)
+        .and_then(|json| {
+            json.get("dependencies").and_then(|v| v.as_array()).map(|deps| {
+                deps.iter()
+                    .filter_map(|d| d.get("path").and_then(|p| p.as_str()))
+                    .map(PathBuf::from)
+                    .collect()
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// ：按配置收集文件，结果按相对路径排序以保证输出可复现。
+fn collect_files(
+    project: &Path,
+    profile: &PackageProfile,
+    dependency_paths: &[PathBuf],
+) -> Result<Vec<PackFile>, String> {
+    let include = build_globset(&profile.include)?;
+    let exclude = build_globset(&profile.exclude)?;
+    let relative_of = |path: &Path| -> Option<String> {
+        path.strip_prefix(project)
+            .ok()
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+    };
+
+    let mut files = Vec::new();
+    let walker = WalkDir::new(project)
+        .follow_links(false)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            if e.depth() == 0 {
+                return true;
+            }
+            if dependency_paths.iter().any(|d| e.path().starts_with(d)) {
+                return false;
+            }
+            // 目录本身被 `dir/**` 排除时整体跳过
+            match relative_of(e.path()) {
+                Some(rel) if e.file_type().is_dir() => !exclude.is_match(format!("{}/", rel)),
+                _ => true,
+            }
+        });
+    for entry in walker.filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Some(relative) = relative_of(entry.path()) else { continue };
+        if exclude.is_match(&relative) || (!include.is_empty() && !include.is_match(&relative)) {
+            continue;
+        }
+        let metadata = entry.metadata().map_err(|e| format!("读取文件信息失败 {}: {}", relative, e))?;
+        files.push(PackFile {
+            relative,
+            path: entry.path().to_path_buf(),
+            size: metadata.len(),
+            modified: metadata.modified().ok().and_then(system_time_nanos),
+            sha256: String::new(),
+            changed: true,
+        });
+    }
+    files.sort_by(|a, b| a.relative.cmp(&b.relative));
+    Ok(files)
+}
+
+fn system_time_nanos(time: SystemTime) -> Option<u128> {
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_nanos())
+}
+
+fn is_text_file(relative: &str) -> bool {
+    relative
+        .rsplit_once('.')
+        .map(|(_, ext)| TEXT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn load_cache_index(cache_dir: &Path) -> PackageCacheIndex {
+    fs::read(cache_dir.join("index.json"))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache_index(cache_dir: &Path, files: &[PackFile]) {
+    let index = PackageCacheIndex {
+        files: files
+            .iter()
+            .map(|f| {
+                let cached = CachedFile {
+                    size: f.size,
+                    modified: f.modified,
+                    sha256: f.sha256.clone(),
+                };
+                (f.relative.clone(), cached)
+            })
+            .collect(),
+    };
+    match serde_json::to_vec(&index) {
+        Ok(bytes) => {
+            if let Err(e) = fs::write(cache_dir.join("index.json"), bytes) {
+                println!("[packaging] 写入打包缓存索引失败: {}", e);
+            }
+        }
+        Err(e) => println!("[packaging] 序列化打包缓存索引失败: {}", e),
+    }
+}
+
+/// ：计算文件哈希；大小与修改时间都未变化时直接沿用缓存中的哈希。
+fn hash_files(
+    mut files: Vec<PackFile>,
+    cache: &PackageCacheIndex,
+    progress: &Progress,
+) -> Result<Vec<PackFile>, String> {
+    let total = files.len();
+    let done = AtomicUsize::new(0);
+    files.par_iter_mut().try_for_each(|f| {
+        progress.check_cancelled()?;
+        let cached = cache
+            .files
+            .get(&f.relative)
+            .filter(|c| c.size == f.size && c.modified.is_some() && c.modified == f.modified);
+        match cached {
+            Some(c) => {
+                f.sha256 = c.sha256.clone();
+                f.changed = false;
+            }
+            None => {
+                let bytes = fs::read(&f.path).map_err(|e| format!("读取文件失败 {}: {}", f.relative, e))?;
+                f.sha256 = format!("{:x}", Sha256::digest(&bytes));
+                f.changed = cache.files.get(&f.relative).map(|c| c.sha256 != f.sha256).unwrap_or(true);
+            }
+        }
+        let current = done.fetch_add(1, Ordering::Relaxed) + 1;
+        progress.emit("hash", current, total, &f.relative);
+        Ok::<(), String>(())
+    })?;
+    Ok(files)
+}
+
+/// ：把单个文件压缩成只含一个条目（以哈希命名）的内存 zip，供之后原样复制。
+fn compress_entry(file: &PackFile, options: zip::write::SimpleFileOptions) -> Result<Vec<u8>, String> {
+    let bytes = fs::read(&file.path).map_err(|e| format!("读取文件失败 {}: {}", file.relative, e))?;
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    writer
+        .start_file(file.sha256.as_str(), options)
+        .and_then(|_| writer.write_all(&bytes).map_err(zip::result::ZipError::from))
+        .map_err(|e| format!("压缩文件失败 {}: {}", file.relative, e))?;
+    writer
+        .finish()
+        .map(|cursor| cursor.into_inner())
+        .map_err(|e| format!("压缩文件失败 {}: {}", file.relative, e))
+}
+
+/// ：写入 zip，条目顺序与时间戳固定，相同输入得到相同的压缩包。
+///
+/// 压缩后的条目按内容哈希保存在缓存目录的 blobs.zip 中：命中的条目直接复制压缩数据，
+/// 其余文件分批并行压缩后追加。最终输出从 blobs.zip 按相对路径重命名复制。
+fn write_zip(output: &Path, files: &[PackFile], cache_dir: &Path, progress: &Progress) -> Result<PathBuf, String> {
+    use zip::write::SimpleFileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .compression_level(Some(6))
+        .last_modified_time(zip::DateTime::default());
+
+    let blobs_path = cache_dir.join("blobs.zip");
+    let mut old_blobs = fs::File::open(&blobs_path).ok().and_then(|f| ZipArchive::new(f).ok());
+    let cached: HashSet<String> = old_blobs
+        .as_ref()
+        .map(|z| z.file_names().map(|n| n.to_string()).collect())
+        .unwrap_or_default();
+
+    // 每个内容哈希只需要一份
+    let mut seen: HashSet<&str> = HashSet::new();
+    let unique: Vec<&PackFile> = files.iter().filter(|f| seen.insert(f.sha256.as_str())).collect();
+    let (reused, missing): (Vec<&PackFile>, Vec<&PackFile>) =
+        unique.into_iter().partition(|f| cached.contains(&f.sha256));
+
+    let tmp_blobs_path = cache_dir.join("blobs.zip.tmp");
+    let tmp_file = fs::File::create(&tmp_blobs_path).map_err(|e| format!("创建打包缓存失败: {}", e))?;
+    let mut blobs = ZipWriter::new(tmp_file);
+
+    if let Some(old) = old_blobs.as_mut() {
+        for f in &reused {
+            let entry = old.by_name(&f.sha256).map_err(|e| format!("读取打包缓存失败: {}", e))?;
+            blobs.raw_copy_file(entry).map_err(|e| format!("写入打包缓存失败: {}", e))?;
+        }
+    }
+    drop(old_blobs);
+
+    let total = missing.len();
+    let mut done = 0usize;
+    let chunk_size = rayon::current_num_threads().max(1) * 4;
+    for chunk in missing.chunks(chunk_size) {
+        progress.check_cancelled()?;
+        let compressed: Vec<Vec<u8>> = chunk
+            .par_iter()
+            .map(|f| compress_entry(f, options))
+            .collect::<Result<_, _>>()?;
+        for (f, bytes) in chunk.iter().zip(compressed) {
+            let mut single = ZipArchive::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+            let entry = single.by_index(0).map_err(|e| e.to_string())?;
+            blobs.raw_copy_file(entry).map_err(|e| format!("写入打包缓存失败: {}", e))?;
+            done += 1;
+            progress.emit("compress", done, total, &f.relative);
+        }
+    }
+    blobs.finish().map_err(|e| format!("写入打包缓存失败: {}", e))?;
+    fs::rename(&tmp_blobs_path, &blobs_path).map_err(|e| format!("更新打包缓存失败: {}", e))?;
+
+    progress.check_cancelled()?;
+    let blob_file = fs::File::open(&blobs_path).map_err(|e| format!("读取打包缓存失败: {}", e))?;
+    let mut blobs = ZipArchive::new(blob_file).map_err(|e| format!("读取打包缓存失败: {}", e))?;
+
+    if output.exists() {
+        fs::remove_file(output).map_err(|e| format!("无法覆盖已存在的文件: {}", e))?;
+    }
+    let file = fs::File::create(output).map_err(|e| format!("创建 ZIP 文件失败: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    for (i, f) in files.iter().enumerate() {
+        let entry = blobs.by_name(&f.sha256).map_err(|e| format!("读取打包缓存失败 {}: {}", f.relative, e))?;
+        zip.raw_copy_file_rename(entry, &f.relative)
+            .map_err(|e| format!("无法添加文件到 ZIP {}: {}", f.relative, e))?;
+        progress.emit("write", i + 1, files.len(), &f.relative);
+    }
+    zip.finish().map_err(|e| format!("完成 ZIP 文件失败: {}", e))?;
+    Ok(output.to_path_buf())
+}
+
+/// ：写入目录布局。只复制有变化或目标缺失的文件，并删除不再打包的旧文件。
+fn write_folder(output: &Path, files: &[PackFile], progress: &Progress) -> Result<PathBuf, String> {
+    let done = AtomicUsize::new(0);
+    files.par_iter().try_for_each(|f| {
+        progress.check_cancelled()?;
+        let target = output.join(&f.relative);
+        let up_to_date = !f.changed && fs::metadata(&target).map(|m| m.len() == f.size).unwrap_or(false);
+        if !up_to_date {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("创建目录失败 {}: {}", parent.display(), e))?;
+            }
+            fs::copy(&f.path, &target).map_err(|e| format!("写入文件失败 {}: {}", f.relative, e))?;
+        }
+        let current = done.fetch_add(1, Ordering::Relaxed) + 1;
+        progress.emit("write", current, files.len(), &f.relative);
+        Ok::<(), String>(())
+    })?;
+
+    let keep: HashSet<String> = files.iter().map(|f| f.relative.clone()).collect();
+    for entry in WalkDir::new(output).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(output)
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+        if !keep.contains(&relative) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    Ok(output.to_path_buf())
+}
+
+/// ：生成启动器使用的 `<name>.mod`，path 指向 `mod/<name>`。
+fn write_mod_file(project: &Path, package_dir: &Path, name: &str, version: Option<&str>) -> Result<(), String> {
+    let original = fs::read(project.join("descriptor.mod"))
+        .ok()
+        .and_then(|bytes| crate::decode_text_bytes(&bytes))
+        .map(|(content, _)| content)
+        .unwrap_or_default();
+    let mut descriptor = mod_descriptor::parse_descriptor(&original);
+    if descriptor.name.is_empty() {
+        descriptor.name = name.to_string();
+    }
+    descriptor.path = Some(format!("mod/{}", name));
+    descriptor.archive = None;
+    if let Some(version) = version {
+        descriptor.version = Some(version.to_string());
+    }
+    let content = mod_descriptor::update_descriptor(&original, &descriptor);
+    crate::write_text_file(&package_dir.join(format!("{}.mod", name)), &content, encoding_rs::UTF_8)
+}
+
+fn read_project_version(project: &Path) -> Option<String> {
+    read_project_json(project)?
+        .get("version")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+/// ：把新版本号写进待打包的 descriptor.mod 副本（放在缓存目录），项目中的原文件保持不变。
+fn stage_descriptor(files: &mut [PackFile], cache_dir: &Path, version: &str) -> Result<(), String> {
+    let Some(file) = files.iter_mut().find(|f| f.relative == "descriptor.mod") else {
+        return Ok(());
+    };
+    let bytes = fs::read(&file.path).map_err(|e| format!("读取 descriptor.mod 失败: {}", e))?;
+    let Some((original, encoding)) = crate::decode_text_bytes(&bytes) else {
+        return Ok(());
+    };
+    let staged = cache_dir.join("descriptor.mod");
+    crate::write_text_file(&staged, &with_descriptor_version(&original, version), encoding)?;
+    let staged_bytes = fs::read(&staged).map_err(|e| format!("读取 descriptor.mod 失败: {}", e))?;
+    file.path = staged;
+    file.size = staged_bytes.len() as u64;
+    file.modified = None;
+    file.sha256 = format!("{:x}", Sha256::digest(&staged_bytes));
+    file.changed = true;
+    Ok(())
+}
+
+/// ：打包成功后把新版本号写回 project.json 与 descriptor.mod。
+fn write_project_version(project: &Path, version: &str) -> Result<(), String> {
+    update_project_json(project, |obj| {
+        obj.insert("version".to_string(), serde_json::json!(version));
+    })?;
+
+    let descriptor_path = project.join("descriptor.mod");
+    if let Ok(bytes) = fs::read(&descriptor_path) {
+        if let Some((original, encoding)) = crate::decode_text_bytes(&bytes) {
+            crate::write_text_file(&descriptor_path, &with_descriptor_version(&original, version), encoding)?;
+        }
+    }
+    Ok(())
+}
+
+fn with_descriptor_version(original: &str, version: &str) -> String {
+    let mut descriptor = mod_descriptor::parse_descriptor(original);
+    descriptor.version = Some(version.to_string());
+    mod_descriptor::update_descriptor(original, &descriptor)
+}
+
+/// ：`1.2.3` 形式的版本号递增；非数字部分原样保留并归零其后的位。
+fn bump_version(version: &str, bump: VersionBump) -> String {
+    let mut parts: Vec<u64> = version
+        .trim()
+        .trim_start_matches('v')
+        .split('.')
+        .map(|p| p.chars().take_while(|c| c.is_ascii_digit()).collect::<String>())
+        .map(|p| p.parse().unwrap_or(0))
+        .collect();
+    parts.resize(parts.len().max(3), 0);
+    let index = match bump {
+        VersionBump::Major => 0,
+        VersionBump::Minor => 1,
+        VersionBump::Patch => 2,
+    };
+    parts[index] += 1;
+    for p in parts.iter_mut().skip(index + 1) {
+        *p = 0;
+    }
+    parts.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bump_version() {
+        assert_eq!(bump_version("1.2.3", VersionBump::Patch), "1.2.4");
+        assert_eq!(bump_version("1.2.3", VersionBump::Minor), "1.3.0");
+        assert_eq!(bump_version("v1.9", VersionBump::Major), "2.0.0");
+        assert_eq!(bump_version("", VersionBump::Patch), "0.0.1");
+    }
+
+    #[test]
+    fn test_version_bump_is_staged_until_packaged() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path().join("project");
+        fs::create_dir_all(&root).expect("mkdir");
+        fs::write(root.join("descriptor.mod"), "name=\"a\"\nversion=\"1.0.0\"\n").expect("write");
+        fs::write(root.join("project.json"), r#"{"name":"a","version":"1.0.0"}"#).expect("write");
+        let cache_dir = dir.path().join("cache");
+        fs::create_dir_all(&cache_dir).expect("mkdir");
+        let progress = Progress {
+            app: None,
+            cancelled: Arc::default(),
+        };
+
+        let mut files = collect_files(&root, &PackageProfile::default(), &[])
+            .and_then(|f| hash_files(f, &PackageCacheIndex::default(), &progress))
+            .expect("collect");
+        stage_descriptor(&mut files, &cache_dir, "1.0.1").expect("stage");
+        let original = fs::read_to_string(root.join("descriptor.mod")).expect("read");
+        assert!(original.contains("1.0.0") && !original.contains("1.0.1"));
+        assert_eq!(read_project_version(&root).as_deref(), Some("1.0.0"));
+
+        let output = write_folder(&dir.path().join("out"), &files, &progress).expect("folder");
+        let packaged = fs::read_to_string(output.join("descriptor.mod")).expect("read");
+        assert!(packaged.contains("1.0.1"));
+
+        write_project_version(&root, "1.0.1").expect("write back");
+        assert_eq!(read_project_version(&root).as_deref(), Some("1.0.1"));
+        assert!(fs::read_to_string(root.join("descriptor.mod")).expect("read").contains("1.0.1"));
+    }
+
+    #[test]
+    fn test_cancel_only_affects_matching_pack() {
+        let first = CancelToken::register("pack-1");
+        let second = CancelToken::register("pack-2");
+        assert!(cancel_packaging("pack-1".to_string()));
+        assert!(first.flag.load(Ordering::Relaxed));
+        assert!(!second.flag.load(Ordering::Relaxed));
+
+        drop(first);
+        assert!(!cancel_packaging("pack-1".to_string()));
+        assert!(!cancel_packaging("unknown".to_string()));
+    }
+
+    #[test]
+    fn test_default_excludes() {
+        let set = build_globset(&PackageProfile::default().exclude).expect("valid globs");
+        for excluded in [".git/HEAD", "project.json", "gfx/art/flag.psd", "package/a.zip", "notes/todo.txt"] {
+            assert!(set.is_match(excluded), "{}", excluded);
+        }
+        for kept in ["descriptor.mod", "common/ideas/a.txt", "gfx/flags/GER.tga"] {
+            assert!(!set.is_match(kept), "{}", kept);
+        }
+        assert!(set.is_match(".git/"));
+    }
+
+    #[test]
+    fn test_incremental_zip_is_reproducible() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path().join("project");
+        let root = root.as_path();
+        fs::create_dir_all(root.join("common/ideas")).expect("mkdir");
+        fs::write(root.join("common/ideas/a.txt"), "ideas = { }").expect("write");
+        fs::write(root.join("descriptor.mod"), "name=\"a\"").expect("write");
+        let cache_dir = dir.path().join("cache");
+        fs::create_dir_all(&cache_dir).expect("mkdir");
+        let progress = Progress {
+            app: None,
+            cancelled: Arc::default(),
+        };
+        let profile = PackageProfile::default();
+
+        let mut outputs = Vec::new();
+        let mut index = PackageCacheIndex::default();
+        for run in 0..2 {
+            let files = collect_files(root, &profile, &[])
+                .and_then(|f| hash_files(f, &index, &progress))
+                .expect("collect");
+            assert_eq!(files.iter().any(|f| f.changed), run == 0);
+            let output = dir.path().join(format!("out{}.zip", run));
+            write_zip(&output, &files, &cache_dir, &progress).expect("zip");
+            save_cache_index(&cache_dir, &files);
+            index = load_cache_index(&cache_dir);
+            outputs.push(fs::read(output).expect("read"));
+        }
+        assert_eq!(outputs[0], outputs[1]);
+    }
+}
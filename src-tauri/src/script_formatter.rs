@@ -0,0 +1,617 @@
+#![deny(clippy::unwrap_used)]
+
+//! 脚本格式化
+//!
+//! 基于 clausewitz 语法树重新排版 `.txt` / `.gui` / `.gfx`：
+//! 统一缩进与 `key = value` 间距、把短块折叠成单行、保留全部注释。
+//! 排版只依赖语法树，因此对同一份输出再次格式化结果不变。
+
+use crate::clausewitz::{self, Block, Field, Node, Value};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+const SCRIPT_EXTENSIONS: [&str; 3] = ["txt", "gui", "gfx"];
+
+/// 格式化风格
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FormatOptions {
+    /// 使用 Tab 缩进，否则使用 `indent_size` 个空格
+    pub use_tabs: bool,
+    pub indent_size: usize,
+    /// 是否把短块折叠成一行，例如 `provinces = { 1 2 3 }`
+    pub collapse_blocks: bool,
+    /// 折叠后的行宽上限（Tab 按 indent_size 计）
+    pub max_line_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            use_tabs: true,
+            indent_size: 4,
+            collapse_blocks: true,
+            max_line_width: 80,
+        }
+    }
+}
+
+/// 只格式化部分内容时的字节区间
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct FormatRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormattedFile {
+    pub path: String,
+    pub changed: bool,
+    /// dry-run 模式下的 unified diff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatResponse {
+    pub success: bool,
+    pub message: String,
+    /// 单个文件或传入内容的格式化结果
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+    pub files: Vec<FormattedFile>,
+}
+
+/// ：格式化脚本。
+/// - 传入 `content` 时只格式化这段内容并返回结果，不写盘；
+/// - `path` 为文件时格式化该文件，可配合 `range` 只处理选区；
+/// - `path` 为目录时递归处理其中的 .txt/.gui/.gfx。
+///
+/// `dry_run` 为 true 时不写入文件，只返回 diff。
+#[tauri::command]
+pub fn format_script(
+    path: Option<String>,
+    content: Option<String>,
+    range: Option<FormatRange>,
+    options: Option<FormatOptions>,
+    dry_run: Option<bool>,
+) -> FormatResponse {
+    let options = options.unwrap_or_default();
+    let dry_run = dry_run.unwrap_or(false);
+    let label = path.clone().unwrap_or_else(|| "untitled".to_string());
+
+    if let Some(source) = content {
+        return match format_with_range(&source, range, &options) {
+            Ok(formatted) => {
+                let changed = formatted != source;
+                let diff = if dry_run && changed {
+                    Some(unified_diff(&label, &source, &formatted))
+                } else {
+                    None
+                };
+                FormatResponse {
+                    success: true,
+                    message: if changed { "格式化完成".to_string() } else { "内容无需格式化".to_string() },
+                    formatted: Some(formatted),
+                    files: vec![FormattedFile {
+                        path: label,
+                        changed,
+                        diff,
+                        error: None,
+                    }],
+                }
+            }
+            Err(e) => failure(e),
+        };
+    }
+
+    let Some(path) = path else {
+        return failure("未提供文件路径或内容".to_string());
+    };
+    let target = Path::new(&path);
+
+    if target.is_dir() {
+        let files: Vec<String> = WalkDir::new(target)
+            .into_iter()
+            .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file() && is_script_file(e.path()))
+            .map(|e| e.path().to_string_lossy().replace('\\', "/"))
+            .collect();
+
+        let results: Vec<FormattedFile> = files
+            .par_iter()
+            .map(|file| format_file(file, None, &options, dry_run).0)
+            .collect();
+
+        let changed = results.iter().filter(|f| f.changed).count();
+        let failed = results.iter().filter(|f| f.error.is_some()).count();
+        let verb = if dry_run { "需要格式化" } else { "已格式化" };
+        return FormatResponse {
+            success: failed == 0,
+            message: format!("共 {} 个文件，{} {} 个，失败 {} 个", results.len(), verb, changed, failed),
+            formatted: None,
+            files: results,
+        };
+    }
+
+    if !target.is_file() {
+        return failure(format!("路径不存在: {}", path));
+    }
+
+    let (file, formatted) = format_file(&path, range, &options, dry_run);
+    match &file.error {
+        Some(e) => FormatResponse {
+            success: false,
+            message: e.clone(),
+            formatted: None,
+            files: vec![file],
+        },
+        None => FormatResponse {
+            success: true,
+            message: if file.changed { "格式化完成".to_string() } else { "文件无需格式化".to_string() },
+            formatted,
+            files: vec![file],
+        },
+    }
+}
+
+fn failure(message: String) -> FormatResponse {
+    FormatResponse {
+        success: false,
+        message,
+        formatted: None,
+        files: Vec::new(),
+    }
+}
+
+fn is_script_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| {
+            let ext = ext.to_string_lossy().to_lowercase();
+            SCRIPT_EXTENSIONS.contains(&ext.as_str())
+        })
+        .unwrap_or(false)
+}
+
+/// 格式化单个文件，非 dry-run 且内容变化时写回。
+fn format_file(
+    path: &str,
+    range: Option<FormatRange>,
+    options: &FormatOptions,
+    dry_run: bool,
+) -> (FormattedFile, Option<String>) {
+    let mut file = FormattedFile {
+        path: path.to_string(),
+        changed: false,
+        diff: None,
+        error: None,
+    };
+
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            file.error = Some(format!("读取文件失败: {}", e));
+            return (file, None);
+        }
+    };
+    let Some((source, encoding)) = crate::decode_text_bytes(&bytes) else {
+        file.error = Some("无法识别文件编码，已跳过".to_string());
+        return (file, None);
+    };
+
+    let formatted = match format_with_range(&source, range, options) {
+        Ok(f) => f,
+        Err(e) => {
+            file.error = Some(e);
+            return (file, None);
+        }
+    };
+
+    file.changed = formatted != source;
+    if file.changed {
+        if dry_run {
+            file.diff = Some(unified_diff(path, &source, &formatted));
+        } else if let Err(e) = crate::write_text_file(Path::new(path), &formatted, encoding) {
+            file.error = Some(e);
+        }
+    }
+    (file, Some(formatted))
+}
+
+fn format_with_range(
+    source: &str,
+    range: Option<FormatRange>,
+    options: &FormatOptions,
+) -> Result<String, String> {
+    match range {
+        Some(r) => format_range(source, r.start, r.end, options),
+        None => format_source(source, options),
+    }
+}
+
+fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(path, path)
+        .to_string()
+}
+
+/// 格式化整份脚本。存在语法错误时拒绝处理，避免破坏原文。
+pub fn format_source(source: &str, options: &FormatOptions) -> Result<String, String> {
+    let doc = parse_checked(source)?;
+    let mut printer = Printer::new(source, options);
+    printer.body(&doc.root.nodes, 0, false);
+
+    let mut out = String::with_capacity(source.len());
+    if source.starts_with('\u{feff}') {
+        out.push('\u{feff}');
+    }
+    if !printer.lines.is_empty() {
+        out.push_str(&printer.lines.join(printer.eol));
+        out.push_str(printer.eol);
+    }
+    Ok(out)
+}
+
+/// 只格式化与 `[start, end)` 相交的节点，其余内容保持原样。
+/// 选区会向外扩展到最内层完整包含它的块中的完整条目。
+pub fn format_range(
+    source: &str,
+    start: usize,
+    end: usize,
+    options: &FormatOptions,
+) -> Result<String, String> {
+    let doc = parse_checked(source)?;
+    let (start, end) = (start.min(end), start.max(end).min(source.len()));
+
+    // 找到完整包含选区的最内层块
+    let mut nodes: &[Node] = &doc.root.nodes;
+    let mut depth = 0usize;
+    'descend: loop {
+        for node in nodes {
+            let child = match node {
+                Node::Field(Field { value: Value::Block(b), .. }) | Node::Value(Value::Block(b)) => b,
+                _ => continue,
+            };
+            let inner = child.inner_span();
+            if start >= inner.start && end <= inner.end {
+                nodes = &child.nodes;
+                depth += 1;
+                continue 'descend;
+            }
+        }
+        break;
+    }
+
+    let hits: Vec<usize> = nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| !matches!(n, Node::Whitespace(_)))
+        .filter(|(_, n)| {
+            let span = n.span();
+            if start == end {
+                span.contains(start)
+            } else {
+                span.start < end && span.end > start
+            }
+        })
+        .map(|(i, _)| i)
+        .collect();
+    let (Some(&first), Some(&last)) = (hits.first(), hits.last()) else {
+        return Ok(source.to_string());
+    };
+
+    let mut printer = Printer::new(source, options);
+    printer.body(&nodes[first..=last], depth, false);
+    let mut text = printer.lines.join(printer.eol);
+
+    // 起点之前只有缩进时连同缩进一起替换，否则保持行内位置
+    let mut replace_start = nodes[first].span().start;
+    let line_start = source[..replace_start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let prefix = &source[line_start..replace_start];
+    if prefix.trim_start_matches('\u{feff}').chars().all(|c| c == ' ' || c == '\t') {
+        replace_start = line_start + (prefix.len() - prefix.trim_start_matches('\u{feff}').len());
+    } else {
+        text = text.trim_start().to_string();
+    }
+    let replace_end = nodes[last].span().end;
+
+    let mut out = String::with_capacity(source.len());
+    out.push_str(&source[..replace_start]);
+    out.push_str(&text);
+    out.push_str(&source[replace_end..]);
+    Ok(out)
+}
+
+fn parse_checked(source: &str) -> Result<clausewitz::Document, String> {
+    let doc = clausewitz::parse(source);
+    if let Some(err) = doc.errors.first() {
+        let line = clausewitz::LineIndex::new(source).line(err.span.start);
+        return Err(format!("第 {} 行存在语法错误（{}），已取消格式化", line, err.message));
+    }
+    Ok(doc)
+}
+
+/// 上一个输出的条目类型，用于判断注释是否跟在行尾、裸值是否续在同一行
+#[derive(Clone, Copy, PartialEq)]
+enum Prev {
+    None,
+    Opener,
+    Field,
+    Value,
+    Comment,
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    options: &'a FormatOptions,
+    eol: &'static str,
+    lines: Vec<String>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str, options: &'a FormatOptions) -> Self {
+        Printer {
+            source,
+            options,
+            eol: if source.contains("\r\n") { "\r\n" } else { "\n" },
+            lines: Vec::new(),
+        }
+    }
+
+    fn text(&self, span: clausewitz::Span) -> &'a str {
+        span.slice(self.source)
+    }
+
+    fn indent(&self, depth: usize) -> String {
+        if self.options.use_tabs {
+            "\t".repeat(depth)
+        } else {
+            " ".repeat(depth * self.options.indent_size)
+        }
+    }
+
+    fn append_to_last(&mut self, text: &str) {
+        if let Some(last) = self.lines.last_mut() {
+            last.push(' ');
+            last.push_str(text);
+        }
+    }
+
+    /// 输出块内容。`opener` 表示上一行是 `key = {`，紧随其后的注释会跟在该行末尾。
+    fn body(&mut self, nodes: &[Node], depth: usize, opener: bool) {
+        let mut prev = if opener { Prev::Opener } else { Prev::None };
+        let mut newline_before = false;
+        let mut blank_before = false;
+
+        for node in nodes {
+            match node {
+                Node::Whitespace(span) => {
+                    let newlines = self.text(*span).matches('\n').count();
+                    newline_before = newlines > 0;
+                    // 连续空行压缩为一行，块首的空行直接丢弃
+                    blank_before = newlines >= 2 && !matches!(prev, Prev::None | Prev::Opener);
+                    continue;
+                }
+                Node::Comment(span) => {
+                    let comment = self.text(*span).trim_end();
+                    if !newline_before && prev != Prev::None {
+                        self.append_to_last(comment);
+                    } else {
+                        self.push_blank(blank_before);
+                        let line = format!("{}{}", self.indent(depth), comment);
+                        self.lines.push(line);
+                    }
+                    prev = Prev::Comment;
+                }
+                Node::Field(field) => {
+                    self.push_blank(blank_before);
+                    self.field(field, depth);
+                    prev = Prev::Field;
+                }
+                Node::Value(Value::Scalar(scalar)) if prev == Prev::Value && !newline_before => {
+                    // 源文件中同一行的裸值保持在同一行
+                    let text = self.text(scalar.span);
+                    self.append_to_last(text);
+                }
+                Node::Value(value) => {
+                    self.push_blank(blank_before);
+                    self.value(value, depth, String::new());
+                    prev = Prev::Value;
+                }
+                Node::Error(span) => {
+                    // parse_checked 已拒绝含错误的文档，这里仅原样保留
+                    self.push_blank(blank_before);
+                    let line = format!("{}{}", self.indent(depth), self.text(*span).trim());
+                    self.lines.push(line);
+                    prev = Prev::Field;
+                }
+            }
+            newline_before = false;
+            blank_before = false;
+        }
+    }
+
+    fn push_blank(&mut self, blank: bool) {
+        if blank {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn field(&mut self, field: &Field, depth: usize) {
+        let head = format!(
+            "{} {} ",
+            self.text(field.key.span),
+            field.operator.as_str()
+        );
+        let first_line = self.lines.len();
+        self.value(&field.value, depth, head);
+        // 键与值之间的注释移到首行末尾
+        for span in &field.comments {
+            let comment = self.text(*span).trim_end().to_string();
+            if let Some(line) = self.lines.get_mut(first_line) {
+                line.push(' ');
+                line.push_str(&comment);
+            }
+        }
+    }
+
+    /// 输出值，`head` 为同一行中值之前的内容（如 `key = `）。
+    fn value(&mut self, value: &Value, depth: usize, head: String) {
+        let indent = self.indent(depth);
+        match value {
+            Value::Scalar(scalar) => {
+                let line = format!("{}{}{}", indent, head, self.text(scalar.span));
+                self.lines.push(line);
+            }
+            Value::Color(color) => {
+                let head = format!("{}{} ", head, self.text(color.model.span));
+                self.block(&color.block, depth, &indent, head);
+            }
+            Value::Block(block) => self.block(block, depth, &indent, head),
+        }
+    }
+
+    fn block(&mut self, block: &Block, depth: usize, indent: &str, head: String) {
+        if let Some(inline) = self.inline(block) {
+            let line = format!("{}{}{}", indent, head, inline);
+            if self.fits(depth, &line) {
+                self.lines.push(line);
+                return;
+            }
+        }
+        self.lines.push(format!("{}{}{{", indent, head));
+        self.body(&block.nodes, depth + 1, true);
+        self.lines.push(format!("{}}}", indent));
+    }
+
+    fn fits(&self, depth: usize, line: &str) -> bool {
+        let indent_width = if self.options.use_tabs {
+            depth * self.options.indent_size
+        } else {
+            0
+        };
+        let text_width = line.trim_start_matches('\t').chars().count();
+        indent_width + text_width <= self.options.max_line_width
+    }
+
+    /// 尝试把块排成单行：不含注释与嵌套块时才折叠，空块总是写作 `{ }`。
+    fn inline(&self, block: &Block) -> Option<String> {
+        let mut items: Vec<String> = Vec::new();
+        for node in &block.nodes {
+            match node {
+                Node::Whitespace(_) => {}
+                Node::Field(field) if field.comments.is_empty() => {
+                    let value = match &field.value {
+                        Value::Scalar(s) => self.text(s.span).to_string(),
+                        Value::Color(c) => format!("{} {}", self.text(c.model.span), self.inline(&c.block)?),
+                        Value::Block(_) => return None,
+                    };
+                    items.push(format!(
+                        "{} {} {}",
+                        self.text(field.key.span),
+                        field.operator.as_str(),
+                        value
+                    ));
+                }
+                Node::Value(Value::Scalar(s)) => items.push(self.text(s.span).to_string()),
+                _ => return None,
+            }
+        }
+        if items.is_empty() {
+            return Some("{ }".to_string());
+        }
+        if !self.options.collapse_blocks {
+            return None;
+        }
+        Some(format!("{{ {} }}", items.join(" ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(src: &str) -> String {
+        format_source(src, &FormatOptions::default()).expect("format")
+    }
+
+    #[test]
+    fn test_basic_layout_and_idempotence() {
+        let src = "\u{feff}# header\nfocus={id=GER_a   x =1\ncompletion_reward = {\nadd_political_power=100 # pp\n\n\n\nadd_stability = 0.1 } }\n";
+        let out = fmt(src);
+        assert_eq!(
+            out,
+            "\u{feff}# header\nfocus = {\n\tid = GER_a\n\tx = 1\n\tcompletion_reward = {\n\t\tadd_political_power = 100 # pp\n\n\t\tadd_stability = 0.1\n\t}\n}\n"
+        );
+        assert_eq!(fmt(&out), out);
+    }
+
+    #[test]
+    fn test_collapse_short_blocks() {
+        let src = "state = {\n    provinces = {\n        1 2 3\n    }\n    color = rgb { 10 20 30 }\n    empty = {\n    }\n}\n";
+        let options = FormatOptions {
+            use_tabs: false,
+            indent_size: 2,
+            ..FormatOptions::default()
+        };
+        let out = format_source(src, &options).expect("format");
+        assert_eq!(
+            out,
+            "state = {\n  provinces = { 1 2 3 }\n  color = rgb { 10 20 30 }\n  empty = { }\n}\n"
+        );
+
+        let options = FormatOptions {
+            collapse_blocks: false,
+            ..FormatOptions::default()
+        };
+        let out = format_source("a = { 1 2 3 }\n", &options).expect("format");
+        assert_eq!(out, "a = {\n\t1 2 3\n}\n");
+        assert_eq!(format_source(&out, &options).expect("format"), out);
+    }
+
+    #[test]
+    fn test_comments_kept() {
+        let src = "a = { # open\n  b = c\n  # own line\n  d = { 1 2 } # tail\n} # end\r\n";
+        let out = fmt(src);
+        assert_eq!(
+            out,
+            "a = { # open\r\n\tb = c\r\n\t# own line\r\n\td = { 1 2 } # tail\r\n} # end\r\n"
+        );
+        assert_eq!(fmt(&out), out);
+    }
+
+    #[test]
+    fn test_range_and_errors() {
+        let src = "a = {\n      b=1\n   c  =  2\n}\nd=3\n";
+        let start = src.find("b=1").expect("b");
+        let out = format_range(src, start, start + 3, &FormatOptions::default()).expect("range");
+        assert_eq!(out, "a = {\n\tb = 1\n   c  =  2\n}\nd=3\n");
+
+        assert!(format_source("a = { b = 1\n", &FormatOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_format_file_keeps_encoding() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("gbk.txt");
+        let (bytes, _, _) = encoding_rs::GBK.encode("a={name=\"德意志国防军第一装甲师\"}\n");
+        fs::write(&path, &bytes).expect("write");
+
+        let path = path.to_string_lossy().to_string();
+        let (file, _) = format_file(&path, None, &FormatOptions::default(), false);
+        assert!(file.error.is_none() && file.changed);
+        let (expected, _, _) = encoding_rs::GBK.encode("a = { name = \"德意志国防军第一装甲师\" }\n");
+        assert_eq!(fs::read(&path).expect("read"), expected.into_owned());
+    }
+}
@@ -0,0 +1,1017 @@
+#![deny(clippy::unwrap_used)]
+
+//! 脚本诊断引擎
+//!
+//! 对 `.txt` / `.gui` 脚本执行一组可单独开关的规则，返回带区间、严重级别、规则 ID
+//! 与可选快速修复的诊断。规则的启用状态保存在设置的 `lintRules` 中（未配置时默认启用），
+//! 调用方也可以临时覆盖。依赖定义的规则（idea、本地化键、sprite）基于符号索引，
+//! 修正相关规则基于修正数据库。
+
+use crate::clausewitz::{self, Block, Document, Field, LineIndex, Span, Value};
+use crate::localisation::{self, LocalisationDatabase};
+use crate::localisation_lint::{self, GfxCatalogue, LocalisationContext};
+use crate::modifier_db::{self, ModifierDatabase, ModifierScope};
+use crate::symbol_index::{self, IndexRoots, SymbolIndex, SymbolKind};
+use crate::tag_validator;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+/// ：诊断严重级别。
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// ：快速修复中的单次文本替换，区间为字节偏移。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintEdit {
+    pub start: usize,
+    pub end: usize,
+    pub new_text: String,
+}
+
+/// ：快速修复。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintFix {
+    pub title: String,
+    pub edits: Vec<LintEdit>,
+}
+
+/// ：单条诊断，行列号从 1 开始，start/end 为字节区间。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintDiagnostic {
+    pub file: String,
+    pub rule: String,
+    pub severity: LintSeverity,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+    pub fixes: Vec<LintFix>,
+}
+
+/// ：规则说明，供设置界面展示。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintRuleInfo {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub severity: LintSeverity,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintRulesResult {
+    pub success: bool,
+    pub message: String,
+    pub rules: Vec<LintRuleInfo>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintResult {
+    pub success: bool,
+    pub message: String,
+    pub file_count: usize,
+    pub diagnostics: Vec<LintDiagnostic>,
+}
+
+/// ：规则在文件中的一处发现，由引擎补全位置与规则信息。
+pub(crate) struct Finding {
+    pub(crate) span: Span,
+    pub(crate) message: String,
+    pub(crate) fixes: Vec<LintFix>,
+}
+
+/// ：单个文件的检查上下文。
+struct FileContext<'a> {
+    relative: &'a str,
+    content: &'a str,
+    doc: &'a Document,
+    lines: &'a LineIndex,
+    tags: &'a HashSet<String>,
+    modifiers: &'a ModifierDatabase,
+    symbols: Option<&'a SymbolIndex>,
+}
+
+struct LintRule {
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    severity: LintSeverity,
+    check: RuleCheck,
+}
+
+/// ：规则的检查函数，脚本规则作用于 `.txt` / `.gui`，本地化规则作用于 `.yml`。
+#[derive(Clone, Copy)]
+enum RuleCheck {
+    Script(fn(&FileContext, &mut Vec<Finding>)),
+    Localisation(fn(&LocalisationContext, &mut Vec<Finding>)),
+}
+
+const RULES: [LintRule; 13] = [
+    LintRule {
+        id: "undefined-tag",
+        name: "未定义的国家标签",
+        description: "国家作用域的键、效果中的 target、ROOT/TAG 与 TAG = { } 作用域切换引用的标签必须已定义",
+        severity: LintSeverity::Error,
+        check: RuleCheck::Script(check_undefined_tag),
+    },
+    LintRule {
+        id: "undefined-idea",
+        name: "未定义的 idea",
+        description: "add_ideas 中引用的 idea 必须在 common/ideas 中定义",
+        severity: LintSeverity::Error,
+        check: RuleCheck::Script(check_undefined_idea),
+    },
+    LintRule {
+        id: "unknown-modifier",
+        name: "未知的修正",
+        description: "idea、特质与动态修正中的键必须是已知修正（内置修正表及 common/modifier_definitions）",
+        severity: LintSeverity::Warning,
+        check: RuleCheck::Script(check_unknown_modifier),
+    },
+    LintRule {
+        id: "modifier-scope",
+        name: "修正作用域不符",
+        description: "州修正用于国家、国家修正用于将领特质等作用域错误",
+        severity: LintSeverity::Warning,
+        check: RuleCheck::Script(check_modifier_scope),
+    },
+    LintRule {
+        id: "duplicate-key",
+        name: "重复的键",
+        description: "国策、决议与事件中只能出现一次的键（id、icon、x、y、cost 等）以及修正块中的修正重复出现",
+        severity: LintSeverity::Warning,
+        check: RuleCheck::Script(check_duplicate_keys),
+    },
+    LintRule {
+        id: "empty-block",
+        name: "空块",
+        description: "没有任何内容的 `key = { }`",
+        severity: LintSeverity::Info,
+        check: RuleCheck::Script(check_empty_blocks),
+    },
+    LintRule {
+        id: "unknown-localisation-key",
+        name: "未知的本地化键",
+        description: "自定义提示、事件标题 / 描述 / 选项引用的本地化键必须存在",
+        severity: LintSeverity::Warning,
+        check: RuleCheck::Script(check_unknown_localisation_key),
+    },
+    LintRule {
+        id: "missing-sprite",
+        name: "缺失的 sprite",
+        description: "引用的 GFX_ 名称必须在 interface 或 gfx 下的 .gfx 中定义",
+        severity: LintSeverity::Warning,
+        check: RuleCheck::Script(check_missing_sprite),
+    },
+    LintRule {
+        id: "loc-unclosed-colour",
+        name: "未闭合的颜色代码",
+        description: "本地化文本中 §X 设置的颜色必须以 §! 结束",
+        severity: LintSeverity::Warning,
+        check: RuleCheck::Localisation(localisation_lint::check_unclosed_colour),
+    },
+    LintRule {
+        id: "loc-unknown-colour",
+        name: "未定义的颜色代码",
+        description: "§ 后的颜色代码必须是内置颜色或 .gfx 中 textcolors 定义的颜色",
+        severity: LintSeverity::Error,
+        check: RuleCheck::Localisation(localisation_lint::check_unknown_colour),
+    },
+    LintRule {
+        id: "loc-unknown-icon",
+        name: "未知的文本图标",
+        description: "£icon 必须有对应的 spriteType（icon 或 GFX_icon）",
+        severity: LintSeverity::Warning,
+        check: RuleCheck::Localisation(localisation_lint::check_unknown_icon),
+    },
+    LintRule {
+        id: "loc-missing-key",
+        name: "引用不存在的本地化键",
+        description: "文本中 $KEY$ 引用的键必须在同一语言中存在",
+        severity: LintSeverity::Warning,
+        check: RuleCheck::Localisation(localisation_lint::check_missing_key),
+    },
+    LintRule {
+        id: "loc-recursion",
+        name: "本地化引用成环",
+        description: "$KEY$ 互相嵌套形成循环，游戏中会无限展开",
+        severity: LintSeverity::Error,
+        check: RuleCheck::Localisation(localisation_lint::check_recursion),
+    },
+];
+
+/// ：这些键下的 `modifier = { }` 是 AI 权重调整，而非数值修正。
+const AI_WEIGHT_KEYS: [&str; 5] = ["ai_will_do", "ai_chance", "ai_weight", "weight", "ai_factor"];
+
+/// ：内容为修正的块。
+const MODIFIER_BLOCK_NAMES: [&str; 4] = [
+    "modifier",
+    "non_shared_modifier",
+    "corps_commander_modifier",
+    "field_marshal_modifier",
+];
+
+/// ：修正块中允许出现的非修正键。
+const MODIFIER_BLOCK_KEYS: [&str; 2] = ["custom_modifier_tooltip", "tag"];
+
+/// ：动态修正定义中除修正以外的键。
+const DYNAMIC_MODIFIER_KEYS: [&str; 4] = ["icon", "enable", "remove_trigger", "attacker_modifier"];
+
+/// ：国策、决议与事件定义块中只能出现一次的键。
+const UNIQUE_KEYS: [&str; 18] = [
+    "id",
+    "x",
+    "y",
+    "cost",
+    "relative_position_id",
+    "is_triggered_only",
+    "fire_only_once",
+    "available",
+    "bypass",
+    "allowed",
+    "visible",
+    "completion_reward",
+    "ai_will_do",
+    "cancel_if_invalid",
+    "continue_if_invalid",
+    "days_remove",
+    "days_re_enable",
+    "mean_time_to_happen",
+];
+
+/// ：可以写多个带 `trigger` 的条件块，只有两次都是标量时才算重复。
+const SCALAR_UNIQUE_KEYS: [&str; 2] = ["icon", "picture"];
+
+/// ：直接引用本地化键的键。
+const LOCALISATION_KEYS: [&str; 3] = ["custom_effect_tooltip", "custom_trigger_tooltip", "localization_key"];
+
+const EVENT_TYPES: [&str; 5] = [
+    "country_event",
+    "news_event",
+    "state_event",
+    "unit_leader_event",
+    "operative_leader_event",
+];
+
+/// ：参与项目检查的顶层目录。
+const LINTED_DIRS: [&str; 5] = ["common", "events", "history", "interface", "localisation"];
+
+/// 列出全部规则及其启用状态
+#[tauri::command]
+pub fn list_lint_rules() -> LintRulesResult {
+    let enabled = enabled_rules(None);
+    LintRulesResult {
+        success: true,
+        message: format!("共 {} 条规则", RULES.len()),
+        rules: RULES
+            .iter()
+            .map(|r| LintRuleInfo {
+                id: r.id.to_string(),
+                name: r.name.to_string(),
+                description: r.description.to_string(),
+                severity: r.severity,
+                enabled: enabled.contains(r.id),
+            })
+            .collect(),
+    }
+}
+
+/// 检查单个文件，`content` 为编辑器中尚未保存的内容，缺省时读取文件
+#[tauri::command]
+pub fn lint_file(
+    file_path: String,
+    content: Option<String>,
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+    rules: Option<HashMap<String, bool>>,
+) -> LintResult {
+    let content = match content {
+        Some(c) => c,
+        None => match fs::read(&file_path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(e) => return lint_failure(format!("读取文件失败: {}", e)),
+        },
+    };
+    let relative = relative_path(&file_path, project_root.as_deref());
+    let languages: Vec<String> = if relative.ends_with(".yml") {
+        localisation::parse_localisation(&content)
+            .0
+            .or_else(|| localisation::file_name_language(&relative))
+            .into_iter()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let (diagnostics, note) = with_environment(project_root, game_root, dependency_roots, rules, &languages, |env, symbols| {
+        lint_content(&file_path, &relative, &content, env, symbols)
+    });
+    LintResult {
+        success: true,
+        message: format!("发现 {} 个问题{}", diagnostics.len(), note),
+        file_count: 1,
+        diagnostics,
+    }
+}
+
+/// 检查整个项目（common / events / history / interface 下的脚本与 localisation 下的本地化文件）
+#[tauri::command]
+pub fn lint_project(
+    project_root: String,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+    rules: Option<HashMap<String, bool>>,
+) -> LintResult {
+    let root = Path::new(&project_root);
+    if !root.exists() {
+        return lint_failure("项目路径不存在".to_string());
+    }
+
+    let mut files: Vec<String> = Vec::new();
+    for dir in LINTED_DIRS {
+        let base = root.join(dir);
+        if !base.exists() {
+            continue;
+        }
+        for entry in WalkDir::new(&base).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_file() && is_linted_file(&entry.path().to_string_lossy()) {
+                files.push(entry.path().to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+
+    let file_count = files.len();
+    let mut languages: Vec<String> = files.iter().filter_map(|f| localisation::file_name_language(&f.to_lowercase())).collect();
+    languages.sort();
+    languages.dedup();
+    let project = Some(project_root.clone());
+    let (diagnostics, note) = with_environment(project, game_root, dependency_roots, rules, &languages, |env, symbols| {
+        let mut diagnostics: Vec<LintDiagnostic> = files
+            .par_iter()
+            .flat_map_iter(|path| {
+                let content = match fs::read(path) {
+                    Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                    Err(e) => return vec![read_error(path, e)],
+                };
+                let relative = relative_path(path, Some(&project_root));
+                lint_content(path, &relative, &content, env, symbols)
+            })
+            .collect();
+        diagnostics.sort_by(|a, b| a.file.cmp(&b.file).then(a.start.cmp(&b.start)));
+        diagnostics
+    });
+
+    LintResult {
+        success: true,
+        message: format!("检查了 {} 个文件，发现 {} 个问题{}", file_count, diagnostics.len(), note),
+        file_count,
+        diagnostics,
+    }
+}
+
+/// ：无法读取的文件作为一条错误诊断返回，而不是当作空文件检查。
+fn read_error(file: &str, error: std::io::Error) -> LintDiagnostic {
+    LintDiagnostic {
+        file: file.to_string(),
+        rule: "read-error".to_string(),
+        severity: LintSeverity::Error,
+        line: 1,
+        column: 1,
+        end_line: 1,
+        end_column: 1,
+        start: 0,
+        end: 0,
+        message: format!("读取文件失败: {}", error),
+        fixes: Vec::new(),
+    }
+}
+
+fn lint_failure(message: String) -> LintResult {
+    LintResult {
+        success: false,
+        message,
+        file_count: 0,
+        diagnostics: Vec::new(),
+    }
+}
+
+// ==================== 引擎 ====================
+
+/// ：一次检查共享的数据。
+struct LintEnvironment {
+    enabled: HashSet<&'static str>,
+    tags: HashSet<String>,
+    modifiers: Arc<ModifierDatabase>,
+    /// 内置颜色加上 `textcolors` 中定义的颜色
+    colours: HashSet<char>,
+    gfx: Option<Arc<GfxCatalogue>>,
+    localisation: Option<Arc<LocalisationDatabase>>,
+}
+
+/// ：读取设置中的 `lintRules`，再叠加调用方的覆盖项。未配置的规则默认启用。
+fn enabled_rules(overrides: Option<HashMap<String, bool>>) -> HashSet<&'static str> {
+    let settings = crate::load_settings().data.unwrap_or_default();
+    let configured = settings.get("lintRules").and_then(|v| v.as_object());
+    let overrides = overrides.unwrap_or_default();
+
+    RULES
+        .iter()
+        .map(|r| r.id)
+        .filter(|id| {
+            overrides
+                .get(*id)
+                .copied()
+                .or_else(|| configured.and_then(|c| c.get(*id)).and_then(|v| v.as_bool()))
+                .unwrap_or(true)
+        })
+        .collect()
+}
+
+/// ：准备标签集合与符号索引后执行检查。索引不可用时依赖定义的规则会被跳过，
+/// 额外返回一段说明附加到结果消息中。
+fn with_environment<T>(
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+    rules: Option<HashMap<String, bool>>,
+    languages: &[String],
+    run: impl Fn(&LintEnvironment, Option<&SymbolIndex>) -> T,
+) -> (T, String) {
+    let enabled = enabled_rules(rules);
+    let tags = if enabled.contains("undefined-tag") {
+        tag_validator::ensure_tag_cache(project_root.clone(), game_root.clone(), dependency_roots.clone())
+    } else {
+        HashSet::new()
+    };
+    let modifiers = if enabled.contains("unknown-modifier") || enabled.contains("modifier-scope") {
+        modifier_db::load_database(project_root.as_deref(), game_root.as_deref(), dependency_roots.clone())
+    } else {
+        Arc::default()
+    };
+    let has_roots = project_root.is_some() || game_root.is_some();
+    let gfx = (has_roots && ["loc-unknown-colour", "loc-unknown-icon"].iter().any(|id| enabled.contains(id)))
+        .then(|| localisation_lint::load_catalogue(project_root.as_deref(), game_root.as_deref(), dependency_roots.clone()));
+    let mut colours: HashSet<char> = localisation_lint::BUILTIN_COLOURS.chars().collect();
+    if let Some(gfx) = &gfx {
+        colours.extend(gfx.colours.iter().copied());
+    }
+    // 只加载被检查文件涉及的语言
+    let localisation = (has_roots && !languages.is_empty() && ["loc-missing-key", "loc-recursion"].iter().any(|id| enabled.contains(id)))
+        .then(|| {
+            let languages: Vec<&str> = languages.iter().map(String::as_str).collect();
+            localisation::load_database(project_root.as_deref(), game_root.as_deref(), dependency_roots.clone(), Some(&languages))
+        });
+    let env = LintEnvironment {
+        enabled,
+        tags,
+        modifiers,
+        colours,
+        gfx,
+        localisation,
+    };
+
+    if project_root.is_none() && game_root.is_none() {
+        return (run(&env, None), "（未提供项目与游戏目录，已跳过依赖定义的规则）".to_string());
+    }
+
+    let roots = IndexRoots::new(project_root, game_root, dependency_roots);
+    match symbol_index::with_index(&roots, |index| run(&env, Some(index))) {
+        Ok((result, _)) => (result, String::new()),
+        Err(e) => (run(&env, None), format!("（{}，已跳过依赖定义的规则）", e)),
+    }
+}
+
+fn is_script_file(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    lower.ends_with(".txt") || lower.ends_with(".gui")
+}
+
+fn is_localisation_file(path: &str) -> bool {
+    path.to_lowercase().ends_with(".yml")
+}
+
+fn is_linted_file(path: &str) -> bool {
+    is_script_file(path) || is_localisation_file(path)
+}
+
+/// ：相对项目根目录的小写路径，不在项目内时返回完整路径。
+fn relative_path(path: &str, project_root: Option<&str>) -> String {
+    let normalized = path.replace('\\', "/");
+    project_root
+        .map(|root| root.replace('\\', "/"))
+        .and_then(|root| {
+            normalized
+                .strip_prefix(root.trim_end_matches('/'))
+                .map(|rest| rest.trim_start_matches('/').to_string())
+        })
+        .unwrap_or(normalized)
+        .to_lowercase()
+}
+
+/// ：对一份内容执行全部已启用的规则。
+fn lint_content(
+    file: &str,
+    relative: &str,
+    content: &str,
+    env: &LintEnvironment,
+    symbols: Option<&SymbolIndex>,
+) -> Vec<LintDiagnostic> {
+    let enabled: Vec<&LintRule> = RULES.iter().filter(|r| env.enabled.contains(r.id)).collect();
+    let lines = LineIndex::new(content);
+    let mut found: Vec<(&LintRule, Vec<Finding>)> = Vec::new();
+    if is_script_file(relative) {
+        let doc = clausewitz::parse(content);
+        let ctx = FileContext {
+            relative,
+            content,
+            doc: &doc,
+            lines: &lines,
+            tags: &env.tags,
+            modifiers: &env.modifiers,
+            symbols,
+        };
+        for rule in &enabled {
+            if let RuleCheck::Script(check) = rule.check {
+                let mut findings = Vec::new();
+                check(&ctx, &mut findings);
+                found.push((rule, findings));
+            }
+        }
+    } else if is_localisation_file(relative) {
+        let (language, entries) = localisation::parse_localisation(content);
+        let language = language.or_else(|| localisation::file_name_language(relative));
+        let ctx = LocalisationContext {
+            language: language.as_deref(),
+            entries: &entries,
+            colours: &env.colours,
+            sprites: env.gfx.as_deref().map(|g| &g.sprites),
+            database: env.localisation.as_deref(),
+        };
+        for rule in &enabled {
+            if let RuleCheck::Localisation(check) = rule.check {
+                let mut findings = Vec::new();
+                check(&ctx, &mut findings);
+                found.push((rule, findings));
+            }
+        }
+    }
+
+    let mut diagnostics: Vec<LintDiagnostic> = found
+        .into_iter()
+        .flat_map(|(rule, findings)| findings.into_iter().map(move |f| (rule, f)))
+        .map(|(rule, f)| {
+            let (line, column) = lines.line_col(content, f.span.start);
+            let (end_line, end_column) = lines.line_col(content, f.span.end);
+            LintDiagnostic {
+                file: file.to_string(),
+                rule: rule.id.to_string(),
+                severity: rule.severity,
+                line,
+                column,
+                end_line,
+                end_column,
+                start: f.span.start,
+                end: f.span.end,
+                message: f.message,
+                fixes: f.fixes,
+            }
+        })
+        .collect();
+    diagnostics.sort_by_key(|d| d.start);
+    diagnostics
+}
+
+/// ：删除整个字段的修复；字段独占一行时连同该行一起删除。
+fn remove_field_fix(title: &str, content: &str, field: &Field) -> LintFix {
+    let mut start = field.span.start;
+    let mut end = field.span.end;
+    let line_start = content[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = content[end..].find('\n').map(|i| end + i + 1).unwrap_or(content.len());
+    let before_blank = content[line_start..start].trim().is_empty();
+    let after_blank = content[end..line_end].trim().is_empty();
+    if before_blank && after_blank {
+        start = line_start;
+        end = line_end;
+    }
+    LintFix {
+        title: title.to_string(),
+        edits: vec![LintEdit {
+            start,
+            end,
+            new_text: String::new(),
+        }],
+    }
+}
+
+/// ：脚本变量、参数或内联脚本等无法静态解析的值。
+fn is_dynamic(text: &str) -> bool {
+    text.is_empty() || text.contains(['@', '[', '$', ' ', ':'])
+}
+
+// ==================== 规则 ====================
+
+fn check_undefined_tag(ctx: &FileContext, out: &mut Vec<Finding>) {
+    // 标签集合为空时说明未能加载，避免全部误报
+    if ctx.tags.is_empty() || !ctx.relative.ends_with(".txt") {
+        return;
+    }
+    for error in tag_validator::validate_tags_internal(ctx.content, ctx.tags) {
+        out.push(Finding {
+            span: Span::new(error.start, error.end),
+            message: error.message,
+            fixes: Vec::new(),
+        });
+    }
+}
+
+fn check_undefined_idea(ctx: &FileContext, out: &mut Vec<Finding>) {
+    let Some(symbols) = ctx.symbols else { return };
+    ctx.doc.root.visit_fields(&mut |field, _| {
+        if !field.key_is("add_ideas") {
+            return;
+        }
+        let scalars: Vec<_> = match &field.value {
+            Value::Scalar(s) => vec![s],
+            Value::Block(b) => b.scalars().collect(),
+            Value::Color(_) => Vec::new(),
+        };
+        for idea in scalars {
+            if !is_dynamic(&idea.text) && !symbols.is_defined_as(&idea.text, SymbolKind::Idea) {
+                out.push(Finding {
+                    span: idea.span,
+                    message: format!("未定义的 idea: {}", idea.text),
+                    fixes: Vec::new(),
+                });
+            }
+        }
+    });
+}
+
+/// ：数值修正块。AI 权重下的 modifier 以及带 factor / add 的块不算。
+fn is_modifier_block(field: &Field, ancestors: &[&Field]) -> bool {
+    let Some(block) = field.value.as_block() else {
+        return false;
+    };
+    MODIFIER_BLOCK_NAMES.iter().any(|k| field.key_is(k))
+        && !ancestors.iter().any(|a| AI_WEIGHT_KEYS.iter().any(|k| a.key_is(k)))
+        && !["factor", "add", "base"].iter().any(|k| block.field(k).is_some())
+}
+
+/// ：按文件所在目录确定修正应作用的范围，未知目录只检查是否存在。
+fn expected_scopes(relative: &str) -> Option<&'static [ModifierScope]> {
+    if relative.starts_with("common/ideas/") || relative.starts_with("common/country_leader/") {
+        Some(&[ModifierScope::Country])
+    } else if relative.starts_with("common/unit_leader/") {
+        Some(&[ModifierScope::UnitLeader])
+    } else if relative.starts_with("common/dynamic_modifiers/") {
+        Some(&[ModifierScope::Country, ModifierScope::State])
+    } else {
+        None
+    }
+}
+
+/// ：文件中所有修正条目：modifier 类块内的标量字段，以及动态修正定义中的修正。
+fn modifier_entries<'a>(ctx: &FileContext<'a>) -> Vec<&'a Field> {
+    let mut entries: Vec<&'a Field> = Vec::new();
+    let is_entry = |f: &&Field| {
+        f.value.as_scalar().is_some()
+            && !is_dynamic(f.key.as_str())
+            && !MODIFIER_BLOCK_KEYS.iter().any(|k| f.key_is(k))
+    };
+    ctx.doc.root.visit_fields(&mut |field, ancestors| {
+        if is_modifier_block(field, ancestors) {
+            entries.extend(field.value.as_block().into_iter().flat_map(Block::fields).filter(is_entry));
+        }
+    });
+    if ctx.relative.starts_with("common/dynamic_modifiers/") {
+        for definition in ctx.doc.root.fields().filter_map(|f| f.value.as_block()) {
+            entries.extend(
+                definition
+                    .fields()
+                    .filter(is_entry)
+                    .filter(|f| !DYNAMIC_MODIFIER_KEYS.iter().any(|k| f.key_is(k))),
+            );
+        }
+    }
+    entries
+}
+
+fn check_unknown_modifier(ctx: &FileContext, out: &mut Vec<Finding>) {
+    for entry in modifier_entries(ctx) {
+        let key = entry.key.as_str();
+        if ctx.modifiers.get_exact(key).is_some() {
+            continue;
+        }
+        let fixes = match ctx.modifiers.get(key) {
+            Some(info) => vec![LintFix {
+                title: format!("改为 {}", info.name),
+                edits: vec![LintEdit {
+                    start: entry.key.span.start,
+                    end: entry.key.span.end,
+                    new_text: info.name.clone(),
+                }],
+            }],
+            None => Vec::new(),
+        };
+        out.push(Finding {
+            span: entry.key.span,
+            message: format!("未知的修正: {}", key),
+            fixes,
+        });
+    }
+}
+
+fn check_modifier_scope(ctx: &FileContext, out: &mut Vec<Finding>) {
+    let Some(expected) = expected_scopes(ctx.relative) else { return };
+    for entry in modifier_entries(ctx) {
+        let Some(info) = ctx.modifiers.get(entry.key.as_str()) else { continue };
+        // 作用域未知时不检查
+        if info.scopes.is_empty() || info.scopes.iter().any(|s| expected.contains(s)) {
+            continue;
+        }
+        let labels = |scopes: &[ModifierScope]| scopes.iter().map(|s| s.label()).collect::<Vec<_>>().join("/");
+        out.push(Finding {
+            span: entry.key.span,
+            message: format!(
+                "修正 {} 只能用于{}作用域，此处为{}",
+                info.name,
+                labels(&info.scopes),
+                labels(expected)
+            ),
+            fixes: Vec::new(),
+        });
+    }
+}
+
+/// ：国策、决议与事件的定义块。其他块中 `x`、`cost`、`visible` 等键可以合法地重复（如 `OR` 中的条件）。
+fn is_definition_block(relative: &str, field: &Field, ancestors: &[&Field]) -> bool {
+    if relative.starts_with("common/national_focus/") {
+        field.key_is("focus") || field.key_is("shared_focus")
+    } else if relative.starts_with("common/decisions/") && !relative.starts_with("common/decisions/categories/") {
+        // 分类 = { 决议 = { } }
+        ancestors.len() == 1
+    } else if relative.starts_with("events/") {
+        ancestors.is_empty() && EVENT_TYPES.iter().any(|k| field.key_is(k))
+    } else {
+        false
+    }
+}
+
+fn check_duplicate_keys(ctx: &FileContext, out: &mut Vec<Finding>) {
+    ctx.doc.root.visit_fields(&mut |field, ancestors| {
+        let Some(block) = field.value.as_block() else { return };
+        let modifier_block = is_modifier_block(field, ancestors);
+        let definition = is_definition_block(ctx.relative, field, ancestors);
+        if !modifier_block && !definition {
+            return;
+        }
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for entry in block.fields() {
+            if entry.operator != clausewitz::Operator::Eq {
+                continue;
+            }
+            let scalar = entry.value.as_scalar().is_some();
+            let unique = (definition && UNIQUE_KEYS.iter().any(|k| entry.key_is(k)))
+                || (definition && scalar && SCALAR_UNIQUE_KEYS.iter().any(|k| entry.key_is(k)))
+                || (modifier_block && scalar && !MODIFIER_BLOCK_KEYS.iter().any(|k| entry.key_is(k)));
+            if !unique {
+                continue;
+            }
+            let key = entry.key.as_str().to_lowercase();
+            match seen.get(&key) {
+                Some(first) => out.push(Finding {
+                    span: entry.key.span,
+                    message: format!("重复的键 {}（首次出现在第 {} 行）", entry.key.as_str(), ctx.lines.line(*first)),
+                    fixes: vec![remove_field_fix("删除重复的键", ctx.content, entry)],
+                }),
+                None => {
+                    seen.insert(key, entry.span.start);
+                }
+            }
+        }
+    });
+}
+
+fn is_empty_block(block: &Block) -> bool {
+    block
+        .nodes
+        .iter()
+        .all(|n| matches!(n, clausewitz::Node::Whitespace(_)))
+}
+
+fn check_empty_blocks(ctx: &FileContext, out: &mut Vec<Finding>) {
+    ctx.doc.root.visit_fields(&mut |field, _| {
+        let Some(block) = field.value.as_block() else { return };
+        // 未闭合的块由括号检查负责
+        if block.close.is_some() && is_empty_block(block) {
+            out.push(Finding {
+                span: field.span,
+                message: format!("空块: {}", field.key.as_str()),
+                fixes: vec![remove_field_fix("删除空块", ctx.content, field)],
+            });
+        }
+    });
+}
+
+/// ：事件中 title / desc / option.name 以及自定义提示引用的本地化键。
+fn is_localisation_reference(field: &Field, ancestors: &[&Field], in_events: bool) -> bool {
+    if LOCALISATION_KEYS.iter().any(|k| field.key_is(k)) {
+        return true;
+    }
+    if !in_events {
+        return false;
+    }
+    let parent = ancestors.last();
+    let parent_is = |keys: &[&str]| parent.map(|p| keys.iter().any(|k| p.key_is(k))).unwrap_or(false);
+    ((field.key_is("title") || field.key_is("desc")) && parent_is(&EVENT_TYPES))
+        || (field.key_is("text") && parent_is(&["title", "desc"]))
+        || (field.key_is("name") && parent_is(&["option"]))
+}
+
+fn check_unknown_localisation_key(ctx: &FileContext, out: &mut Vec<Finding>) {
+    let Some(symbols) = ctx.symbols else { return };
+    if !ctx.relative.ends_with(".txt") {
+        return;
+    }
+    let in_events = ctx.relative.starts_with("events/");
+    ctx.doc.root.visit_fields(&mut |field, ancestors| {
+        let Some(value) = field.value.as_scalar() else { return };
+        if is_dynamic(&value.text) || !is_localisation_reference(field, ancestors, in_events) {
+            return;
+        }
+        if !symbols.is_defined_as(&value.text, SymbolKind::LocalisationKey) {
+            out.push(Finding {
+                span: value.span,
+                message: format!("未找到本地化键: {}", value.text),
+                fixes: Vec::new(),
+            });
+        }
+    });
+}
+
+fn check_missing_sprite(ctx: &FileContext, out: &mut Vec<Finding>) {
+    let Some(symbols) = ctx.symbols else { return };
+    ctx.doc.root.visit_fields(&mut |field, _| {
+        let Some(value) = field.value.as_scalar() else { return };
+        if !value.text.starts_with("GFX_") || is_dynamic(&value.text) {
+            return;
+        }
+        if !symbols.is_defined_as(&value.text, SymbolKind::Sprite) {
+            out.push(Finding {
+                span: value.span,
+                message: format!("未定义的 sprite: {}", value.text),
+                fixes: Vec::new(),
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(rule: &'static str, relative: &str, content: &str) -> Vec<LintDiagnostic> {
+        lint_with(rule, relative, content, HashSet::new(), None)
+    }
+
+    fn lint_with(
+        rule: &'static str,
+        relative: &str,
+        content: &str,
+        tags: HashSet<String>,
+        symbols: Option<&SymbolIndex>,
+    ) -> Vec<LintDiagnostic> {
+        let env = LintEnvironment {
+            enabled: HashSet::from([rule]),
+            tags,
+            modifiers: modifier_db::load_database(None, None, None),
+            colours: localisation_lint::BUILTIN_COLOURS.chars().collect(),
+            gfx: None,
+            localisation: None,
+        };
+        lint_content(relative, relative, content, &env, symbols)
+    }
+
+    fn messages(found: &[LintDiagnostic]) -> Vec<&str> {
+        found.iter().map(|d| d.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_unknown_modifier() {
+        let content = "ideas = { country = { a = { modifier = { stability_factor = 0.1 Stability_Weekly = 1 bogus_mod = 2 } } } }\n\
+                       focus = { ai_will_do = { modifier = { factor = 0 has_war = yes } } }";
+        let found = lint("unknown-modifier", "common/ideas/a.txt", content);
+        let messages: Vec<_> = found.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, ["未知的修正: Stability_Weekly", "未知的修正: bogus_mod"]);
+        assert_eq!(found[0].fixes[0].edits[0].new_text, "stability_weekly");
+
+        let scoped = lint("modifier-scope", "common/ideas/a.txt", "a = { modifier = { local_building_slots = 1 } }");
+        assert_eq!(scoped.len(), 1);
+        assert!(scoped[0].message.contains("local_building_slots"));
+    }
+
+    #[test]
+    fn test_duplicate_and_empty_blocks() {
+        let content = "focus = {\n\tid = a\n\tx = 1\n\tx = 2\n\tcompletion_reward = { }\n}\n";
+        let duplicates = lint("duplicate-key", "common/national_focus/a.txt", content);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!((duplicates[0].line, duplicates[0].column), (4, 2));
+        let edit = &duplicates[0].fixes[0].edits[0];
+        assert_eq!(&content[edit.start..edit.end], "\tx = 2\n");
+
+        let conditional = "focus = {\n\tid = a\n\ticon = { trigger = { tag = GER } value = GFX_a }\n\ticon = { trigger = { tag = ENG } value = GFX_b }\n\tmodifier = { custom_modifier_tooltip = a_tt custom_modifier_tooltip = b_tt }\n}\n";
+        assert!(lint("duplicate-key", "common/national_focus/a.txt", conditional).is_empty());
+        let scalar_icons = lint("duplicate-key", "common/national_focus/a.txt", "focus = { icon = GFX_a icon = GFX_b }");
+        assert_eq!(scalar_icons.len(), 1);
+
+        let empty = lint("empty-block", "common/national_focus/a.txt", content);
+        assert_eq!(empty.len(), 1);
+        assert_eq!(empty[0].severity, LintSeverity::Info);
+        assert_eq!(empty[0].line, 5);
+    }
+
+    #[test]
+    fn test_duplicate_keys_only_in_definitions() {
+        // 触发器中重复的条件键不是错误
+        let trigger = "a = { OR = { visible = { tag = GER } visible = { tag = ENG } } x = 1 x = 2 }";
+        assert!(lint("duplicate-key", "common/scripted_triggers/a.txt", trigger).is_empty());
+
+        let decision = "category = {\n\tdecision = {\n\t\tcost = 10\n\t\tcost = 20\n\t}\n}\n";
+        assert_eq!(lint("duplicate-key", "common/decisions/a.txt", decision).len(), 1);
+        assert!(lint("duplicate-key", "common/decisions/categories/a.txt", decision).is_empty());
+
+        let event = "country_event = {\n\tid = a.1\n\tid = a.2\n\toption = { country_event = { id = a.3 days = 1 } }\n}\n";
+        let found = lint("duplicate-key", "events/a.txt", event);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].line, 3);
+    }
+
+    #[test]
+    fn test_undefined_tag() {
+        let tags = HashSet::from(["GER".to_string()]);
+        let content = "my_effect = {\n\tGER = { add_stability = 0.1 }\n\tXYZ = { add_stability = 0.1 }\n}\n";
+        let found = lint_with("undefined-tag", "common/scripted_effects/a.txt", content, tags.clone(), None);
+        assert_eq!(found.len(), 1);
+        assert_eq!(&content[found[0].start..found[0].end], "XYZ");
+        // 标签未能加载时不报告
+        assert!(lint("undefined-tag", "common/scripted_effects/a.txt", content).is_empty());
+    }
+
+    #[test]
+    fn test_undefined_idea() {
+        let symbols = SymbolIndex::from_project_files(&[(
+            "common/ideas/a.txt",
+            "ideas = { country = { known_idea = { } } }",
+        )]);
+        let content = "effect = { add_ideas = { known_idea unknown_idea } add_ideas = other_idea add_ideas = [?var] }";
+        let found = lint_with("undefined-idea", "common/national_focus/a.txt", content, HashSet::new(), Some(&symbols));
+        assert_eq!(messages(&found), ["未定义的 idea: unknown_idea", "未定义的 idea: other_idea"]);
+    }
+
+    #[test]
+    fn test_unknown_localisation_key() {
+        let symbols = SymbolIndex::from_project_files(&[(
+            "localisation/a_l_english.yml",
+            "\u{feff}l_english:\n a.1.t:0 \"Title\"\n known_tt:0 \"Tip\"\n",
+        )]);
+        let event = "country_event = {\n\tid = a.1\n\ttitle = a.1.t\n\tdesc = a.1.d\n\toption = { name = a.1.a custom_effect_tooltip = known_tt }\n}\n";
+        let found = lint_with("unknown-localisation-key", "events/a.txt", event, HashSet::new(), Some(&symbols));
+        assert_eq!(messages(&found), ["未找到本地化键: a.1.d", "未找到本地化键: a.1.a"]);
+
+        // 事件以外只检查自定义提示
+        let focus = "focus = { id = a title = missing_title custom_effect_tooltip = missing_tt }";
+        let found = lint_with("unknown-localisation-key", "common/national_focus/a.txt", focus, HashSet::new(), Some(&symbols));
+        assert_eq!(messages(&found), ["未找到本地化键: missing_tt"]);
+    }
+
+    #[test]
+    fn test_missing_sprite() {
+        let symbols = SymbolIndex::from_project_files(&[(
+            "interface/a.gfx",
+            "spriteTypes = { spriteType = { name = GFX_known texturefile = a.dds } }",
+        )]);
+        let content = "focus = { id = a icon = GFX_known }\nfocus = { id = b icon = GFX_missing }\n";
+        let found = lint_with("missing-sprite", "common/national_focus/a.txt", content, HashSet::new(), Some(&symbols));
+        assert_eq!(messages(&found), ["未定义的 sprite: GFX_missing"]);
+    }
+}
@@ -0,0 +1,492 @@
+#![deny(clippy::unwrap_used)]
+
+//! 脚本化效果与脚本化触发器
+//!
+//! 索引 `common/scripted_effects/*.txt` 与 `common/scripted_triggers/*.txt` 中的定义，
+//! 记录每个定义调用的其他脚本化效果/触发器以及 `$PARAM$` 形式的参数，
+//! 并报告递归调用、从未使用的定义与调用未定义的脚本化效果/触发器。
+//!
+//! 没有内置效果/触发器列表可供比对，未定义调用只识别带参数的写法
+//! `name = { PARAM = value }`（键含小写字母、块内全部是大写键的标量赋值）。
+
+use crate::clausewitz::{self, Field, LineIndex, Value};
+use crate::file_cache::{self, FileSetCache, FileStamps};
+use crate::graph::find_cycles;
+use crate::load_order::{self, LoadOrderRoot, RootSource};
+use crate::symbol_index::{self, IndexRoots};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+const EFFECTS_DIR: &str = "common/scripted_effects";
+const TRIGGERS_DIR: &str = "common/scripted_triggers";
+
+/// ：效果内部以触发器求值的块。
+const TRIGGER_BLOCKS: [&str; 2] = ["limit", "trigger"];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum ScriptedKind {
+    Effect,
+    Trigger,
+}
+
+impl ScriptedKind {
+    fn label(self) -> &'static str {
+        match self {
+            ScriptedKind::Effect => "脚本化效果",
+            ScriptedKind::Trigger => "脚本化触发器",
+        }
+    }
+}
+
+/// ：定义体内对另一个脚本化效果/触发器的调用。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptedCall {
+    pub name: String,
+    pub kind: ScriptedKind,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptedEntry {
+    pub name: String,
+    pub kind: ScriptedKind,
+    pub source: RootSource,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub calls: Vec<ScriptedCall>,
+    /// 定义体中出现的 `$PARAM$` / `[[PARAM]` 参数名，按首次出现排序
+    pub parameters: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ScriptedWarningKind {
+    Recursion,
+    Unused,
+    Undefined,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptedWarning {
+    pub kind: ScriptedWarningKind,
+    pub name: String,
+    pub message: String,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptedLoadResponse {
+    pub success: bool,
+    pub message: String,
+    pub entries: Option<Vec<ScriptedEntry>>,
+    pub warnings: Vec<ScriptedWarning>,
+}
+
+/// 加载脚本化效果与脚本化触发器
+#[tauri::command]
+pub fn load_scripted_definitions(
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> ScriptedLoadResponse {
+    let roots = load_order::load_order(project_root.as_deref(), dependency_roots.clone(), game_root.as_deref());
+    if roots.is_empty() {
+        return ScriptedLoadResponse {
+            success: false,
+            message: "未提供项目或游戏目录".to_string(),
+            entries: None,
+            warnings: Vec::new(),
+        };
+    }
+
+    let loaded = load_all(&roots);
+    // 没有游戏目录时原版定义缺失，不报告未定义调用
+    let has_game = game_root.as_deref().is_some_and(|g| !g.trim().is_empty());
+    let names: HashSet<&str> = loaded
+        .entries
+        .iter()
+        .filter(|e| e.source != RootSource::Game)
+        .map(|e| e.name.as_str())
+        .collect();
+    let index_roots = IndexRoots::new(project_root, game_root, dependency_roots);
+    let sites = symbol_index::with_index(&index_roots, |index| index.reference_sites(&names))
+        .map(|(sites, _)| sites)
+        .ok();
+    let warnings = collect_warnings(&loaded, sites.as_ref(), has_game);
+
+    let effects = loaded.entries.iter().filter(|e| e.kind == ScriptedKind::Effect).count();
+    ScriptedLoadResponse {
+        success: true,
+        message: format!(
+            "共 {} 个脚本化效果、{} 个脚本化触发器，{} 条警告",
+            effects,
+            loaded.entries.len() - effects,
+            warnings.len()
+        ),
+        entries: Some(loaded.entries.clone()),
+        warnings,
+    }
+}
+
+// ==================== 收集 ====================
+
+#[derive(Debug, Default)]
+struct LoadedScripted {
+    entries: Vec<ScriptedEntry>,
+    /// 调用未定义名称的带参数写法，按所在定义记录
+    undefined: Vec<(usize, CallCandidate)>,
+}
+
+static SCRIPTED_CACHE: Lazy<FileSetCache<LoadedScripted>> = Lazy::new(FileSetCache::new);
+
+/// ：按加载顺序收集生效文件（同一相对路径只取优先级最高的根目录），文件未变化时复用缓存。
+fn load_all(roots: &[LoadOrderRoot]) -> Arc<LoadedScripted> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut files: FileStamps = Vec::new();
+    let mut meta: Vec<(ScriptedKind, RootSource)> = Vec::new();
+    for (index, root) in roots.iter().enumerate() {
+        for (dir, kind) in [(EFFECTS_DIR, ScriptedKind::Effect), (TRIGGERS_DIR, ScriptedKind::Trigger)] {
+            if load_order::blanked_by(roots, index, dir).is_some() {
+                continue;
+            }
+            let base = Path::new(&root.path).join(dir);
+            let Ok(entries) = fs::read_dir(&base) else { continue };
+            let mut paths: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+            paths.sort();
+            for path in paths {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+                if !path.is_file() || !name.ends_with(".txt") || !seen.insert(format!("{}/{}", dir, name)) {
+                    continue;
+                }
+                files.push((path.to_string_lossy().replace('\\', "/"), file_cache::modified(&path)));
+                meta.push((kind, root.source));
+            }
+        }
+    }
+
+    SCRIPTED_CACHE.get_or_build(file_cache::roots_key(roots), files, |files| {
+        let parsed: Vec<Vec<ParsedDefinition>> = files
+            .par_iter()
+            .zip(meta.par_iter())
+            .map(|((path, _), (kind, source))| {
+                let content = fs::read(path)
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                    .unwrap_or_default();
+                parse_file(path, &content, *kind, *source)
+            })
+            .collect();
+        resolve_calls(parsed.into_iter().flatten().collect())
+    })
+}
+
+// ==================== 解析 ====================
+
+/// ：可能是调用的字段：`name = yes/no` 或 `name = { ... }`。
+#[derive(Debug, Clone)]
+struct CallCandidate {
+    name: String,
+    /// 处于触发器上下文（触发器定义内，或效果的 `limit` / `trigger` 块内）
+    trigger_context: bool,
+    /// `name = { PARAM = value }` 形式
+    parameter_call: bool,
+    start: usize,
+    end: usize,
+    line: usize,
+}
+
+#[derive(Debug)]
+struct ParsedDefinition {
+    entry: ScriptedEntry,
+    candidates: Vec<CallCandidate>,
+}
+
+fn parse_file(file: &str, content: &str, kind: ScriptedKind, source: RootSource) -> Vec<ParsedDefinition> {
+    let doc = clausewitz::parse(content);
+    let index = LineIndex::new(content);
+    doc.root
+        .fields()
+        .filter_map(|field| {
+            let body = field.value.as_block()?;
+            let mut candidates = Vec::new();
+            body.visit_fields(&mut |inner: &Field, ancestors: &[&Field]| {
+                let parameter_call = match &inner.value {
+                    Value::Scalar(s) if s.eq_ignore_case("yes") || s.eq_ignore_case("no") => false,
+                    Value::Block(b) => is_parameter_block(b),
+                    _ => return,
+                };
+                candidates.push(CallCandidate {
+                    name: inner.key.as_str().to_string(),
+                    trigger_context: kind == ScriptedKind::Trigger
+                        || ancestors.iter().any(|a| TRIGGER_BLOCKS.iter().any(|k| a.key_is(k))),
+                    parameter_call,
+                    start: inner.key.span.start,
+                    end: inner.key.span.end,
+                    line: index.line(inner.key.span.start),
+                });
+            });
+            Some(ParsedDefinition {
+                entry: ScriptedEntry {
+                    name: field.key.as_str().to_string(),
+                    kind,
+                    source,
+                    file: file.to_string(),
+                    start: field.span.start,
+                    end: field.span.end,
+                    line: index.line(field.span.start),
+                    calls: Vec::new(),
+                    parameters: parameters(body.inner_span().slice(content)),
+                },
+                candidates,
+            })
+        })
+        .collect()
+}
+
+/// ：非空且全部是大写键的标量赋值，例如 `{ TAG = GER AMOUNT = 5 }`。
+fn is_parameter_block(block: &clausewitz::Block) -> bool {
+    let mut fields = block.fields().peekable();
+    fields.peek().is_some()
+        && block.nodes.iter().all(|n| !matches!(n, clausewitz::Node::Value(_)))
+        && fields.all(|f| {
+            matches!(f.value, Value::Scalar(_))
+                && f.key.as_str().chars().any(|c| c.is_ascii_uppercase())
+                && !f.key.as_str().chars().any(|c| c.is_ascii_lowercase())
+        })
+}
+
+/// ：`$NAME$`、`$NAME|默认值$` 与条件块 `[[NAME]` / `[[!NAME]` 中的参数名。
+fn parameters(text: &str) -> Vec<String> {
+    let is_name = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let mut names: Vec<String> = Vec::new();
+    let mut push = |name: &str| {
+        if is_name(name) && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    };
+
+    let mut rest = text;
+    while let Some(open) = rest.find('$') {
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('$') else { break };
+        let inner = &after[..close];
+        let name = inner.split('|').next().unwrap_or_default();
+        if is_name(name) {
+            push(name);
+            rest = &after[close + 1..];
+        } else {
+            // 不是参数（例如跨行的孤立 `$`），从下一个 `$` 重新开始
+            rest = after;
+        }
+    }
+
+    let mut rest = text;
+    while let Some(open) = rest.find("[[") {
+        let after = &rest[open + 2..];
+        if let Some(close) = after.find(']') {
+            push(after[..close].trim_start_matches('!'));
+        }
+        rest = after;
+    }
+    names
+}
+
+/// ：把候选字段解析为调用。优先匹配上下文对应的类型，其次是另一种类型。
+fn resolve_calls(parsed: Vec<ParsedDefinition>) -> LoadedScripted {
+    let mut defined: HashMap<ScriptedKind, HashSet<String>> = HashMap::new();
+    for definition in &parsed {
+        defined
+            .entry(definition.entry.kind)
+            .or_default()
+            .insert(definition.entry.name.clone());
+    }
+    let is_defined = |kind: ScriptedKind, name: &str| defined.get(&kind).is_some_and(|s| s.contains(name));
+
+    let mut loaded = LoadedScripted::default();
+    for (position, definition) in parsed.into_iter().enumerate() {
+        let mut entry = definition.entry;
+        for candidate in definition.candidates {
+            let (preferred, other) = if candidate.trigger_context {
+                (ScriptedKind::Trigger, ScriptedKind::Effect)
+            } else {
+                (ScriptedKind::Effect, ScriptedKind::Trigger)
+            };
+            let kind = [preferred, other].into_iter().find(|k| is_defined(*k, &candidate.name));
+            match kind {
+                Some(kind) => entry.calls.push(ScriptedCall {
+                    name: candidate.name,
+                    kind,
+                    start: candidate.start,
+                    end: candidate.end,
+                    line: candidate.line,
+                }),
+                None if candidate.parameter_call && candidate.name.chars().any(|c| c.is_ascii_lowercase()) => {
+                    loaded.undefined.push((position, candidate));
+                }
+                None => {}
+            }
+        }
+        loaded.entries.push(entry);
+    }
+    loaded
+}
+
+// ==================== 警告 ====================
+
+/// ：`sites` 为项目与依赖项中的引用位置，缺失时不检查未使用的定义。
+fn collect_warnings(
+    loaded: &LoadedScripted,
+    sites: Option<&HashMap<String, Vec<(String, usize)>>>,
+    check_undefined: bool,
+) -> Vec<ScriptedWarning> {
+    let mut warnings = Vec::new();
+    let first_of: HashMap<&str, &ScriptedEntry> = loaded
+        .entries
+        .iter()
+        .rev()
+        .map(|e| (e.name.as_str(), e))
+        .collect();
+
+    let mut edges: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for entry in loaded.entries.iter().filter(|e| e.source != RootSource::Game) {
+        edges
+            .entry(entry.name.as_str())
+            .or_default()
+            .extend(entry.calls.iter().map(|c| c.name.as_str()));
+    }
+    for cycle in find_cycles(&edges) {
+        let Some(entry) = cycle.first().and_then(|name| first_of.get(name.as_str())) else { continue };
+        warnings.push(warning(
+            ScriptedWarningKind::Recursion,
+            entry,
+            format!("{}递归调用：{}", entry.kind.label(), cycle.join(" → ")),
+        ));
+    }
+
+    if let Some(sites) = sites {
+        let game_names: HashSet<&str> = loaded
+            .entries
+            .iter()
+            .filter(|e| e.source == RootSource::Game)
+            .map(|e| e.name.as_str())
+            .collect();
+        for entry in loaded.entries.iter().filter(|e| e.source != RootSource::Game) {
+            // 覆盖原版定义时由原版脚本调用，无法判断
+            if game_names.contains(entry.name.as_str()) {
+                continue;
+            }
+            let called = loaded
+                .entries
+                .iter()
+                .any(|other| other.name != entry.name && other.calls.iter().any(|c| c.name == entry.name));
+            let referenced = sites.get(&entry.name).into_iter().flatten().any(|(file, start)| {
+                !(*file == entry.file && (entry.start..entry.end).contains(start))
+            });
+            if !called && !referenced {
+                warnings.push(warning(
+                    ScriptedWarningKind::Unused,
+                    entry,
+                    format!("{} {} 从未被使用", entry.kind.label(), entry.name),
+                ));
+            }
+        }
+    }
+
+    if check_undefined {
+        for (position, candidate) in &loaded.undefined {
+            let Some(caller) = loaded.entries.get(*position) else { continue };
+            if caller.source == RootSource::Game {
+                continue;
+            }
+            warnings.push(ScriptedWarning {
+                kind: ScriptedWarningKind::Undefined,
+                name: candidate.name.clone(),
+                message: format!("{} 调用了未定义的 {}", caller.name, candidate.name),
+                file: caller.file.clone(),
+                start: candidate.start,
+                end: candidate.end,
+                line: candidate.line,
+            });
+        }
+    }
+    warnings
+}
+
+fn warning(kind: ScriptedWarningKind, entry: &ScriptedEntry, message: String) -> ScriptedWarning {
+    ScriptedWarning {
+        kind,
+        name: entry.name.clone(),
+        message,
+        file: entry.file.clone(),
+        start: entry.start,
+        end: entry.end,
+        line: entry.line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EFFECTS: &str = r#"
+give_bonus = {
+    add_political_power = $AMOUNT|50$
+    [[!SILENT] log = "bonus" ]
+    if = { limit = { is_ready = yes } chain_a = yes }
+}
+chain_a = { chain_b = yes }
+chain_b = { chain_a = yes }
+lonely_effect = { missing_effect = { TAG = GER } }
+"#;
+
+    const TRIGGERS: &str = "is_ready = { has_war = no }\n";
+
+    fn load() -> LoadedScripted {
+        let mut parsed = parse_file("/mod/common/scripted_effects/a.txt", EFFECTS, ScriptedKind::Effect, RootSource::Project);
+        parsed.extend(parse_file("/mod/common/scripted_triggers/a.txt", TRIGGERS, ScriptedKind::Trigger, RootSource::Project));
+        resolve_calls(parsed)
+    }
+
+    #[test]
+    fn test_calls_and_parameters() {
+        let loaded = load();
+        let bonus = &loaded.entries[0];
+        assert_eq!(bonus.name, "give_bonus");
+        assert_eq!(bonus.parameters, vec!["AMOUNT", "SILENT"]);
+        let calls: Vec<(&str, ScriptedKind)> = bonus.calls.iter().map(|c| (c.name.as_str(), c.kind)).collect();
+        assert_eq!(calls, vec![("is_ready", ScriptedKind::Trigger), ("chain_a", ScriptedKind::Effect)]);
+        assert_eq!(loaded.undefined.len(), 1);
+        assert_eq!(loaded.undefined[0].1.name, "missing_effect");
+    }
+
+    #[test]
+    fn test_warnings() {
+        let loaded = load();
+        let sites: HashMap<String, Vec<(String, usize)>> = HashMap::from([(
+            "give_bonus".to_string(),
+            vec![("/mod/events/a.txt".to_string(), 10)],
+        )]);
+        let warnings = collect_warnings(&loaded, Some(&sites), true);
+        let of = |kind| warnings.iter().filter(|w| w.kind == kind).map(|w| w.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(of(ScriptedWarningKind::Recursion), vec!["chain_a"]);
+        assert_eq!(of(ScriptedWarningKind::Unused), vec!["lonely_effect"]);
+        assert_eq!(of(ScriptedWarningKind::Undefined), vec!["missing_effect"]);
+        assert!(warnings[0].message.contains("chain_a → chain_b → chain_a"));
+    }
+}
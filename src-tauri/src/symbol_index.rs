@@ -0,0 +1,1192 @@
+#![deny(clippy::unwrap_used)]
+
+//! 项目符号索引
+//!
+//! 为项目、依赖项与游戏目录中的国策、idea、事件、决议、脚本效果/触发器、
+//! on_action、人物、sprite、GUI 窗口、本地化键、州、国家标签与 MIO 特质建立统一索引，
+//! 提供跳转到定义与查找引用。索引按文件 mtime 增量更新，并持久化到缓存目录。
+
+use crate::clausewitz::{self, Block, LineIndex, Node, Scalar, Value};
+use crate::file_cache::{game_root_stamps, FileSetCache};
+use crate::gui_engine::{SPRITE_TYPES, WINDOW_TYPES};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// ：符号类型。
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum SymbolKind {
+    Focus,
+    Idea,
+    Event,
+    Decision,
+    ScriptedEffect,
+    ScriptedTrigger,
+    OnAction,
+    Character,
+    Sprite,
+    GuiWindow,
+    LocalisationKey,
+    State,
+    CountryTag,
+    MioTrait,
+}
+
+/// ：符号来源，与 TagSource / IdeaSource 保持一致。
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolSource {
+    Project,
+    Game,
+    Dependency,
+}
+
+impl SymbolSource {
+    /// ：覆盖优先级，数值越大越优先（Project > Dependency > Game）。
+    pub fn priority(self) -> u8 {
+        match self {
+            SymbolSource::Game => 0,
+            SymbolSource::Dependency => 1,
+            SymbolSource::Project => 2,
+        }
+    }
+}
+
+/// ：符号定义位置，行列号从 1 开始，start/end 为名称所在的字节区间。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolDefinition {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+    pub source: SymbolSource,
+}
+
+/// ：符号引用位置。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolReference {
+    pub name: String,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+    pub source: SymbolSource,
+}
+
+/// ：构建索引的返回值。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolIndexResponse {
+    pub success: bool,
+    pub message: String,
+    pub file_count: usize,
+    pub counts: HashMap<SymbolKind, usize>,
+}
+
+/// ：跳转定义 / 查找引用的返回值。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolLookupResponse {
+    pub success: bool,
+    pub message: String,
+    pub symbol: Option<String>,
+    pub definitions: Vec<SymbolDefinition>,
+    pub references: Vec<SymbolReference>,
+}
+
+/// ：单个文件中的符号，位置信息相对该文件。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedSymbol {
+    name: String,
+    kind: SymbolKind,
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+/// ：单个文件中的引用。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedReference {
+    name: String,
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+/// ：文件级索引条目，按 mtime 判断是否需要重新解析。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileIndex {
+    source: SymbolSource,
+    modified: Option<SystemTime>,
+    symbols: Vec<IndexedSymbol>,
+    references: Vec<IndexedReference>,
+}
+
+/// ：某组根目录对应的完整索引。
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct SymbolIndex {
+    files: HashMap<String, FileIndex>,
+    /// 名称 -> (文件, 符号下标)，加载后重建，不落盘
+    #[serde(skip)]
+    by_name: HashMap<String, Vec<(String, usize)>>,
+}
+
+/// ：全局索引容器，key 为“项目路径||游戏路径||依赖项路径”。
+static SYMBOL_INDEX: Lazy<RwLock<HashMap<String, SymbolIndex>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// ：参与索引的顶层目录。
+const INDEXED_DIRS: [&str; 6] = ["common", "events", "gfx", "history", "interface", "localisation"];
+
+/// ：游戏目录的文件列表，只在 launcher-settings.json 或各索引目录自身的 mtime 变化时重新遍历。
+static GAME_FILES: Lazy<FileSetCache<Vec<IndexFileInfo>>> = Lazy::new(FileSetCache::new);
+
+/// ：游戏目录本地化体量很大，只索引以下语言。
+const GAME_LOCALISATION_LANGUAGES: [&str; 2] = ["english", "simp_chinese"];
+
+const EVENT_TYPES: [&str; 5] = [
+    "country_event",
+    "news_event",
+    "state_event",
+    "unit_leader_event",
+    "operative_leader_event",
+];
+
+/// ：候选文件信息。
+#[derive(Debug, Clone)]
+struct IndexFileInfo {
+    path: String,
+    relative: String,
+    source: SymbolSource,
+    modified: Option<SystemTime>,
+    /// 位于压缩包形式的依赖项中时为压缩包路径，`modified` 取压缩包的 mtime
+    archive: Option<String>,
+}
+
+/// ：构建（或增量刷新）符号索引，返回各类符号数量。
+#[tauri::command]
+pub fn build_symbol_index(
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> SymbolIndexResponse {
+    let roots = IndexRoots::new(project_root, game_root, dependency_roots);
+    let result = with_index(&roots, |index| {
+        let mut counts: HashMap<SymbolKind, usize> = HashMap::new();
+        for file in index.files.values() {
+            for symbol in &file.symbols {
+                *counts.entry(symbol.kind).or_insert(0) += 1;
+            }
+        }
+        (index.files.len(), counts)
+    });
+
+    match result {
+        Ok(((file_count, counts), updated)) => {
+            let total: usize = counts.values().sum();
+            SymbolIndexResponse {
+                success: true,
+                message: format!(
+                    "索引完成，{} 个文件（更新 {} 个），共 {} 个符号",
+                    file_count, updated, total
+                ),
+                file_count,
+                counts,
+            }
+        }
+        Err(err) => SymbolIndexResponse {
+            success: false,
+            message: err,
+            file_count: 0,
+            counts: HashMap::new(),
+        },
+    }
+}
+
+/// ：跳转到定义。`content` 为编辑器中尚未保存的内容，缺省时读取文件。
+#[tauri::command]
+pub fn goto_definition(
+    file: String,
+    offset: usize,
+    content: Option<String>,
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> SymbolLookupResponse {
+    let content = match content {
+        Some(c) => c,
+        None => match fs::read(&file) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(e) => return lookup_failure(format!("读取文件失败: {}", e)),
+        },
+    };
+
+    let Some(symbol) = symbol_at(&file, &content, offset) else {
+        return lookup_failure("光标处没有可识别的符号".to_string());
+    };
+
+    let roots = IndexRoots::new(project_root, game_root, dependency_roots);
+    match with_index(&roots, |index| collect_definitions(index, &symbol)) {
+        Ok((definitions, _)) => SymbolLookupResponse {
+            success: true,
+            message: if definitions.is_empty() {
+                format!("未找到 {} 的定义", symbol)
+            } else {
+                format!("找到 {} 处定义", definitions.len())
+            },
+            symbol: Some(symbol),
+            definitions,
+            references: Vec::new(),
+        },
+        Err(err) => lookup_failure(err),
+    }
+}
+
+/// ：查找符号的全部引用（项目与依赖项内），同时返回其定义。
+#[tauri::command]
+pub fn find_references(
+    symbol: String,
+    project_root: Option<String>,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+) -> SymbolLookupResponse {
+    let symbol = symbol.trim().to_string();
+    if symbol.is_empty() {
+        return lookup_failure("符号名称为空".to_string());
+    }
+
+    let roots = IndexRoots::new(project_root, game_root, dependency_roots);
+    let result = with_index(&roots, |index| {
+        let definitions = collect_definitions(index, &symbol);
+        let mut references: Vec<SymbolReference> = Vec::new();
+        for (path, file) in &index.files {
+            for r in file.references.iter().filter(|r| r.name == symbol) {
+                // 定义处本身也会被记为引用，这里剔除
+                let is_definition = definitions
+                    .iter()
+                    .any(|d| &d.file == path && d.start == r.start);
+                if !is_definition {
+                    references.push(SymbolReference {
+                        name: r.name.clone(),
+                        file: path.clone(),
+                        line: r.line,
+                        column: r.column,
+                        start: r.start,
+                        end: r.end,
+                        source: file.source,
+                    });
+                }
+            }
+        }
+        references.sort_by(|a, b| a.file.cmp(&b.file).then(a.start.cmp(&b.start)));
+        (definitions, references)
+    });
+
+    match result {
+        Ok(((definitions, references), _)) => SymbolLookupResponse {
+            success: true,
+            message: format!("找到 {} 处引用", references.len()),
+            symbol: Some(symbol),
+            definitions,
+            references,
+        },
+        Err(err) => lookup_failure(err),
+    }
+}
+
+fn lookup_failure(message: String) -> SymbolLookupResponse {
+    SymbolLookupResponse {
+        success: false,
+        message,
+        symbol: None,
+        definitions: Vec::new(),
+        references: Vec::new(),
+    }
+}
+
+/// ：按来源优先级排序后的定义列表。
+fn collect_definitions(index: &SymbolIndex, name: &str) -> Vec<SymbolDefinition> {
+    let mut definitions: Vec<SymbolDefinition> = index
+        .by_name
+        .get(name)
+        .into_iter()
+        .flatten()
+        .filter_map(|(path, idx)| {
+            let file = index.files.get(path)?;
+            let s = file.symbols.get(*idx)?;
+            Some(SymbolDefinition {
+                name: s.name.clone(),
+                kind: s.kind,
+                file: path.clone(),
+                line: s.line,
+                column: s.column,
+                start: s.start,
+                end: s.end,
+                source: file.source,
+            })
+        })
+        .collect();
+    definitions.sort_by(|a, b| {
+        b.source
+            .priority()
+            .cmp(&a.source.priority())
+            .then(a.file.cmp(&b.file))
+            .then(a.start.cmp(&b.start))
+    });
+    definitions
+}
+
+// ==================== 供重构命令使用 ====================
+
+/// ：某名称在文件中的一次出现（定义或引用），区间不含引号。
+#[derive(Debug, Clone)]
+pub(crate) struct Occurrence {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SymbolIndex {
+    /// ：名称在任一来源中是否有定义。
+    pub(crate) fn is_defined(&self, name: &str) -> bool {
+        self.by_name.contains_key(name)
+    }
+
+    /// ：名称是否有指定类型的定义。
+    pub(crate) fn is_defined_as(&self, name: &str, kind: SymbolKind) -> bool {
+        self.by_name.get(name).into_iter().flatten().any(|(path, idx)| {
+            self.files
+                .get(path)
+                .and_then(|f| f.symbols.get(*idx))
+                .map(|s| s.kind == kind)
+                .unwrap_or(false)
+        })
+    }
+
+    pub(crate) fn definitions_of(&self, name: &str) -> Vec<SymbolDefinition> {
+        collect_definitions(self, name)
+    }
+
+    /// ：指定来源中含有满足条件的定义或引用的文件。
+    pub(crate) fn files_mentioning(&self, source: SymbolSource, matches: impl Fn(&str) -> bool) -> Vec<String> {
+        let mut files: Vec<String> = self
+            .files
+            .iter()
+            .filter(|(_, f)| f.source == source)
+            .filter(|(_, f)| {
+                f.symbols.iter().any(|s| matches(&s.name)) || f.references.iter().any(|r| matches(&r.name))
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// ：直接由内存中的项目文件（相对路径, 内容）建立索引，路径为 `/mod/<相对路径>`，供测试使用。
+    #[cfg(test)]
+    pub(crate) fn from_project_files(files: &[(&str, &str)]) -> Self {
+        let mut index = SymbolIndex::default();
+        for (relative, content) in files {
+            let info = IndexFileInfo {
+                path: format!("/mod/{}", relative),
+                relative: relative.to_string(),
+                source: SymbolSource::Project,
+                modified: None,
+                archive: None,
+            };
+            index.files.insert(info.path.clone(), index_file(&info, content));
+        }
+        rebuild_name_table(&mut index);
+        index
+    }
+
+    /// ：给定名称在项目与依赖项中的全部引用位置，名称 -> [(文件, 起始偏移)]。
+    pub(crate) fn reference_sites(&self, names: &HashSet<&str>) -> HashMap<String, Vec<(String, usize)>> {
+        let mut sites: HashMap<String, Vec<(String, usize)>> = HashMap::new();
+        for (path, file) in &self.files {
+            for reference in file.references.iter().filter(|r| names.contains(r.name.as_str())) {
+                sites
+                    .entry(reference.name.clone())
+                    .or_default()
+                    .push((path.clone(), reference.start));
+            }
+        }
+        sites
+    }
+}
+
+/// ：按索引规则重新扫描一份内容，返回全部定义与引用（按位置排序、去重）。
+pub(crate) fn scan_occurrences(root: &str, path: &str, content: &str) -> Vec<Occurrence> {
+    let relative = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .trim_start_matches('/')
+        .to_lowercase();
+    let info = IndexFileInfo {
+        path: path.to_string(),
+        relative,
+        source: SymbolSource::Project,
+        modified: None,
+        archive: None,
+    };
+    let file = index_file(&info, content);
+
+    let mut occurrences: Vec<Occurrence> = file
+        .symbols
+        .into_iter()
+        .map(|s| Occurrence {
+            name: s.name,
+            start: s.start,
+            end: s.end,
+            line: s.line,
+            column: s.column,
+        })
+        .chain(file.references.into_iter().map(|r| Occurrence {
+            name: r.name,
+            start: r.start,
+            end: r.end,
+            line: r.line,
+            column: r.column,
+        }))
+        .collect();
+    occurrences.sort_by_key(|o| o.start);
+    occurrences.dedup_by_key(|o| o.start);
+    occurrences
+}
+
+// ==================== 索引维护 ====================
+
+/// ：规范化后的根目录集合。
+pub(crate) struct IndexRoots {
+    pub(crate) project: Option<String>,
+    game: Option<String>,
+    dependencies: Vec<String>,
+}
+
+impl IndexRoots {
+    pub(crate) fn new(project: Option<String>, game: Option<String>, dependencies: Option<Vec<String>>) -> Self {
+        IndexRoots {
+            project: normalize_root(project.as_deref()),
+            game: normalize_root(game.as_deref()),
+            dependencies: dependencies
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|s| normalize_root(Some(&s)))
+                .collect(),
+        }
+    }
+
+    fn cache_key(&self) -> String {
+        format!(
+            "{}||{}||{}",
+            self.project.clone().unwrap_or_default(),
+            self.game.clone().unwrap_or_default(),
+            self.dependencies.join("|")
+        )
+    }
+}
+
+/// 规范化根路径，兼容空字符串以及去除多余分隔符。
+fn normalize_root(path: Option<&str>) -> Option<String> {
+    let raw = path?.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    let replaced = raw.replace('\\', "/");
+    let without_tail = replaced.trim_end_matches('/');
+    if without_tail.is_empty() {
+        None
+    } else {
+        Some(without_tail.to_string())
+    }
+}
+
+/// ：刷新索引后在读锁下执行查询，额外返回本次重新解析的文件数。
+pub(crate) fn with_index<T>(roots: &IndexRoots, query: impl FnOnce(&SymbolIndex) -> T) -> Result<(T, usize), String> {
+    let key = roots.cache_key();
+    let files = collect_file_infos(roots);
+
+    // 只读检查：全部文件未变化时直接查询
+    {
+        let cache = SYMBOL_INDEX.read().map_err(|_| "符号索引被锁定".to_string())?;
+        if let Some(index) = cache.get(&key) {
+            if is_index_current(index, &files) {
+                return Ok((query(index), 0));
+            }
+        }
+    }
+
+    let mut cache = SYMBOL_INDEX.write().map_err(|_| "符号索引被锁定".to_string())?;
+    let index = cache
+        .entry(key.clone())
+        .or_insert_with(|| load_persisted(&key).unwrap_or_default());
+
+    let updated = refresh_index(index, &files);
+    if updated > 0 || index.by_name.is_empty() {
+        rebuild_name_table(index);
+    }
+    if updated > 0 {
+        persist(&key, index);
+    }
+    Ok((query(index), updated))
+}
+
+fn is_index_current(index: &SymbolIndex, files: &[IndexFileInfo]) -> bool {
+    index.files.len() == files.len()
+        && files.iter().all(|info| {
+            index
+                .files
+                .get(&info.path)
+                .map(|f| f.modified == info.modified && f.source == info.source)
+                .unwrap_or(false)
+        })
+}
+
+/// ：重新解析 mtime 变化或新增的文件，移除已删除的文件，返回重新解析的数量。
+fn refresh_index(index: &mut SymbolIndex, files: &[IndexFileInfo]) -> usize {
+    let stale: Vec<&IndexFileInfo> = files
+        .iter()
+        .filter(|info| {
+            index
+                .files
+                .get(&info.path)
+                .map(|f| f.modified != info.modified || f.source != info.source)
+                .unwrap_or(true)
+        })
+        .collect();
+
+    let (archived, plain): (Vec<&IndexFileInfo>, Vec<&IndexFileInfo>) =
+        stale.into_iter().partition(|info| info.archive.is_some());
+    let mut parsed: Vec<(String, FileIndex)> = plain
+        .par_iter()
+        .filter_map(|info| {
+            let bytes = fs::read(&info.path).ok()?;
+            let content = String::from_utf8_lossy(&bytes);
+            Some((info.path.clone(), index_file(info, &content)))
+        })
+        .collect();
+    parsed.extend(index_archived(&archived));
+
+    let alive: std::collections::HashSet<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    let before = index.files.len();
+    index.files.retain(|path, _| alive.contains(path.as_str()));
+    let removed = before - index.files.len();
+
+    let updated = parsed.len();
+    for (path, file) in parsed {
+        index.files.insert(path, file);
+    }
+    updated + removed
+}
+
+/// ：每个压缩包只打开一次，读取其中需要重新解析的文件。
+fn index_archived(files: &[&IndexFileInfo]) -> Vec<(String, FileIndex)> {
+    let mut by_archive: HashMap<&str, HashMap<&str, &IndexFileInfo>> = HashMap::new();
+    for info in files {
+        if let Some(archive) = &info.archive {
+            by_archive
+                .entry(archive.as_str())
+                .or_default()
+                .insert(info.relative.as_str(), info);
+        }
+    }
+    let mut parsed = Vec::new();
+    for (archive, wanted) in by_archive {
+        let Ok(entries) = crate::dependency::read_archive_entries(Path::new(archive), |r| wanted.contains_key(r)) else {
+            continue;
+        };
+        let batch: Vec<(String, FileIndex)> = entries
+            .par_iter()
+            .filter_map(|entry| {
+                let info = wanted.get(entry.relative.as_str())?;
+                let content = String::from_utf8_lossy(&entry.bytes);
+                Some((info.path.clone(), index_file(info, &content)))
+            })
+            .collect();
+        parsed.extend(batch);
+    }
+    parsed
+}
+
+fn rebuild_name_table(index: &mut SymbolIndex) {
+    let mut by_name: HashMap<String, Vec<(String, usize)>> = HashMap::new();
+    for (path, file) in &index.files {
+        for (i, symbol) in file.symbols.iter().enumerate() {
+            by_name
+                .entry(symbol.name.clone())
+                .or_default()
+                .push((path.clone(), i));
+        }
+    }
+    index.by_name = by_name;
+}
+
+fn persisted_path(key: &str) -> std::path::PathBuf {
+    crate::get_named_cache_dir("symbol-index").join(format!("{}.json", crate::hash_string(key)))
+}
+
+fn load_persisted(key: &str) -> Option<SymbolIndex> {
+    let bytes = fs::read(persisted_path(key)).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn persist(key: &str, index: &SymbolIndex) {
+    match serde_json::to_vec(index) {
+        Ok(bytes) => {
+            if let Err(e) = fs::write(persisted_path(key), bytes) {
+                println!("[symbol_index] 写入索引缓存失败: {}", e);
+            }
+        }
+        Err(e) => println!("[symbol_index] 序列化索引失败: {}", e),
+    }
+}
+
+/// ：收集所有根目录下参与索引的文件。
+fn collect_file_infos(roots: &IndexRoots) -> Vec<IndexFileInfo> {
+    let mut out: Vec<IndexFileInfo> = Vec::new();
+    if let Some(project) = &roots.project {
+        add_files_under_root(project, SymbolSource::Project, &mut out);
+    }
+    if let Some(game) = &roots.game {
+        out.extend(game_file_infos(game).iter().cloned());
+    }
+    for dep in &roots.dependencies {
+        add_files_under_root(dep, SymbolSource::Dependency, &mut out);
+    }
+    out
+}
+
+/// ：游戏目录体量大且很少变化，文件列表按廉价的目录戳缓存，避免每次查询都完整遍历。
+fn game_file_infos(game: &str) -> Arc<Vec<IndexFileInfo>> {
+    let stamps = game_root_stamps(Path::new(game), INDEXED_DIRS);
+    GAME_FILES.get_or_build(game.to_string(), stamps, |_| {
+        let mut out = Vec::new();
+        add_files_under_root(game, SymbolSource::Game, &mut out);
+        out
+    })
+}
+
+fn add_files_under_root(root: &str, source: SymbolSource, out: &mut Vec<IndexFileInfo>) {
+    if crate::dependency::is_archive(Path::new(root)) {
+        add_archive_files(root, source, out);
+        return;
+    }
+    for dir in INDEXED_DIRS {
+        let base = Path::new(root).join(dir);
+        if !base.exists() {
+            continue;
+        }
+        for entry in WalkDir::new(&base).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.path().to_string_lossy().replace('\\', "/");
+            let relative = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .trim_start_matches('/')
+                .to_lowercase();
+            if !is_indexed_file(&relative, source) {
+                continue;
+            }
+            let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+            out.push(IndexFileInfo {
+                path,
+                relative,
+                source,
+                modified,
+                archive: None,
+            });
+        }
+    }
+}
+
+/// ：压缩包形式的依赖项不解压，列出包内参与索引的文件，无法读取时跳过。
+fn add_archive_files(archive: &str, source: SymbolSource, out: &mut Vec<IndexFileInfo>) {
+    let Ok(names) = crate::dependency::list_archive_files(Path::new(archive)) else {
+        return;
+    };
+    let modified = fs::metadata(archive).ok().and_then(|m| m.modified().ok());
+    for (path, relative) in names {
+        let in_indexed_dir = INDEXED_DIRS.iter().any(|dir| relative.starts_with(&format!("{}/", dir)));
+        if in_indexed_dir && is_indexed_file(&relative, source) {
+            out.push(IndexFileInfo {
+                path,
+                relative,
+                source,
+                modified,
+                archive: Some(archive.to_string()),
+            });
+        }
+    }
+}
+
+fn is_indexed_file(relative: &str, source: SymbolSource) -> bool {
+    if relative.starts_with("gfx/") {
+        return relative.ends_with(".gfx");
+    }
+    if relative.ends_with(".yml") {
+        if source != SymbolSource::Game {
+            return true;
+        }
+        return GAME_LOCALISATION_LANGUAGES
+            .iter()
+            .any(|lang| relative.contains(&format!("/{}/", lang)) || relative.contains(&format!("_l_{}.", lang)));
+    }
+    relative.ends_with(".txt") || relative.ends_with(".gui") || relative.ends_with(".gfx")
+}
+
+// ==================== 单文件解析 ====================
+
+fn index_file(info: &IndexFileInfo, content: &str) -> FileIndex {
+    let lines = LineIndex::new(content);
+    let mut symbols: Vec<IndexedSymbol> = Vec::new();
+    let mut references: Vec<IndexedReference> = Vec::new();
+    // 游戏目录只提供定义，引用只在项目与依赖项中统计
+    let track_references = info.source != SymbolSource::Game;
+
+    let mut add_symbol = |kind: SymbolKind, scalar: &Scalar| {
+        let (start, end) = name_span(scalar);
+        let (line, column) = lines.line_col(content, start);
+        symbols.push(IndexedSymbol {
+            name: scalar.text.clone(),
+            kind,
+            start,
+            end,
+            line,
+            column,
+        });
+    };
+
+    if info.relative.ends_with(".yml") {
+        index_localisation(content, &lines, &mut symbols, track_references.then_some(&mut references));
+        return FileIndex {
+            source: info.source,
+            modified: info.modified,
+            symbols,
+            references,
+        };
+    }
+
+    let doc = clausewitz::parse(content);
+    let root = &doc.root;
+    let rel = info.relative.as_str();
+
+    if rel.starts_with("common/national_focus/") {
+        root.visit_fields(&mut |field, _| {
+            let is_focus = ["focus", "shared_focus", "joint_focus"].iter().any(|k| field.key_is(k));
+            if let Some(id) = field.value.as_block().filter(|_| is_focus).and_then(|b| id_scalar(b, "id")) {
+                add_symbol(SymbolKind::Focus, id);
+            }
+        });
+    } else if rel.starts_with("common/ideas/") {
+        root.visit_fields(&mut |field, _| {
+            if !field.key_is("ideas") {
+                return;
+            }
+            let Some(categories) = field.value.as_block() else {
+                return;
+            };
+            for category in categories.fields() {
+                for idea in category.value.as_block().into_iter().flat_map(Block::fields) {
+                    if idea.value.as_block().is_some() {
+                        add_symbol(SymbolKind::Idea, &idea.key);
+                    }
+                }
+            }
+        });
+    } else if rel.starts_with("events/") {
+        for field in root.fields() {
+            let is_event = EVENT_TYPES.iter().any(|k| field.key_is(k));
+            if let Some(id) = field.value.as_block().filter(|_| is_event).and_then(|b| id_scalar(b, "id")) {
+                add_symbol(SymbolKind::Event, id);
+            }
+        }
+    } else if rel.starts_with("common/decisions/") && !rel.starts_with("common/decisions/categories/") {
+        for category in root.fields() {
+            for decision in category.value.as_block().into_iter().flat_map(Block::fields) {
+                if decision.value.as_block().is_some() {
+                    add_symbol(SymbolKind::Decision, &decision.key);
+                }
+            }
+        }
+    } else if rel.starts_with("common/scripted_effects/") || rel.starts_with("common/scripted_triggers/") {
+        let kind = if rel.starts_with("common/scripted_effects/") {
+            SymbolKind::ScriptedEffect
+        } else {
+            SymbolKind::ScriptedTrigger
+        };
+        for field in root.fields().filter(|f| f.value.as_block().is_some()) {
+            add_symbol(kind, &field.key);
+        }
+    } else if rel.starts_with("common/on_actions/") {
+        for block in root.fields_named("on_actions").filter_map(|f| f.value.as_block()) {
+            for field in block.fields() {
+                add_symbol(SymbolKind::OnAction, &field.key);
+            }
+        }
+    } else if rel.starts_with("common/characters/") {
+        for block in root.fields_named("characters").filter_map(|f| f.value.as_block()) {
+            for field in block.fields().filter(|f| f.value.as_block().is_some()) {
+                add_symbol(SymbolKind::Character, &field.key);
+            }
+        }
+    } else if (rel.starts_with("interface/") || rel.starts_with("gfx/")) && rel.ends_with(".gfx") {
+        root.visit_fields(&mut |field, _| {
+            let is_sprite = SPRITE_TYPES.iter().any(|k| field.key_is(k));
+            if let Some(name) = field.value.as_block().filter(|_| is_sprite).and_then(|b| id_scalar(b, "name")) {
+                add_symbol(SymbolKind::Sprite, name);
+            }
+        });
+    } else if rel.starts_with("interface/") && rel.ends_with(".gui") {
+        root.visit_fields(&mut |field, _| {
+            let is_window = WINDOW_TYPES.iter().any(|k| field.key_is(k));
+            if let Some(name) = field.value.as_block().filter(|_| is_window).and_then(|b| id_scalar(b, "name")) {
+                add_symbol(SymbolKind::GuiWindow, name);
+            }
+        });
+    } else if rel.starts_with("history/states/") {
+        for block in root.fields_named("state").filter_map(|f| f.value.as_block()) {
+            if let Some(id) = id_scalar(block, "id") {
+                add_symbol(SymbolKind::State, id);
+            }
+        }
+    } else if rel.starts_with("common/country_tags/") {
+        for field in root.fields() {
+            let code = field.key.as_str();
+            let is_tag = code.len() == 3 && code.chars().all(|c| c.is_ascii_alphanumeric());
+            if is_tag && field.value.as_scalar().is_some() {
+                add_symbol(SymbolKind::CountryTag, &field.key);
+            }
+        }
+    } else if rel.starts_with("common/military_industrial_organization/") {
+        root.visit_fields(&mut |field, _| {
+            let is_trait = ["trait", "add_trait", "override_trait"].iter().any(|k| field.key_is(k));
+            if let Some(token) = field.value.as_block().filter(|_| is_trait).and_then(|b| id_scalar(b, "token")) {
+                add_symbol(SymbolKind::MioTrait, token);
+            }
+        });
+    }
+
+    if track_references {
+        collect_references(root, None, content, &lines, &mut references);
+    }
+
+    FileIndex {
+        source: info.source,
+        modified: info.modified,
+        symbols,
+        references,
+    }
+}
+
+/// ：名称本身的字节区间，带引号的标量不含引号。
+fn name_span(scalar: &Scalar) -> (usize, usize) {
+    let start = if scalar.quoted { scalar.span.start + 1 } else { scalar.span.start };
+    (start, start + scalar.text.len())
+}
+
+fn id_scalar<'a>(block: &'a Block, key: &str) -> Option<&'a Scalar> {
+    block
+        .fields_named(key)
+        .find_map(|f| f.value.as_scalar())
+        .filter(|s| !s.text.is_empty())
+}
+
+/// ：记录块内所有可能指向符号的标量（键与值）。
+/// 纯数字只在州相关的键下记录，避免把坐标、数值当作州引用。
+fn collect_references(
+    block: &Block,
+    parent_key: Option<&str>,
+    content: &str,
+    lines: &LineIndex,
+    out: &mut Vec<IndexedReference>,
+) {
+    for node in &block.nodes {
+        match node {
+            Node::Field(field) => {
+                push_reference(&field.key, parent_key, content, lines, out);
+                match &field.value {
+                    Value::Scalar(s) => push_reference(s, Some(field.key.as_str()), content, lines, out),
+                    Value::Block(b) => collect_references(b, Some(field.key.as_str()), content, lines, out),
+                    Value::Color(_) => {}
+                }
+            }
+            Node::Value(Value::Scalar(s)) => push_reference(s, parent_key, content, lines, out),
+            Node::Value(Value::Block(b)) => collect_references(b, parent_key, content, lines, out),
+            _ => {}
+        }
+    }
+}
+
+fn push_reference(
+    scalar: &Scalar,
+    key: Option<&str>,
+    content: &str,
+    lines: &LineIndex,
+    out: &mut Vec<IndexedReference>,
+) {
+    let text = scalar.text.as_str();
+    if text.is_empty() || text.eq_ignore_ascii_case("yes") || text.eq_ignore_ascii_case("no") {
+        return;
+    }
+    if text.chars().all(|c| c.is_ascii_digit()) {
+        let state_key = key
+            .map(|k| {
+                let k = k.to_ascii_lowercase();
+                k.contains("state") || k.contains("capital")
+            })
+            .unwrap_or(false);
+        if !state_key {
+            return;
+        }
+    } else if text.parse::<f64>().is_ok() {
+        return;
+    }
+    let (start, end) = name_span(scalar);
+    let (line, column) = lines.line_col(content, start);
+    out.push(IndexedReference {
+        name: text.to_string(),
+        start,
+        end,
+        line,
+        column,
+    });
+}
+
+/// ：解析 yml 中的 `KEY:0 "..."` 定义，以及文本内 `$KEY$` 引用。
+fn index_localisation(
+    content: &str,
+    lines: &LineIndex,
+    symbols: &mut Vec<IndexedSymbol>,
+    mut references: Option<&mut Vec<IndexedReference>>,
+) {
+    let mut offset = 0usize;
+    for (i, raw) in content.split('\n').enumerate() {
+        let line_start = offset;
+        offset += raw.len() + 1;
+        let line_no = i + 1;
+
+        let trimmed = raw.trim_start_matches(['\u{feff}', ' ', '\t']);
+        if trimmed.starts_with('#') {
+            continue;
+        }
+        let key_start = line_start + (raw.len() - trimmed.len());
+        let Some(colon) = trimmed.find(':') else {
+            continue;
+        };
+        let key = &trimmed[..colon];
+        if key.is_empty() || !key.chars().all(is_loc_key_char) {
+            continue;
+        }
+        let rest = &trimmed[colon + 1..];
+        let after_version = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+        let Some(quote) = after_version.trim_start().strip_prefix('"') else {
+            // `l_english:` 之类的头部没有文本
+            continue;
+        };
+
+        let (_, column) = lines.line_col(content, key_start);
+        symbols.push(IndexedSymbol {
+            name: key.to_string(),
+            kind: SymbolKind::LocalisationKey,
+            start: key_start,
+            end: key_start + key.len(),
+            line: line_no,
+            column,
+        });
+
+        if let Some(refs) = references.as_deref_mut() {
+            let text_start = line_start + (raw.len() - quote.len());
+            let mut parts = quote.split('$');
+            let mut pos = text_start;
+            if let Some(first) = parts.next() {
+                pos += first.len() + 1;
+            }
+            // split 后奇数位置为 `$...$` 内部
+            for (n, part) in parts.enumerate() {
+                if n % 2 == 0 && !part.is_empty() && part.chars().all(is_loc_key_char) {
+                    let (_, column) = lines.line_col(content, pos);
+                    refs.push(IndexedReference {
+                        name: part.to_string(),
+                        start: pos,
+                        end: pos + part.len(),
+                        line: line_no,
+                        column,
+                    });
+                }
+                pos += part.len() + 1;
+            }
+        }
+    }
+}
+
+/// ：提取 yml 内容中定义的全部本地化键。
+pub(crate) fn localisation_keys(content: &str) -> Vec<String> {
+    let lines = LineIndex::new(content);
+    let mut symbols = Vec::new();
+    index_localisation(content, &lines, &mut symbols, None);
+    symbols.into_iter().map(|s| s.name).collect()
+}
+
+fn is_loc_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '\'')
+}
+
+/// ：取出光标处的符号名称。脚本文件基于语法树，yml 按字符类切分。
+fn symbol_at(file: &str, content: &str, offset: usize) -> Option<String> {
+    if file.to_lowercase().ends_with(".yml") {
+        let bytes = content.as_bytes();
+        let is_key_byte = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'-');
+        let mut start = offset.min(bytes.len());
+        while start > 0 && is_key_byte(bytes[start - 1]) {
+            start -= 1;
+        }
+        let mut end = offset.min(bytes.len());
+        while end < bytes.len() && is_key_byte(bytes[end]) {
+            end += 1;
+        }
+        return content.get(start..end).filter(|s| !s.is_empty()).map(|s| s.to_string());
+    }
+
+    let doc = clausewitz::parse(content);
+    scalar_at(&doc.root, offset).map(|s| s.text.clone())
+}
+
+fn scalar_at(block: &Block, offset: usize) -> Option<&Scalar> {
+    for node in &block.nodes {
+        if !node.span().contains(offset) {
+            continue;
+        }
+        let found = match node {
+            Node::Field(field) if field.key.span.contains(offset) => Some(&field.key),
+            Node::Field(field) => match &field.value {
+                Value::Scalar(s) if s.span.contains(offset) => Some(s),
+                Value::Block(b) => scalar_at(b, offset),
+                _ => None,
+            },
+            Node::Value(Value::Scalar(s)) => Some(s),
+            Node::Value(Value::Block(b)) => scalar_at(b, offset),
+            _ => None,
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(relative: &str, source: SymbolSource) -> IndexFileInfo {
+        IndexFileInfo {
+            path: format!("/mod/{}", relative),
+            relative: relative.to_string(),
+            source,
+            modified: None,
+            archive: None,
+        }
+    }
+
+    #[test]
+    fn test_index_focus_and_references() {
+        let src = "focus_tree = {\n\tfocus = {\n\t\tid = GER_rearm\n\t\tprerequisite = { focus = GER_start }\n\t\tx = 3\n\t}\n}\n";
+        let file = index_file(&info("common/national_focus/ger.txt", SymbolSource::Project), src);
+        let names: Vec<&str> = file.symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["GER_rearm"]);
+        assert_eq!(file.symbols[0].line, 3);
+        assert_eq!(&src[file.symbols[0].start..file.symbols[0].end], "GER_rearm");
+        assert!(file.references.iter().any(|r| r.name == "GER_start"));
+        assert!(!file.references.iter().any(|r| r.name == "3"));
+    }
+
+    #[test]
+    fn test_index_localisation() {
+        let src = "\u{feff}l_english:\n GER_rearm:0 \"Rearm $GER_name$\"\n # GER_x:0 \"no\"\n";
+        let file = index_file(&info("localisation/english/ger_l_english.yml", SymbolSource::Project), src);
+        assert_eq!(file.symbols.len(), 1);
+        assert_eq!(file.symbols[0].name, "GER_rearm");
+        assert_eq!(file.symbols[0].line, 2);
+        assert_eq!(file.references.len(), 1);
+        let r = &file.references[0];
+        assert_eq!(&src[r.start..r.end], "GER_name");
+    }
+
+    #[test]
+    fn test_symbol_at() {
+        let src = "a = { effect = my_effect }";
+        let offset = src.find("my_effect").expect("token") + 2;
+        assert_eq!(symbol_at("x.txt", src, offset).as_deref(), Some("my_effect"));
+        assert_eq!(symbol_at("x.yml", " KEY_a:0 \"t\"", 3).as_deref(), Some("KEY_a"));
+    }
+
+    #[test]
+    fn test_index_zipped_dependency() {
+        use std::io::Write;
+        let dir = tempfile::tempdir().expect("tempdir");
+        let archive = dir.path().join("dep.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&archive).expect("create zip"));
+        for (name, content) in [
+            ("common/scripted_effects/dep.txt", "dep_effect = { add_political_power = 1 }"),
+            ("descriptor.mod", "name = \"dep\""),
+        ] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default()).expect("start file");
+            zip.write_all(content.as_bytes()).expect("write entry");
+        }
+        zip.finish().expect("finish zip");
+
+        let root = archive.to_string_lossy().replace('\\', "/");
+        let mut files = Vec::new();
+        add_files_under_root(&root, SymbolSource::Dependency, &mut files);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, format!("{}/common/scripted_effects/dep.txt", root));
+
+        let mut index = SymbolIndex::default();
+        assert_eq!(refresh_index(&mut index, &files), 1);
+        rebuild_name_table(&mut index);
+        assert!(index.is_defined_as("dep_effect", SymbolKind::ScriptedEffect));
+    }
+
+    #[test]
+    fn test_game_file_list_cached_and_gfx_indexed() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let write = |relative: &str, content: &str| {
+            let path = dir.path().join(relative);
+            fs::create_dir_all(path.parent().expect("parent")).expect("mkdir");
+            fs::write(path, content).expect("write");
+        };
+        write("gfx/flags/GER.tga", "");
+        write("gfx/custom.gfx", "spriteTypes = {\n\tspriteType = {\n\t\tname = GFX_custom\n\t}\n}\n");
+        let game = dir.path().to_string_lossy().replace('\\', "/");
+
+        let files = game_file_infos(&game);
+        assert_eq!(files.len(), 1);
+        let file = index_file(&files[0], &fs::read_to_string(&files[0].path).expect("read"));
+        assert_eq!(file.symbols[0].kind, SymbolKind::Sprite);
+
+        // 嵌套目录中的变化不影响目录戳，游戏更新时 launcher-settings.json 随之变化
+        write("gfx/flags/new.gfx", "");
+        assert_eq!(game_file_infos(&game).len(), 1);
+        write("launcher-settings.json", "{}");
+        assert_eq!(game_file_infos(&game).len(), 2);
+    }
+}
@@ -0,0 +1,325 @@
+#![deny(clippy::unwrap_used)]
+
+//! 跨文件重命名
+//!
+//! 借助符号索引找出项目内某个标识符的全部定义与引用，连同派生的本地化键
+//! （`<id>_desc`、事件的 `<id>.t` 等）一起改名。先返回预览，确认后一次性写入；
+//! 每个文件按原编码写回，UTF-8 BOM 原样保留。
+
+use crate::symbol_index::{self, IndexRoots, SymbolSource};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// ：随标识符一起改名的本地化键后缀。
+const DERIVED_SUFFIXES: [&str; 2] = ["_desc", "_tt"];
+
+/// ：单处修改。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameEdit {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub old_text: String,
+    pub new_text: String,
+    /// 修改前的整行文本
+    pub line_text: String,
+    /// 仅应用本处修改后的整行文本
+    pub preview: String,
+}
+
+/// ：单个文件的修改集合。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameFileEdits {
+    pub file: String,
+    pub encoding: String,
+    pub edits: Vec<RenameEdit>,
+}
+
+/// ：命令返回值。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameResponse {
+    pub success: bool,
+    pub message: String,
+    pub old_name: String,
+    pub new_name: String,
+    pub applied: bool,
+    pub edit_count: usize,
+    pub files: Vec<RenameFileEdits>,
+}
+
+/// ：准备写回的文件内容。
+struct PendingWrite {
+    path: PathBuf,
+    content: String,
+    encoding: &'static encoding_rs::Encoding,
+}
+
+/// ：重命名项目中定义的符号。`apply` 为 false（默认）时只返回预览。
+/// 只修改项目目录内的文件，依赖项与游戏目录仅用于识别定义。
+#[tauri::command]
+pub fn rename_symbol(
+    symbol: String,
+    new_name: String,
+    project_root: String,
+    game_root: Option<String>,
+    dependency_roots: Option<Vec<String>>,
+    apply: Option<bool>,
+) -> RenameResponse {
+    let old_name = symbol.trim().to_string();
+    let new_name = new_name.trim().to_string();
+    let apply = apply.unwrap_or(false);
+
+    let fail = |message: String| RenameResponse {
+        success: false,
+        message,
+        old_name: old_name.clone(),
+        new_name: new_name.clone(),
+        applied: false,
+        edit_count: 0,
+        files: Vec::new(),
+    };
+
+    if let Err(e) = validate_name(&new_name) {
+        return fail(e);
+    }
+    if old_name.is_empty() {
+        return fail("原符号名称为空".to_string());
+    }
+    if old_name == new_name {
+        return fail("新旧名称相同".to_string());
+    }
+
+    let roots = IndexRoots::new(Some(project_root), game_root, dependency_roots);
+    let Some(project) = roots.project.clone() else {
+        return fail("项目路径无效".to_string());
+    };
+
+    let lookup = symbol_index::with_index(&roots, |index| {
+        let defined_in_project = index
+            .definitions_of(&old_name)
+            .iter()
+            .any(|d| d.source == SymbolSource::Project);
+        let conflict = index.is_defined(&new_name);
+        let files = index.files_mentioning(SymbolSource::Project, |name| {
+            renamed(name, &old_name, &new_name).is_some()
+        });
+        (defined_in_project, conflict, files)
+    });
+    let (defined_in_project, conflict, candidates) = match lookup {
+        Ok((result, _)) => result,
+        Err(e) => return fail(e),
+    };
+    if !defined_in_project {
+        return fail(format!("{} 未在项目中定义，无法重命名", old_name));
+    }
+    if conflict {
+        return fail(format!("{} 已存在定义，请换一个名称", new_name));
+    }
+
+    let mut files: Vec<RenameFileEdits> = Vec::new();
+    let mut pending: Vec<PendingWrite> = Vec::new();
+    for path in candidates {
+        let bytes = match fs::read(&path) {
+            Ok(b) => b,
+            Err(e) => return fail(format!("读取文件失败 {}: {}", path, e)),
+        };
+        let Some((content, encoding)) = crate::decode_text_bytes(&bytes) else {
+            return fail(format!("无法识别文件编码: {}", path));
+        };
+
+        let edits = collect_edits(&project, &path, &content, &old_name, &new_name);
+        if edits.is_empty() {
+            continue;
+        }
+        let updated = apply_edits(&content, &edits);
+        files.push(RenameFileEdits {
+            file: path.clone(),
+            encoding: encoding.name().to_string(),
+            edits,
+        });
+        pending.push(PendingWrite {
+            path: PathBuf::from(path),
+            content: updated,
+            encoding,
+        });
+    }
+
+    let edit_count: usize = files.iter().map(|f| f.edits.len()).sum();
+    if edit_count == 0 {
+        return fail(format!("未找到 {} 的可修改位置", old_name));
+    }
+
+    if apply {
+        if let Err(e) = write_all(&pending) {
+            return fail(e);
+        }
+    }
+
+    RenameResponse {
+        success: true,
+        message: if apply {
+            format!("已在 {} 个文件中修改 {} 处", files.len(), edit_count)
+        } else {
+            format!("将在 {} 个文件中修改 {} 处", files.len(), edit_count)
+        },
+        old_name,
+        new_name,
+        applied: apply,
+        edit_count,
+        files,
+    }
+}
+
+/// ：名称只能包含脚本中可直接书写的字符。
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("新名称为空".to_string());
+    }
+    let invalid = name
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, '"' | '{' | '}' | '#' | '=' | '<' | '>' | '$' | ':'));
+    if invalid {
+        return Err(format!("新名称包含非法字符: {}", name));
+    }
+    Ok(())
+}
+
+/// ：计算某个名称改名后的结果；不受影响时返回 None。
+/// 带命名空间的事件 id（含 `.`）会连同 `<id>.t`、`<id>.a` 等一起改名。
+fn renamed(name: &str, old: &str, new: &str) -> Option<String> {
+    if name == old {
+        return Some(new.to_string());
+    }
+    let rest = name.strip_prefix(old)?;
+    if DERIVED_SUFFIXES.contains(&rest) || (old.contains('.') && rest.starts_with('.') && rest.len() > 1) {
+        return Some(format!("{}{}", new, rest));
+    }
+    None
+}
+
+fn collect_edits(project: &str, path: &str, content: &str, old: &str, new: &str) -> Vec<RenameEdit> {
+    symbol_index::scan_occurrences(project, path, content)
+        .into_iter()
+        .filter_map(|o| {
+            let new_text = renamed(&o.name, old, new)?;
+            // 索引可能来自旧内容，按当前文本再核对一次
+            if content.get(o.start..o.end) != Some(o.name.as_str()) {
+                return None;
+            }
+            let line_start = content[..o.start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let line_end = content[o.end..].find('\n').map(|i| o.end + i).unwrap_or(content.len());
+            let line_text = content[line_start..line_end].trim_end_matches('\r').to_string();
+            let preview = format!(
+                "{}{}{}",
+                &content[line_start..o.start],
+                new_text,
+                content[o.end..line_end].trim_end_matches('\r')
+            );
+            Some(RenameEdit {
+                start: o.start,
+                end: o.end,
+                line: o.line,
+                column: o.column,
+                old_text: o.name,
+                new_text,
+                line_text,
+                preview,
+            })
+        })
+        .collect()
+}
+
+fn apply_edits(content: &str, edits: &[RenameEdit]) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut last = 0usize;
+    for edit in edits {
+        out.push_str(&content[last..edit.start]);
+        out.push_str(&edit.new_text);
+        last = edit.end;
+    }
+    out.push_str(&content[last..]);
+    out
+}
+
+/// ：先把全部新内容写到临时文件，再逐个替换原文件；任何一步失败都恢复原状。
+fn write_all(pending: &[PendingWrite]) -> Result<(), String> {
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for item in pending {
+        let tmp = temp_path(&item.path);
+        if let Err(e) = crate::write_text_file(&tmp, &item.content, item.encoding) {
+            cleanup(&staged);
+            let _ = fs::remove_file(&tmp);
+            return Err(format!("{}: {}", item.path.display(), e));
+        }
+        staged.push((tmp, item.path.clone()));
+    }
+
+    let mut originals: HashMap<PathBuf, Vec<u8>> = HashMap::new();
+    for (tmp, target) in &staged {
+        match fs::read(target) {
+            Ok(bytes) => {
+                originals.insert(target.clone(), bytes);
+            }
+            Err(e) => {
+                cleanup(&staged);
+                return Err(format!("读取文件失败 {}: {}", target.display(), e));
+            }
+        }
+        if let Err(e) = fs::rename(tmp, target) {
+            // 回滚已替换的文件
+            for (path, bytes) in &originals {
+                if path != target {
+                    let _ = fs::write(path, bytes);
+                }
+            }
+            cleanup(&staged);
+            return Err(format!("替换文件失败 {}: {}", target.display(), e));
+        }
+    }
+    Ok(())
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".rename.tmp");
+    path.with_file_name(name)
+}
+
+fn cleanup(staged: &[(PathBuf, PathBuf)]) {
+    for (tmp, _) in staged {
+        let _ = fs::remove_file(tmp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renamed_rules() {
+        assert_eq!(renamed("GER_rearm", "GER_rearm", "GER_arm").as_deref(), Some("GER_arm"));
+        assert_eq!(renamed("GER_rearm_desc", "GER_rearm", "GER_arm").as_deref(), Some("GER_arm_desc"));
+        assert_eq!(renamed("GER_rearm_more", "GER_rearm", "GER_arm"), None);
+        assert_eq!(renamed("ger.1.t", "ger.1", "ger.5").as_deref(), Some("ger.5.t"));
+        assert_eq!(renamed("ger.10", "ger.1", "ger.5"), None);
+    }
+
+    #[test]
+    fn test_collect_and_apply_edits() {
+        let content = "\u{feff}l_english:\n GER_rearm:0 \"Rearm\"\n GER_rearm_desc:0 \"$GER_rearm$\"\n";
+        let edits = collect_edits("/mod", "/mod/localisation/english/a_l_english.yml", content, "GER_rearm", "GER_arm");
+        assert_eq!(edits.len(), 3);
+        let out = apply_edits(content, &edits);
+        assert_eq!(out, "\u{feff}l_english:\n GER_arm:0 \"Rearm\"\n GER_arm_desc:0 \"$GER_arm$\"\n");
+
+        let script = "focus = { id = GER_rearm icon = \"GFX_a\" }\n";
+        let edits = collect_edits("/mod", "/mod/common/national_focus/a.txt", script, "GFX_a", "GFX_b");
+        assert_eq!(apply_edits(script, &edits), "focus = { id = GER_rearm icon = \"GFX_b\" }\n");
+    }
+}
@@ -1,10 +1,10 @@
 #![deny(clippy::unwrap_used)]
 
 use once_cell::sync::Lazy;
-use regex::Regex;
 use serde::Serialize;
 use std::collections::HashSet;
 use std::sync::RwLock;
+use crate::clausewitz::{self, LineIndex, Span, Value};
 use crate::country_tags::{load_country_tags, TagEntry, TagLoadResponse};
 
 /// ：单个标签引用的错误信息。
@@ -12,6 +12,10 @@ use crate::country_tags::{load_country_tags, TagEntry, TagLoadResponse};
 #[serde(rename_all = "camelCase")]
 pub struct TagValidationError {
     pub line: usize,
+    pub column: usize,
+    /// 标签所在的字节区间
+    pub start: usize,
+    pub end: usize,
     pub message: String,
 }
 
@@ -34,26 +38,6 @@ struct TagCache {
 /// ：共享标签缓存，版本号用于判断是否需要刷新。
 static TAG_CACHE: Lazy<RwLock<Option<TagCache>>> = Lazy::new(|| RwLock::new(None));
 
-/// ：匹配各种需要检查的模式。
-static TARGET_BLOCK_REGEX: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"(?is)\b[a-zA-Z0-9_\.]+\s*=\s*\{[^{}]*?target\s*=\s*([A-Za-z0-9]{2,4})")
-        .expect("无效的 TARGET_BLOCK_REGEX 正则表达式模式")
-});
-
-/// ：匹配原始等号形式。
-static DIRECT_ASSIGN_REGEX: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(
-        r"(?i)\b(original_tag|tag|add_core_of|owner|ROOT/[A-Za-z0-9_]+|FROM/[A-Za-z0-9_]+)\s*=\s*([A-Za-z0-9]{2,4})",
-    )
-    .expect("无效的 DIRECT_ASSIGN_REGEX 正则表达式模式")
-});
-
-/// ：匹配作用域块 `ROOT/X = {` 或 `FROM/X = {`。
-static SCOPE_BLOCK_REGEX: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"(?i)\b(ROOT|FROM)/([A-Za-z0-9]{2,4})\s*=\s*\{")
-        .expect("无效的 SCOPE_BLOCK_REGEX 正则表达式模式")
-});
-
 fn normalize_tag(tag: &str) -> String {
     tag.trim().to_uppercase()
 }
@@ -62,7 +46,7 @@ fn build_tag_set(tags: &[TagEntry]) -> HashSet<String> {
     tags.iter().map(|entry| entry.code.clone()).collect()
 }
 
-fn ensure_tag_cache(
+pub(crate) fn ensure_tag_cache(
     project_root: Option<String>,
     game_root: Option<String>,
     dependency_roots: Option<Vec<String>>,
@@ -96,73 +80,192 @@ fn ensure_tag_cache(
         .unwrap_or_default()
 }
 
-fn collect_direct_assignments(content: &str) -> Vec<(usize, String)> {
-    DIRECT_ASSIGN_REGEX
-        .captures_iter(content)
-        .filter_map(|caps| {
-            let full = caps.get(0)?;
-            let tag = normalize_tag(caps.get(2)?.as_str());
-            let line = content[..full.start()].chars().filter(|&c| c == '\n').count() + 1;
-            Some((line, tag))
-        })
-        .collect()
+/// ：值为国家作用域的键。
+const COUNTRY_KEYS: [&str; 40] = [
+    "tag",
+    "original_tag",
+    "owner",
+    "controller",
+    "add_core_of",
+    "remove_core_of",
+    "add_claim_by",
+    "remove_claim_by",
+    "is_core_of",
+    "is_claimed_by",
+    "is_owned_by",
+    "is_controlled_by",
+    "is_owned_and_controlled_by",
+    "transfer_state_to",
+    "country_exists",
+    "exists",
+    "has_war_with",
+    "has_defensive_war_with",
+    "has_offensive_war_with",
+    "has_war_together_with",
+    "is_in_faction_with",
+    "is_puppet_of",
+    "is_subject_of",
+    "is_neighbor_of",
+    "is_ally_with",
+    "is_guaranteed_by",
+    "has_guaranteed",
+    "has_non_aggression_pact_with",
+    "has_military_access_to",
+    "give_guarantee",
+    "give_military_access",
+    "puppet",
+    "release",
+    "release_puppet",
+    "add_to_faction",
+    "remove_from_faction",
+    "inherit_technology",
+    "set_cosmetic_tag_of",
+    "add_opinion_modifier_from",
+    "end_puppet",
+];
+
+/// ：块内 `target` / `tag` 字段为国家的效果与触发器。
+const TARGET_PARENTS: [&str; 16] = [
+    "declare_war_on",
+    "create_wargoal",
+    "annex_country",
+    "diplomatic_relation",
+    "add_opinion_modifier",
+    "reverse_add_opinion_modifier",
+    "remove_opinion_modifier",
+    "has_opinion",
+    "has_opinion_modifier",
+    "set_autonomy",
+    "give_resource_rights",
+    "white_peace",
+    "send_equipment",
+    "add_relation_modifier",
+    "has_relation_modifier",
+    "start_civil_war",
+];
+
+/// ：作用域关键字，可用 `.` 连写，例如 `FROM.FROM`、`PREV.OWNER`。
+const SCOPE_KEYWORDS: [&str; 8] = ["THIS", "ROOT", "FROM", "PREV", "OWNER", "CONTROLLER", "OVERLORD", "FACTION_LEADER"];
+
+/// ：逻辑运算等与标签同形的大写键。
+const RESERVED_KEYS: [&str; 6] = ["AND", "OR", "NOT", "NOR", "NAND", "XOR"];
+
+/// ：运行时解析的作用域：事件目标、变量、脚本参数等。
+fn is_runtime_reference(text: &str) -> bool {
+    let lower = text.to_ascii_lowercase();
+    ["event_target:", "global_event_target:", "var:", "sv:"].iter().any(|p| lower.starts_with(p))
+        || text.starts_with('@')
+        || text.contains(['[', '$'])
 }
 
-fn collect_scope_blocks(content: &str) -> Vec<(usize, String)> {
-    SCOPE_BLOCK_REGEX
-        .captures_iter(content)
-        .filter_map(|caps| {
-            let full = caps.get(0)?;
-            let tag = normalize_tag(caps.get(2)?.as_str());
-            let line = content[..full.start()].chars().filter(|&c| c == '\n').count() + 1;
-            Some((line, tag))
-        })
-        .collect()
+/// ：`FROM`、`PREV.PREV`、`ROOT.OWNER` 等作用域链。
+fn is_scope_chain(text: &str) -> bool {
+    text.split('.')
+        .all(|part| SCOPE_KEYWORDS.iter().any(|k| part.eq_ignore_ascii_case(k)))
 }
 
-fn collect_target_blocks(content: &str) -> Vec<(usize, String)> {
-    TARGET_BLOCK_REGEX
-        .captures_iter(content)
-        .filter_map(|caps| {
-            let full = caps.get(0)?;
-            let tag = normalize_tag(caps.get(1)?.as_str());
-            let line = content[..full.start()].chars().filter(|&c| c == '\n').count() + 1;
-            Some((line, tag))
-        })
-        .collect()
+/// ：运行时生成的动态国家标签 `D01`…`D99`。
+fn is_dynamic_tag(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 3 && bytes[0] == b'D' && bytes[1].is_ascii_digit() && bytes[2].is_ascii_digit()
 }
 
-fn validate_tags_internal(content: &str, tags: &HashSet<String>) -> Vec<TagValidationError> {
-    let mut errors: Vec<TagValidationError> = Vec::new();
+/// ：形如国家标签的文本：3 位大写字母或数字，首位为字母。
+fn is_tag_candidate(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 3
+        && bytes[0].is_ascii_uppercase()
+        && bytes.iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
 
-    for (line, tag) in collect_direct_assignments(content) {
-        if !tags.contains(&tag) {
-            errors.push(TagValidationError {
-                line,
-                message: format!("未定义的国家标签: {}", tag),
-            });
-        }
+/// ：需要与标签集合比对的值，作用域关键字、运行时引用与动态标签都直接放行。
+fn checked_tag(text: &str) -> Option<&str> {
+    if is_runtime_reference(text) || is_scope_chain(text) || is_dynamic_tag(text) {
+        return None;
     }
+    is_tag_candidate(text).then_some(text)
+}
 
-    for (line, tag) in collect_scope_blocks(content) {
-        if !tags.contains(&tag) {
-            errors.push(TagValidationError {
-                line,
-                message: format!("作用域引用未定义的国家标签: {}", tag),
-            });
-        }
+/// ：拆分 `ROOT/X`、`FROM/X` 形式的键，返回斜杠后的标签。
+fn split_scope_key(key: &str) -> Option<&str> {
+    let (prefix, rest) = key.split_once('/')?;
+    if prefix.eq_ignore_ascii_case("ROOT") || prefix.eq_ignore_ascii_case("FROM") {
+        Some(rest)
+    } else {
+        None
     }
+}
 
-    for (line, tag) in collect_target_blocks(content) {
-        if !tags.contains(&tag) {
-            errors.push(TagValidationError {
-                line,
-                message: format!("target = {} 未定义", tag),
-            });
+/// ：标签引用的种类，对应不同的错误提示。
+enum TagReference {
+    Direct,
+    ScopeBlock,
+    ScopeSwitch,
+    Target,
+}
+
+/// ：遍历语法树，收集所有需要校验的标签引用。
+fn collect_tag_references(content: &str) -> Vec<(Span, String, TagReference)> {
+    let doc = clausewitz::parse(content);
+    let mut refs = Vec::new();
+
+    doc.root.visit_fields(&mut |field, ancestors| {
+        let key = field.key.as_str();
+        match &field.value {
+            Value::Scalar(value) if !value.quoted => {
+                let in_target_parent = ancestors
+                    .last()
+                    .map(|parent| TARGET_PARENTS.iter().any(|k| parent.key_is(k)))
+                    .unwrap_or(false);
+                let kind = if COUNTRY_KEYS.iter().any(|k| field.key_is(k)) || split_scope_key(key).is_some() {
+                    Some(TagReference::Direct)
+                } else if in_target_parent && (field.key_is("target") || field.key_is("tag")) {
+                    Some(TagReference::Target)
+                } else {
+                    None
+                };
+                if let (Some(kind), Some(tag)) = (kind, checked_tag(value.as_str())) {
+                    refs.push((value.span, normalize_tag(tag), kind));
+                }
+            }
+            Value::Block(_) => {
+                if let Some(tag) = split_scope_key(key).and_then(checked_tag) {
+                    refs.push((field.key.span, normalize_tag(tag), TagReference::ScopeBlock));
+                } else if !field.key.quoted && !RESERVED_KEYS.contains(&key) {
+                    // `GER = { ... }` 切换到该国作用域（顶层是定义名，不算）
+                    if let Some(tag) = checked_tag(key).filter(|_| !ancestors.is_empty()) {
+                        refs.push((field.key.span, normalize_tag(tag), TagReference::ScopeSwitch));
+                    }
+                }
+            }
+            _ => {}
         }
-    }
+    });
+
+    refs
+}
 
-    errors
+pub(crate) fn validate_tags_internal(content: &str, tags: &HashSet<String>) -> Vec<TagValidationError> {
+    let index = LineIndex::new(content);
+    collect_tag_references(content)
+        .into_iter()
+        .filter(|(_, tag, _)| !tags.contains(tag))
+        .map(|(span, tag, kind)| {
+            let (line, column) = index.line_col(content, span.start);
+            TagValidationError {
+                line,
+                column,
+                start: span.start,
+                end: span.end,
+                message: match kind {
+                    TagReference::Direct => format!("未定义的国家标签: {}", tag),
+                    TagReference::ScopeBlock => format!("作用域引用未定义的国家标签: {}", tag),
+                    TagReference::ScopeSwitch => format!("切换到未定义的国家作用域: {}", tag),
+                    TagReference::Target => format!("target = {} 未定义", tag),
+                },
+            }
+        })
+        .collect()
 }
 
 /// ：对给定文本执行标签校验。
@@ -207,3 +310,48 @@ pub fn validate_tags(
 ) -> TagValidationResponse {
     validate_tags_content(&content, project_root, game_root, dependency_roots)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn undefined(content: &str) -> Vec<(usize, String)> {
+        let tags: HashSet<String> = ["GER", "ENG"].iter().map(|t| t.to_string()).collect();
+        validate_tags_internal(content, &tags).into_iter().map(|e| (e.line, e.message)).collect()
+    }
+
+    #[test]
+    fn test_scope_keywords_and_runtime_references() {
+        let content = "effect = {\n\
+            owner = ROOT\n\
+            is_owned_by = FROM.FROM\n\
+            add_core_of = PREV\n\
+            tag = event_target:my_target\n\
+            has_war_with = var:enemy\n\
+            country_exists = D01\n\
+            target = yes\n\
+            declare_war_on = { target = THIS type = annex_everything }\n\
+            GER = { add_political_power = 10 }\n\
+            AND = { tag = ENG }\n\
+        }";
+        assert!(undefined(content).is_empty(), "{:?}", undefined(content));
+    }
+
+    #[test]
+    fn test_undefined_tags() {
+        let content = "effect = {\n\
+            owner = SOV\n\
+            ROOT/ITA = { }\n\
+            FRA = { annex_country = { target = JAP } }\n\
+        }";
+        assert_eq!(
+            undefined(content),
+            [
+                (2, "未定义的国家标签: SOV".to_string()),
+                (3, "作用域引用未定义的国家标签: ITA".to_string()),
+                (4, "切换到未定义的国家作用域: FRA".to_string()),
+                (4, "target = JAP 未定义".to_string()),
+            ]
+        );
+    }
+}
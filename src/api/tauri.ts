@@ -1,4 +1,5 @@
 import { invoke } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
 import { openUrl as tauriOpenUrl } from '@tauri-apps/plugin-opener'
 
 // ==================== 类型定义 ====================
@@ -454,6 +455,37 @@ export async function getModifierList(): Promise<JsonResult<string>> {
   return await invoke('get_modifier_list')
 }
 
+export type ModifierValueType = 'percent' | 'flat' | 'bool'
+export type ModifierScope = 'country' | 'state' | 'unitLeader'
+
+export interface ModifierInfo {
+  name: string
+  description: string
+  category: string
+  valueType: ModifierValueType
+  /** 为空时表示作用域未知 */
+  scopes: ModifierScope[]
+  source: 'builtin' | 'project' | 'dependency' | 'game'
+  file?: string
+}
+
+export interface ModifierDatabaseResult {
+  success: boolean
+  message: string
+  modifiers: ModifierInfo[]
+}
+
+/**
+ * 获取结构化的修正表（内置 modifier.txt + common/modifier_definitions）
+ */
+export async function getModifierDatabase(
+  projectRoot?: string,
+  gameRoot?: string,
+  dependencyRoots?: string[]
+): Promise<ModifierDatabaseResult> {
+  return await invoke('get_modifier_database', { projectRoot, gameRoot, dependencyRoots })
+}
+
 export async function installPlugin(sourcePath: string): Promise<InstalledPlugin> {
   return await invoke('install_plugin', { sourcePath })
 }
@@ -648,16 +680,608 @@ export async function buildDirectoryTreeFast(
   return await invoke('build_directory_tree_fast', { path, maxDepth })
 }
 
-// ==================== Focus Localizations ====================
+// ==================== Focus Localizations ====================
+
+export interface FocusLocalizationLoadResponse {
+  success: boolean
+  message: string
+  map?: Record<string, string>
+}
+
+export async function loadFocusLocalizations(roots: string[]): Promise<FocusLocalizationLoadResponse> {
+  return invoke<FocusLocalizationLoadResponse>('load_focus_localizations', { roots })
+}
+
+// ==================== 国策树 ====================
+
+export type FocusKind = 'focus' | 'shared' | 'joint'
+
+export interface FocusDto {
+  id: string
+  kind: FocusKind
+  icon?: string
+  x: number
+  y: number
+  /** 按 relative_position_id 累加后的坐标 */
+  absoluteX: number
+  absoluteY: number
+  relativePositionId?: string
+  /** 每组内为“或”，组之间为“且” */
+  prerequisites: string[][]
+  mutuallyExclusive: string[]
+  cost?: number
+  /** allow_branch 块的原文 */
+  allowBranch?: string
+  file: string
+  start: number
+  end: number
+  line: number
+}
+
+export type FocusWarningKind =
+  | 'cycle'
+  | 'overlap'
+  | 'danglingPrerequisite'
+  | 'danglingReference'
+  | 'missingIcon'
+  | 'duplicateId'
+
+export interface FocusWarning {
+  kind: FocusWarningKind
+  focus: string
+  message: string
+  file: string
+  start: number
+  end: number
+  line: number
+}
+
+export interface FocusTreeDto {
+  id: string
+  default: boolean
+  /** 树内国策加上 shared_focus / joint_focus 引入的国策 */
+  focuses: FocusDto[]
+  sharedFocuses: string[]
+  warnings: FocusWarning[]
+  start: number
+  end: number
+  line: number
+}
+
+export interface FocusTreeData {
+  sourceFile: string
+  trees: FocusTreeDto[]
+  /** 本文件中定义的共享国策 */
+  sharedFocuses: FocusDto[]
+  warnings: FocusWarning[]
+}
+
+/**
+ * 解析国策树文件，contentOverride 为编辑器中未保存的内容
+ */
+export async function parseFocusTree(params: {
+  filePath: string
+  contentOverride?: string
+  projectPath?: string
+  gameDirectory?: string
+  dependencyRoots?: string[]
+}): Promise<FocusTreeData> {
+  return await invoke('parse_focus_tree', {
+    filePath: params.filePath,
+    contentOverride: params.contentOverride,
+    projectPath: params.projectPath,
+    gameDirectory: params.gameDirectory,
+    dependencyRoots: params.dependencyRoots
+  })
+}
+
+/**
+ * x / y 为写入脚本的值；给出 relativeTo 时为相对该国策的偏移。
+ * 省略 relativeTo 时保留原有的 relative_position_id，detach 为 true 时才删除
+ */
+export interface FocusMove {
+  focusId: string
+  x: number
+  y: number
+  relativeTo?: string
+  detach?: boolean
+}
+
+export interface FocusLayoutResult {
+  success: boolean
+  message: string
+  moved: string[]
+  /** 写回后的文件内容 */
+  content?: string
+}
+
+/**
+ * 移动单个国策，只改写其 x / y / relative_position_id
+ */
+export async function moveFocus(
+  filePath: string,
+  focusId: string,
+  x: number,
+  y: number,
+  relativeTo?: string,
+  detach?: boolean
+): Promise<FocusLayoutResult> {
+  return invoke<FocusLayoutResult>('move_focus', { filePath, focusId, x, y, relativeTo, detach })
+}
+
+/**
+ * 批量移动国策
+ */
+export async function moveFocuses(filePath: string, moves: FocusMove[]): Promise<FocusLayoutResult> {
+  return invoke<FocusLayoutResult>('move_focuses', { filePath, moves })
+}
+
+/**
+ * 自动排列国策树（按前置条件分层，消除空隙与重叠）
+ */
+export async function autoLayoutFocusTree(filePath: string, treeId?: string): Promise<FocusLayoutResult> {
+  return invoke<FocusLayoutResult>('auto_layout_focus_tree', { filePath, treeId })
+}
+
+// ==================== 事件链图 ====================
+
+export type EventSourceKind = 'event' | 'onAction' | 'focus' | 'decision' | 'scriptedEffect'
+
+export interface EventNamespace {
+  name: string
+  file: string
+  line: number
+}
+
+export interface EventOptionDto {
+  name?: string
+  start: number
+  end: number
+  line: number
+}
+
+export interface EventNode {
+  id: string
+  namespace?: string
+  /** country_event、news_event 等 */
+  eventType: string
+  title?: string
+  isTriggeredOnly: boolean
+  fireOnlyOnce: boolean
+  hidden: boolean
+  /** 折算成天（月按 30 天、年按 365 天） */
+  meanTimeToHappen?: number
+  options: EventOptionDto[]
+  file: string
+  source: RootSource
+  start: number
+  end: number
+  line: number
+}
+
+/** 一次触发事件，source 为事件 ID、on_action 名、国策 ID、决议 ID 或脚本效果名 */
+export interface EventCall {
+  sourceKind: EventSourceKind
+  source: string
+  option?: string
+  target: string
+  eventType: string
+  days?: number
+  randomDays?: number
+  file: string
+  start: number
+  end: number
+  line: number
+}
+
+export type EventWarningKind = 'unreachable' | 'undefined' | 'duplicateId'
+
+export interface EventWarning {
+  kind: EventWarningKind
+  event: string
+  message: string
+  file: string
+  start: number
+  end: number
+  line: number
+}
+
+export interface EventGraphResult {
+  success: boolean
+  message: string
+  namespaces: EventNamespace[]
+  events: EventNode[]
+  calls: EventCall[]
+  warnings: EventWarning[]
+}
+
+/**
+ * 构建项目与依赖项的事件链图；提供 gameRoot 时调用原版事件不算未定义
+ */
+export async function eventGraph(
+  projectRoot?: string,
+  gameRoot?: string,
+  dependencyRoots?: string[]
+): Promise<EventGraphResult> {
+  return invoke<EventGraphResult>('event_graph', { projectRoot, gameRoot, dependencyRoots })
+}
+
+// ==================== 决议 ====================
+
+export interface DecisionAiWeight {
+  base?: number
+  factor?: number
+  /** modifier = { } 的个数 */
+  modifiers: number
+}
+
+export interface DecisionEntry {
+  id: string
+  category: string
+  icon?: string
+  /** 原文，可能是数字或 @变量 */
+  cost?: string
+  daysRemove?: number
+  daysReEnable?: number
+  fireOnlyOnce: boolean
+  aiWillDo?: DecisionAiWeight
+  /** visible 块的原文 */
+  visible?: string
+  /** available 块的原文 */
+  available?: string
+  targeted: boolean
+  targets: string[]
+  targetArray?: string
+  source: RootSource
+  file: string
+  start: number
+  end: number
+  line: number
+}
+
+export interface DecisionCategoryEntry {
+  id: string
+  icon?: string
+  picture?: string
+  decisionCount: number
+  source: RootSource
+  file: string
+  start: number
+  end: number
+  line: number
+}
+
+export type DecisionWarningKind = 'undefinedCategory' | 'missingIcon'
+
+export interface DecisionWarning {
+  kind: DecisionWarningKind
+  id: string
+  message: string
+  file: string
+  start: number
+  end: number
+  line: number
+}
+
+export interface DecisionLoadResponse {
+  success: boolean
+  message: string
+  categories?: DecisionCategoryEntry[]
+  decisions?: DecisionEntry[]
+  warnings: DecisionWarning[]
+}
+
+/**
+ * 加载决议与决议类别，附带未定义类别与缺失图标的警告
+ */
+export async function loadDecisions(
+  projectRoot?: string,
+  gameRoot?: string,
+  dependencyRoots?: string[]
+): Promise<DecisionLoadResponse> {
+  return invoke<DecisionLoadResponse>('load_decisions', { projectRoot, gameRoot, dependencyRoots })
+}
+
+// ==================== 脚本化效果与触发器 ====================
+
+export type ScriptedKind = 'effect' | 'trigger'
+
+export interface ScriptedCall {
+  name: string
+  kind: ScriptedKind
+  start: number
+  end: number
+  line: number
+}
+
+export interface ScriptedEntry {
+  name: string
+  kind: ScriptedKind
+  source: RootSource
+  file: string
+  start: number
+  end: number
+  line: number
+  calls: ScriptedCall[]
+  /** `$PARAM$` / `[[PARAM]` 参数名，供补全使用 */
+  parameters: string[]
+}
+
+export type ScriptedWarningKind = 'recursion' | 'unused' | 'undefined'
+
+export interface ScriptedWarning {
+  kind: ScriptedWarningKind
+  name: string
+  message: string
+  file: string
+  start: number
+  end: number
+  line: number
+}
+
+export interface ScriptedLoadResponse {
+  success: boolean
+  message: string
+  entries?: ScriptedEntry[]
+  warnings: ScriptedWarning[]
+}
+
+/**
+ * 加载脚本化效果与脚本化触发器，附带递归、未使用与未定义调用的警告
+ */
+export async function loadScriptedDefinitions(
+  projectRoot?: string,
+  gameRoot?: string,
+  dependencyRoots?: string[]
+): Promise<ScriptedLoadResponse> {
+  return invoke<ScriptedLoadResponse>('load_scripted_definitions', { projectRoot, gameRoot, dependencyRoots })
+}
+
+// ==================== 角色 ====================
+
+export type PortraitCategory = 'civilian' | 'army' | 'navy'
+
+export interface CharacterPortrait {
+  category: PortraitCategory
+  /** `large` 或 `small` */
+  size: string
+  /** 脚本原文：GFX 名称或纹理路径 */
+  value: string
+  /** 解析出的纹理绝对路径，可交给 readImageAsBase64 */
+  texture?: string
+}
+
+export interface CountryLeaderRole {
+  ideology?: string
+  traits: string[]
+  expire?: string
+}
+
+export interface AdvisorRole {
+  slot?: string
+  ideaToken?: string
+  ledger?: string
+  cost?: number
+  traits: string[]
+}
+
+export type CommanderKind = 'corpsCommander' | 'fieldMarshal' | 'navyLeader'
+
+export interface CommanderRole {
+  kind: CommanderKind
+  skill?: number
+  attackSkill?: number
+  defenseSkill?: number
+  planningSkill?: number
+  logisticsSkill?: number
+  maneuveringSkill?: number
+  coordinationSkill?: number
+  traits: string[]
+}
+
+export interface CharacterEntry {
+  id: string
+  name?: string
+  portraits: CharacterPortrait[]
+  countryLeader: CountryLeaderRole[]
+  advisors: AdvisorRole[]
+  commanders: CommanderRole[]
+  source: RootSource
+  file: string
+  start: number
+  end: number
+  line: number
+}
+
+export type CharacterWarningKind = 'undefinedCharacter'
+
+export interface CharacterWarning {
+  kind: CharacterWarningKind
+  id: string
+  message: string
+  file: string
+  start: number
+  end: number
+  line: number
+}
+
+export interface CharacterLoadResponse {
+  success: boolean
+  message: string
+  characters?: CharacterEntry[]
+  warnings: CharacterWarning[]
+}
+
+/**
+ * 加载角色（领导人、顾问与将领）并解析立绘纹理，附带招募未定义角色的警告
+ */
+export async function loadCharacters(
+  projectRoot?: string,
+  gameRoot?: string,
+  dependencyRoots?: string[]
+): Promise<CharacterLoadResponse> {
+  return invoke<CharacterLoadResponse>('load_characters', { projectRoot, gameRoot, dependencyRoots })
+}
+
+export async function resetCharacterCache(): Promise<boolean> {
+  return await invoke('reset_character_cache')
+}
+
+// ==================== 本地化数据库 ====================
+
+export type LocalisationReferenceKind = 'scope' | 'key' | 'icon'
+
+/** 文本中的引用，start/end 为名称在文本内的字节区间 */
+export interface LocalisationReference {
+  kind: LocalisationReferenceKind
+  name: string
+  start: number
+  end: number
+}
+
+export interface LocalisationValue {
+  language: string
+  value: string
+  version?: number
+  file: string
+  line: number
+  source: RootSource
+  /** 来自 replace/ 目录 */
+  replace: boolean
+  references: LocalisationReference[]
+}
+
+export interface LocalisationEntry {
+  key: string
+  values: LocalisationValue[]
+}
+
+export interface LocalisationLookupResult {
+  success: boolean
+  message: string
+  languages: string[]
+  entries: LocalisationEntry[]
+}
+
+export interface MissingLocalisation {
+  language: string
+  keys: string[]
+}
+
+export interface MissingLocalisationReport {
+  success: boolean
+  message: string
+  languages: string[]
+  missing: MissingLocalisation[]
+}
+
+/**
+ * 查询本地化键在各语言下的定义（含版本号、文件、行号与引用），指定语言时只加载这些语言
+ */
+export async function lookupLocalisation(
+  keys: string[],
+  projectRoot?: string,
+  gameRoot?: string,
+  dependencyRoots?: string[],
+  languages?: string[]
+): Promise<LocalisationLookupResult> {
+  return await invoke('lookup_localisation', { keys, projectRoot, gameRoot, dependencyRoots, languages })
+}
+
+/**
+ * 列出项目各语言缺失的本地化键，未指定语言时取项目用到的全部语言
+ */
+export async function getMissingLocalisation(
+  languages?: string[],
+  projectRoot?: string,
+  gameRoot?: string,
+  dependencyRoots?: string[]
+): Promise<MissingLocalisationReport> {
+  return await invoke('get_missing_localisation', { languages, projectRoot, gameRoot, dependencyRoots })
+}
+
+export interface LocalisationUpsert {
+  key: string
+  value: string
+  /** 指定时同时改写版本号，否则保留原有版本号（新键为 0） */
+  version?: number
+}
+
+export interface LocalisationWriteResult {
+  success: boolean
+  message: string
+  updated: number
+  added: number
+  /** 被修改或新建的文件 */
+  files: string[]
+}
+
+/**
+ * 批量写入本地化键：已有键就地修改（保留版本号、注释与顺序），
+ * 新键追加到 targetFile（相对项目根目录），文件不存在时以 UTF-8 BOM 与 l_<语言>: 头部创建
+ */
+export async function upsertLocalisationKeys(
+  projectRoot: string,
+  language: string,
+  entries: LocalisationUpsert[],
+  targetFile?: string
+): Promise<LocalisationWriteResult> {
+  return await invoke('upsert_localisation_keys', { projectRoot, language, entries, targetFile })
+}
+
+export type LocalisationFormat = 'csv' | 'xliff'
+
+export interface LocalisationExportResult {
+  success: boolean
+  message: string
+  files: string[]
+  count: number
+}
+
+export interface LocalisationConflict {
+  key: string
+  language: string
+  /** 导出时的译文 */
+  exported: string
+  /** yml 中当前的译文 */
+  current: string
+  /** 导入文件中的译文 */
+  imported: string
+}
 
-export interface FocusLocalizationLoadResponse {
+export interface LocalisationImportResult {
   success: boolean
   message: string
-  map?: Record<string, string>
+  updated: number
+  added: number
+  unchanged: number
+  skipped: number
+  conflicts: LocalisationConflict[]
+  /** 项目中已不存在的键 */
+  removed: string[]
+  files: string[]
 }
 
-export async function loadFocusLocalizations(roots: string[]): Promise<FocusLocalizationLoadResponse> {
-  return invoke<FocusLocalizationLoadResponse>('load_focus_localizations', { roots })
+/**
+ * 导出项目本地化供译者使用（CSV: key,source,target,file,base 或 XLIFF 1.2），
+ * 每种目标语言一个文件；onlyPending 时只导出缺失或版本过期的译文
+ */
+export async function exportLocalisation(
+  projectRoot: string,
+  format: LocalisationFormat,
+  sourceLanguage: string,
+  languages: string[],
+  outputDir: string,
+  onlyPending?: boolean
+): Promise<LocalisationExportResult> {
+  return await invoke('export_localisation', { projectRoot, format, sourceLanguage, languages, outputDir, onlyPending })
+}
+
+/**
+ * 导入译者返回的 CSV / XLIFF，导出后被改动过的译文列为冲突，不会覆盖
+ */
+export async function importLocalisation(projectRoot: string, file: string): Promise<LocalisationImportResult> {
+  return await invoke('import_localisation', { projectRoot, file })
 }
 
 // ==================== Country Tags ====================
@@ -666,6 +1290,15 @@ export interface TagEntry {
   code: string
   name?: string
   source: 'project' | 'game' | 'dependency'
+  /** 来自 dynamic_tags = yes 文件或 create_dynamic_country */
+  dynamic?: boolean
+  /** colors.txt 中的地图颜色（#rrggbb） */
+  color?: string
+  colorUi?: string
+  /** 脚本中 set_cosmetic_tag 设置过的外观标签 */
+  cosmeticTags?: string[]
+  /** history/countries 下对应的历史文件路径 */
+  historyFile?: string
 }
 
 export interface TagLoadResponse {
@@ -684,6 +1317,9 @@ export async function loadCountryTags(
 
 export interface TagValidationError {
   line: number
+  column: number
+  start: number
+  end: number
   message: string
 }
 
@@ -807,6 +1443,8 @@ export interface Dependency {
   type: DependencyType
   addedAt: string
   enabled: boolean
+  /** 加载优先级，数值越大越优先（默认 0） */
+  priority?: number
 }
 
 /**
@@ -844,6 +1482,19 @@ export interface DependencyIndexResult {
   message: string
   ideaCount?: number
   tagCount?: number
+  localisationCount?: number
+  spriteCount?: number
+  fileCount: number
+  errors: DependencyIndexError[]
+}
+
+/**
+ * 依赖项索引时单个文件的错误
+ */
+export interface DependencyIndexError {
+  file: string
+  line?: number
+  message: string
 }
 
 /**
@@ -871,21 +1522,130 @@ export async function validateDependencyPath(path: string): Promise<DependencyVa
 }
 
 /**
- * 索引依赖项的 Idea 和 Tag 数据
+ * 索引依赖项的 Idea、Tag、本地化与 Sprite 数据
  */
 export async function indexDependency(dependencyPath: string): Promise<DependencyIndexResult> {
   return await invoke('index_dependency', { dependencyPath })
 }
 
+/**
+ * 描述文件（descriptor.mod / *.mod）字段
+ */
+export interface ModDescriptor {
+  name: string
+  version?: string | null
+  tags: string[]
+  picture?: string | null
+  supportedVersion?: string | null
+  path?: string | null
+  archive?: string | null
+  replacePath: string[]
+  dependencies: string[]
+  remoteFileId?: string | null
+}
+
+/**
+ * descriptor.mod 读写结果
+ */
+export interface DescriptorResult {
+  success: boolean
+  message: string
+  descriptorPath: string
+  descriptor: ModDescriptor | null
+  /** project.json 中记录的 replace_path */
+  projectReplacePath: string[]
+  replacePathInSync: boolean
+}
+
+/**
+ * 读取项目的 descriptor.mod
+ */
+export async function readDescriptor(projectPath: string): Promise<DescriptorResult> {
+  return await invoke('read_descriptor', { projectPath })
+}
+
+/**
+ * 写回 descriptor.mod（保留未知字段与注释），并同步 project.json 的 replace_path
+ */
+export async function writeDescriptor(
+  projectPath: string,
+  descriptor: ModDescriptor
+): Promise<DescriptorResult> {
+  return await invoke('write_descriptor', { projectPath, descriptor })
+}
+
+export type LocalModSource = 'documents' | 'workshop'
+
+/**
+ * 本地发现的 Mod
+ */
+export interface LocalMod {
+  descriptorFile: string
+  source: LocalModSource
+  descriptor: ModDescriptor
+  contentPath: string | null
+  isArchive: boolean
+  /** 可直接加入项目的依赖项条目 */
+  dependency: Dependency | null
+}
+
+export interface DiscoverModsResult {
+  success: boolean
+  message: string
+  scannedDirs: string[]
+  mods: LocalMod[]
+}
+
+/**
+ * 扫描用户 mod/ 目录与创意工坊目录中的 Mod
+ */
+export async function discoverLocalMods(params: {
+  documentsDir?: string
+  workshopDir?: string
+  gameDirectory?: string
+} = {}): Promise<DiscoverModsResult> {
+  return await invoke('discover_local_mods', {
+    documentsDir: params.documentsDir,
+    workshopDir: params.workshopDir,
+    gameDirectory: params.gameDirectory
+  })
+}
+
 // ==================== 项目打包 ====================
 
 /**
  * 打包选项
  */
 export interface PackageOptions {
+  /** 本次打包的任务 id，取消时传给 cancelPackaging */
+  packId: string
   projectPath: string
   outputName: string
   excludeDependencies: boolean
+  /** 打包配置名称，缺省时使用第一个配置 */
+  profile?: string
+  /** 覆盖配置中的校验模式 */
+  validation?: PackageValidation
+  /** 校验标签与贴图时使用的游戏目录 */
+  gameRoot?: string
+  dependencyRoots?: string[]
+}
+
+/**
+ * 打包前校验模式：off 不校验 / warn 仅提示 / block 有错误时中止
+ */
+export type PackageValidation = 'off' | 'warn' | 'block'
+
+/**
+ * 打包前校验诊断
+ */
+export interface PackageDiagnostic {
+  severity: 'error' | 'warning'
+  rule: string
+  /** 相对于项目根目录的路径 */
+  file: string
+  line?: number | null
+  message: string
 }
 
 /**
@@ -896,6 +1656,36 @@ export interface PackageResult {
   message: string
   outputPath?: string
   fileSize?: number
+  fileCount?: number
+  manifestPath?: string
+  version?: string
+  diagnostics?: PackageDiagnostic[]
+}
+
+/**
+ * 打包输出布局：zip 压缩包 / 创意工坊目录 / .mod + 目录
+ */
+export type PackageLayout = 'zip' | 'workshop' | 'modFolder'
+
+export type VersionBump = 'major' | 'minor' | 'patch'
+
+/**
+ * 打包配置（保存在 project.json 的 package_profiles 中）
+ */
+export interface PackageProfile {
+  name: string
+  include: string[]
+  exclude: string[]
+  layout: PackageLayout
+  versionBump?: VersionBump | null
+  outputName?: string | null
+  validation?: PackageValidation
+}
+
+export interface PackageProfilesResult {
+  success: boolean
+  message: string
+  profiles: PackageProfile[]
 }
 
 /**
@@ -904,13 +1694,59 @@ export interface PackageResult {
 export async function packProject(options: PackageOptions): Promise<PackageResult> {
   return await invoke('pack_project', {
     opts: {
+      packId: options.packId,
       projectPath: options.projectPath,
       outputName: options.outputName,
-      excludeDependencies: options.excludeDependencies
+      excludeDependencies: options.excludeDependencies,
+      profile: options.profile,
+      validation: options.validation,
+      gameRoot: options.gameRoot,
+      dependencyRoots: options.dependencyRoots
     }
   })
 }
 
+/**
+ * 打包进度（package-progress 事件）
+ */
+export interface PackageProgress {
+  stage: 'scan' | 'hash' | 'validate' | 'compress' | 'write' | 'done'
+  current: number
+  total: number
+  message: string
+}
+
+/**
+ * 监听打包进度，返回取消监听函数
+ */
+export async function onPackageProgress(handler: (progress: PackageProgress) => void): Promise<UnlistenFn> {
+  return await listen<PackageProgress>('package-progress', event => handler(event.payload))
+}
+
+/**
+ * 取消指定的打包任务，任务不存在时返回 false
+ */
+export async function cancelPackaging(packId: string): Promise<boolean> {
+  return await invoke('cancel_packaging', { packId })
+}
+
+/**
+ * 读取打包配置
+ */
+export async function loadPackageProfiles(projectPath: string): Promise<PackageProfilesResult> {
+  return await invoke('load_package_profiles', { projectPath })
+}
+
+/**
+ * 保存打包配置
+ */
+export async function savePackageProfiles(
+  projectPath: string,
+  profiles: PackageProfile[]
+): Promise<PackageProfilesResult> {
+  return await invoke('save_package_profiles', { projectPath, profiles })
+}
+
 // ==================== 图片读取 ====================
 
 /**
@@ -1334,3 +2170,343 @@ export async function parseMioPreview(params: {
     dependencyRoots: params.dependencyRoots
   })
 }
+
+// ==================== 脚本格式化 ====================
+
+export interface FormatOptions {
+  useTabs?: boolean
+  indentSize?: number
+  collapseBlocks?: boolean
+  maxLineWidth?: number
+}
+
+export interface FormattedFile {
+  path: string
+  changed: boolean
+  diff?: string
+  error?: string
+}
+
+export interface FormatResponse {
+  success: boolean
+  message: string
+  formatted?: string
+  files: FormattedFile[]
+}
+
+/**
+ * 格式化脚本：传入 content 时只返回结果；path 可为文件或项目目录
+ */
+export async function formatScript(params: {
+  path?: string
+  content?: string
+  range?: { start: number; end: number }
+  options?: FormatOptions
+  dryRun?: boolean
+}): Promise<FormatResponse> {
+  return await invoke('format_script', {
+    path: params.path,
+    content: params.content,
+    range: params.range,
+    options: params.options,
+    dryRun: params.dryRun
+  })
+}
+
+// ==================== 脚本诊断 ====================
+
+export type LintSeverity = 'error' | 'warning' | 'info'
+
+export interface LintEdit {
+  start: number
+  end: number
+  newText: string
+}
+
+export interface LintFix {
+  title: string
+  edits: LintEdit[]
+}
+
+export interface LintDiagnostic {
+  file: string
+  rule: string
+  severity: LintSeverity
+  line: number
+  column: number
+  endLine: number
+  endColumn: number
+  start: number
+  end: number
+  message: string
+  fixes: LintFix[]
+}
+
+export interface LintRuleInfo {
+  id: string
+  name: string
+  description: string
+  severity: LintSeverity
+  enabled: boolean
+}
+
+export interface LintRulesResult {
+  success: boolean
+  message: string
+  rules: LintRuleInfo[]
+}
+
+export interface LintResult {
+  success: boolean
+  message: string
+  fileCount: number
+  diagnostics: LintDiagnostic[]
+}
+
+/**
+ * 列出诊断规则及其启用状态（来自设置中的 lintRules）
+ */
+export async function listLintRules(): Promise<LintRulesResult> {
+  return invoke<LintRulesResult>('list_lint_rules')
+}
+
+/**
+ * 检查单个文件，content 为编辑器中未保存的内容；rules 可临时覆盖规则开关
+ */
+export async function lintFile(params: {
+  filePath: string
+  content?: string
+  projectRoot?: string
+  gameRoot?: string
+  dependencyRoots?: string[]
+  rules?: Record<string, boolean>
+}): Promise<LintResult> {
+  return invoke<LintResult>('lint_file', {
+    filePath: params.filePath,
+    content: params.content,
+    projectRoot: params.projectRoot,
+    gameRoot: params.gameRoot,
+    dependencyRoots: params.dependencyRoots,
+    rules: params.rules
+  })
+}
+
+/**
+ * 检查整个项目
+ */
+export async function lintProject(
+  projectRoot: string,
+  gameRoot?: string,
+  dependencyRoots?: string[],
+  rules?: Record<string, boolean>
+): Promise<LintResult> {
+  return invoke<LintResult>('lint_project', { projectRoot, gameRoot, dependencyRoots, rules })
+}
+
+// ==================== 符号索引 ====================
+
+export type SymbolKind =
+  | 'focus'
+  | 'idea'
+  | 'event'
+  | 'decision'
+  | 'scriptedEffect'
+  | 'scriptedTrigger'
+  | 'onAction'
+  | 'character'
+  | 'sprite'
+  | 'guiWindow'
+  | 'localisationKey'
+  | 'state'
+  | 'countryTag'
+  | 'mioTrait'
+
+export type SymbolSource = 'project' | 'game' | 'dependency'
+
+export interface SymbolDefinition {
+  name: string
+  kind: SymbolKind
+  file: string
+  line: number
+  column: number
+  start: number
+  end: number
+  source: SymbolSource
+}
+
+export interface SymbolReference {
+  name: string
+  file: string
+  line: number
+  column: number
+  start: number
+  end: number
+  source: SymbolSource
+}
+
+export interface SymbolIndexResponse {
+  success: boolean
+  message: string
+  fileCount: number
+  counts: Partial<Record<SymbolKind, number>>
+}
+
+export interface SymbolLookupResponse {
+  success: boolean
+  message: string
+  symbol?: string
+  definitions: SymbolDefinition[]
+  references: SymbolReference[]
+}
+
+export async function buildSymbolIndex(
+  projectRoot?: string,
+  gameRoot?: string,
+  dependencyRoots?: string[]
+): Promise<SymbolIndexResponse> {
+  return invoke<SymbolIndexResponse>('build_symbol_index', { projectRoot, gameRoot, dependencyRoots })
+}
+
+/**
+ * 跳转到定义，content 为编辑器中未保存的内容
+ */
+export async function gotoDefinition(params: {
+  file: string
+  offset: number
+  content?: string
+  projectRoot?: string
+  gameRoot?: string
+  dependencyRoots?: string[]
+}): Promise<SymbolLookupResponse> {
+  return invoke<SymbolLookupResponse>('goto_definition', {
+    file: params.file,
+    offset: params.offset,
+    content: params.content,
+    projectRoot: params.projectRoot,
+    gameRoot: params.gameRoot,
+    dependencyRoots: params.dependencyRoots
+  })
+}
+
+export async function findReferences(
+  symbol: string,
+  projectRoot?: string,
+  gameRoot?: string,
+  dependencyRoots?: string[]
+): Promise<SymbolLookupResponse> {
+  return invoke<SymbolLookupResponse>('find_references', { symbol, projectRoot, gameRoot, dependencyRoots })
+}
+
+export interface RenameEdit {
+  start: number
+  end: number
+  line: number
+  column: number
+  oldText: string
+  newText: string
+  lineText: string
+  preview: string
+}
+
+export interface RenameFileEdits {
+  file: string
+  encoding: string
+  edits: RenameEdit[]
+}
+
+export interface RenameResponse {
+  success: boolean
+  message: string
+  oldName: string
+  newName: string
+  applied: boolean
+  editCount: number
+  files: RenameFileEdits[]
+}
+
+/**
+ * 重命名符号：apply 为 false 时仅返回预览
+ */
+export async function renameSymbol(params: {
+  symbol: string
+  newName: string
+  projectRoot: string
+  gameRoot?: string
+  dependencyRoots?: string[]
+  apply?: boolean
+}): Promise<RenameResponse> {
+  return invoke<RenameResponse>('rename_symbol', {
+    symbol: params.symbol,
+    newName: params.newName,
+    projectRoot: params.projectRoot,
+    gameRoot: params.gameRoot,
+    dependencyRoots: params.dependencyRoots,
+    apply: params.apply
+  })
+}
+
+// ==================== 加载顺序 ====================
+
+export type RootSource = 'project' | 'dependency' | 'game'
+export type ProviderStatus = 'winner' | 'overridden' | 'replaced'
+export type DefinitionKind = 'idea' | 'focusTree' | 'eventNamespace' | 'sprite'
+
+export interface LoadOrderRoot {
+  path: string
+  name: string
+  source: RootSource
+  priority: number | null
+  replacePaths: string[]
+}
+
+export interface OverrideProvider {
+  root: string
+  rootName: string
+  source: RootSource
+  file: string
+  status: ProviderStatus
+}
+
+export interface FileOverride {
+  path: string
+  winner: OverrideProvider | null
+  overridden: OverrideProvider[]
+}
+
+export interface DefinitionOverride {
+  kind: DefinitionKind
+  name: string
+  winner: OverrideProvider
+  overridden: OverrideProvider[]
+}
+
+export interface ReplacedPath {
+  directory: string
+  replacedBy: string
+  replacedByName: string
+  blankedFiles: string[]
+}
+
+export interface OverrideReport {
+  success: boolean
+  message: string
+  roots: LoadOrderRoot[]
+  files: FileOverride[]
+  definitions: DefinitionOverride[]
+  replaced: ReplacedPath[]
+}
+
+/**
+ * 计算项目、依赖项与游戏本体之间的覆盖关系
+ */
+export async function computeOverrideReport(params: {
+  projectRoot?: string
+  gameRoot?: string
+  dependencyRoots?: string[]
+}): Promise<OverrideReport> {
+  return invoke<OverrideReport>('compute_override_report', {
+    projectRoot: params.projectRoot,
+    gameRoot: params.gameRoot,
+    dependencyRoots: params.dependencyRoots
+  })
+}
@@ -150,7 +150,8 @@ export function useDependencyManager(projectPath?: string) {
       path,
       type: validation.type || 'hoi4mod',
       addedAt: new Date().toISOString(),
-      enabled: true
+      enabled: true,
+      priority: 0
     }
 
     state.value.dependencies.push(newDependency)
@@ -231,6 +232,38 @@ export function useDependencyManager(projectPath?: string) {
     }
   }
 
+  /**
+   * 设置依赖项加载优先级（数值越大越优先）
+   */
+  async function setDependencyPriority(
+    id: string,
+    priority: number
+  ): Promise<{ success: boolean; message: string }> {
+    const dependency = state.value.dependencies.find(dep => dep.id === id)
+    if (!dependency) {
+      return {
+        success: false,
+        message: '依赖项不存在'
+      }
+    }
+
+    const previous = dependency.priority
+    dependency.priority = Math.trunc(priority)
+
+    // 保存到文件
+    const saveResult = await saveDependenciesList()
+    if (!saveResult.success) {
+      // 如果保存失败，回滚
+      dependency.priority = previous
+      return saveResult
+    }
+
+    return {
+      success: true,
+      message: `优先级已设置为 ${dependency.priority}`
+    }
+  }
+
   /**
    * 索引依赖项
    */
@@ -290,8 +323,10 @@ export function useDependencyManager(projectPath?: string) {
 
   // Computed properties
   const dependenciesList = computed(() => state.value.dependencies)
-  const enabledDependencies = computed(() => 
-    state.value.dependencies.filter(dep => dep.enabled)
+  const enabledDependencies = computed(() =>
+    state.value.dependencies
+      .filter(dep => dep.enabled)
+      .sort((a, b) => (b.priority ?? 0) - (a.priority ?? 0))
   )
   const isLoading = computed(() => state.value.loading)
   const dependencyCount = computed(() => state.value.dependencies.length)
@@ -309,6 +344,7 @@ export function useDependencyManager(projectPath?: string) {
     addDependency,
     removeDependency,
     toggleDependency,
+    setDependencyPriority,
     validatePath,
     indexDependency: indexDependencyData,
     indexAllDependencies,
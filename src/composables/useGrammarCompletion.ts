@@ -966,12 +966,25 @@ function createTagCompletions(tags: TagEntry[]): GrammarCompletionItem[] {
   return tags.map((tag) => ({
     label: tag.code,
     type: 'variable',
-    detail: `[Tag -> (${tag.source === 'game' ? '游戏内置' : '项目自定义'})]`,
+    detail: `[Tag -> (${tag.source === 'game' ? '游戏内置' : '项目自定义'})${tag.dynamic ? ' 动态' : ''}]`,
+    info: createTagInfo(tag),
     boost: 30,
     source: 'tag'
   }))
 }
 
+/**
+ * 生成 Tag 的悬浮说明：名称、颜色、外观标签与历史文件
+ */
+function createTagInfo(tag: TagEntry): string | undefined {
+  const lines: string[] = []
+  if (tag.name) lines.push(tag.name)
+  if (tag.color) lines.push(`颜色: ${tag.color}`)
+  if (tag.cosmeticTags?.length) lines.push(`外观标签: ${tag.cosmeticTags.join(', ')}`)
+  if (tag.historyFile) lines.push(`历史文件: ${tag.historyFile}`)
+  return lines.length > 0 ? lines.join('\n') : undefined
+}
+
 /**
  * 合并并排序补全项，遵循字母序与来源优先级
  * 来源优先级: keyword > tag > idea
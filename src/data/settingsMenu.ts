@@ -79,6 +79,13 @@ export const settingsMenuData: SettingsMenuCategory[] = [
         description: '自动保存和错误处理',
         category: 'editor'
       },
+      {
+        id: 'editor-lint',
+        title: '脚本诊断',
+        icon: 'eye',
+        description: '启用或关闭各条诊断规则',
+        category: 'editor'
+      },
       {
         id: 'map-settings',
         title: '地图预览',
@@ -308,6 +308,8 @@ describe('useDependencyManager', () => {
         success: true,
         ideaCount: 10,
         tagCount: 5,
+        fileCount: 3,
+        errors: [],
         message: ''
     })
     
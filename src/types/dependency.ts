@@ -25,6 +25,8 @@ export interface Dependency {
   addedAt: string
   /** 是否启用 */
   enabled: boolean
+  /** 加载优先级，数值越大越优先（默认 0） */
+  priority?: number
 }
 
 /**